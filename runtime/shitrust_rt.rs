@@ -0,0 +1,672 @@
+// Runtime library for compiled ShitRust programs.
+//
+// This file is not part of the shitrust crate: the compiler builds it on its own as a
+// static library (see src/runtime.rs) and links it into every executable. It only
+// depends on `core` and a few C library functions, so it works anywhere `cc` does.
+//
+// Strings, lists and dicts are heap objects with a reference count. Compiled code holds
+// pointers to them and calls `shitrust_retain`/`shitrust_release` as references are
+// copied and dropped. Mutating functions take the address of the variable holding the
+// object and copy the object first if it is shared, which gives them value semantics.
+// Closures keep the variables they capture in an environment, another such object.
+//
+// Flags and kinds are passed as u64 so that no argument is narrower than 64 bits, and
+// callers never have to care how the C ABI extends small integers.
+#![no_std]
+#![allow(clippy::missing_safety_doc)]
+
+use core::fmt::Write;
+use core::{ptr, slice};
+
+extern "C" {
+    fn malloc(size: usize) -> *mut u8;
+    fn realloc(ptr: *mut u8, size: usize) -> *mut u8;
+    fn free(ptr: *mut u8);
+    fn write(fd: i32, buf: *const u8, count: usize) -> isize;
+    fn abort() -> !;
+}
+
+const KIND_STRING: u8 = 1;
+const KIND_LIST: u8 = 2;
+const KIND_DICT: u8 = 3;
+const KIND_ENVIRONMENT: u8 = 4;
+
+// How list elements and dict values are stored: plain bits, or a pointer to another object
+const VALUE_PLAIN: u8 = 0;
+const VALUE_OBJECT: u8 = 1;
+
+// Dict keys are compared by their bits, except for string keys
+const KEY_STRING: u8 = 1;
+
+#[repr(C)]
+pub struct Header {
+    refcount: usize,
+    kind: u8,
+}
+
+#[repr(C)]
+pub struct Str {
+    header: Header,
+    len: usize,
+    data: *mut u8,
+}
+
+#[repr(C)]
+pub struct List {
+    header: Header,
+    value_kind: u8,
+    len: usize,
+    capacity: usize,
+    items: *mut u64,
+}
+
+const SLOT_EMPTY: u8 = 0;
+const SLOT_FULL: u8 = 1;
+const SLOT_DELETED: u8 = 2;
+
+#[repr(C)]
+struct Entry {
+    state: u8,
+    hash: u64,
+    key: u64,
+    value: u64,
+}
+
+#[repr(C)]
+pub struct Dict {
+    header: Header,
+    key_kind: u8,
+    value_kind: u8,
+    len: usize,
+    // Full and deleted entries; the table grows before this reaches 3/4 of the capacity
+    used: usize,
+    capacity: usize,
+    entries: *mut Entry,
+}
+
+// The captured variables of a closure, laid out by the compiler in `data`
+#[repr(C)]
+pub struct Environment {
+    header: Header,
+    // Releases the references held by the captured variables, if any
+    drop: Option<unsafe extern "C" fn(*mut u8)>,
+    data: *mut u8,
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    fail("internal runtime error")
+}
+
+// The precompiled `core` refers to this even though nothing unwinds with panic=abort
+#[no_mangle]
+extern "C" fn rust_eh_personality() {}
+
+fn fail(message: &str) -> ! {
+    unsafe {
+        write(2, b"error: ".as_ptr(), 7);
+        write(2, message.as_ptr(), message.len());
+        write(2, b"\n".as_ptr(), 1);
+        abort()
+    }
+}
+
+unsafe fn allocate<T>(count: usize) -> *mut T {
+    let size = count.checked_mul(core::mem::size_of::<T>()).unwrap_or_else(|| fail("out of memory"));
+    let memory = malloc(size.max(1)) as *mut T;
+    if memory.is_null() {
+        fail("out of memory");
+    }
+    memory
+}
+
+unsafe fn reallocate<T>(memory: *mut T, count: usize) -> *mut T {
+    let size = count.checked_mul(core::mem::size_of::<T>()).unwrap_or_else(|| fail("out of memory"));
+    let memory = realloc(memory as *mut u8, size.max(1)) as *mut T;
+    if memory.is_null() {
+        fail("out of memory");
+    }
+    memory
+}
+
+// ---------------------------------------------------------------------------
+// Reference counting
+// ---------------------------------------------------------------------------
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_retain(object: *mut Header) {
+    if !object.is_null() {
+        (*object).refcount += 1;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_release(object: *mut Header) {
+    if object.is_null() {
+        return;
+    }
+    
+    (*object).refcount -= 1;
+    if (*object).refcount > 0 {
+        return;
+    }
+    
+    match (*object).kind {
+        KIND_STRING => {
+            let string = object as *mut Str;
+            free((*string).data);
+        },
+        KIND_LIST => {
+            let list = object as *mut List;
+            release_values((*list).value_kind, (*list).items, (*list).len);
+            free((*list).items as *mut u8);
+        },
+        KIND_DICT => {
+            let dict = object as *mut Dict;
+            for entry in entries(dict) {
+                if entry.state == SLOT_FULL {
+                    release_key((*dict).key_kind, entry.key);
+                    release_value((*dict).value_kind, entry.value);
+                }
+            }
+            free((*dict).entries as *mut u8);
+        },
+        KIND_ENVIRONMENT => {
+            let environment = object as *mut Environment;
+            if let Some(drop) = (*environment).drop {
+                drop((*environment).data);
+            }
+            free((*environment).data);
+        },
+        _ => fail("release of an invalid object"),
+    }
+    free(object as *mut u8);
+}
+
+unsafe fn retain_value(kind: u8, value: u64) {
+    if kind == VALUE_OBJECT {
+        shitrust_retain(value as usize as *mut Header);
+    }
+}
+
+unsafe fn release_value(kind: u8, value: u64) {
+    if kind == VALUE_OBJECT {
+        shitrust_release(value as usize as *mut Header);
+    }
+}
+
+unsafe fn release_values(kind: u8, values: *mut u64, len: usize) {
+    if kind == VALUE_OBJECT {
+        for index in 0..len {
+            release_value(kind, *values.add(index));
+        }
+    }
+}
+
+unsafe fn retain_key(kind: u8, key: u64) {
+    retain_value(if kind == KEY_STRING { VALUE_OBJECT } else { VALUE_PLAIN }, key);
+}
+
+unsafe fn release_key(kind: u8, key: u64) {
+    release_value(if kind == KEY_STRING { VALUE_OBJECT } else { VALUE_PLAIN }, key);
+}
+
+// ---------------------------------------------------------------------------
+// Strings
+// ---------------------------------------------------------------------------
+
+unsafe fn string_with_capacity(capacity: usize) -> *mut Str {
+    let string = allocate::<Str>(1);
+    ptr::write(string, Str {
+        header: Header { refcount: 1, kind: KIND_STRING },
+        len: 0,
+        data: allocate::<u8>(capacity),
+    });
+    string
+}
+
+unsafe fn string_bytes<'a>(string: *const Str) -> &'a [u8] {
+    slice::from_raw_parts((*string).data, (*string).len)
+}
+
+unsafe fn string_push(string: *mut Str, bytes: &[u8], capacity: &mut usize) {
+    let needed = (*string).len + bytes.len();
+    if needed > *capacity {
+        *capacity = needed.max(*capacity * 2);
+        (*string).data = reallocate((*string).data, *capacity);
+    }
+    ptr::copy_nonoverlapping(bytes.as_ptr(), (*string).data.add((*string).len), bytes.len());
+    (*string).len = needed;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_new(data: *const u8, len: usize) -> *mut Str {
+    let string = string_with_capacity(len);
+    ptr::copy_nonoverlapping(data, (*string).data, len);
+    (*string).len = len;
+    string
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_concat(left: *const Str, right: *const Str) -> *mut Str {
+    let (left, right) = (string_bytes(left), string_bytes(right));
+    let mut capacity = left.len() + right.len();
+    let string = string_with_capacity(capacity);
+    string_push(string, left, &mut capacity);
+    string_push(string, right, &mut capacity);
+    string
+}
+
+/// The number of characters, not bytes
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_len(string: *const Str) -> i64 {
+    string_bytes(string).iter().filter(|&&byte| byte & 0xC0 != 0x80).count() as i64
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_eq(left: *const Str, right: *const Str) -> bool {
+    string_bytes(left) == string_bytes(right)
+}
+
+/// Orders strings by their bytes, returning -1, 0 or 1
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_compare(left: *const Str, right: *const Str) -> i32 {
+    string_bytes(left).cmp(string_bytes(right)) as i32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_contains(string: *const Str, needle: *const Str) -> bool {
+    let (haystack, needle) = (string_bytes(string), string_bytes(needle));
+    needle.is_empty() || haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+/// A NUL-terminated copy of the string to pass to a C function, freed with `shitrust_cstr_free`
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_to_cstr(string: *const Str) -> *mut u8 {
+    let bytes = string_bytes(string);
+    let c_string = allocate::<u8>(bytes.len() + 1);
+    ptr::copy_nonoverlapping(bytes.as_ptr(), c_string, bytes.len());
+    *c_string.add(bytes.len()) = 0;
+    c_string
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_cstr_free(c_string: *mut u8) {
+    free(c_string);
+}
+
+/// Copies a string a C function returned, which stays owned by C; null becomes the empty string
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_from_cstr(c_string: *const u8) -> *mut Str {
+    if c_string.is_null() {
+        return string_with_capacity(0);
+    }
+    
+    let mut len = 0;
+    while *c_string.add(len) != 0 {
+        len += 1;
+    }
+    shitrust_string_new(c_string, len)
+}
+
+// Formats with the same rules as the interpreter, which uses Rust's `Display`
+struct Formatter {
+    string: *mut Str,
+    capacity: usize,
+}
+
+impl Write for Formatter {
+    fn write_str(&mut self, text: &str) -> core::fmt::Result {
+        unsafe { string_push(self.string, text.as_bytes(), &mut self.capacity) };
+        Ok(())
+    }
+}
+
+unsafe fn format_string(args: core::fmt::Arguments) -> *mut Str {
+    let mut formatter = Formatter { string: string_with_capacity(16), capacity: 16 };
+    let _ = formatter.write_fmt(args);
+    formatter.string
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_from_int(value: i64) -> *mut Str {
+    format_string(format_args!("{}", value))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_from_float(value: f64) -> *mut Str {
+    format_string(format_args!("{}", value))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_from_bool(value: u64) -> *mut Str {
+    format_string(format_args!("{}", value != 0))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_string_from_char(code: u64) -> *mut Str {
+    let value = u32::try_from(code).ok().and_then(char::from_u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+    format_string(format_args!("{}", value))
+}
+
+// Reports a runtime error of compiled code, such as an integer overflow, and aborts
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_fail(message: *const u8, len: usize) -> ! {
+    fail(core::str::from_utf8_unchecked(slice::from_raw_parts(message, len)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_print(string: *const Str, newline: u64) {
+    let bytes = string_bytes(string);
+    write(1, bytes.as_ptr(), bytes.len());
+    if newline != 0 {
+        write(1, b"\n".as_ptr(), 1);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Lists
+// ---------------------------------------------------------------------------
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_list_new(value_kind: u64, capacity: usize) -> *mut List {
+    let list = allocate::<List>(1);
+    ptr::write(list, List {
+        header: Header { refcount: 1, kind: KIND_LIST },
+        value_kind: value_kind as u8,
+        len: 0,
+        capacity,
+        items: allocate::<u64>(capacity),
+    });
+    list
+}
+
+// Makes sure the list in `slot` is referenced only from there, copying it if it is shared
+unsafe fn unique_list(slot: *mut *mut List) -> *mut List {
+    let list = *slot;
+    if (*list).header.refcount == 1 {
+        return list;
+    }
+    
+    let copy = shitrust_list_new((*list).value_kind as u64, (*list).len);
+    ptr::copy_nonoverlapping((*list).items, (*copy).items, (*list).len);
+    (*copy).len = (*list).len;
+    for index in 0..(*list).len {
+        retain_value((*list).value_kind, *(*list).items.add(index));
+    }
+    
+    shitrust_release(list as *mut Header);
+    *slot = copy;
+    copy
+}
+
+unsafe fn list_index(list: *const List, index: i64) -> usize {
+    if index < 0 || index as u64 >= (*list).len as u64 {
+        fail("list index out of bounds");
+    }
+    index as usize
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_list_len(list: *const List) -> i64 {
+    (*list).len as i64
+}
+
+/// Returns the element without adding a reference to it
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_list_get(list: *const List, index: i64) -> u64 {
+    *(*list).items.add(list_index(list, index))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_list_set(slot: *mut *mut List, index: i64, value: u64) {
+    let list = unique_list(slot);
+    let index = list_index(list, index);
+    retain_value((*list).value_kind, value);
+    release_value((*list).value_kind, *(*list).items.add(index));
+    *(*list).items.add(index) = value;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_list_push(slot: *mut *mut List, value: u64) {
+    let list = unique_list(slot);
+    if (*list).len == (*list).capacity {
+        (*list).capacity = ((*list).capacity * 2).max(4);
+        (*list).items = reallocate((*list).items, (*list).capacity);
+    }
+    retain_value((*list).value_kind, value);
+    *(*list).items.add((*list).len) = value;
+    (*list).len += 1;
+}
+
+/// Removes the last element; the caller takes over the list's reference to it
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_list_pop(slot: *mut *mut List) -> u64 {
+    let list = unique_list(slot);
+    if (*list).len == 0 {
+        fail("pop from an empty list");
+    }
+    (*list).len -= 1;
+    *(*list).items.add((*list).len)
+}
+
+// ---------------------------------------------------------------------------
+// Dicts
+// ---------------------------------------------------------------------------
+
+unsafe fn entries<'a>(dict: *const Dict) -> &'a mut [Entry] {
+    slice::from_raw_parts_mut((*dict).entries, (*dict).capacity)
+}
+
+unsafe fn hash_key(kind: u8, key: u64) -> u64 {
+    if kind == KEY_STRING {
+        // FNV-1a
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for &byte in string_bytes(key as usize as *const Str) {
+            hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+        hash
+    } else {
+        // The splitmix64 finalizer
+        let mut hash = key;
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+        hash ^ (hash >> 31)
+    }
+}
+
+unsafe fn keys_equal(kind: u8, left: u64, right: u64) -> bool {
+    if kind == KEY_STRING {
+        shitrust_string_eq(left as usize as *const Str, right as usize as *const Str)
+    } else {
+        left == right
+    }
+}
+
+unsafe fn empty_entries(capacity: usize) -> *mut Entry {
+    let entries = allocate::<Entry>(capacity);
+    for index in 0..capacity {
+        ptr::write(entries.add(index), Entry { state: SLOT_EMPTY, hash: 0, key: 0, value: 0 });
+    }
+    entries
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_dict_new(key_kind: u64, value_kind: u64) -> *mut Dict {
+    let capacity = 8;
+    let dict = allocate::<Dict>(1);
+    ptr::write(dict, Dict {
+        header: Header { refcount: 1, kind: KIND_DICT },
+        key_kind: key_kind as u8,
+        value_kind: value_kind as u8,
+        len: 0,
+        used: 0,
+        capacity,
+        entries: empty_entries(capacity),
+    });
+    dict
+}
+
+// The entry holding `key`, or None. Linear probing over a power-of-two table.
+unsafe fn find_entry<'a>(dict: *const Dict, key: u64, hash: u64) -> Option<&'a mut Entry> {
+    let mask = (*dict).capacity - 1;
+    let mut index = hash as usize & mask;
+    loop {
+        let entry = &mut *(*dict).entries.add(index);
+        match entry.state {
+            SLOT_EMPTY => return None,
+            SLOT_FULL if entry.hash == hash && keys_equal((*dict).key_kind, entry.key, key) => return Some(entry),
+            _ => index = (index + 1) & mask,
+        }
+    }
+}
+
+// Rebuilds the table without deleted entries, doubling it if it is more than half full
+unsafe fn rehash(dict: *mut Dict) {
+    let old_entries = (*dict).entries;
+    let old_capacity = (*dict).capacity;
+    if (*dict).len * 2 >= old_capacity {
+        (*dict).capacity *= 2;
+    }
+    
+    (*dict).entries = empty_entries((*dict).capacity);
+    let mask = (*dict).capacity - 1;
+    for old in slice::from_raw_parts(old_entries, old_capacity) {
+        if old.state != SLOT_FULL {
+            continue;
+        }
+        let mut index = old.hash as usize & mask;
+        while (*(*dict).entries.add(index)).state == SLOT_FULL {
+            index = (index + 1) & mask;
+        }
+        ptr::write((*dict).entries.add(index), Entry { state: SLOT_FULL, ..*old });
+    }
+    (*dict).used = (*dict).len;
+    free(old_entries as *mut u8);
+}
+
+unsafe fn unique_dict(slot: *mut *mut Dict) -> *mut Dict {
+    let dict = *slot;
+    if (*dict).header.refcount == 1 {
+        return dict;
+    }
+    
+    let copy = allocate::<Dict>(1);
+    ptr::write(copy, Dict {
+        header: Header { refcount: 1, kind: KIND_DICT },
+        entries: allocate::<Entry>((*dict).capacity),
+        ..*dict
+    });
+    ptr::copy_nonoverlapping((*dict).entries, (*copy).entries, (*dict).capacity);
+    for entry in entries(dict) {
+        if entry.state == SLOT_FULL {
+            retain_key((*dict).key_kind, entry.key);
+            retain_value((*dict).value_kind, entry.value);
+        }
+    }
+    
+    shitrust_release(dict as *mut Header);
+    *slot = copy;
+    copy
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_dict_len(dict: *const Dict) -> i64 {
+    (*dict).len as i64
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_dict_contains(dict: *const Dict, key: u64) -> bool {
+    find_entry(dict, key, hash_key((*dict).key_kind, key)).is_some()
+}
+
+/// Returns the value without adding a reference to it
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_dict_get(dict: *const Dict, key: u64) -> u64 {
+    match find_entry(dict, key, hash_key((*dict).key_kind, key)) {
+        Some(entry) => entry.value,
+        None => fail("key not found in dict"),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_dict_insert(slot: *mut *mut Dict, key: u64, value: u64) {
+    let dict = unique_dict(slot);
+    let hash = hash_key((*dict).key_kind, key);
+    retain_value((*dict).value_kind, value);
+    
+    if let Some(entry) = find_entry(dict, key, hash) {
+        release_value((*dict).value_kind, entry.value);
+        entry.value = value;
+        return;
+    }
+    
+    if ((*dict).used + 1) * 4 > (*dict).capacity * 3 {
+        rehash(dict);
+    }
+    
+    // Reuse the first deleted entry on the probe path if there is one
+    let mask = (*dict).capacity - 1;
+    let mut index = hash as usize & mask;
+    while (*(*dict).entries.add(index)).state == SLOT_FULL {
+        index = (index + 1) & mask;
+    }
+    let entry = &mut *(*dict).entries.add(index);
+    if entry.state == SLOT_EMPTY {
+        (*dict).used += 1;
+    }
+    
+    retain_key((*dict).key_kind, key);
+    *entry = Entry { state: SLOT_FULL, hash, key, value };
+    (*dict).len += 1;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_dict_remove(slot: *mut *mut Dict, key: u64) -> bool {
+    let hash = hash_key((**slot).key_kind, key);
+    if find_entry(*slot, key, hash).is_none() {
+        return false;
+    }
+    
+    let dict = unique_dict(slot);
+    let entry = find_entry(dict, key, hash).unwrap_or_else(|| fail("dict entry disappeared"));
+    release_key((*dict).key_kind, entry.key);
+    release_value((*dict).value_kind, entry.value);
+    entry.state = SLOT_DELETED;
+    (*dict).len -= 1;
+    true
+}
+
+/// A new list of the dict's keys, in no particular order
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_dict_keys(dict: *const Dict) -> *mut List {
+    let key_kind = if (*dict).key_kind == KEY_STRING { VALUE_OBJECT } else { VALUE_PLAIN };
+    let mut list = shitrust_list_new(key_kind as u64, (*dict).len);
+    for entry in entries(dict) {
+        if entry.state == SLOT_FULL {
+            shitrust_list_push(&mut list, entry.key);
+        }
+    }
+    list
+}
+
+// ---------------------------------------------------------------------------
+// Closures
+// ---------------------------------------------------------------------------
+
+/// A new environment with `size` bytes for captured variables, aligned for any of them
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_env_new(size: usize, drop: Option<unsafe extern "C" fn(*mut u8)>) -> *mut Environment {
+    let environment = allocate::<Environment>(1);
+    ptr::write(environment, Environment {
+        header: Header { refcount: 1, kind: KIND_ENVIRONMENT },
+        drop,
+        data: allocate::<u64>(size.div_ceil(8)) as *mut u8,
+    });
+    environment
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn shitrust_env_data(environment: *const Environment) -> *mut u8 {
+    (*environment).data
+}
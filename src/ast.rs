@@ -1,10 +1,14 @@
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Type {
     Int,
     Float,
     Bool,
     String,
     Char,
+    BigInt,
+    Decimal,
     List(Box<Type>),
     Dict(Box<Type>, Box<Type>),
     Tuple(Vec<Type>),
@@ -16,6 +20,10 @@ pub enum Type {
     Custom(String),
     Function(Vec<Type>, Box<Type>),
     Reference(Box<Type>, bool),
+    /// A raw C pointer, `ptr`, for functions in `extern` blocks
+    Pointer,
+    /// A 32-bit C `int`, `c_int`, for functions in `extern` blocks. ShitRust sees it as `int`.
+    CInt,
     Array(Box<Type>, Option<usize>),
     Generic(String, Vec<Type>),
     Trait(String),
@@ -23,10 +31,13 @@ pub enum Type {
     Never,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Literal {
     Int(i64),
     Float(f64),
+    // Exact numbers keep their source digits, e.g. "12345678901234567890" or "19.99"
+    BigInt(String),
+    Decimal(String),
     Bool(bool),
     String(String),
     Char(char),
@@ -37,7 +48,7 @@ pub enum Literal {
     None,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BinOp {
     Add,
     Sub,
@@ -63,14 +74,22 @@ pub enum BinOp {
     Exponent,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UnaryOp {
     Neg,
     Not,
     BitNot,
 }
 
-#[derive(Debug, Clone)]
+/// The int methods with explicit overflow behavior, named `<mode>_<operation>`. The `checked_*`
+/// ones return an option.
+pub const INT_METHODS: &[&str] = &[
+    "wrapping_add", "wrapping_sub", "wrapping_mul", "wrapping_div", "wrapping_rem", "wrapping_pow", "wrapping_neg",
+    "checked_add", "checked_sub", "checked_mul", "checked_div", "checked_rem", "checked_pow", "checked_neg",
+    "saturating_add", "saturating_sub", "saturating_mul", "saturating_div", "saturating_pow", "saturating_neg",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expr {
     Literal(Literal),
     Identifier(String),
@@ -129,6 +148,10 @@ pub enum Expr {
         expr: Box<Expr>,
         target_type: Type,
     },
+    TypeTest {
+        expr: Box<Expr>,
+        target_type: Type,
+    },
     ListComprehension {
         expr: Box<Expr>,
         iterable: Box<Expr>,
@@ -147,16 +170,28 @@ pub enum Expr {
         initial: Box<Expr>,
         chain: Vec<Box<Expr>>,
     },
+    /// The block body of a lambda, whose value is what the block returns
+    Block(Vec<Stmt>),
 }
 
-#[derive(Debug, Clone)]
+impl Expr {
+    /// The statements run by a lambda with this body: a block's own, or a `return` of the expression
+    pub fn lambda_body(&self) -> Vec<Stmt> {
+        match self {
+            Expr::Block(stmts) => stmts.clone(),
+            expr => vec![Stmt::Return(Some(expr.clone()))],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum OptionalChainItem {
     Field(String),
     Method(String, Vec<Expr>),
     Index(Box<Expr>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Stmt {
     Expr(Expr),
     Let {
@@ -198,6 +233,7 @@ pub enum Stmt {
         is_async: bool,
         is_public: bool,
         generic_params: Vec<String>,
+        bounds: Vec<(String, Vec<String>)>,
     },
     Struct {
         name: String,
@@ -239,9 +275,13 @@ pub enum Stmt {
     },
     Impl {
         trait_name: Option<String>,
+        // The type arguments written after the trait and type names, e.g. `int` in `Box<int>`
+        trait_args: Vec<Type>,
         type_name: String,
+        type_args: Vec<Type>,
         methods: Vec<Stmt>,
         generic_params: Vec<String>,
+        bounds: Vec<(String, Vec<String>)>,
     },
     Const {
         name: String,
@@ -255,9 +295,76 @@ pub enum Stmt {
         is_public: bool,
         generic_params: Vec<String>,
     },
+    /// `extern "C" { fn ...; }`: functions of a C library, see `#[link(...)]`
+    Extern {
+        abi: String,
+        functions: Vec<ExternFunction>,
+    },
+    Attributed {
+        attributes: Vec<Attribute>,
+        stmt: Box<Stmt>,
+    },
+    /// A statement with the line and column it starts at, see `Parser::with_locations`
+    Located {
+        line: usize,
+        column: usize,
+        stmt: Box<Stmt>,
+    },
+}
+
+impl Stmt {
+    /// The statement with any `#[...]` attributes and source locations stripped off
+    pub fn without_attributes(&self) -> &Stmt {
+        match self {
+            Stmt::Attributed { stmt, .. } | Stmt::Located { stmt, .. } => stmt.without_attributes(),
+            _ => self,
+        }
+    }
+    
+    /// The `#[...]` attributes on the statement, outermost first
+    pub fn attributes(&self) -> Vec<&Attribute> {
+        match self {
+            Stmt::Attributed { attributes, stmt } => attributes.iter().chain(stmt.attributes()).collect(),
+            Stmt::Located { stmt, .. } => stmt.attributes(),
+            _ => Vec::new(),
+        }
+    }
+    
+    /// The libraries named by `#[link(...)]` attributes on the statement
+    pub fn link_libraries(&self) -> Vec<String> {
+        self.attributes()
+            .into_iter()
+            .filter(|attribute| attribute.name == "link")
+            .flat_map(|attribute| attribute.args.iter().cloned())
+            .collect()
+    }
+    
+    /// The line and column the statement starts at, if the parser recorded them
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match self {
+            Stmt::Located { line, column, .. } => Some((*line, *column)),
+            Stmt::Attributed { stmt, .. } => stmt.location(),
+            _ => None,
+        }
+    }
+}
+
+/// An attribute such as `#[allow(unused_variables)]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attribute {
+    pub name: String,
+    pub args: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+/// A function declared in an `extern` block, implemented by a C library
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternFunction {
+    pub name: String,
+    pub params: Vec<(String, Type)>,
+    pub return_type: Type,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TraitMethod {
     pub name: String,
     pub params: Vec<(String, Type)>,
@@ -266,7 +373,7 @@ pub struct TraitMethod {
     pub is_async: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Pattern {
     Wildcard,
     Literal(Literal),
@@ -287,7 +394,207 @@ pub enum Pattern {
     },
 }
 
-#[derive(Debug, Clone)]
+/// Calls `visit` on every expression in `statements`, including those nested in other expressions
+/// and statements: in source order, each before the expressions inside it. The position of an
+/// expression in this order is its id, which is the same in every copy of the AST.
+pub fn walk_exprs(statements: &[Stmt], visit: &mut dyn FnMut(&Expr)) {
+    for stmt in statements {
+        walk_stmt(stmt, visit);
+    }
+}
+
+/// `walk_exprs` for changing the expressions; the ids are the same
+pub fn walk_exprs_mut(statements: &mut [Stmt], visit: &mut dyn FnMut(&mut Expr)) {
+    for stmt in statements {
+        walk_stmt_mut(stmt, visit);
+    }
+}
+
+// Both walks are this code, with `$mutability` being nothing or `mut`, so that they cannot
+// visit expressions in different orders
+macro_rules! expr_walk {
+    ($walk_stmt:ident, $walk_expr:ident, $walk_literal:ident $(, $mutability:tt)?) => {
+        fn $walk_stmt(stmt: &$($mutability)? Stmt, visit: &mut dyn FnMut(&$($mutability)? Expr)) {
+            match stmt {
+                Stmt::Expr(expr) | Stmt::Let { value: expr, .. } | Stmt::Const { value: expr, .. } |
+                Stmt::Return(Some(expr)) => $walk_expr(expr, visit),
+                Stmt::Assign { target, value } => {
+                    $walk_expr(target, visit);
+                    $walk_expr(value, visit);
+                },
+                Stmt::If { condition, then_block, else_block } => {
+                    $walk_expr(condition, visit);
+                    for stmt in then_block {
+                        $walk_stmt(stmt, visit);
+                    }
+                    if let Some(else_block) = else_block {
+                        for stmt in else_block {
+                            $walk_stmt(stmt, visit);
+                        }
+                    }
+                },
+                Stmt::While { condition: expr, body } | Stmt::For { iterator: expr, body, .. } => {
+                    $walk_expr(expr, visit);
+                    for stmt in body {
+                        $walk_stmt(stmt, visit);
+                    }
+                },
+                Stmt::Match { expr, arms } => {
+                    $walk_expr(expr, visit);
+                    for (_, body) in arms {
+                        for stmt in body {
+                            $walk_stmt(stmt, visit);
+                        }
+                    }
+                },
+                Stmt::Function { body, .. } | Stmt::Struct { methods: body, .. } | Stmt::Impl { methods: body, .. } |
+                Stmt::Async { block: body } | Stmt::Loop { body } => {
+                    for stmt in body {
+                        $walk_stmt(stmt, visit);
+                    }
+                },
+                Stmt::Try { block, catch_blocks, finally_block } => {
+                    for stmt in block {
+                        $walk_stmt(stmt, visit);
+                    }
+                    for (_, body) in catch_blocks {
+                        for stmt in body {
+                            $walk_stmt(stmt, visit);
+                        }
+                    }
+                    if let Some(finally_block) = finally_block {
+                        for stmt in finally_block {
+                            $walk_stmt(stmt, visit);
+                        }
+                    }
+                },
+                Stmt::Trait { methods, .. } => {
+                    for method in methods {
+                        if let TraitMethod { body: Some(body), .. } = method {
+                            for stmt in body {
+                                $walk_stmt(stmt, visit);
+                            }
+                        }
+                    }
+                },
+                Stmt::Attributed { stmt, .. } | Stmt::Located { stmt, .. } => $walk_stmt(stmt, visit),
+                Stmt::Return(None) | Stmt::Break | Stmt::Continue | Stmt::Enum { .. } | Stmt::Import { .. } |
+                Stmt::Use { .. } | Stmt::TypeAlias { .. } | Stmt::Extern { .. } => (),
+            }
+        }
+        
+        fn $walk_expr(expr: &$($mutability)? Expr, visit: &mut dyn FnMut(&$($mutability)? Expr)) {
+            visit(&$($mutability)? *expr);
+            match expr {
+                Expr::Literal(literal) => $walk_literal(literal, visit),
+                Expr::Identifier(_) => (),
+                Expr::BinaryOp { left, right, .. } => {
+                    $walk_expr(left, visit);
+                    $walk_expr(right, visit);
+                },
+                Expr::UnaryOp { expr, .. } | Expr::Await { expr } | Expr::Try { expr } |
+                Expr::TypeCast { expr, .. } | Expr::TypeTest { expr, .. } | Expr::FieldAccess { object: expr, .. } |
+                Expr::Lambda { body: expr, .. } => $walk_expr(expr, visit),
+                Expr::Call { func: callee, args } | Expr::MethodCall { object: callee, args, .. } => {
+                    $walk_expr(callee, visit);
+                    for arg in args {
+                        $walk_expr(arg, visit);
+                    }
+                },
+                Expr::Index { target, index } => {
+                    $walk_expr(target, visit);
+                    $walk_expr(index, visit);
+                },
+                Expr::TernaryIf { condition, then_expr, else_expr } => {
+                    $walk_expr(condition, visit);
+                    $walk_expr(then_expr, visit);
+                    $walk_expr(else_expr, visit);
+                },
+                Expr::RangeExpr { start, end, .. } => {
+                    if let Some(start) = start {
+                        $walk_expr(start, visit);
+                    }
+                    if let Some(end) = end {
+                        $walk_expr(end, visit);
+                    }
+                },
+                Expr::OptionalChain { expr, chain } => {
+                    $walk_expr(expr, visit);
+                    for item in chain {
+                        match item {
+                            OptionalChainItem::Field(_) => (),
+                            OptionalChainItem::Method(_, args) => {
+                                for arg in args {
+                                    $walk_expr(arg, visit);
+                                }
+                            },
+                            OptionalChainItem::Index(index) => $walk_expr(index, visit),
+                        }
+                    }
+                },
+                Expr::ListComprehension { expr, iterable, condition, .. } => {
+                    $walk_expr(expr, visit);
+                    $walk_expr(iterable, visit);
+                    if let Some(condition) = condition {
+                        $walk_expr(condition, visit);
+                    }
+                },
+                Expr::Match { expr, arms } => {
+                    $walk_expr(expr, visit);
+                    for (_, body) in arms {
+                        $walk_expr(body, visit);
+                    }
+                },
+                Expr::StructInit { fields, .. } => {
+                    for (_, value) in fields {
+                        $walk_expr(value, visit);
+                    }
+                },
+                Expr::PipelineChain { initial, chain } => {
+                    $walk_expr(initial, visit);
+                    for stage in chain {
+                        $walk_expr(stage, visit);
+                    }
+                },
+                Expr::Block(stmts) => {
+                    for stmt in stmts {
+                        $walk_stmt(stmt, visit);
+                    }
+                },
+            }
+        }
+        
+        fn $walk_literal(literal: &$($mutability)? Literal, visit: &mut dyn FnMut(&$($mutability)? Expr)) {
+            match literal {
+                Literal::List(items) | Literal::Tuple(items) => {
+                    for item in items {
+                        $walk_expr(item, visit);
+                    }
+                },
+                Literal::Dict(entries) => {
+                    for (key, value) in entries {
+                        $walk_expr(key, visit);
+                        $walk_expr(value, visit);
+                    }
+                },
+                Literal::Range { start, end, .. } => {
+                    if let Some(start) = start {
+                        $walk_expr(start, visit);
+                    }
+                    if let Some(end) = end {
+                        $walk_expr(end, visit);
+                    }
+                },
+                _ => (),
+            }
+        }
+    };
+}
+
+expr_walk!(walk_stmt, walk_expr, walk_literal);
+expr_walk!(walk_stmt_mut, walk_expr_mut, walk_literal_mut, mut);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
     pub statements: Vec<Stmt>,
     pub source_file: Option<String>,
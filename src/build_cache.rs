@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::error::ShitRustError;
+
+/// Where `shitrust compile` keeps its cache unless told otherwise, relative to the working directory
+pub const DEFAULT_CACHE_DIR: &str = "target/shitrust";
+
+/// What linking a module needs besides its object file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModuleArtifacts {
+    /// The module's object file
+    #[serde(skip)]
+    pub object: PathBuf,
+    /// Libraries named by the module's `#[link(...)]` attributes
+    pub link_libraries: Vec<String>,
+    /// The C header of a library's `#[export]` functions
+    pub c_header: Option<String>,
+}
+
+/// Object files of compiled modules, keyed by a hash of everything they were built from.
+/// An entry is `<module>-<key>.o` with a `<module>-<key>.json` describing it. Each module
+/// has one entry, the one of its latest build.
+pub struct BuildCache {
+    dir: PathBuf,
+}
+
+impl BuildCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        BuildCache { dir: dir.into() }
+    }
+
+    /// Hashes the inputs of a build into a cache key
+    pub fn key(inputs: &[&str]) -> String {
+        let mut hasher = Sha256::new();
+        for input in inputs {
+            // Lengths keep ("ab", "c") and ("a", "bc") apart
+            hasher.update((input.len() as u64).to_le_bytes());
+            hasher.update(input.as_bytes());
+        }
+        hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// The artifacts of `module` built with `key`, if an earlier build stored them
+    pub fn load(&self, module: &str, key: &str) -> Option<ModuleArtifacts> {
+        let object = self.entry_path(module, key, "o");
+        if !object.exists() {
+            return None;
+        }
+
+        // An entry that cannot be read is rebuilt and overwritten
+        let metadata = fs::read_to_string(self.entry_path(module, key, "json")).ok()?;
+        let artifacts: ModuleArtifacts = serde_json::from_str(&metadata).ok()?;
+        Some(ModuleArtifacts { object, ..artifacts })
+    }
+
+    /// Copies the object file of `artifacts` into the cache, replacing the module's previous entry.
+    /// Returns the artifacts with the cached copy.
+    pub fn store(&self, module: &str, key: &str, artifacts: ModuleArtifacts) -> Result<ModuleArtifacts, ShitRustError> {
+        let io_error = |e: std::io::Error| ShitRustError::IOException(
+            format!("Failed to write to the build cache in {}: {}", self.dir.display(), e)
+        );
+        fs::create_dir_all(&self.dir).map_err(io_error)?;
+
+        let metadata = serde_json::to_string_pretty(&artifacts)
+            .map_err(|e| ShitRustError::RuntimeError(format!("Failed to describe the cached module: {}", e)))?;
+
+        // The metadata goes first and the object is renamed into place last, so a concurrent
+        // build never sees an entry with only part of its files
+        let object = self.entry_path(module, key, "o");
+        let partial = self.dir.join(format!("partial-{}-{}.o", key, std::process::id()));
+        fs::write(self.entry_path(module, key, "json"), metadata).map_err(io_error)?;
+        fs::copy(&artifacts.object, &partial).map_err(io_error)?;
+        fs::rename(&partial, &object).map_err(io_error)?;
+
+        self.evict(module, key).map_err(io_error)?;
+        Ok(ModuleArtifacts { object, ..artifacts })
+    }
+
+    // Removes the entries of `module` other than the one with `key`
+    fn evict(&self, module: &str, key: &str) -> std::io::Result<()> {
+        let prefix = format!("{}-", module);
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            // Only `<module>-<key>.<extension>`, not the entries of a module named `<module>-<suffix>`
+            let Some((old_key, extension)) = file_name.strip_prefix(&prefix).and_then(|rest| rest.split_once('.')) else {
+                continue;
+            };
+            let is_key = old_key.len() == 64 && old_key.bytes().all(|byte| byte.is_ascii_hexdigit());
+            if is_key && old_key != key && (extension == "o" || extension == "json") {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the cache directory and everything in it. Returns whether there was one.
+    pub fn clean(&self) -> Result<bool, ShitRustError> {
+        if !self.dir.exists() {
+            return Ok(false);
+        }
+        fs::remove_dir_all(&self.dir)
+            .map_err(|e| ShitRustError::IOException(format!("Failed to remove {}: {}", self.dir.display(), e)))?;
+        Ok(true)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, module: &str, key: &str, extension: &str) -> PathBuf {
+        self.dir.join(format!("{}-{}.{}", module, key, extension))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifacts(dir: &Path, contents: &str) -> ModuleArtifacts {
+        let object = dir.join("module.o");
+        fs::write(&object, contents).unwrap();
+        ModuleArtifacts { object, link_libraries: vec!["m".to_string()], c_header: None }
+    }
+
+    fn key(source: &str) -> String {
+        BuildCache::key(&[source])
+    }
+
+    #[test]
+    fn keys_separate_their_inputs() {
+        assert_eq!(BuildCache::key(&["ab", "c"]), BuildCache::key(&["ab", "c"]));
+        assert_ne!(BuildCache::key(&["ab", "c"]), BuildCache::key(&["a", "bc"]));
+        assert_eq!(BuildCache::key(&["x"]).len(), 64);
+    }
+
+    #[test]
+    fn stored_entries_are_loaded() {
+        let scratch = tempfile::tempdir().unwrap();
+        let cache = BuildCache::new(scratch.path().join("cache"));
+        assert!(cache.load("main", &key("v1")).is_none());
+
+        let stored = cache.store("main", &key("v1"), artifacts(scratch.path(), "v1")).unwrap();
+        let loaded = cache.load("main", &key("v1")).unwrap();
+        assert_eq!(loaded.object, stored.object);
+        assert_eq!(loaded.link_libraries, vec!["m".to_string()]);
+        assert_eq!(fs::read_to_string(&loaded.object).unwrap(), "v1");
+    }
+
+    #[test]
+    fn storing_replaces_the_previous_entry_of_the_module() {
+        let scratch = tempfile::tempdir().unwrap();
+        let cache = BuildCache::new(scratch.path().join("cache"));
+
+        cache.store("main", &key("v1"), artifacts(scratch.path(), "v1")).unwrap();
+        cache.store("main-test", &key("v1"), artifacts(scratch.path(), "test")).unwrap();
+        cache.store("main", &key("v2"), artifacts(scratch.path(), "v2")).unwrap();
+
+        assert!(cache.load("main", &key("v1")).is_none());
+        assert!(cache.load("main", &key("v2")).is_some());
+        assert!(cache.load("main-test", &key("v1")).is_some());
+        assert_eq!(fs::read_dir(cache.dir()).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn clean_removes_the_cache() {
+        let scratch = tempfile::tempdir().unwrap();
+        let cache = BuildCache::new(scratch.path().join("cache"));
+        assert!(!cache.clean().unwrap());
+
+        cache.store("main", &key("v1"), artifacts(scratch.path(), "v1")).unwrap();
+        assert!(cache.clean().unwrap());
+        assert!(!cache.dir().exists());
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use serde::Deserialize;
+use crate::ast::Program;
+use crate::error::ShitRustError;
+
+/// Ends a bundled executable, after the program and its length
+const BUNDLE_MAGIC: &[u8; 16] = b"SHITRUST-BUNDLE1";
+
+/// Writes an executable that runs `program` with the interpreter: a copy of `runtime`, the
+/// `shitrust` executable, with the program appended as JSON. The program's length and
+/// `BUNDLE_MAGIC` follow it, for `embedded_program` to find it from the end of the file.
+pub fn write_bundle(program: &Program, runtime: &Path, output: &Path) -> Result<(), ShitRustError> {
+    let io_error = |e: std::io::Error| ShitRustError::IOException(format!("Failed to write {}: {}", output.display(), e));
+
+    let mut bundle = fs::read(runtime)
+        .map_err(|e| ShitRustError::IOException(format!("Failed to read the interpreter {}: {}", runtime.display(), e)))?;
+
+    let payload = serde_json::to_vec(program)
+        .map_err(|e| ShitRustError::RuntimeError(format!("Failed to serialize the program: {}", e)))?;
+    // A bundle that cannot read its program back should fail now, not when it is run
+    read_program(&payload)?;
+    bundle.extend_from_slice(&payload);
+    bundle.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bundle.extend_from_slice(BUNDLE_MAGIC);
+
+    fs::write(output, &bundle).map_err(io_error)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(output, fs::Permissions::from_mode(0o755)).map_err(io_error)?;
+    }
+
+    Ok(())
+}
+
+/// The program bundled into the running executable, if it is a bundle. Only an executable that
+/// ends with a bundle trailer but has a broken program in front of it is an error; anything
+/// that keeps the trailer from being read means the executable is not a bundle.
+pub fn embedded_program() -> Result<Option<Program>, ShitRustError> {
+    let io_error = |e: std::io::Error| ShitRustError::IOException(format!("Failed to read the bundled program: {}", e));
+
+    let Some((mut file, payload_end, length)) = bundle_trailer() else {
+        return Ok(None);
+    };
+
+    let start = payload_end.checked_sub(length as u64)
+        .ok_or_else(|| ShitRustError::RuntimeError("The bundled program is truncated".to_string()))?;
+    let mut payload = vec![0; length];
+    file.seek(SeekFrom::Start(start)).map_err(io_error)?;
+    file.read_exact(&mut payload).map_err(io_error)?;
+
+    read_program(&payload).map(Some)
+}
+
+/// The running executable, where its bundled program ends and the program's length, if the
+/// executable ends with a bundle trailer
+fn bundle_trailer() -> Option<(File, u64, usize)> {
+    let mut file = File::open(std::env::current_exe().ok()?).ok()?;
+
+    // Only the trailer is read unless there is a program in front of it
+    let trailer_length = 8 + BUNDLE_MAGIC.len() as u64;
+    let payload_end = file.metadata().ok()?.len().checked_sub(trailer_length)?;
+
+    let mut trailer = vec![0; trailer_length as usize];
+    file.seek(SeekFrom::Start(payload_end)).ok()?;
+    file.read_exact(&mut trailer).ok()?;
+    let length = payload_length(&trailer)?;
+    Some((file, payload_end, length))
+}
+
+/// Decodes a bundled program. ASTs nest as deeply as the expressions in the program do, so
+/// the JSON is read without serde_json's limit of 128 levels.
+fn read_program(payload: &[u8]) -> Result<Program, ShitRustError> {
+    let mut deserializer = serde_json::Deserializer::from_slice(payload);
+    deserializer.disable_recursion_limit();
+    Program::deserialize(&mut deserializer)
+        .and_then(|program| deserializer.end().map(|_| program))
+        .map_err(|e| ShitRustError::RuntimeError(format!("The bundled program is invalid: {}", e)))
+}
+
+/// The length of the program at the end of `bytes`, when they end with a bundle trailer
+fn payload_length(bytes: &[u8]) -> Option<usize> {
+    let magic_start = bytes.len().checked_sub(BUNDLE_MAGIC.len())?;
+    if &bytes[magic_start..] != BUNDLE_MAGIC {
+        return None;
+    }
+
+    let length_start = magic_start.checked_sub(8)?;
+    let length = u64::from_le_bytes(bytes[length_start..magic_start].try_into().ok()?);
+    Some(length as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Program {
+        let tokens = crate::lexer::Lexer::new(source).scan_tokens().unwrap();
+        crate::parser::Parser::new(tokens).parse().unwrap()
+    }
+
+    #[test]
+    fn trailers_give_the_payload_length() {
+        let mut bytes = b"payload".to_vec();
+        bytes.extend_from_slice(&7u64.to_le_bytes());
+        bytes.extend_from_slice(BUNDLE_MAGIC);
+        assert_eq!(payload_length(&bytes), Some(7));
+
+        assert_eq!(payload_length(b"not a bundle"), None);
+        assert_eq!(payload_length(BUNDLE_MAGIC), None);
+    }
+
+    #[test]
+    fn deeply_nested_programs_round_trip() {
+        let source = format!("let x = {};\n", vec!["1"; 100].join(" + "));
+        let program = parse(&source);
+
+        let payload = serde_json::to_vec(&program).unwrap();
+        let read = read_program(&payload).unwrap();
+        assert_eq!(format!("{:?}", read), format!("{:?}", program));
+    }
+
+    #[test]
+    fn corrupt_payloads_are_errors() {
+        assert!(read_program(b"{\"statements\": [").is_err());
+        assert!(read_program(b"{\"statements\": [], \"source_file\": null}").is_ok());
+        assert!(read_program(b"{\"statements\": [], \"source_file\": null} trailing").is_err());
+    }
+
+    #[test]
+    fn the_test_executable_is_not_a_bundle() {
+        assert!(matches!(embedded_program(), Ok(None)));
+    }
+}
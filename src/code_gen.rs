@@ -1,22 +1,197 @@
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
+use inkwell::intrinsics::Intrinsic;
 use inkwell::context::Context;
-use inkwell::module::Module;
-use inkwell::targets::{InitializationConfig, Target};
-use inkwell::values::{BasicValueEnum, FunctionValue, PointerValue};
-use inkwell::types::BasicTypeEnum;
+use inkwell::debug_info::{
+    debug_metadata_version, AsDIScope, DICompileUnit, DIFlags, DIFlagsConstants, DIScope, DIType,
+    DWARFEmissionKind, DWARFSourceLanguage, DebugInfoBuilder,
+};
+use inkwell::module::{FlagBehavior, Linkage, Module};
+use inkwell::passes::PassBuilderOptions;
+use inkwell::targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetData, TargetMachine, TargetTriple};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, CallSiteValue, FunctionValue, IntValue, PointerValue};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType, StructType};
 use inkwell::OptimizationLevel;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::Path;
-use crate::ast::{Program, Stmt, Expr, Literal, Type as AstType, BinOp, UnaryOp};
+use crate::ast::{walk_exprs, Program, Stmt, Expr, ExternFunction, Literal, OptionalChainItem, Pattern, Type as AstType, BinOp, UnaryOp};
+use crate::compiler::CrateType;
 use crate::error::ShitRustError;
+use crate::runtime::{KEY_INT, KEY_STRING, VALUE_OBJECT, VALUE_PLAIN};
+
+// A struct lowered to a named LLVM struct type with its fields in declaration order
+#[derive(Clone)]
+struct StructLayout<'ctx> {
+    llvm_type: StructType<'ctx>,
+    fields: Vec<String>,
+}
+
+// An enum lowered to a tagged union `{ i32 tag, [N x i64] payload }`. Each variant's payload
+// is a struct type of its own that is stored at the start of the payload words.
+#[derive(Clone)]
+struct EnumLayout<'ctx> {
+    llvm_type: StructType<'ctx>,
+    variants: Vec<(String, StructType<'ctx>)>,
+}
+
+// A generic struct or enum, lowered once for each list of type arguments it is used with. The
+// specializations are named like `Pair<int>`, which is also how they are written in ShitRust.
+#[derive(Clone)]
+struct GenericType {
+    generic_params: Vec<String>,
+    shape: GenericShape,
+    // Methods of the type, including those of `impl` blocks for it
+    methods: Vec<Stmt>,
+}
+
+#[derive(Clone)]
+enum GenericShape {
+    Struct(Vec<(String, AstType)>),
+    Enum(Vec<(String, Vec<AstType>)>),
+}
+
+// A specialized function or method whose body is generated after the rest of the program, with
+// the type parameters of its definition bound to LLVM types
+struct PendingBody<'ctx> {
+    function: FunctionValue<'ctx>,
+    definition: Stmt,
+    type_params: HashMap<String, BasicTypeEnum<'ctx>>,
+    // The specialized type a method belongs to
+    receiver: Option<String>,
+}
+
+// Where `continue` and `break` jump to inside a loop
+struct LoopTargets<'ctx> {
+    continue_block: BasicBlock<'ctx>,
+    break_block: BasicBlock<'ctx>,
+}
+
+// A lowered match arm: the block its body starts in and the stack slots its pattern binds
+struct MatchArm<'ctx> {
+    block: BasicBlock<'ctx>,
+    bindings: Vec<(String, PointerValue<'ctx>, BasicTypeEnum<'ctx>)>,
+}
+
+// Strings, lists and dicts are pointers to reference-counted objects of the runtime library.
+// Each type gets a named wrapper struct, such as `shitrust.List<i64>`, so the element types
+// of a value are known from its LLVM type.
+#[derive(Clone, Copy)]
+enum HeapType<'ctx> {
+    String,
+    List(BasicTypeEnum<'ctx>),
+    Dict(BasicTypeEnum<'ctx>, BasicTypeEnum<'ctx>),
+}
+
+// A lambda being lifted to a function of its own, see `generate_lambda`
+struct LiftedLambda<'a, 'ctx> {
+    name: String,
+    params: Vec<String>,
+    param_types: Vec<BasicTypeEnum<'ctx>>,
+    // The struct type of the environment and the variables stored in its fields
+    layout: StructType<'ctx>,
+    captured: Vec<String>,
+    body: &'a Expr,
+}
+
+// What a function takes in a leading pointer parameter, before its declared parameters
+enum LeadingParam<'ctx> {
+    None,
+    // The receiver of a method, bound as `this` and `self`
+    Receiver(BasicTypeEnum<'ctx>),
+    // The environment of a lambda, holding the variables named in `captured` as the fields of `layout`
+    Environment { layout: StructType<'ctx>, captured: Vec<String> },
+}
+
+// Per-function state for reference counting and returns
+#[derive(Default)]
+struct FunctionState<'ctx> {
+    // Stack slots that own the references stored in them; released when the function returns
+    owned_slots: Vec<(PointerValue<'ctx>, BasicTypeEnum<'ctx>)>,
+    // Owned values produced while evaluating the current statements, released at their end
+    temporaries: Vec<(PointerValue<'ctx>, BasicTypeEnum<'ctx>)>,
+    // Every `return` stores its value in the slot and branches to the return block
+    return_block: Option<BasicBlock<'ctx>>,
+    return_slot: Option<(PointerValue<'ctx>, BasicTypeEnum<'ctx>)>,
+}
+
+// The function stubs of interpreted functions call, see `generate_interpreter_call`. It takes
+// the function's index and its arguments as 64-bit words, and returns the result as one.
+pub const INTERPRETER_ENTRY: &str = "shitrust_interpret_function";
+
+// DWARF type encodings of the basic types
+const DW_ATE_ADDRESS: u32 = 0x01;
+const DW_ATE_BOOLEAN: u32 = 0x02;
+const DW_ATE_FLOAT: u32 = 0x04;
+const DW_ATE_SIGNED: u32 = 0x05;
+const DW_ATE_UTF: u32 = 0x10;
+
+// DWARF debug info of the module, created by `generate_code` when it is enabled
+struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    compile_unit: DICompileUnit<'ctx>,
+    // Enclosing scopes, innermost last, each with the position that was current when it was entered
+    scopes: Vec<(DIScope<'ctx>, u32, u32)>,
+    // Line and column of the statement being generated
+    line: u32,
+    column: u32,
+    // Types described so far, by how they are written in ShitRust
+    types: HashMap<String, DIType<'ctx>>,
+}
 
 pub struct CodeGen<'ctx> {
     context: &'ctx Context,
     module: Module<'ctx>,
     builder: Builder<'ctx>,
-    named_values: HashMap<String, PointerValue<'ctx>>,
+    // Local variables: their stack slot and the type stored in it
+    named_values: HashMap<String, (PointerValue<'ctx>, BasicTypeEnum<'ctx>)>,
     current_function: Option<FunctionValue<'ctx>>,
-    printf_function: FunctionValue<'ctx>,
+    function_state: FunctionState<'ctx>,
+    struct_layouts: HashMap<String, StructLayout<'ctx>>,
+    enum_layouts: HashMap<String, EnumLayout<'ctx>>,
+    // Symbols of methods, which take the receiver as a leading `this` pointer unless they are static
+    methods: HashSet<String>,
+    static_methods: HashSet<String>,
+    // The type `Self` refers to while lowering methods
+    self_type: Option<String>,
+    // Enclosing loops, innermost last
+    loop_stack: Vec<LoopTargets<'ctx>>,
+    // Wrapper struct names of the string, list and dict types created so far
+    heap_types: RefCell<HashMap<String, HeapType<'ctx>>>,
+    // Signatures of the function types created so far, by the name of their closure struct
+    closure_types: RefCell<HashMap<String, FunctionType<'ctx>>>,
+    // Numbers the functions lambdas are lifted to
+    lambda_count: usize,
+    // Functions declared in `extern` blocks, and the libraries their `#[link(...)]` attributes name
+    extern_functions: HashMap<String, ExternFunction>,
+    link_libraries: Vec<String>,
+    // Signatures of the `#[export]` functions, which libraries make callable from C
+    exports: Vec<ExternFunction>,
+    crate_type: CrateType,
+    // With `run --jit`, functions the code generator cannot lower are run by the interpreter.
+    // Their signatures, indexed by the number their stubs pass to `INTERPRETER_ENTRY`.
+    interpreter_fallback: bool,
+    interpreted_functions: Vec<ExternFunction>,
+    // Names bound by top-level `let`s, which only the compiled `main` has values for
+    module_bindings: HashSet<String>,
+    // Generic functions and types by name, and the type arguments the type checker found them used with
+    generic_functions: HashMap<String, Stmt>,
+    generic_types: HashMap<String, GenericType>,
+    instantiations: HashMap<String, Vec<Vec<AstType>>>,
+    // The generic type and type arguments of each specialized struct and enum, by its name
+    specialized_types: HashMap<String, (String, Vec<BasicTypeEnum<'ctx>>)>,
+    // What the type parameters stand for while a specialization is lowered
+    type_params: HashMap<String, BasicTypeEnum<'ctx>>,
+    pending_bodies: Vec<PendingBody<'ctx>>,
+    optimization_level: OptimizationLevel,
+    // Machine the module is compiled for; the host unless a triple is given
+    target_triple: Option<String>,
+    target_cpu: String,
+    target_features: String,
+    reloc_mode: RelocMode,
+    debug_info_enabled: bool,
+    debug_info: Option<DebugInfo<'ctx>>,
 }
 
 impl<'ctx> CodeGen<'ctx> {
@@ -27,107 +202,800 @@ impl<'ctx> CodeGen<'ctx> {
         // Initialize LLVM targets for the current machine
         Target::initialize_all(&InitializationConfig::default());
         
-        // Add printf declaration for printing
-        let i8_ptr_type = context.i8_type().ptr_type(Default::default());
-        let printf_type = context.i32_type().fn_type(
-            &[i8_ptr_type.into()], 
-            true  // variadic
-        );
-        let printf_func = module.add_function("printf", printf_type, None);
-        
         CodeGen {
             context,
             module,
             builder,
             named_values: HashMap::new(),
             current_function: None,
-            printf_function: printf_func,
+            function_state: FunctionState::default(),
+            struct_layouts: HashMap::new(),
+            enum_layouts: HashMap::new(),
+            methods: HashSet::new(),
+            static_methods: HashSet::new(),
+            self_type: None,
+            loop_stack: Vec::new(),
+            heap_types: RefCell::new(HashMap::new()),
+            closure_types: RefCell::new(HashMap::new()),
+            lambda_count: 0,
+            extern_functions: HashMap::new(),
+            link_libraries: Vec::new(),
+            exports: Vec::new(),
+            crate_type: CrateType::Bin,
+            interpreter_fallback: false,
+            interpreted_functions: Vec::new(),
+            module_bindings: HashSet::new(),
+            generic_functions: HashMap::new(),
+            generic_types: HashMap::new(),
+            instantiations: HashMap::new(),
+            specialized_types: HashMap::new(),
+            type_params: HashMap::new(),
+            pending_bodies: Vec::new(),
+            optimization_level: OptimizationLevel::Default,
+            target_triple: None,
+            target_cpu: "generic".to_string(),
+            target_features: String::new(),
+            // Position independent code links into the PIE executables compilers produce by default
+            reloc_mode: RelocMode::PIC,
+            debug_info_enabled: false,
+            debug_info: None,
         }
     }
     
+    pub fn set_optimization_level(&mut self, level: crate::compiler::OptimizationLevel) {
+        self.optimization_level = match level {
+            crate::compiler::OptimizationLevel::None => OptimizationLevel::None,
+            crate::compiler::OptimizationLevel::Less => OptimizationLevel::Less,
+            crate::compiler::OptimizationLevel::Default => OptimizationLevel::Default,
+            crate::compiler::OptimizationLevel::Aggressive => OptimizationLevel::Aggressive,
+        };
+    }
+    
+    // Selects the machine to compile for. `native` as the CPU or features means those of the host.
+    pub fn set_target(&mut self, triple: Option<&str>, cpu: Option<&str>, features: Option<&str>) {
+        self.target_triple = triple.map(str::to_string);
+        self.target_cpu = match cpu {
+            Some("native") => TargetMachine::get_host_cpu_name().to_string(),
+            Some(cpu) => cpu.to_string(),
+            None => "generic".to_string(),
+        };
+        self.target_features = match features {
+            Some("native") => TargetMachine::get_host_cpu_features().to_string(),
+            Some(features) => features.to_string(),
+            None => String::new(),
+        };
+    }
+    
+    pub fn set_relocation_model(&mut self, model: crate::compiler::RelocationModel) {
+        self.reloc_mode = match model {
+            crate::compiler::RelocationModel::Pic => RelocMode::PIC,
+            crate::compiler::RelocationModel::Static => RelocMode::Static,
+        };
+    }
+    
+    // Libraries get no `main`, and only their `#[export]` functions are visible outside the module
+    pub fn set_crate_type(&mut self, crate_type: CrateType) {
+        self.crate_type = crate_type;
+    }
+    
+    // Lets functions whose bodies cannot be lowered call into the interpreter instead, see
+    // `generate_interpreter_call`
+    pub fn enable_interpreter_fallback(&mut self) {
+        self.interpreter_fallback = true;
+    }
+    
+    // The functions left to the interpreter, in the order of the indices their stubs pass
+    pub fn interpreted_functions(&self) -> &[ExternFunction] {
+        &self.interpreted_functions
+    }
+    
+    // Statements only carry locations when parsed with `Parser::with_locations`
+    pub fn enable_debug_info(&mut self) {
+        self.debug_info_enabled = true;
+    }
+    
+    // Type arguments of the generic functions and types, from `TypeChecker::instantiations`. They are
+    // specialized up front; uses the checker could not resolve are specialized where they are compiled.
+    pub fn set_instantiations(&mut self, instantiations: &HashMap<String, Vec<Vec<AstType>>>) {
+        self.instantiations = instantiations.clone();
+    }
+    
+    // Libraries named by `#[link(...)]` on the program's extern blocks, for the linker
+    pub fn link_libraries(&self) -> &[String] {
+        &self.link_libraries
+    }
+    
     pub fn generate_code(&mut self, program: &Program) -> Result<(), ShitRustError> {
-        // First pass: register all function declarations
+        // Struct and enum layouts depend on the target's data layout
+        let target_machine = self.create_target_machine()?;
+        self.module.set_triple(&target_machine.get_triple());
+        self.module.set_data_layout(&target_machine.get_target_data().get_data_layout());
+        
+        if self.debug_info_enabled {
+            self.create_debug_info(program.source_file.as_deref());
+        }
+        
+        // First pass: lower struct and enum types, then register all function and method declarations.
+        // Generic ones are kept to be specialized for the type arguments they are used with.
+        self.collect_generics(&program.statements);
+        self.declare_types(&program.statements)?;
+        
         for stmt in &program.statements {
-            if let Stmt::Function { name, params, return_type, .. } = stmt {
-                self.declare_function(name, params, return_type)?;
+            let exported = stmt.attributes().iter().any(|attribute| attribute.name == "export");
+            match stmt.without_attributes() {
+                Stmt::Function { name, params, return_type, generic_params, is_public, .. } if generic_params.is_empty() => {
+                    let function = self.declare_function(name, params, return_type)?;
+                    if exported {
+                        self.export_function(function, name, params, return_type, *is_public)?;
+                    }
+                },
+                Stmt::Function { name, .. } if exported => {
+                    return Err(ShitRustError::TypeError(format!("Generic function {} cannot be exported to C", name)));
+                },
+                Stmt::Struct { name, methods, .. } => self.declare_methods(name, methods)?,
+                Stmt::Impl { type_name, methods, generic_params, .. } if generic_params.is_empty() => {
+                    self.declare_methods(type_name, methods)?;
+                },
+                Stmt::Extern { functions, .. } => {
+                    self.link_libraries.extend(stmt.link_libraries());
+                    for function in functions {
+                        self.declare_extern_function(function)?;
+                    }
+                },
+                _ => (),
             }
         }
+        self.declare_instantiations()?;
         
-        // Generate main function if it doesn't exist
-        let main_function = match self.module.get_function("main") {
-            Some(func) => func,
+        // Generate main function if it doesn't exist; it returns 0 after the top-level statements
+        let library = self.crate_type != CrateType::Bin;
+        let (main_function, mut main_block) = match self.module.get_function("main") {
+            Some(func) => (Some(func), None),
+            None if library => (None, None),
             None => {
-                // Create a minimal main function that just returns 0
                 let main_type = self.context.i32_type().fn_type(&[], false);
                 let main_func = self.module.add_function("main", main_type, None);
                 let entry = self.context.append_basic_block(main_func, "entry");
-                self.builder.position_at_end(entry);
-                self.builder.build_return(Some(&self.context.i32_type().const_int(0, false)));
-                main_func
+                self.current_function = Some(main_func);
+                self.prepare_return(main_func);
+                if let Some(debug_info) = self.debug_info.as_mut() {
+                    debug_info.line = 1;
+                }
+                self.enter_debug_function(main_func);
+                (Some(main_func), Some(entry))
             }
         };
+        let implicit_main = main_block.is_some();
+        
+        self.module_bindings = program.statements.iter()
+            .filter_map(|stmt| match stmt.without_attributes() {
+                Stmt::Let { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
         
         // Second pass: generate code for function bodies
         for stmt in &program.statements {
-            match stmt {
-                Stmt::Function { name, body, .. } => {
+            match stmt.without_attributes() {
+                Stmt::Function { name, params, return_type, body, generic_params, .. } if generic_params.is_empty() => {
                     let function = self.module.get_function(name)
                         .ok_or_else(|| ShitRustError::RuntimeError(format!("No function named {}", name)))?;
                     
-                    let entry = self.context.append_basic_block(function, "entry");
-                    self.builder.position_at_end(entry);
-                    
-                    // Save current function
-                    self.current_function = Some(function);
-                    
-                    // Generate code for function body
-                    for stmt in body {
-                        self.generate_stmt(stmt)?;
-                    }
-                    
-                    // If we don't have a terminator (like return), add one
-                    if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
-                        if function.get_type().get_return_type().is_none() {
-                            self.builder.build_return(None);
-                        } else {
-                            // Default return value based on return type
-                            let ret_type = function.get_type().get_return_type().unwrap();
-                            if ret_type == self.context.i32_type().into() {
-                                self.builder.build_return(Some(&self.context.i32_type().const_int(0, false)));
-                            } else if ret_type == self.context.f64_type().into() {
-                                self.builder.build_return(Some(&self.context.f64_type().const_float(0.0)));
-                            } else {
-                                return Err(ShitRustError::RuntimeError(
-                                    format!("Unsupported return type for function {}", name)
-                                ));
-                            }
-                        }
+                    let param_names: Vec<String> = params.iter().map(|(param, _)| param.clone()).collect();
+                    let last_function = self.module.get_last_function();
+                    self.set_debug_location(stmt);
+                    match self.generate_function_body(function, &param_names, LeadingParam::None, body) {
+                        Err(_) if self.can_interpret(name, params, return_type, body) => {
+                            self.generate_interpreter_call(function, last_function, name, params, return_type);
+                        },
+                        result => result?,
                     }
                 },
+                Stmt::Struct { name, methods, .. } => self.generate_methods(name, methods)?,
+                Stmt::Impl { type_name, methods, generic_params, .. } if generic_params.is_empty() => {
+                    self.generate_methods(type_name, methods)?;
+                },
+                // Generic functions and methods are generated for each specialization
+                Stmt::Function { .. } | Stmt::Impl { .. } => (),
+                // Enum types and extern functions were declared in the first pass
+                Stmt::Enum { .. } | Stmt::Extern { .. } => (),
                 _ => {
-                    // Top-level statements are put in the main function
-                    let main_block = main_function.get_first_basic_block().unwrap();
-                    self.builder.position_at_end(main_block);
+                    // Top-level statements are put in the main function, continuing where the previous one ended
+                    let Some(main_function) = main_function.filter(|_| !library) else {
+                        return Err(ShitRustError::TypeError(
+                            "Libraries cannot have top-level statements, only declarations".to_string()
+                        ));
+                    };
+                    let block = main_block.or_else(|| main_function.get_first_basic_block()).unwrap();
+                    self.builder.position_at_end(block);
                     self.current_function = Some(main_function);
                     
                     // Handle non-function statements
                     if !matches!(stmt, Stmt::Function { .. }) {
-                        self.generate_stmt(stmt)?;
+                        self.generate_stmt_with_temporaries(stmt)?;
                     }
+                    main_block = self.builder.get_insert_block();
+                }
+            }
+        }
+        self.generate_pending_bodies()?;
+        
+        if implicit_main {
+            self.builder.position_at_end(main_block.unwrap());
+            self.current_function = main_function;
+            if !self.block_terminated() {
+                self.builder.build_unconditional_branch(self.function_state.return_block.unwrap());
+            }
+            self.finish_return();
+        }
+        
+        if let Some(debug_info) = &self.debug_info {
+            debug_info.builder.finalize();
+        }
+        
+        // Everything but the exports is internal to a library, so its symbols cannot clash with the
+        // program it is linked into. Declarations are left alone, they are implemented elsewhere.
+        if library {
+            for function in self.module.get_functions() {
+                let name = function.get_name().to_string_lossy();
+                if function.count_basic_blocks() > 0 && !self.exports.iter().any(|export| export.name == name) {
+                    function.set_linkage(Linkage::Internal);
                 }
             }
         }
         
         // Verify the module
-        if self.module.verify().is_err() {
-            return Err(ShitRustError::RuntimeError("Generated LLVM IR is invalid".to_string()));
+        if let Err(e) = self.module.verify() {
+            return Err(ShitRustError::RuntimeError(format!("Generated LLVM IR is invalid: {}", e)));
+        }
+        
+        Ok(())
+    }
+    
+    // Lowers every non-generic struct and enum to a named LLVM struct type. All names are created
+    // up front so field types can refer to types declared later in the program.
+    fn declare_types(&mut self, statements: &[Stmt]) -> Result<(), ShitRustError> {
+        for stmt in statements {
+            match stmt.without_attributes() {
+                Stmt::Struct { name, fields, generic_params, .. } if generic_params.is_empty() => {
+                    self.struct_layouts.insert(name.clone(), StructLayout {
+                        llvm_type: self.context.opaque_struct_type(name),
+                        fields: fields.iter().map(|(field, _, _)| field.clone()).collect(),
+                    });
+                },
+                Stmt::Enum { name, variants, generic_params, .. } if generic_params.is_empty() => {
+                    self.enum_layouts.insert(name.clone(), EnumLayout {
+                        llvm_type: self.context.opaque_struct_type(name),
+                        variants: variants.iter()
+                            .map(|(variant, _)| (variant.clone(), self.context.opaque_struct_type(&format!("{}::{}", name, variant))))
+                            .collect(),
+                    });
+                },
+                _ => (),
+            }
+        }
+        
+        let mut pending_enums = Vec::new();
+        for stmt in statements {
+            match stmt.without_attributes() {
+                Stmt::Struct { name, fields, generic_params, .. } if generic_params.is_empty() => {
+                    let field_types = fields.iter()
+                        .map(|(_, typ, _)| self.lower_type(typ))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.struct_layouts[name].llvm_type.set_body(&field_types, false);
+                },
+                Stmt::Enum { name, variants, generic_params, .. } if generic_params.is_empty() => {
+                    let layout = self.enum_layouts[name].clone();
+                    for ((_, payload), (_, payload_type)) in variants.iter().zip(&layout.variants) {
+                        let field_types = payload.iter()
+                            .map(|typ| self.lower_type(typ))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        payload_type.set_body(&field_types, false);
+                    }
+                    pending_enums.push(layout);
+                },
+                _ => (),
+            }
+        }
+        
+        // The payload area is sized for the largest variant, so an enum can only be laid out once
+        // every type stored in it has a size; repeat until no more enums can be completed
+        while !pending_enums.is_empty() {
+            let (ready, waiting): (Vec<_>, Vec<_>) = pending_enums.into_iter()
+                .partition(|layout| layout.variants.iter().all(|(_, payload)| payload.is_sized()));
+            
+            if ready.is_empty() {
+                let names: Vec<String> = waiting.iter()
+                    .filter_map(|layout| layout.llvm_type.get_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .collect();
+                return Err(ShitRustError::TypeError(
+                    format!("Recursive types have infinite size: {}", names.join(", "))
+                ));
+            }
+            
+            for layout in ready {
+                self.complete_enum_layout(&layout)?;
+            }
+            
+            pending_enums = waiting;
+        }
+        
+        Ok(())
+    }
+    
+    // Gives an enum its tag and a payload area that fits its largest variant
+    fn complete_enum_layout(&self, layout: &EnumLayout<'ctx>) -> Result<(), ShitRustError> {
+        // Enum values are copied without reference counting
+        if layout.variants.iter().any(|(_, payload)| payload.get_field_types().into_iter().any(|typ| self.needs_refcount(typ))) {
+            return Err(ShitRustError::TypeError(format!(
+                "Enum '{}' cannot hold strings, lists, dicts or functions in compiled code",
+                layout.llvm_type.get_name().unwrap().to_string_lossy()
+            )));
+        }
+        
+        let target_data = TargetData::create(&self.module.get_data_layout().as_str().to_string_lossy());
+        let payload_size = layout.variants.iter()
+            .map(|(_, payload)| target_data.get_abi_size(payload))
+            .max()
+            .unwrap_or(0);
+        // The payload is stored as i64 words to keep it 8-byte aligned
+        let words = payload_size.div_ceil(8) as u32;
+        
+        layout.llvm_type.set_body(&[
+            self.context.i32_type().into(),
+            self.context.i64_type().array_type(words).into(),
+        ], false);
+        Ok(())
+    }
+    
+    // The symbol of a method; `Point::new` is also how a method is called through its type
+    fn method_symbol(type_name: &str, method: &str) -> String {
+        format!("{}::{}", type_name, method)
+    }
+    
+    // A method whose body never mentions `this` or `self` is static, like `Point::new`
+    fn is_static_method(body: &[Stmt]) -> bool {
+        let mut uses_receiver = false;
+        walk_exprs(body, &mut |expr| {
+            if matches!(expr, Expr::Identifier(name) if name == "this" || name == "self") {
+                uses_receiver = true;
+            }
+        });
+        !uses_receiver
+    }
+    
+    // Declares the methods of a struct or enum. Each takes a pointer to its receiver as a leading
+    // `this` parameter, except static ones; methods of types that were not lowered, such as generic
+    // ones, are skipped.
+    fn declare_methods(&mut self, type_name: &str, methods: &[Stmt]) -> Result<(), ShitRustError> {
+        if !self.struct_layouts.contains_key(type_name) && !self.enum_layouts.contains_key(type_name) {
+            return Ok(());
+        }
+        
+        self.self_type = Some(type_name.to_string());
+        for method in methods {
+            if let Stmt::Function { name, params, return_type, body, .. } = method.without_attributes() {
+                let symbol = Self::method_symbol(type_name, name);
+                let mut method_params = Vec::new();
+                if Self::is_static_method(body) {
+                    self.static_methods.insert(symbol.clone());
+                } else {
+                    method_params.push((
+                        "this".to_string(),
+                        AstType::Reference(Box::new(AstType::Custom(type_name.to_string())), true),
+                    ));
+                }
+                method_params.extend(params.iter().cloned());
+                
+                self.declare_function(&symbol, &method_params, return_type)?;
+                self.methods.insert(symbol);
+            }
+        }
+        self.self_type = None;
+        
+        Ok(())
+    }
+    
+    fn generate_methods(&mut self, type_name: &str, methods: &[Stmt]) -> Result<(), ShitRustError> {
+        let receiver_type = match self.ast_type_to_llvm_type(&AstType::Custom(type_name.to_string())) {
+            Ok(receiver_type) => receiver_type,
+            // Not lowered, see `declare_methods`
+            Err(_) => return Ok(()),
+        };
+        
+        self.self_type = Some(type_name.to_string());
+        for method in methods {
+            if let Stmt::Function { name, params, body, .. } = method.without_attributes() {
+                let symbol = Self::method_symbol(type_name, name);
+                let function = self.module.get_function(&symbol)
+                    .ok_or_else(|| ShitRustError::RuntimeError(format!("No method named {}", symbol)))?;
+                
+                let params: Vec<String> = params.iter().map(|(param, _)| param.clone()).collect();
+                let leading = match self.static_methods.contains(&symbol) {
+                    true => LeadingParam::None,
+                    false => LeadingParam::Receiver(receiver_type),
+                };
+                self.set_debug_location(method);
+                self.generate_function_body(function, &params, leading, body)?;
+            }
         }
+        self.self_type = None;
         
         Ok(())
     }
     
+    // Generates the body of a declared function. Parameters are copied into stack slots; a method's
+    // receiver is bound as `this` and `self` directly, so field assignments are visible to the caller.
+    fn generate_function_body(
+        &mut self,
+        function: FunctionValue<'ctx>,
+        params: &[String],
+        leading: LeadingParam<'ctx>,
+        body: &[Stmt],
+    ) -> Result<(), ShitRustError> {
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        
+        // Save current function
+        self.current_function = Some(function);
+        let outer_values = std::mem::take(&mut self.named_values);
+        let outer_state = std::mem::take(&mut self.function_state);
+        let outer_loops = std::mem::take(&mut self.loop_stack);
+        let scope_depth = self.debug_info.as_ref().map_or(0, |debug_info| debug_info.scopes.len());
+        self.prepare_return(function);
+        self.enter_debug_function(function);
+        
+        let mut param_values = function.get_param_iter();
+        let mut arg_number = 1;
+        match leading {
+            LeadingParam::None => (),
+            LeadingParam::Receiver(receiver_type) => {
+                let this = param_values.next().unwrap().into_pointer_value();
+                self.named_values.insert("this".to_string(), (this, receiver_type));
+                self.named_values.insert("self".to_string(), (this, receiver_type));
+                self.declare_debug_variable("self", this, receiver_type, Some(arg_number));
+                arg_number += 1;
+            },
+            LeadingParam::Environment { layout, captured } => {
+                // Captured variables are used in place, so assignments to them last between calls
+                let environment = param_values.next().unwrap();
+                if !captured.is_empty() {
+                    let data = self.call_runtime("shitrust_env_data", &[environment.into()]).into_pointer_value();
+                    for (index, name) in captured.iter().enumerate() {
+                        let slot = self.builder.build_struct_gep(layout, data, index as u32, name).unwrap();
+                        let typ = layout.get_field_type_at_index(index as u32).unwrap();
+                        self.named_values.insert(name.clone(), (slot, typ));
+                        self.declare_debug_variable(name, slot, typ, None);
+                    }
+                }
+                arg_number += 1;
+            },
+        }
+        // Arguments are borrowed from the caller; the function takes its own references
+        for (param_name, value) in params.iter().zip(param_values) {
+            let slot = self.create_owned_slot(param_name, value.get_type());
+            self.store_owned(slot, value.get_type(), value);
+            self.named_values.insert(param_name.clone(), (slot, value.get_type()));
+            self.declare_debug_variable(param_name, slot, value.get_type(), Some(arg_number));
+            arg_number += 1;
+        }
+        
+        // Generate code for function body
+        let result = self.generate_block(body);
+        
+        // Falling off the end returns the default value of the return type
+        if result.is_ok() && !self.block_terminated() {
+            let block = self.builder.get_insert_block().unwrap();
+            // Every path into this block already returned, e.g. after an `if` whose branches both return
+            if block.get_first_use().is_none() && block != entry {
+                self.builder.build_unreachable();
+            } else {
+                self.builder.build_unconditional_branch(self.function_state.return_block.unwrap());
+            }
+        }
+        if result.is_ok() {
+            self.finish_return();
+        }
+        // An error may have left the scopes of nested blocks open
+        if let Some(debug_info) = self.debug_info.as_mut() {
+            debug_info.scopes.truncate(scope_depth + 1);
+        }
+        self.leave_debug_scope();
+        
+        // Restored even on errors, which lambdas recover from
+        self.named_values = outer_values;
+        self.function_state = outer_state;
+        self.loop_stack = outer_loops;
+        result
+    }
+    
+    // Creates the return block of `function` and the slot `return` stores its value in, which
+    // starts out as the default value of the return type
+    fn prepare_return(&mut self, function: FunctionValue<'ctx>) {
+        self.function_state.return_block = Some(self.context.append_basic_block(function, "return"));
+        self.function_state.return_slot = function.get_type().get_return_type()
+            .map(|typ| (self.create_zeroed_alloca("return_value", typ), typ));
+    }
+    
+    // Fills in the return block: the function's references are released and the value is returned
+    fn finish_return(&mut self) {
+        self.builder.position_at_end(self.function_state.return_block.unwrap());
+        for (slot, typ) in &self.function_state.owned_slots {
+            self.build_release(*slot, *typ);
+        }
+        
+        match self.function_state.return_slot {
+            Some((slot, typ)) => {
+                let value = self.builder.build_load(typ, slot, "return_value");
+                self.builder.build_return(Some(&value));
+            },
+            None => {
+                self.builder.build_return(None);
+            },
+        }
+    }
+    
+    // Creates the compile unit for `source_file`; everything is then described as coming from it
+    fn create_debug_info(&mut self, source_file: Option<&str>) {
+        let path = Path::new(source_file.unwrap_or("main.sr"));
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let filename = path.file_name()
+            .map_or_else(|| "main.sr".to_string(), |name| name.to_string_lossy().into_owned());
+        let directory = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_string_lossy().into_owned(),
+            _ => ".".to_string(),
+        };
+        
+        let i32_type = self.context.i32_type();
+        self.module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            i32_type.const_int(debug_metadata_version() as u64, false),
+        );
+        self.module.add_basic_value_flag("Dwarf Version", FlagBehavior::Warning, i32_type.const_int(4, false));
+        
+        let (builder, compile_unit) = self.module.create_debug_info_builder(
+            true,
+            // Debuggers have no ShitRust support; C makes them print values and evaluate expressions sensibly
+            DWARFSourceLanguage::C,
+            &filename,
+            &directory,
+            concat!("shitrust ", env!("CARGO_PKG_VERSION")),
+            self.optimization_level != OptimizationLevel::None,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+        
+        self.debug_info = Some(DebugInfo {
+            builder,
+            compile_unit,
+            scopes: Vec::new(),
+            line: 0,
+            column: 0,
+            types: HashMap::new(),
+        });
+    }
+    
+    // Attaches a subprogram starting at the current line to `function` and enters its scope
+    fn enter_debug_function(&mut self, function: FunctionValue<'ctx>) {
+        if self.debug_info.is_none() {
+            return;
+        }
+        
+        let function_type = function.get_type();
+        let return_type = function_type.get_return_type().map(|typ| self.debug_type(typ));
+        let param_types: Vec<DIType<'ctx>> = function_type.get_param_types().into_iter()
+            .map(|typ| self.debug_type(typ))
+            .collect();
+        let name = function.get_name().to_string_lossy().into_owned();
+        let is_optimized = self.optimization_level != OptimizationLevel::None;
+        
+        let debug_info = self.debug_info.as_mut().unwrap();
+        let file = debug_info.compile_unit.get_file();
+        let subroutine_type = debug_info.builder.create_subroutine_type(file, return_type, &param_types, DIFlags::ZERO);
+        let subprogram = debug_info.builder.create_function(
+            debug_info.compile_unit.as_debug_info_scope(),
+            &name,
+            None,
+            file,
+            debug_info.line,
+            subroutine_type,
+            false,
+            true,
+            debug_info.line,
+            DIFlags::PROTOTYPED,
+            is_optimized,
+        );
+        function.set_subprogram(subprogram);
+        
+        debug_info.scopes.push((subprogram.as_debug_info_scope(), debug_info.line, debug_info.column));
+        self.apply_debug_location();
+    }
+    
+    // Enters a lexical block starting at the current position; false outside of any function scope
+    fn enter_debug_block(&mut self) -> bool {
+        let Some(debug_info) = self.debug_info.as_mut() else {
+            return false;
+        };
+        let Some(&(parent, _, _)) = debug_info.scopes.last() else {
+            return false;
+        };
+        
+        let block = debug_info.builder.create_lexical_block(
+            parent,
+            debug_info.compile_unit.get_file(),
+            debug_info.line,
+            debug_info.column,
+        );
+        debug_info.scopes.push((block.as_debug_info_scope(), debug_info.line, debug_info.column));
+        true
+    }
+    
+    // Leaves the innermost scope. Code generated afterwards belongs to the statement that entered it,
+    // such as the branches at the end of a loop body.
+    fn leave_debug_scope(&mut self) {
+        let Some(debug_info) = self.debug_info.as_mut() else {
+            return;
+        };
+        
+        if let Some((_, line, column)) = debug_info.scopes.pop() {
+            debug_info.line = line;
+            debug_info.column = column;
+        }
+        self.apply_debug_location();
+    }
+    
+    // Moves the current position to the start of `stmt`, if the parser recorded it
+    fn set_debug_location(&mut self, stmt: &Stmt) {
+        let (Some(debug_info), Some((line, column))) = (self.debug_info.as_mut(), stmt.location()) else {
+            return;
+        };
+        
+        debug_info.line = line as u32;
+        debug_info.column = column as u32;
+        self.apply_debug_location();
+    }
+    
+    // Gives the instructions built from now on the current position
+    fn apply_debug_location(&self) {
+        let Some(debug_info) = &self.debug_info else {
+            return;
+        };
+        
+        match debug_info.scopes.last() {
+            Some(&(scope, _, _)) => {
+                let location = debug_info.builder.create_debug_location(
+                    self.context,
+                    debug_info.line,
+                    debug_info.column,
+                    scope,
+                    None,
+                );
+                self.builder.set_current_debug_location(location);
+            },
+            None => self.builder.unset_current_debug_location(),
+        }
+    }
+    
+    // Tells the debugger that the variable `name` lives at `slot`; `arg_number` counts parameters from 1
+    fn declare_debug_variable(&mut self, name: &str, slot: PointerValue<'ctx>, typ: BasicTypeEnum<'ctx>, arg_number: Option<u32>) {
+        if self.debug_info.as_ref().map_or(true, |debug_info| debug_info.scopes.is_empty()) {
+            return;
+        }
+        
+        let debug_type = self.debug_type(typ);
+        let block = self.builder.get_insert_block().unwrap();
+        let debug_info = self.debug_info.as_ref().unwrap();
+        let (scope, _, _) = *debug_info.scopes.last().unwrap();
+        let file = debug_info.compile_unit.get_file();
+        
+        let variable = match arg_number {
+            Some(arg_number) => debug_info.builder.create_parameter_variable(
+                scope, name, arg_number, file, debug_info.line, debug_type, true, DIFlags::ZERO,
+            ),
+            None => debug_info.builder.create_auto_variable(
+                scope, name, file, debug_info.line, debug_type, true, DIFlags::ZERO, 0,
+            ),
+        };
+        let location = debug_info.builder.create_debug_location(self.context, debug_info.line, debug_info.column, scope, None);
+        debug_info.builder.insert_declare_at_end(slot, Some(variable), None, location, block);
+    }
+    
+    // The debug info type of a value type. Strings, lists and dicts are shown as the address of
+    // their runtime object.
+    fn debug_type(&mut self, typ: BasicTypeEnum<'ctx>) -> DIType<'ctx> {
+        let name = match typ {
+            BasicTypeEnum::PointerType(_) => "ptr".to_string(),
+            _ => self.describe_type(typ),
+        };
+        if let Some(debug_type) = self.debug_info.as_ref().unwrap().types.get(&name) {
+            return *debug_type;
+        }
+        
+        let target_data = TargetData::create(&self.module.get_data_layout().as_str().to_string_lossy());
+        let debug_type = match typ {
+            BasicTypeEnum::StructType(struct_type) if self.heap_type_of(typ).is_none() => {
+                self.debug_struct_type(&name, struct_type, &target_data)
+            },
+            _ => {
+                let encoding = match name.as_str() {
+                    "int" => DW_ATE_SIGNED,
+                    "float" => DW_ATE_FLOAT,
+                    "bool" => DW_ATE_BOOLEAN,
+                    "char" => DW_ATE_UTF,
+                    _ => DW_ATE_ADDRESS,
+                };
+                // The ABI size, so a bool takes a byte
+                let size = target_data.get_abi_size(&typ) * 8;
+                self.debug_info.as_ref().unwrap().builder
+                    .create_basic_type(&name, size, encoding, DIFlags::ZERO)
+                    .unwrap()
+                    .as_type()
+            },
+        };
+        
+        self.debug_info.as_mut().unwrap().types.insert(name, debug_type);
+        debug_type
+    }
+    
+    // Describes a struct with its fields, a closure as its two pointers, or an enum with just its tag
+    // since the payload depends on the variant
+    fn debug_struct_type(&mut self, name: &str, struct_type: StructType<'ctx>, target_data: &TargetData) -> DIType<'ctx> {
+        let field_names = match self.struct_layouts.get(name) {
+            Some(layout) => layout.fields.clone(),
+            None if self.closure_signature(struct_type.into()).is_some() => {
+                vec!["function".to_string(), "environment".to_string()]
+            },
+            None => vec!["tag".to_string()],
+        };
+        
+        let mut members = Vec::new();
+        for (index, field) in field_names.iter().enumerate() {
+            let field_type = struct_type.get_field_type_at_index(index as u32).unwrap();
+            let member_type = self.debug_type(field_type);
+            
+            let debug_info = self.debug_info.as_ref().unwrap();
+            let member = debug_info.builder.create_member_type(
+                debug_info.compile_unit.as_debug_info_scope(),
+                field,
+                debug_info.compile_unit.get_file(),
+                0,
+                target_data.get_abi_size(&field_type) * 8,
+                target_data.get_abi_alignment(&field_type) * 8,
+                target_data.offset_of_element(&struct_type, index as u32).unwrap() * 8,
+                DIFlags::PUBLIC,
+                member_type,
+            );
+            members.push(member.as_type());
+        }
+        
+        let debug_info = self.debug_info.as_ref().unwrap();
+        debug_info.builder.create_struct_type(
+            debug_info.compile_unit.as_debug_info_scope(),
+            name,
+            debug_info.compile_unit.get_file(),
+            0,
+            target_data.get_abi_size(&struct_type) * 8,
+            target_data.get_abi_alignment(&struct_type) * 8,
+            DIFlags::PUBLIC,
+            None,
+            &members,
+            0,
+            None,
+            name,
+        ).as_type()
+    }
+    
     fn declare_function(
-        &self, 
+        &mut self, 
         name: &str, 
         params: &[(String, AstType)], 
         return_type: &AstType
@@ -135,12 +1003,12 @@ impl<'ctx> CodeGen<'ctx> {
         // Convert ShitRust types to LLVM types
         let param_types: Vec<BasicTypeEnum> = params
             .iter()
-            .map(|(_, typ)| self.ast_type_to_llvm_type(typ))
+            .map(|(_, typ)| self.lower_type(typ))
             .collect::<Result<Vec<_>, _>>()?;
         
         let return_llvm_type = match return_type {
             AstType::Void => None,
-            _ => Some(self.ast_type_to_llvm_type(return_type)?),
+            _ => Some(self.lower_type(return_type)?),
         };
         
         let fn_type = match return_llvm_type {
@@ -160,46 +1028,600 @@ impl<'ctx> CodeGen<'ctx> {
         Ok(function)
     }
     
-    fn ast_type_to_llvm_type(&self, typ: &AstType) -> Result<BasicTypeEnum<'ctx>, ShitRustError> {
-        match typ {
-            AstType::Int => Ok(self.context.i64_type().into()),
-            AstType::Float => Ok(self.context.f64_type().into()),
-            AstType::Bool => Ok(self.context.bool_type().into()),
-            AstType::String => Ok(self.context.i8_type().ptr_type(Default::default()).into()),
-            AstType::Char => Ok(self.context.i8_type().into()),
-            _ => Err(ShitRustError::TypeError(format!("Unsupported type: {:?}", typ))),
+    // Declares a C function with external linkage. Strings are passed to C as `char *`, bools
+    // as `bool`, which C expects zero-extended, and C ints as sign-extended `int`.
+    fn declare_extern_function(&mut self, declaration: &ExternFunction) -> Result<FunctionValue<'ctx>, ShitRustError> {
+        let name = &declaration.name;
+        if let Some(function) = self.module.get_function(name) {
+            if self.extern_functions.contains_key(name) {
+                return Ok(function);
+            }
+            return Err(ShitRustError::TypeError(format!("Extern function {} has the name of another function", name)));
+        }
+        
+        let c_type = |typ: &AstType| -> Result<BasicTypeEnum<'ctx>, ShitRustError> {
+            match typ {
+                AstType::Int => Ok(self.context.i64_type().into()),
+                AstType::CInt => Ok(self.context.i32_type().into()),
+                AstType::Float => Ok(self.context.f64_type().into()),
+                AstType::Bool => Ok(self.context.bool_type().into()),
+                AstType::String | AstType::Pointer => Ok(self.context.i8_type().ptr_type(Default::default()).into()),
+                _ => Err(ShitRustError::TypeError(format!(
+                    "Extern function {} uses {:?}, but only int, c_int, float, bool, string and ptr can be passed to C", name, typ
+                ))),
+            }
+        };
+        
+        let param_types = declaration.params.iter()
+            .map(|(_, typ)| c_type(typ).map(Into::into))
+            .collect::<Result<Vec<BasicMetadataTypeEnum>, _>>()?;
+        let fn_type = match &declaration.return_type {
+            AstType::Void => self.context.void_type().fn_type(&param_types, false),
+            typ => c_type(typ)?.fn_type(&param_types, false),
+        };
+        
+        let function = self.module.add_function(name, fn_type, Some(Linkage::External));
+        let zeroext = self.context.create_enum_attribute(Attribute::get_named_enum_kind_id("zeroext"), 0);
+        let signext = self.context.create_enum_attribute(Attribute::get_named_enum_kind_id("signext"), 0);
+        let extension = |typ: &AstType| match typ {
+            AstType::Bool => Some(zeroext),
+            AstType::CInt => Some(signext),
+            _ => None,
+        };
+        for (i, (_, typ)) in declaration.params.iter().enumerate() {
+            if let Some(attribute) = extension(typ) {
+                function.add_attribute(AttributeLoc::Param(i as u32), attribute);
+            }
+        }
+        if let Some(attribute) = extension(&declaration.return_type) {
+            function.add_attribute(AttributeLoc::Return, attribute);
         }
+        
+        self.extern_functions.insert(name.clone(), declaration.clone());
+        Ok(function)
     }
     
-    fn generate_stmt(&mut self, stmt: &Stmt) -> Result<(), ShitRustError> {
-        match stmt {
-            Stmt::Expr(expr) => {
-                self.generate_expr(expr)?;
-                Ok(())
-            },
-            Stmt::Let { name, type_hint: _, value, mutable: _ } => {
-                let expr_value = self.generate_expr(value)?;
-                
-                // Allocate space on the stack
-                let alloca = self.create_entry_block_alloca(name, expr_value.get_type());
-                
-                // Store the value
-                self.builder.build_store(alloca, expr_value);
-                
-                // Add to our symbol table
-                self.named_values.insert(name.clone(), alloca);
-                
-                Ok(())
+    // Checks that an `#[export]` function can be called from C: it must be `pub` and only use
+    // types that C has. Its symbol is its name, as `c_header` declares it.
+    fn export_function(
+        &mut self,
+        function: FunctionValue<'ctx>,
+        name: &str,
+        params: &[(String, AstType)],
+        return_type: &AstType,
+        is_public: bool,
+    ) -> Result<(), ShitRustError> {
+        if !is_public {
+            return Err(ShitRustError::TypeError(format!("Exported function {} must be pub", name)));
+        }
+        
+        for (param, typ) in params {
+            if matches!(typ, AstType::Void) || Self::c_type_name(typ).is_none() {
+                return Err(ShitRustError::TypeError(format!(
+                    "Parameter {} of exported function {} is {:?}, but only int, float, bool and ptr can be passed from C",
+                    param, name, typ
+                )));
+            }
+        }
+        if Self::c_type_name(return_type).is_none() {
+            return Err(ShitRustError::TypeError(format!(
+                "Exported function {} returns {:?}, but only int, float, bool, ptr and void can be returned to C",
+                name, return_type
+            )));
+        }
+        
+        let zeroext = self.context.create_enum_attribute(Attribute::get_named_enum_kind_id("zeroext"), 0);
+        for (i, (_, typ)) in params.iter().enumerate() {
+            if *typ == AstType::Bool {
+                function.add_attribute(AttributeLoc::Param(i as u32), zeroext);
+            }
+        }
+        if *return_type == AstType::Bool {
+            function.add_attribute(AttributeLoc::Return, zeroext);
+        }
+        
+        self.exports.push(ExternFunction {
+            name: name.to_string(),
+            params: params.to_vec(),
+            return_type: return_type.clone(),
+        });
+        Ok(())
+    }
+    
+    // How a type that can cross into C is written there
+    fn c_type_name(typ: &AstType) -> Option<&'static str> {
+        match typ {
+            AstType::Int => Some("int64_t"),
+            AstType::Float => Some("double"),
+            AstType::Bool => Some("bool"),
+            AstType::Pointer => Some("void *"),
+            AstType::Void => Some("void"),
+            _ => None,
+        }
+    }
+    
+    // `int64_t name`, or `void *name` for pointers
+    fn c_declarator(typ: &AstType, name: &str) -> String {
+        let c_type = Self::c_type_name(typ).unwrap();
+        if c_type.ends_with('*') {
+            format!("{}{}", c_type, name)
+        } else {
+            format!("{} {}", c_type, name)
+        }
+    }
+    
+    // A C header declaring the `#[export]` functions, guarded by `<NAME>_H`
+    pub fn c_header(&self, name: &str) -> String {
+        let guard: String = name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect();
+        
+        let mut header = format!("/* C interface of {}, generated by shitrust. */\n\n", name);
+        header.push_str(&format!("#ifndef {}_H\n#define {}_H\n\n", guard, guard));
+        header.push_str("#include <stdbool.h>\n#include <stdint.h>\n\n");
+        header.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+        
+        for export in &self.exports {
+            let params: Vec<String> = export.params.iter()
+                .map(|(param, typ)| Self::c_declarator(typ, param))
+                .collect();
+            let params = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+            header.push_str(&format!("{}({});\n", Self::c_declarator(&export.return_type, &export.name), params));
+        }
+        
+        header.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+        header.push_str(&format!("#endif /* {}_H */\n", guard));
+        header
+    }
+    
+    // Calls a C function. Strings are passed as NUL-terminated copies that are freed after the
+    // call, and a returned C string is copied, as C keeps ownership of it. Returns None for void functions.
+    fn generate_extern_call(&mut self, declaration: &ExternFunction, args: &[Expr]) -> Result<Option<BasicValueEnum<'ctx>>, ShitRustError> {
+        let function = self.module.get_function(&declaration.name).unwrap();
+        if declaration.params.len() != args.len() {
+            return Err(ShitRustError::RuntimeError(
+                format!("Expected {} arguments but got {}", declaration.params.len(), args.len())
+            ));
+        }
+        
+        let mut arg_values: Vec<BasicMetadataValueEnum> = Vec::new();
+        let mut c_strings = Vec::new();
+        for ((param, typ), (arg, c_param)) in declaration.params.iter().zip(args.iter().zip(function.get_param_iter())) {
+            let value = self.generate_expr(arg)?;
+            let value = match typ {
+                AstType::String if self.is_string(value.get_type()) => {
+                    let c_string = self.call_runtime("shitrust_string_to_cstr", &[self.heap_pointer(value).into()]);
+                    c_strings.push(c_string);
+                    c_string
+                },
+                // Like a conversion to `int` in C, only the low 32 bits are kept
+                AstType::CInt if value.get_type() == self.context.i64_type().into() => {
+                    self.builder.build_int_truncate(value.into_int_value(), self.context.i32_type(), "cinttmp").into()
+                },
+                _ if *typ != AstType::String && value.get_type() == c_param.get_type() => value,
+                _ => return Err(ShitRustError::TypeError(format!(
+                    "Parameter {} of {} is {:?}, got {}", param, declaration.name, typ, self.describe_type(value.get_type())
+                ))),
+            };
+            arg_values.push(value.into());
+        }
+        
+        let call = self.builder.build_call(function, &arg_values, &format!("{}_call", declaration.name));
+        for c_string in c_strings {
+            self.call_runtime("shitrust_cstr_free", &[c_string.into()]);
+        }
+        
+        let Some(result) = self.call_result(call) else {
+            return Ok(None);
+        };
+        Ok(Some(match declaration.return_type {
+            AstType::String => self.generate_runtime_object("shitrust_string_from_cstr", &[result.into()], self.string_type()).0,
+            // C ints are ints once they are back, so they are never mistaken for chars
+            AstType::CInt => self.builder.build_int_s_extend(result.into_int_value(), self.context.i64_type(), "inttmp").into(),
+            _ => result,
+        }))
+    }
+    
+    fn ast_type_to_llvm_type(&self, typ: &AstType) -> Result<BasicTypeEnum<'ctx>, ShitRustError> {
+        match typ {
+            AstType::Int => Ok(self.context.i64_type().into()),
+            AstType::Float => Ok(self.context.f64_type().into()),
+            AstType::Bool => Ok(self.context.bool_type().into()),
+            AstType::String => Ok(self.string_type()),
+            AstType::List(element) => {
+                let element = self.ast_type_to_llvm_type(element)?;
+                self.list_type(element)
             },
-            Stmt::If { condition, then_block, else_block } => {
-                let cond_value = self.generate_expr(condition)?;
+            AstType::Dict(key, value) => {
+                let key = self.ast_type_to_llvm_type(key)?;
+                let value = self.ast_type_to_llvm_type(value)?;
+                self.dict_type(key, value)
+            },
+            // Chars are Unicode scalar values, like the interpreter's
+            AstType::Char => Ok(self.context.i32_type().into()),
+            AstType::Custom(name) if self.type_params.contains_key(name) => Ok(self.type_params[name]),
+            AstType::Custom(name) if name == "Self" && self.self_type.is_some() => {
+                self.ast_type_to_llvm_type(&AstType::Custom(self.self_type.clone().unwrap()))
+            },
+            AstType::Custom(name) if self.struct_layouts.contains_key(name) => Ok(self.struct_layouts[name].llvm_type.into()),
+            AstType::Custom(name) if self.enum_layouts.contains_key(name) => Ok(self.enum_layouts[name].llvm_type.into()),
+            AstType::Generic(name, args) if self.generic_types.contains_key(name) => {
+                let args = args.iter()
+                    .map(|arg| self.ast_type_to_llvm_type(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
                 
-                // Convert condition to i1 (boolean)
-                let cond_val = match cond_value {
-                    BasicValueEnum::IntValue(i) => i,
-                    _ => return Err(ShitRustError::TypeError("Condition must be a boolean".to_string())),
+                // Specializations are created by `lower_type`
+                let symbol = self.specialization_name(name, &args);
+                self.ast_type_to_llvm_type(&AstType::Custom(symbol.clone()))
+                    .map_err(|_| ShitRustError::TypeError(format!("Type {} is not specialized", symbol)))
+            },
+            AstType::Reference(_, _) | AstType::Pointer => Ok(self.context.i8_type().ptr_type(Default::default()).into()),
+            AstType::Option(inner) => match self.ast_type_to_llvm_type(inner)? {
+                inner @ (BasicTypeEnum::IntType(_) | BasicTypeEnum::FloatType(_)) => Ok(self.option_type(inner).into()),
+                inner => Err(ShitRustError::TypeError(format!(
+                    "Optional {} values are not supported in compiled code", self.describe_type(inner)
+                ))),
+            },
+            AstType::Function(params, return_type) => {
+                let params = params.iter()
+                    .map(|param| self.ast_type_to_llvm_type(param))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let return_type = match &**return_type {
+                    AstType::Void => None,
+                    return_type => Some(self.ast_type_to_llvm_type(return_type)?),
+                };
+                Ok(self.closure_type(&params, return_type).into())
+            },
+            _ => Err(ShitRustError::TypeError(format!("Unsupported type: {:?}", typ))),
+        }
+    }
+    
+    // The struct or enum a lowered type belongs to
+    fn nominal_type_name(&self, typ: BasicTypeEnum<'ctx>) -> Option<String> {
+        let BasicTypeEnum::StructType(struct_type) = typ else {
+            return None;
+        };
+        
+        let name = struct_type.get_name()?.to_string_lossy().into_owned();
+        if self.struct_layouts.contains_key(&name) || self.enum_layouts.contains_key(&name) {
+            Some(name)
+        } else {
+            None
+        }
+    }
+    
+    // Index and type of a named field of a struct type
+    fn field_index(&self, typ: BasicTypeEnum<'ctx>, field: &str) -> Result<(u32, BasicTypeEnum<'ctx>), ShitRustError> {
+        let layout = self.nominal_type_name(typ)
+            .and_then(|name| self.struct_layouts.get(&name))
+            .ok_or_else(|| ShitRustError::TypeError(format!("Cannot access field '{}' on {:?}", field, typ)))?;
+        
+        let index = layout.fields.iter().position(|name| name == field)
+            .ok_or_else(|| ShitRustError::TypeError(format!("No field '{}' on {:?}", field, typ)))? as u32;
+        Ok((index, layout.llvm_type.get_field_type_at_index(index).unwrap()))
+    }
+    
+    // The stack slot an expression refers to, for variables and (nested) fields of variables.
+    // Returns None for other expressions, which only produce values.
+    fn generate_place(&mut self, expr: &Expr) -> Result<Option<(PointerValue<'ctx>, BasicTypeEnum<'ctx>)>, ShitRustError> {
+        match expr {
+            Expr::Identifier(name) => Ok(self.named_values.get(name).copied()),
+            Expr::FieldAccess { object, field } => {
+                let Some((object_ptr, object_type)) = self.generate_place(object)? else {
+                    return Ok(None);
                 };
                 
+                let (index, field_type) = self.field_index(object_type, field)?;
+                let field_ptr = self.builder.build_struct_gep(object_type.into_struct_type(), object_ptr, index, field)
+                    .map_err(|_| ShitRustError::RuntimeError(format!("Invalid access to field '{}'", field)))?;
+                Ok(Some((field_ptr, field_type)))
+            },
+            _ => Ok(None),
+        }
+    }
+    
+    fn generate_struct_init(&mut self, name: &str, fields: &[(String, Expr)]) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        if self.generic_types.contains_key(name) {
+            return self.generate_generic_struct_init(name, fields);
+        }
+        
+        let layout = self.struct_layouts.get(name).cloned()
+            .ok_or_else(|| ShitRustError::TypeError(format!("Struct '{}' cannot be used in compiled code", name)))?;
+        
+        // Fields are evaluated in the order they are written
+        let mut values = Vec::new();
+        for (field_name, field_expr) in fields {
+            let index = layout.fields.iter().position(|declared| declared == field_name)
+                .ok_or_else(|| ShitRustError::TypeError(format!("Struct '{}' has no field '{}'", name, field_name)))?;
+            
+            let field_type = layout.llvm_type.get_field_type_at_index(index as u32);
+            values.push((index, self.generate_expr_with_hint(field_expr, field_type)?));
+        }
+        
+        self.build_struct_value(name, &layout, values)
+    }
+    
+    // Puts the values of fields, given with the index of their field, into a new struct value
+    fn build_struct_value(
+        &mut self,
+        name: &str,
+        layout: &StructLayout<'ctx>,
+        values: Vec<(usize, BasicValueEnum<'ctx>)>,
+    ) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let mut value = layout.llvm_type.get_undef();
+        let mut initialized = HashSet::new();
+        for (index, field_value) in values {
+            self.build_retain(field_value);
+            value = self.builder.build_insert_value(value, field_value, index as u32, &layout.fields[index])
+                .unwrap()
+                .into_struct_value();
+            initialized.insert(index);
+        }
+        
+        if let Some(missing) = layout.fields.iter().enumerate().find(|(index, _)| !initialized.contains(index)) {
+            return Err(ShitRustError::TypeError(format!("Missing field '{}' in '{}' initializer", missing.1, name)));
+        }
+        
+        // The new value owns references to its fields
+        if self.needs_refcount(value.get_type().into()) {
+            self.register_temporary(value.into());
+        }
+        Ok(value.into())
+    }
+    
+    // Builds an enum value from `Enum::Variant` or `Enum::Variant(args)`. Variants of generic enums
+    // take their type arguments from `hint`, see `generate_generic_variant`.
+    // Returns None when `path` does not name a variant of a lowered or generic enum.
+    fn generate_variant(
+        &mut self,
+        path: &str,
+        args: &[Expr],
+        hint: Option<BasicTypeEnum<'ctx>>,
+    ) -> Result<Option<BasicValueEnum<'ctx>>, ShitRustError> {
+        let Some((enum_name, variant)) = path.rsplit_once("::") else {
+            return Ok(None);
+        };
+        if self.generic_types.contains_key(enum_name) {
+            return self.generate_generic_variant(enum_name, variant, args, hint);
+        }
+        let Some(layout) = self.enum_layouts.get(enum_name).cloned() else {
+            return Ok(None);
+        };
+        let Some(tag) = layout.variants.iter().position(|(name, _)| name == variant) else {
+            return Ok(None);
+        };
+        
+        let payload_type = layout.variants[tag].1;
+        if payload_type.count_fields() as usize != args.len() {
+            return Err(ShitRustError::TypeError(format!(
+                "Variant '{}' expects {} values but got {}", path, payload_type.count_fields(), args.len()
+            )));
+        }
+        
+        let values = args.iter()
+            .map(|arg| self.generate_expr(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.build_variant(path, &layout, tag, values).map(Some)
+    }
+    
+    // Stores the tag of a variant and the values of its payload in a new enum value
+    fn build_variant(
+        &mut self,
+        path: &str,
+        layout: &EnumLayout<'ctx>,
+        tag: usize,
+        values: Vec<BasicValueEnum<'ctx>>,
+    ) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let (variant, payload_type) = layout.variants[tag].clone();
+        let enum_name = path.rsplit_once("::").map_or(path, |(enum_name, _)| enum_name);
+        
+        let slot = self.create_entry_block_alloca(&variant, layout.llvm_type.into());
+        let tag_ptr = self.builder.build_struct_gep(layout.llvm_type, slot, 0, "tag")
+            .map_err(|_| ShitRustError::RuntimeError(format!("Invalid layout for enum '{}'", enum_name)))?;
+        self.builder.build_store(tag_ptr, self.context.i32_type().const_int(tag as u64, false));
+        
+        if !values.is_empty() {
+            let mut payload = payload_type.get_undef();
+            for (index, value) in values.into_iter().enumerate() {
+                payload = self.builder.build_insert_value(payload, value, index as u32, "payload")
+                    .unwrap()
+                    .into_struct_value();
+            }
+            
+            let payload_ptr = self.builder.build_struct_gep(layout.llvm_type, slot, 1, "payload")
+                .map_err(|_| ShitRustError::RuntimeError(format!("Invalid layout for enum '{}'", enum_name)))?;
+            self.builder.build_store(payload_ptr, payload);
+        }
+        
+        Ok(self.builder.build_load(layout.llvm_type, slot, path))
+    }
+    
+    // Calls a method on a struct or enum, passing the receiver's address as `this`. Static methods
+    // take no receiver. Returns None for void methods.
+    fn generate_method_call(
+        &mut self,
+        receiver: PointerValue<'ctx>,
+        receiver_type: BasicTypeEnum<'ctx>,
+        method: &str,
+        args: &[Expr],
+    ) -> Result<Option<BasicValueEnum<'ctx>>, ShitRustError> {
+        let type_name = self.nominal_type_name(receiver_type)
+            .ok_or_else(|| ShitRustError::RuntimeError(format!("Method '{}' is not supported in compiled code", method)))?;
+        let symbol = Self::method_symbol(&type_name, method);
+        let function = self.module.get_function(&symbol)
+            .ok_or_else(|| ShitRustError::RuntimeError(format!("No method '{}' on {}", method, type_name)))?;
+        
+        let mut arg_values: Vec<BasicMetadataValueEnum> = Vec::new();
+        if !self.static_methods.contains(&symbol) {
+            arg_values.push(receiver.into());
+        }
+        
+        if function.count_params() as usize != arg_values.len() + args.len() {
+            return Err(ShitRustError::RuntimeError(
+                format!("Expected {} arguments but got {}", function.count_params() as usize - arg_values.len(), args.len())
+            ));
+        }
+        
+        for arg in args {
+            let param_type = function.get_nth_param(arg_values.len() as u32).map(|param| param.get_type());
+            arg_values.push(self.generate_expr_with_hint(arg, param_type)?.into());
+        }
+        
+        let call = self.builder.build_call(function, &arg_values, &format!("{}_call", method));
+        Ok(self.owned_call_result(call))
+    }
+    
+    // The value of a call, or None for void calls
+    fn call_result(&self, call: CallSiteValue<'ctx>) -> Option<BasicValueEnum<'ctx>> {
+        call.try_as_basic_value().left()
+    }
+    
+    // The value of a call to a compiled function, which returns a reference owned by the caller
+    fn owned_call_result(&mut self, call: CallSiteValue<'ctx>) -> Option<BasicValueEnum<'ctx>> {
+        let value = self.call_result(call)?;
+        if self.needs_refcount(value.get_type()) {
+            self.register_temporary(value);
+        }
+        Some(value)
+    }
+    
+    // Void calls have no value, so they can only be used as statements
+    fn void_call_error(callee: &str) -> ShitRustError {
+        ShitRustError::RuntimeError(format!("Cannot use void function '{}' in an expression", callee))
+    }
+    
+    fn receiver_error(method: &str) -> ShitRustError {
+        ShitRustError::TypeError(format!("Method {} uses `this`, so it must be called on a value", method))
+    }
+    
+    // Generates a call, which yields None when the function is void
+    fn generate_call(&mut self, func: &Expr, args: &[Expr]) -> Result<Option<BasicValueEnum<'ctx>>, ShitRustError> {
+        if let Expr::Identifier(name) = func {
+            // Handle print/println as special cases
+            if name == "println" || name == "print" {
+                return self.generate_print_call(args, name == "println").map(Some);
+            }
+            
+            if let Some(variant) = self.generate_variant(name, args, None)? {
+                return Ok(Some(variant));
+            }
+            
+            // Variables holding functions shadow the functions of the module
+            if !self.named_values.contains_key(name) {
+                if let Some(declaration) = self.extern_functions.get(name).cloned() {
+                    return self.generate_extern_call(&declaration, args);
+                }
+                
+                if let Some(call) = self.generate_generic_call(name, args)? {
+                    return Ok(self.owned_call_result(call));
+                }
+                
+                // Get the function from the module
+                let function = self.module.get_function(name)
+                    .ok_or_else(|| ShitRustError::UndefinedVariable(name.clone()))?;
+                
+                // Methods called through their type, like `Point::new()`, have no receiver
+                if self.methods.contains(name) && !self.static_methods.contains(name) {
+                    return Err(Self::receiver_error(name));
+                }
+                
+                // Check that we have the right number of arguments
+                if function.count_params() as usize != args.len() {
+                    return Err(ShitRustError::RuntimeError(
+                        format!("Expected {} arguments but got {}", function.count_params(), args.len())
+                    ));
+                }
+                
+                // Generate code for each argument; lambdas take their types from the parameters
+                let mut arg_values: Vec<BasicMetadataValueEnum> = Vec::new();
+                for arg in args {
+                    let param_type = function.get_nth_param(arg_values.len() as u32).map(|param| param.get_type());
+                    arg_values.push(self.generate_expr_with_hint(arg, param_type)?.into());
+                }
+                
+                // Call the function
+                let call = self.builder.build_call(function, &arg_values, &format!("{}_call", name));
+                return Ok(self.owned_call_result(call));
+            }
+        }
+        
+        // Any other callee is a function value
+        let callee = self.generate_expr(func)?;
+        self.generate_closure_call(callee, args)
+    }
+    
+    // Generates a method call, which yields None when the method is void
+    fn generate_method_expr(&mut self, object: &Expr, method: &str, args: &[Expr]) -> Result<Option<BasicValueEnum<'ctx>>, ShitRustError> {
+        let receiver = match self.generate_place(object)? {
+            Some((slot, typ)) if self.heap_type_of(typ).is_some() => return self.generate_heap_method(slot, typ, method, args).map(Some),
+            Some((slot, typ)) if typ.is_struct_type() => return self.generate_method_call(slot, typ, method, args),
+            Some((slot, typ)) => self.builder.build_load(typ, slot, "receiver"),
+            None => self.generate_expr(object)?,
+        };
+        
+        if receiver.is_struct_value() {
+            // Temporaries are spilled so the method can take their address. The slot takes a
+            // reference of its own, since the method may assign to the receiver.
+            self.build_retain(receiver);
+            let slot = self.register_temporary(receiver);
+            return match self.heap_type_of(receiver.get_type()) {
+                Some(_) => self.generate_heap_method(slot, receiver.get_type(), method, args).map(Some),
+                None => self.generate_method_call(slot, receiver.get_type(), method, args),
+            };
+        }
+        
+        let mut arg_values = Vec::new();
+        for arg in args {
+            arg_values.push(self.generate_expr(arg)?);
+        }
+        
+        let value = match receiver {
+            _ if method == "to_string" && arg_values.is_empty() => self.generate_to_string(receiver)?,
+            BasicValueEnum::IntValue(i) => self.generate_int_method(i, method, &arg_values)?,
+            _ => return Err(ShitRustError::RuntimeError(format!("Method '{}' is not supported in compiled code", method))),
+        };
+        Ok(Some(value))
+    }
+    
+    fn generate_stmt(&mut self, stmt: &Stmt) -> Result<(), ShitRustError> {
+        match stmt {
+            Stmt::Expr(expr) => {
+                // Only statements may call void functions
+                match expr {
+                    Expr::Call { func, args } => self.generate_call(func, args).map(drop),
+                    Expr::MethodCall { object, method, args } => self.generate_method_expr(object, method, args).map(drop),
+                    _ => self.generate_expr(expr).map(drop),
+                }
+            },
+            Stmt::Let { name, type_hint, value, mutable: _ } => {
+                // The hint is only needed to type empty lists and dicts, lambdas and variants of generic enums
+                let hint = type_hint.as_ref().and_then(|hint| self.lower_type(hint).ok());
+                let expr_value = self.generate_expr_with_hint(value, hint)?;
+                
+                // Allocate space on the stack
+                let alloca = self.create_owned_slot(name, expr_value.get_type());
+                
+                // Store the value
+                self.store_owned(alloca, expr_value.get_type(), expr_value);
+                
+                // Add to our symbol table
+                self.named_values.insert(name.clone(), (alloca, expr_value.get_type()));
+                self.declare_debug_variable(name, alloca, expr_value.get_type(), None);
+                
+                Ok(())
+            },
+            Stmt::Assign { target: Expr::Index { target, index }, value } => self.generate_element_assign(target, index, value),
+            Stmt::Assign { target, value } => {
+                let (slot, typ) = self.generate_place(target)?
+                    .ok_or_else(|| ShitRustError::RuntimeError("Invalid assignment target".to_string()))?;
+                let value = self.generate_expr_with_hint(value, Some(typ))?;
+                
+                self.store_owned(slot, typ, value);
+                Ok(())
+            },
+            Stmt::If { condition, then_block, else_block } => {
+                let cond_val = self.generate_condition(condition)?;
+                
                 let function = self.current_function.unwrap();
                 let then_bb = self.context.append_basic_block(function, "then");
                 let else_bb = self.context.append_basic_block(function, "else");
@@ -209,166 +1631,2162 @@ impl<'ctx> CodeGen<'ctx> {
                 
                 // Then block
                 self.builder.position_at_end(then_bb);
-                for stmt in then_block {
-                    self.generate_stmt(stmt)?;
-                }
-                self.builder.build_unconditional_branch(merge_bb);
+                self.generate_block(then_block)?;
+                self.branch_unless_terminated(merge_bb);
                 
                 // Else block
                 self.builder.position_at_end(else_bb);
                 if let Some(else_stmts) = else_block {
-                    for stmt in else_stmts {
-                        self.generate_stmt(stmt)?;
-                    }
+                    self.generate_block(else_stmts)?;
                 }
-                self.builder.build_unconditional_branch(merge_bb);
+                self.branch_unless_terminated(merge_bb);
                 
                 // Continue in the merge block
                 self.builder.position_at_end(merge_bb);
                 
                 Ok(())
             },
-            Stmt::Return(value_opt) => {
-                match value_opt {
-                    Some(value) => {
-                        let return_value = self.generate_expr(value)?;
-                        self.builder.build_return(Some(&return_value));
-                    },
-                    None => {
-                        self.builder.build_return(None);
-                    }
+            Stmt::While { condition, body } => {
+                let function = self.current_function.unwrap();
+                let cond_bb = self.context.append_basic_block(function, "while_cond");
+                let body_bb = self.context.append_basic_block(function, "while_body");
+                let after_bb = self.context.append_basic_block(function, "while_end");
+                
+                self.builder.build_unconditional_branch(cond_bb);
+                self.builder.position_at_end(cond_bb);
+                let cond_val = self.generate_condition(condition)?;
+                self.builder.build_conditional_branch(cond_val, body_bb, after_bb);
+                
+                self.builder.position_at_end(body_bb);
+                self.generate_loop_body(body, cond_bb, after_bb)?;
+                self.branch_unless_terminated(cond_bb);
+                
+                self.builder.position_at_end(after_bb);
+                Ok(())
+            },
+            Stmt::Loop { body } => {
+                let function = self.current_function.unwrap();
+                let body_bb = self.context.append_basic_block(function, "loop_body");
+                let after_bb = self.context.append_basic_block(function, "loop_end");
+                
+                self.builder.build_unconditional_branch(body_bb);
+                self.builder.position_at_end(body_bb);
+                self.generate_loop_body(body, body_bb, after_bb)?;
+                self.branch_unless_terminated(body_bb);
+                
+                self.builder.position_at_end(after_bb);
+                Ok(())
+            },
+            Stmt::For { var, iterator, body } => self.generate_for_range(var, iterator, body),
+            Stmt::Break => {
+                let target = self.loop_stack.last().ok_or(ShitRustError::Break)?.break_block;
+                self.builder.build_unconditional_branch(target);
+                Ok(())
+            },
+            Stmt::Continue => {
+                let target = self.loop_stack.last().ok_or(ShitRustError::Continue)?.continue_block;
+                self.builder.build_unconditional_branch(target);
+                Ok(())
+            },
+            Stmt::Match { expr, arms } => {
+                let patterns: Vec<&Pattern> = arms.iter().map(|(pattern, _)| pattern).collect();
+                let lowered = self.generate_match_dispatch(expr, &patterns)?;
+                let merge_bb = self.context.append_basic_block(self.current_function.unwrap(), "match_end");
+                
+                for (arm, (_, body)) in lowered.into_iter().zip(arms) {
+                    self.builder.position_at_end(arm.block);
+                    let outer_values = self.named_values.clone();
+                    for (name, slot, typ) in arm.bindings {
+                        self.declare_debug_variable(&name, slot, typ, None);
+                        self.named_values.insert(name, (slot, typ));
+                    }
+                    
+                    self.generate_block(body)?;
+                    self.named_values = outer_values;
+                    self.branch_unless_terminated(merge_bb);
+                }
+                
+                self.builder.position_at_end(merge_bb);
+                Ok(())
+            },
+            Stmt::Return(value_opt) => {
+                let return_block = self.function_state.return_block.ok_or(ShitRustError::Return)?;
+                let return_slot = self.function_state.return_slot;
+                
+                match (value_opt, return_slot) {
+                    (Some(value), Some((slot, typ))) => {
+                        // The caller receives a reference of its own
+                        let return_value = self.generate_expr_with_hint(value, Some(typ))?;
+                        self.build_retain(return_value);
+                        self.builder.build_store(slot, return_value);
+                    },
+                    (Some(_), None) => {
+                        return Err(ShitRustError::TypeError("Cannot return a value from a void function".to_string()));
+                    },
+                    (None, _) => (),
+                }
+                
+                self.builder.build_unconditional_branch(return_block);
+                Ok(())
+            },
+            Stmt::Attributed { stmt, .. } => self.generate_stmt(stmt),
+            Stmt::Located { stmt: located, .. } => {
+                self.set_debug_location(stmt);
+                self.generate_stmt(located)
+            },
+            // Other statement types would be handled here
+            _ => Err(ShitRustError::RuntimeError(format!("Statement type not yet implemented: {:?}", stmt))),
+        }
+    }
+    
+    fn generate_expr(&mut self, expr: &Expr) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        match expr {
+            Expr::Literal(lit) => self.generate_literal(lit),
+            Expr::Identifier(name) => {
+                if let Some((var, typ)) = self.named_values.get(name) {
+                    Ok(self.builder.build_load(*typ, *var, name))
+                } else if let Some(variant) = self.generate_variant(name, &[], None)? {
+                    Ok(variant)
+                } else if self.extern_functions.contains_key(name) {
+                    Err(ShitRustError::TypeError(format!("C function {} can only be called, not used as a value", name)))
+                } else if let Some(function) = self.module.get_function(name).filter(|_| !self.methods.contains(name)) {
+                    Ok(self.generate_function_value(function))
+                } else {
+                    Err(ShitRustError::UndefinedVariable(name.clone()))
+                }
+            },
+            Expr::BinaryOp { left, op: op @ (BinOp::And | BinOp::Or), right } => {
+                self.generate_short_circuit(op, left, right)
+            },
+            Expr::BinaryOp { left, op: BinOp::NullishCoalescing, right } => {
+                // Both sides are evaluated, like in the interpreter
+                let l_val = self.generate_expr(left)?;
+                let r_val = self.generate_expr(right)?;
+                self.generate_nullish(l_val, r_val)
+            },
+            Expr::BinaryOp { left, op, right } => {
+                let l_val = self.generate_expr(left)?;
+                let r_val = self.generate_expr(right)?;
+                
+                if self.is_string(l_val.get_type()) && self.is_string(r_val.get_type()) {
+                    return self.generate_string_op(op, l_val, r_val);
+                }
+                
+                match op {
+                    BinOp::Add => self.generate_add(l_val, r_val),
+                    BinOp::Sub => self.generate_sub(l_val, r_val),
+                    BinOp::Mul => self.generate_mul(l_val, r_val),
+                    BinOp::Div => self.generate_div(l_val, r_val),
+                    BinOp::Mod => self.generate_rem(l_val, r_val),
+                    BinOp::Eq => self.generate_eq(l_val, r_val),
+                    BinOp::Ne => self.generate_ne(l_val, r_val),
+                    BinOp::Lt => self.generate_lt(l_val, r_val),
+                    BinOp::Le => self.generate_le(l_val, r_val),
+                    BinOp::Gt => self.generate_gt(l_val, r_val),
+                    BinOp::Ge => self.generate_ge(l_val, r_val),
+                    // Other operators would be implemented here
+                    _ => Err(ShitRustError::RuntimeError(format!("Binary operator not implemented: {:?}", op))),
+                }
+            },
+            Expr::Call { func, args } => {
+                let callee = match &**func {
+                    Expr::Identifier(name) => name.as_str(),
+                    _ => "closure",
+                };
+                self.generate_call(func, args)?.ok_or_else(|| Self::void_call_error(callee))
+            },
+            Expr::Lambda { params, body, return_type } => self.generate_lambda(params, body, return_type.as_ref(), None),
+            Expr::TypeCast { expr, target_type } => {
+                let value = self.generate_expr(expr)?;
+                self.generate_cast(value, target_type)
+            },
+            Expr::UnaryOp { op, expr } => {
+                let value = self.generate_expr(expr)?;
+                
+                match (op, value) {
+                    (UnaryOp::Neg, BasicValueEnum::IntValue(i)) => {
+                        let zero = i.get_type().const_zero();
+                        Ok(self.build_checked_int_op("sub", zero, i, "negation")?.into())
+                    },
+                    (UnaryOp::Neg, BasicValueEnum::FloatValue(f)) => {
+                        Ok(self.builder.build_float_neg(f, "negtmp").into())
+                    },
+                    (UnaryOp::Not, BasicValueEnum::IntValue(i)) => {
+                        Ok(self.builder.build_not(i, "nottmp").into())
+                    },
+                    _ => Err(ShitRustError::TypeError(format!("Unsupported operand for {:?}", op))),
+                }
+            },
+            Expr::MethodCall { object, method, args } => {
+                self.generate_method_expr(object, method, args)?.ok_or_else(|| Self::void_call_error(method))
+            },
+            Expr::Index { target, index } => {
+                let container = self.generate_expr(target)?;
+                
+                // Elements are borrowed from the container
+                let bits = match self.heap_type_of(container.get_type()) {
+                    Some(HeapType::List(element)) => {
+                        let index = self.generate_typed_expr(index, self.context.i64_type().into(), "list index")?;
+                        let bits = self.call_runtime("shitrust_list_get", &[self.heap_pointer(container).into(), index.into()]);
+                        (bits, element)
+                    },
+                    Some(HeapType::Dict(key, element)) => {
+                        let key = self.generate_typed_expr(index, key, "dict key")?;
+                        let key_bits = self.to_slot_bits(key);
+                        let bits = self.call_runtime("shitrust_dict_get", &[self.heap_pointer(container).into(), key_bits.into()]);
+                        (bits, element)
+                    },
+                    _ => return Err(ShitRustError::TypeError(
+                        format!("Cannot index {} in compiled code", self.describe_type(container.get_type()))
+                    )),
+                };
+                
+                Ok(self.from_slot_bits(bits.0.into_int_value(), bits.1))
+            },
+            Expr::StructInit { name, fields } => self.generate_struct_init(name, fields),
+            Expr::TernaryIf { condition, then_expr, else_expr } => {
+                let cond_val = self.generate_condition(condition)?;
+                
+                let function = self.current_function.unwrap();
+                let then_bb = self.context.append_basic_block(function, "ternary_then");
+                let else_bb = self.context.append_basic_block(function, "ternary_else");
+                let merge_bb = self.context.append_basic_block(function, "ternary_end");
+                self.builder.build_conditional_branch(cond_val, then_bb, else_bb);
+                
+                let mut incoming = Vec::new();
+                for (block, expr) in [(then_bb, then_expr), (else_bb, else_expr)] {
+                    self.builder.position_at_end(block);
+                    let value = self.generate_expr(expr)?;
+                    incoming.push((value, self.builder.get_insert_block().unwrap()));
+                    self.builder.build_unconditional_branch(merge_bb);
+                }
+                
+                self.builder.position_at_end(merge_bb);
+                self.build_merge_value(&incoming, "ternary_value")
+            },
+            Expr::Match { expr: scrutinee, arms } => {
+                let patterns: Vec<&Pattern> = arms.iter().map(|(pattern, _)| pattern).collect();
+                let lowered = self.generate_match_dispatch(scrutinee, &patterns)?;
+                let merge_bb = self.context.append_basic_block(self.current_function.unwrap(), "match_end");
+                
+                let mut incoming = Vec::new();
+                for (arm, (_, result)) in lowered.into_iter().zip(arms) {
+                    self.builder.position_at_end(arm.block);
+                    let outer_values = self.named_values.clone();
+                    for (name, slot, typ) in arm.bindings {
+                        self.declare_debug_variable(&name, slot, typ, None);
+                        self.named_values.insert(name, (slot, typ));
+                    }
+                    
+                    let value = self.generate_expr(result)?;
+                    self.named_values = outer_values;
+                    incoming.push((value, self.builder.get_insert_block().unwrap()));
+                    self.builder.build_unconditional_branch(merge_bb);
+                }
+                
+                self.builder.position_at_end(merge_bb);
+                self.build_merge_value(&incoming, "match_value")
+            },
+            Expr::FieldAccess { object, field } => {
+                if let Some((slot, typ)) = self.generate_place(expr)? {
+                    return Ok(self.builder.build_load(typ, slot, field));
+                }
+                
+                let value = self.generate_expr(object)?;
+                let (index, _) = self.field_index(value.get_type(), field)?;
+                Ok(self.builder.build_extract_value(value.into_struct_value(), index, field).unwrap())
+            },
+            // Other expression types would be handled here
+            _ => Err(ShitRustError::RuntimeError(format!("Expression type not yet implemented: {:?}", expr))),
+        }
+    }
+    
+    // Generates statements until one of them ends the current block with a return, break or
+    // continue; anything after that is unreachable. Variables declared inside go out of scope at the end.
+    fn generate_block(&mut self, stmts: &[Stmt]) -> Result<(), ShitRustError> {
+        let outer_values = self.named_values.clone();
+        let scoped = self.enter_debug_block();
+        
+        for stmt in stmts {
+            if self.block_terminated() {
+                break;
+            }
+            self.generate_stmt_with_temporaries(stmt)?;
+        }
+        
+        if scoped {
+            self.leave_debug_scope();
+        }
+        self.named_values = outer_values;
+        Ok(())
+    }
+    
+    // Generates a statement and then releases the temporaries its expressions created
+    fn generate_stmt_with_temporaries(&mut self, stmt: &Stmt) -> Result<(), ShitRustError> {
+        let mark = self.function_state.temporaries.len();
+        self.generate_stmt(stmt)?;
+        
+        // After a return the return block releases them
+        let temporaries = self.function_state.temporaries.split_off(mark);
+        if !self.block_terminated() {
+            for (slot, typ) in temporaries {
+                self.build_release(slot, typ);
+                self.builder.build_store(slot, typ.const_zero());
+            }
+        }
+        Ok(())
+    }
+    
+    fn block_terminated(&self) -> bool {
+        self.builder.get_insert_block().map_or(false, |block| block.get_terminator().is_some())
+    }
+    
+    fn branch_unless_terminated(&self, target: BasicBlock<'ctx>) {
+        if !self.block_terminated() {
+            self.builder.build_unconditional_branch(target);
+        }
+    }
+    
+    fn generate_condition(&mut self, condition: &Expr) -> Result<IntValue<'ctx>, ShitRustError> {
+        match self.generate_expr(condition)? {
+            BasicValueEnum::IntValue(i) if i.get_type().get_bit_width() == 1 => Ok(i),
+            _ => Err(ShitRustError::TypeError("Condition must be a boolean".to_string())),
+        }
+    }
+    
+    fn generate_loop_body(&mut self, body: &[Stmt], continue_block: BasicBlock<'ctx>, break_block: BasicBlock<'ctx>) -> Result<(), ShitRustError> {
+        self.loop_stack.push(LoopTargets { continue_block, break_block });
+        let result = self.generate_block(body);
+        self.loop_stack.pop();
+        result
+    }
+    
+    // Lowers `for i in range(start, end)` (or a range expression) to a counting loop over ints.
+    // Inclusive ranges stop after `end` without incrementing past it, so `..=int::MAX` cannot overflow.
+    // Any other iterator must be a list.
+    fn generate_for_range(&mut self, var: &str, iterator: &Expr, body: &[Stmt]) -> Result<(), ShitRustError> {
+        let (start, end, inclusive) = match iterator {
+            Expr::Call { func, args } if matches!(&**func, Expr::Identifier(name) if name == "range") && args.len() == 2 => {
+                (&args[0], &args[1], false)
+            },
+            Expr::RangeExpr { start: Some(start), end: Some(end), inclusive } |
+            Expr::Literal(Literal::Range { start: Some(start), end: Some(end), inclusive }) => (&**start, &**end, *inclusive),
+            _ => return self.generate_for_list(var, iterator, body),
+        };
+        
+        let (start, end) = match (self.generate_expr(start)?, self.generate_expr(end)?) {
+            (BasicValueEnum::IntValue(start), BasicValueEnum::IntValue(end)) => (start, end),
+            _ => return Err(ShitRustError::TypeError("Range bounds must be integers".to_string())),
+        };
+        
+        let counter = self.create_entry_block_alloca(var, start.get_type().into());
+        self.builder.build_store(counter, start);
+        
+        let function = self.current_function.unwrap();
+        let cond_bb = self.context.append_basic_block(function, "for_cond");
+        let body_bb = self.context.append_basic_block(function, "for_body");
+        let step_bb = self.context.append_basic_block(function, "for_step");
+        let after_bb = self.context.append_basic_block(function, "for_end");
+        
+        self.builder.build_unconditional_branch(cond_bb);
+        self.builder.position_at_end(cond_bb);
+        let current = self.builder.build_load(start.get_type(), counter, var).into_int_value();
+        let predicate = if inclusive { inkwell::IntPredicate::SLE } else { inkwell::IntPredicate::SLT };
+        let in_range = self.builder.build_int_compare(predicate, current, end, "in_range");
+        self.builder.build_conditional_branch(in_range, body_bb, after_bb);
+        
+        self.builder.position_at_end(body_bb);
+        let outer_values = self.named_values.clone();
+        self.named_values.insert(var.to_string(), (counter, start.get_type().into()));
+        self.declare_debug_variable(var, counter, start.get_type().into(), None);
+        let result = self.generate_loop_body(body, step_bb, after_bb);
+        self.named_values = outer_values;
+        result?;
+        self.branch_unless_terminated(step_bb);
+        
+        self.builder.position_at_end(step_bb);
+        let current = self.builder.build_load(start.get_type(), counter, var).into_int_value();
+        if inclusive {
+            let increment_bb = self.context.append_basic_block(function, "for_increment");
+            let is_last = self.builder.build_int_compare(inkwell::IntPredicate::EQ, current, end, "is_last");
+            self.builder.build_conditional_branch(is_last, after_bb, increment_bb);
+            self.builder.position_at_end(increment_bb);
+        }
+        let next = self.builder.build_int_add(current, start.get_type().const_int(1, false), "next");
+        self.builder.build_store(counter, next);
+        self.builder.build_unconditional_branch(cond_bb);
+        
+        self.builder.position_at_end(after_bb);
+        Ok(())
+    }
+    
+    // Iterates over the elements of a list. The loop keeps its own reference to the list, so
+    // assignments to the iterated variable in the body do not affect the iteration.
+    fn generate_for_list(&mut self, var: &str, iterator: &Expr, body: &[Stmt]) -> Result<(), ShitRustError> {
+        let list = self.generate_expr(iterator)?;
+        let Some(HeapType::List(element)) = self.heap_type_of(list.get_type()) else {
+            return Err(ShitRustError::RuntimeError(
+                format!("Only bounded ranges and lists can be iterated in compiled code, not {}", self.describe_type(list.get_type()))
+            ));
+        };
+        
+        let list_slot = self.create_owned_slot("iterated", list.get_type());
+        self.store_owned(list_slot, list.get_type(), list);
+        let i64_type = self.context.i64_type();
+        let counter = self.create_entry_block_alloca("index", i64_type.into());
+        self.builder.build_store(counter, i64_type.const_zero());
+        let item_slot = self.create_owned_slot(var, element);
+        
+        let function = self.current_function.unwrap();
+        let cond_bb = self.context.append_basic_block(function, "for_cond");
+        let body_bb = self.context.append_basic_block(function, "for_body");
+        let step_bb = self.context.append_basic_block(function, "for_step");
+        let after_bb = self.context.append_basic_block(function, "for_end");
+        
+        self.builder.build_unconditional_branch(cond_bb);
+        self.builder.position_at_end(cond_bb);
+        let current = self.builder.build_load(i64_type, counter, "index").into_int_value();
+        let list = self.builder.build_load(list.get_type(), list_slot, "iterated");
+        let len = self.call_runtime("shitrust_list_len", &[self.heap_pointer(list).into()]).into_int_value();
+        let in_range = self.builder.build_int_compare(inkwell::IntPredicate::SLT, current, len, "in_range");
+        self.builder.build_conditional_branch(in_range, body_bb, after_bb);
+        
+        self.builder.position_at_end(body_bb);
+        let bits = self.call_runtime("shitrust_list_get", &[self.heap_pointer(list).into(), current.into()]);
+        let item = self.from_slot_bits(bits.into_int_value(), element);
+        self.store_owned(item_slot, element, item);
+        
+        let outer_values = self.named_values.clone();
+        self.named_values.insert(var.to_string(), (item_slot, element));
+        self.declare_debug_variable(var, item_slot, element, None);
+        let result = self.generate_loop_body(body, step_bb, after_bb);
+        self.named_values = outer_values;
+        result?;
+        self.branch_unless_terminated(step_bb);
+        
+        self.builder.position_at_end(step_bb);
+        let current = self.builder.build_load(i64_type, counter, "index").into_int_value();
+        let next = self.builder.build_int_add(current, i64_type.const_int(1, false), "next");
+        self.builder.build_store(counter, next);
+        self.builder.build_unconditional_branch(cond_bb);
+        
+        self.builder.position_at_end(after_bb);
+        Ok(())
+    }
+    
+    // `&&` and `||` only evaluate their right operand when it decides the result
+    fn generate_short_circuit(&mut self, op: &BinOp, left: &Expr, right: &Expr) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let is_and = matches!(op, BinOp::And);
+        let lhs = self.generate_condition(left)?;
+        let lhs_block = self.builder.get_insert_block().unwrap();
+        
+        let function = self.current_function.unwrap();
+        let rhs_bb = self.context.append_basic_block(function, "logic_rhs");
+        let merge_bb = self.context.append_basic_block(function, "logic_end");
+        if is_and {
+            self.builder.build_conditional_branch(lhs, rhs_bb, merge_bb);
+        } else {
+            self.builder.build_conditional_branch(lhs, merge_bb, rhs_bb);
+        }
+        
+        self.builder.position_at_end(rhs_bb);
+        let rhs = self.generate_condition(right)?;
+        let rhs_block = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(merge_bb);
+        
+        self.builder.position_at_end(merge_bb);
+        let short_circuited = self.context.bool_type().const_int(!is_and as u64, false);
+        self.build_merge_value(&[(short_circuited.into(), lhs_block), (rhs.into(), rhs_block)], "logic_value")
+    }
+    
+    // Joins the values flowing into the current block with a phi node
+    fn build_merge_value(&self, incoming: &[(BasicValueEnum<'ctx>, BasicBlock<'ctx>)], name: &str) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let Some((first, _)) = incoming.first() else {
+            return Err(ShitRustError::RuntimeError("Expression has no branches".to_string()));
+        };
+        
+        if incoming.iter().any(|(value, _)| value.get_type() != first.get_type()) {
+            return Err(ShitRustError::TypeError(format!("Branches of '{}' have different types", name)));
+        }
+        
+        let phi = self.builder.build_phi(first.get_type(), name);
+        for (value, block) in incoming {
+            phi.add_incoming(&[(value, *block)]);
+        }
+        Ok(phi.as_basic_value())
+    }
+    
+    // Evaluates a match scrutinee and branches to the first arm whose pattern matches. Enums
+    // switch on their tag and ints, chars and bools switch on their value; matches with range
+    // patterns test the arms one after another instead. When no arm matches the program traps.
+    fn generate_match_dispatch(&mut self, scrutinee: &Expr, patterns: &[&Pattern]) -> Result<Vec<MatchArm<'ctx>>, ShitRustError> {
+        // The scrutinee is kept in memory so that patterns can bind parts of it
+        let (slot, typ) = match self.generate_place(scrutinee)? {
+            Some(place) => place,
+            None => {
+                let value = self.generate_expr(scrutinee)?;
+                let slot = self.create_entry_block_alloca("scrutinee", value.get_type());
+                self.builder.build_store(slot, value);
+                (slot, value.get_type())
+            },
+        };
+        
+        let function = self.current_function.unwrap();
+        let mut arms: Vec<MatchArm> = patterns.iter()
+            .map(|_| MatchArm { block: self.context.append_basic_block(function, "match_arm"), bindings: Vec::new() })
+            .collect();
+        let failed_bb = self.context.append_basic_block(function, "match_failed");
+        
+        let enum_layout = self.nominal_type_name(typ).and_then(|name| self.enum_layouts.get(&name).cloned());
+        if let Some(layout) = enum_layout {
+            self.generate_enum_dispatch(slot, &layout, patterns, &mut arms, failed_bb)?;
+        } else if let BasicTypeEnum::IntType(int_type) = typ {
+            let value = self.builder.build_load(int_type, slot, "scrutinee").into_int_value();
+            self.generate_int_dispatch(value, slot, patterns, &mut arms, failed_bb)?;
+        } else {
+            return Err(ShitRustError::RuntimeError(
+                "Only ints, chars, bools and enums can be matched in compiled code".to_string()
+            ));
+        }
+        
+        self.builder.position_at_end(failed_bb);
+        self.build_trap()?;
+        Ok(arms)
+    }
+    
+    fn generate_enum_dispatch(
+        &mut self,
+        slot: PointerValue<'ctx>,
+        layout: &EnumLayout<'ctx>,
+        patterns: &[&Pattern],
+        arms: &mut [MatchArm<'ctx>],
+        failed_bb: BasicBlock<'ctx>,
+    ) -> Result<(), ShitRustError> {
+        let invalid_layout = || ShitRustError::RuntimeError("Invalid enum layout".to_string());
+        let tag_ptr = self.builder.build_struct_gep(layout.llvm_type, slot, 0, "tag_ptr").map_err(|_| invalid_layout())?;
+        let tag = self.builder.build_load(self.context.i32_type(), tag_ptr, "tag").into_int_value();
+        let payload_ptr = self.builder.build_struct_gep(layout.llvm_type, slot, 1, "payload_ptr").map_err(|_| invalid_layout())?;
+        
+        let variant_tag = |name: &str| {
+            let variant = name.rsplit("::").next().unwrap_or(name);
+            layout.variants.iter().position(|(declared, _)| declared == variant)
+                .ok_or_else(|| ShitRustError::TypeError(format!("Unknown enum variant '{}'", name)))
+        };
+        
+        let mut cases = Vec::new();
+        let mut claimed = HashSet::new();
+        let mut default = None;
+        for (index, pattern) in patterns.iter().enumerate() {
+            // Arms after a catch-all can never be reached
+            if default.is_some() {
+                break;
+            }
+            
+            let alternatives = match pattern {
+                Pattern::Or(alternatives) => alternatives.iter().collect(),
+                _ => vec![*pattern],
+            };
+            
+            for alternative in alternatives {
+                match alternative {
+                    Pattern::Wildcard => default = Some(arms[index].block),
+                    Pattern::Identifier(name) => {
+                        default = Some(arms[index].block);
+                        arms[index].bindings.push((name.clone(), slot, layout.llvm_type.into()));
+                    },
+                    Pattern::EnumVariant { name, values } => {
+                        let tag_value = variant_tag(name)?;
+                        let payload_type = layout.variants[tag_value].1;
+                        if values.len() != payload_type.count_fields() as usize {
+                            return Err(ShitRustError::TypeError(format!(
+                                "Variant '{}' has {} values but the pattern has {}", name, payload_type.count_fields(), values.len()
+                            )));
+                        }
+                        
+                        // The first arm for a variant wins, as in the interpreter
+                        if claimed.insert(tag_value) {
+                            cases.push((self.context.i32_type().const_int(tag_value as u64, false), arms[index].block));
+                        }
+                        
+                        for (field, value_pattern) in values.iter().enumerate() {
+                            match value_pattern {
+                                Pattern::Wildcard => (),
+                                Pattern::Identifier(binding) if !matches!(pattern, Pattern::Or(_)) => {
+                                    // The dispatch block dominates every arm, so the address can be computed here
+                                    let field_ptr = self.builder.build_struct_gep(payload_type, payload_ptr, field as u32, binding)
+                                        .map_err(|_| invalid_layout())?;
+                                    let field_type = payload_type.get_field_type_at_index(field as u32).unwrap();
+                                    arms[index].bindings.push((binding.clone(), field_ptr, field_type));
+                                },
+                                _ => return Err(ShitRustError::RuntimeError(
+                                    format!("Nested patterns in '{}' are not supported in compiled code", name)
+                                )),
+                            }
+                        }
+                    },
+                    _ => return Err(ShitRustError::TypeError("Expected an enum variant pattern".to_string())),
+                }
+            }
+        }
+        
+        self.builder.build_switch(tag, default.unwrap_or(failed_bb), &cases);
+        Ok(())
+    }
+    
+    fn generate_int_dispatch(
+        &mut self,
+        value: IntValue<'ctx>,
+        slot: PointerValue<'ctx>,
+        patterns: &[&Pattern],
+        arms: &mut [MatchArm<'ctx>],
+        failed_bb: BasicBlock<'ctx>,
+    ) -> Result<(), ShitRustError> {
+        for (index, pattern) in patterns.iter().enumerate() {
+            if let Pattern::Identifier(name) = pattern {
+                arms[index].bindings.push((name.clone(), slot, value.get_type().into()));
+            }
+        }
+        
+        let has_ranges = patterns.iter().any(|pattern| match pattern {
+            Pattern::Range { .. } => true,
+            Pattern::Or(alternatives) => alternatives.iter().any(|alternative| matches!(alternative, Pattern::Range { .. })),
+            _ => false,
+        });
+        
+        if has_ranges {
+            // Test the arms in order
+            let function = self.current_function.unwrap();
+            for (index, pattern) in patterns.iter().enumerate() {
+                match self.int_pattern_condition(value, pattern)? {
+                    Some(matched) => {
+                        let next_bb = self.context.append_basic_block(function, "match_next");
+                        self.builder.build_conditional_branch(matched, arms[index].block, next_bb);
+                        self.builder.position_at_end(next_bb);
+                    },
+                    None => {
+                        self.builder.build_unconditional_branch(arms[index].block);
+                        return Ok(());
+                    },
+                }
+            }
+            self.builder.build_unconditional_branch(failed_bb);
+            return Ok(());
+        }
+        
+        let mut cases = Vec::new();
+        let mut claimed = HashSet::new();
+        let mut default = None;
+        for (index, pattern) in patterns.iter().enumerate() {
+            if default.is_some() {
+                break;
+            }
+            
+            let alternatives = match pattern {
+                Pattern::Or(alternatives) => alternatives.iter().collect(),
+                _ => vec![*pattern],
+            };
+            
+            for alternative in alternatives {
+                match alternative {
+                    Pattern::Wildcard | Pattern::Identifier(_) => default = Some(arms[index].block),
+                    Pattern::Literal(literal) => {
+                        let constant = self.pattern_constant(value, literal)?;
+                        // Duplicate cases are not allowed in a switch; the first arm wins
+                        if claimed.insert(constant.get_sign_extended_constant()) {
+                            cases.push((constant, arms[index].block));
+                        }
+                    },
+                    _ => return Err(ShitRustError::TypeError("Expected a literal pattern".to_string())),
+                }
+            }
+        }
+        
+        self.builder.build_switch(value, default.unwrap_or(failed_bb), &cases);
+        Ok(())
+    }
+    
+    // The condition under which an int pattern matches, or None for patterns that always match
+    fn int_pattern_condition(&self, value: IntValue<'ctx>, pattern: &Pattern) -> Result<Option<IntValue<'ctx>>, ShitRustError> {
+        match pattern {
+            Pattern::Wildcard | Pattern::Identifier(_) => Ok(None),
+            Pattern::Literal(literal) => {
+                let constant = self.pattern_constant(value, literal)?;
+                Ok(Some(self.builder.build_int_compare(inkwell::IntPredicate::EQ, value, constant, "matches")))
+            },
+            Pattern::Range { start, end, inclusive } => {
+                let start = self.pattern_constant(value, start)?;
+                let end = self.pattern_constant(value, end)?;
+                let end_predicate = if *inclusive { inkwell::IntPredicate::SLE } else { inkwell::IntPredicate::SLT };
+                
+                let above_start = self.builder.build_int_compare(inkwell::IntPredicate::SGE, value, start, "above_start");
+                let below_end = self.builder.build_int_compare(end_predicate, value, end, "below_end");
+                Ok(Some(self.builder.build_and(above_start, below_end, "in_range")))
+            },
+            Pattern::Or(alternatives) => {
+                let mut matched: Option<IntValue> = None;
+                for alternative in alternatives {
+                    let Some(condition) = self.int_pattern_condition(value, alternative)? else {
+                        return Ok(None);
+                    };
+                    matched = Some(match matched {
+                        Some(previous) => self.builder.build_or(previous, condition, "matches"),
+                        None => condition,
+                    });
+                }
+                Ok(matched)
+            },
+            _ => Err(ShitRustError::TypeError("Expected a literal or range pattern".to_string())),
+        }
+    }
+    
+    fn pattern_constant(&self, value: IntValue<'ctx>, literal: &Literal) -> Result<IntValue<'ctx>, ShitRustError> {
+        let int_type = value.get_type();
+        match literal {
+            Literal::Int(i) => Ok(int_type.const_int(*i as u64, true)),
+            Literal::Char(c) => Ok(int_type.const_int(*c as u64, false)),
+            Literal::Bool(b) => Ok(int_type.const_int(*b as u64, false)),
+            _ => Err(ShitRustError::TypeError(format!("Cannot match {:?} against an integer", literal))),
+        }
+    }
+    
+    // Generics
+    
+    // Keeps the generic functions, structs and enums of the program. The methods of `impl` blocks
+    // for a generic type are added to it; their type parameters must be named as in the type.
+    fn collect_generics(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            match stmt.without_attributes() {
+                Stmt::Function { name, generic_params, .. } if !generic_params.is_empty() => {
+                    self.generic_functions.insert(name.clone(), stmt.clone());
+                },
+                Stmt::Struct { name, fields, methods, generic_params, .. } if !generic_params.is_empty() => {
+                    self.generic_types.insert(name.clone(), GenericType {
+                        generic_params: generic_params.clone(),
+                        shape: GenericShape::Struct(fields.iter().map(|(field, typ, _)| (field.clone(), typ.clone())).collect()),
+                        methods: methods.clone(),
+                    });
+                },
+                Stmt::Enum { name, variants, generic_params, .. } if !generic_params.is_empty() => {
+                    self.generic_types.insert(name.clone(), GenericType {
+                        generic_params: generic_params.clone(),
+                        shape: GenericShape::Enum(variants.clone()),
+                        methods: Vec::new(),
+                    });
+                },
+                _ => (),
+            }
+        }
+        
+        // `impl` blocks may come before the type they are for
+        for stmt in statements {
+            if let Stmt::Impl { type_name, methods, .. } = stmt.without_attributes() {
+                if let Some(generic) = self.generic_types.get_mut(type_name) {
+                    generic.methods.extend(methods.iter().cloned());
+                }
+            }
+        }
+    }
+    
+    // Specializes the generic functions and types for the type arguments the type checker found.
+    // Enum variants are checked as functions named like `Option::Some` with the enum's type
+    // parameters. Type arguments that cannot be lowered, such as `any` or the type parameters of
+    // the generic function a use is in, are skipped.
+    fn declare_instantiations(&mut self) -> Result<(), ShitRustError> {
+        let instantiations = std::mem::take(&mut self.instantiations);
+        for (name, uses) in &instantiations {
+            let type_name = name.split_once("::").map_or(name.as_str(), |(type_name, _)| type_name);
+            let function_params = match self.generic_functions.get(name).map(Stmt::without_attributes) {
+                Some(Stmt::Function { generic_params, .. }) => Some(generic_params.len()),
+                _ => None,
+            };
+            let type_params = self.generic_types.get(type_name).map(|generic| generic.generic_params.len());
+            
+            for type_args in uses {
+                let Ok(type_args) = type_args.iter().map(|arg| self.lower_type(arg)).collect::<Result<Vec<_>, _>>() else {
+                    continue;
+                };
+                
+                if function_params == Some(type_args.len()) {
+                    self.specialize_function(name, type_args)?;
+                } else if type_params == Some(type_args.len()) {
+                    self.specialize_type(type_name, type_args)?;
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // The name of a specialization, such as `Pair<int>` or `identity<string>`
+    fn specialization_name(&self, name: &str, type_args: &[BasicTypeEnum<'ctx>]) -> String {
+        let type_args: Vec<String> = type_args.iter().map(|arg| self.describe_type(*arg)).collect();
+        format!("{}<{}>", name, type_args.join(", "))
+    }
+    
+    // Like `ast_type_to_llvm_type`, but first specializes the generic structs and enums `typ` uses
+    fn lower_type(&mut self, typ: &AstType) -> Result<BasicTypeEnum<'ctx>, ShitRustError> {
+        match typ {
+            AstType::Generic(name, args) if self.generic_types.contains_key(name) => {
+                let args = args.iter()
+                    .map(|arg| self.lower_type(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let symbol = self.specialize_type(name, args)?;
+                return self.ast_type_to_llvm_type(&AstType::Custom(symbol));
+            },
+            AstType::List(element) => {
+                self.lower_type(element)?;
+            },
+            AstType::Dict(key, value) => {
+                self.lower_type(key)?;
+                self.lower_type(value)?;
+            },
+            AstType::Function(params, return_type) => {
+                for param in params {
+                    self.lower_type(param)?;
+                }
+                if !matches!(**return_type, AstType::Void) {
+                    self.lower_type(return_type)?;
+                }
+            },
+            _ => (),
+        }
+        
+        self.ast_type_to_llvm_type(typ)
+    }
+    
+    // Pairs the type parameters of a generic definition with the type arguments of a use
+    fn bind_type_args(
+        name: &str,
+        generic_params: &[String],
+        type_args: &[BasicTypeEnum<'ctx>],
+    ) -> Result<HashMap<String, BasicTypeEnum<'ctx>>, ShitRustError> {
+        if generic_params.len() != type_args.len() {
+            return Err(ShitRustError::TypeError(format!(
+                "'{}' expects {} type arguments but got {}", name, generic_params.len(), type_args.len()
+            )));
+        }
+        Ok(generic_params.iter().cloned().zip(type_args.iter().copied()).collect())
+    }
+    
+    // Lowers the generic struct or enum `name` for `type_args` and declares its methods, unless that
+    // was done before. Returns the name of the specialized type.
+    fn specialize_type(&mut self, name: &str, type_args: Vec<BasicTypeEnum<'ctx>>) -> Result<String, ShitRustError> {
+        let symbol = self.specialization_name(name, &type_args);
+        if self.specialized_types.contains_key(&symbol) {
+            return Ok(symbol);
+        }
+        
+        let generic = self.generic_types[name].clone();
+        let bindings = Self::bind_type_args(name, &generic.generic_params, &type_args)?;
+        self.specialized_types.insert(symbol.clone(), (name.to_string(), type_args));
+        
+        // Specializing may start while another function or method is being generated
+        let outer_params = std::mem::replace(&mut self.type_params, bindings.clone());
+        let outer_self = self.self_type.take();
+        let result = self.lower_specialized_type(&symbol, &generic, &bindings);
+        self.type_params = outer_params;
+        self.self_type = outer_self;
+        result?;
+        
+        Ok(symbol)
+    }
+    
+    fn lower_specialized_type(
+        &mut self,
+        symbol: &str,
+        generic: &GenericType,
+        bindings: &HashMap<String, BasicTypeEnum<'ctx>>,
+    ) -> Result<(), ShitRustError> {
+        // The layout is registered before its fields are lowered, so they can refer to the type
+        match &generic.shape {
+            GenericShape::Struct(fields) => {
+                let llvm_type = self.context.opaque_struct_type(symbol);
+                self.struct_layouts.insert(symbol.to_string(), StructLayout {
+                    llvm_type,
+                    fields: fields.iter().map(|(field, _)| field.clone()).collect(),
+                });
+                
+                let field_types = fields.iter()
+                    .map(|(_, typ)| self.lower_type(typ))
+                    .collect::<Result<Vec<_>, _>>()?;
+                llvm_type.set_body(&field_types, false);
+            },
+            GenericShape::Enum(variants) => {
+                let layout = EnumLayout {
+                    llvm_type: self.context.opaque_struct_type(symbol),
+                    variants: variants.iter()
+                        .map(|(variant, _)| (variant.clone(), self.context.opaque_struct_type(&format!("{}::{}", symbol, variant))))
+                        .collect(),
+                };
+                self.enum_layouts.insert(symbol.to_string(), layout.clone());
+                
+                for ((_, payload), (_, payload_type)) in variants.iter().zip(&layout.variants) {
+                    let field_types = payload.iter()
+                        .map(|typ| self.lower_type(typ))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    payload_type.set_body(&field_types, false);
+                }
+                
+                if !layout.variants.iter().all(|(_, payload)| payload.is_sized()) {
+                    return Err(ShitRustError::TypeError(format!("Recursive types have infinite size: {}", symbol)));
+                }
+                self.complete_enum_layout(&layout)?;
+            },
+        }
+        
+        // Methods with type parameters of their own are not specialized
+        let methods: Vec<Stmt> = generic.methods.iter()
+            .filter(|method| matches!(method.without_attributes(), Stmt::Function { generic_params, .. } if generic_params.is_empty()))
+            .cloned()
+            .collect();
+        self.declare_methods(symbol, &methods)?;
+        
+        for method in methods {
+            if let Stmt::Function { name, .. } = method.without_attributes() {
+                let function = self.module.get_function(&Self::method_symbol(symbol, name)).unwrap();
+                self.pending_bodies.push(PendingBody {
+                    function,
+                    definition: method.clone(),
+                    type_params: bindings.clone(),
+                    receiver: Some(symbol.to_string()),
+                });
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // Declares the generic function `name` for `type_args`, unless that was done before
+    fn specialize_function(&mut self, name: &str, type_args: Vec<BasicTypeEnum<'ctx>>) -> Result<FunctionValue<'ctx>, ShitRustError> {
+        let symbol = self.specialization_name(name, &type_args);
+        if let Some(function) = self.module.get_function(&symbol) {
+            return Ok(function);
+        }
+        
+        let definition = self.generic_functions[name].clone();
+        let Stmt::Function { params, return_type, generic_params, .. } = definition.without_attributes() else {
+            unreachable!("generic functions are function statements");
+        };
+        let bindings = Self::bind_type_args(name, generic_params, &type_args)?;
+        
+        let outer_params = std::mem::replace(&mut self.type_params, bindings.clone());
+        let function = self.declare_function(&symbol, params, return_type);
+        self.type_params = outer_params;
+        let function = function?;
+        
+        self.pending_bodies.push(PendingBody { function, definition, type_params: bindings, receiver: None });
+        Ok(function)
+    }
+    
+    // Generates the bodies of the specialized functions and methods, which may specialize more
+    fn generate_pending_bodies(&mut self) -> Result<(), ShitRustError> {
+        while let Some(pending) = self.pending_bodies.pop() {
+            let Stmt::Function { params, body, .. } = pending.definition.without_attributes() else {
+                continue;
+            };
+            
+            let leading = match &pending.receiver {
+                Some(_) if Self::is_static_method(body) => LeadingParam::None,
+                Some(type_name) => LeadingParam::Receiver(self.ast_type_to_llvm_type(&AstType::Custom(type_name.clone()))?),
+                None => LeadingParam::None,
+            };
+            let params: Vec<String> = params.iter().map(|(param, _)| param.clone()).collect();
+            
+            self.type_params = pending.type_params;
+            self.self_type = pending.receiver.clone();
+            self.set_debug_location(&pending.definition);
+            let result = self.generate_function_body(pending.function, &params, leading, body);
+            self.type_params.clear();
+            self.self_type = None;
+            result?;
+        }
+        
+        Ok(())
+    }
+    
+    // Binds the type parameters in `param` to the matching parts of `actual`, the type of the value
+    // passed for it. Parameters that are bound already keep their type; a value of another type is
+    // reported when it is passed.
+    fn bind_type_params(
+        &self,
+        param: &AstType,
+        actual: BasicTypeEnum<'ctx>,
+        generic_params: &[String],
+        bindings: &mut HashMap<String, BasicTypeEnum<'ctx>>,
+    ) {
+        match param {
+            AstType::Custom(name) if generic_params.contains(name) => {
+                bindings.entry(name.clone()).or_insert(actual);
+            },
+            AstType::List(element) => {
+                if let Some(HeapType::List(actual_element)) = self.heap_type_of(actual) {
+                    self.bind_type_params(element, actual_element, generic_params, bindings);
+                }
+            },
+            AstType::Dict(key, value) => {
+                if let Some(HeapType::Dict(actual_key, actual_value)) = self.heap_type_of(actual) {
+                    self.bind_type_params(key, actual_key, generic_params, bindings);
+                    self.bind_type_params(value, actual_value, generic_params, bindings);
+                }
+            },
+            AstType::Generic(name, args) => {
+                let specialized = self.nominal_type_name(actual)
+                    .and_then(|actual_name| self.specialized_types.get(&actual_name));
+                if let Some((_, actual_args)) = specialized.filter(|(generic_name, _)| generic_name == name) {
+                    for (arg, actual_arg) in args.iter().zip(actual_args) {
+                        self.bind_type_params(arg, *actual_arg, generic_params, bindings);
+                    }
+                }
+            },
+            AstType::Function(params, return_type) => {
+                if let Some(signature) = self.closure_signature(actual) {
+                    for (param, actual_param) in params.iter().zip(signature.get_param_types().into_iter().skip(1)) {
+                        self.bind_type_params(param, actual_param, generic_params, bindings);
+                    }
+                    if let Some(actual_return) = signature.get_return_type() {
+                        self.bind_type_params(return_type, actual_return, generic_params, bindings);
+                    }
+                }
+            },
+            _ => (),
+        }
+    }
+    
+    // The types bound to `generic_params`, in order; all of them must have been inferred
+    fn bound_type_args(
+        name: &str,
+        generic_params: &[String],
+        bindings: &HashMap<String, BasicTypeEnum<'ctx>>,
+    ) -> Result<Vec<BasicTypeEnum<'ctx>>, ShitRustError> {
+        generic_params.iter()
+            .map(|param| bindings.get(param).copied().ok_or_else(|| ShitRustError::TypeError(
+                format!("Cannot infer type parameter '{}' of '{}' in compiled code", param, name)
+            )))
+            .collect()
+    }
+    
+    // Calls a generic function, or a method of a generic type through the type like `Pair::new()`,
+    // specialized for the types of the arguments. Returns None when `name` is neither.
+    fn generate_generic_call(&mut self, name: &str, args: &[Expr]) -> Result<Option<CallSiteValue<'ctx>>, ShitRustError> {
+        let (generic_params, params, method) = match self.generic_functions.get(name).map(Stmt::without_attributes) {
+            Some(Stmt::Function { generic_params, params, .. }) => (generic_params.clone(), params.clone(), None),
+            _ => {
+                let Some((type_name, method)) = name.rsplit_once("::") else {
+                    return Ok(None);
+                };
+                let Some(generic) = self.generic_types.get(type_name) else {
+                    return Ok(None);
+                };
+                let params = generic.methods.iter().find_map(|stmt| match stmt.without_attributes() {
+                    Stmt::Function { name, params, generic_params, .. } if name == method && generic_params.is_empty() => Some(params.clone()),
+                    _ => None,
+                });
+                let Some(params) = params else {
+                    return Ok(None);
+                };
+                (generic.generic_params.clone(), params, Some((type_name, method)))
+            },
+        };
+        
+        if params.len() != args.len() {
+            return Err(ShitRustError::RuntimeError(
+                format!("Expected {} arguments but got {}", params.len(), args.len())
+            ));
+        }
+        
+        // The arguments are generated first, since their types decide the specialization
+        let mut bindings = HashMap::new();
+        let mut arg_values = Vec::new();
+        for ((_, param_type), arg) in params.iter().zip(args) {
+            let value = self.generate_expr(arg)?;
+            self.bind_type_params(param_type, value.get_type(), &generic_params, &mut bindings);
+            arg_values.push(value);
+        }
+        let type_args = Self::bound_type_args(name, &generic_params, &bindings)?;
+        
+        let function = match method {
+            None => self.specialize_function(name, type_args)?,
+            Some((type_name, method)) => {
+                let symbol = Self::method_symbol(&self.specialize_type(type_name, type_args)?, method);
+                if !self.static_methods.contains(&symbol) {
+                    return Err(Self::receiver_error(name));
+                }
+                self.module.get_function(&symbol).unwrap()
+            },
+        };
+        
+        let mut call_args: Vec<BasicMetadataValueEnum> = Vec::new();
+        for (value, param_type) in arg_values.into_iter().zip(function.get_type().get_param_types()) {
+            self.check_value_type(value, param_type, "argument")?;
+            call_args.push(value.into());
+        }
+        
+        Ok(Some(self.builder.build_call(function, &call_args, &format!("{}_call", name))))
+    }
+    
+    // Builds a generic struct, specialized for the types of its field values
+    fn generate_generic_struct_init(&mut self, name: &str, fields: &[(String, Expr)]) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let generic = self.generic_types[name].clone();
+        let GenericShape::Struct(declared) = &generic.shape else {
+            return Err(ShitRustError::TypeError(format!("'{}' is not a struct", name)));
+        };
+        
+        // Fields are evaluated in the order they are written, before their types decide the specialization
+        let mut bindings = HashMap::new();
+        let mut values = Vec::new();
+        for (field_name, field_expr) in fields {
+            let index = declared.iter().position(|(declared, _)| declared == field_name)
+                .ok_or_else(|| ShitRustError::TypeError(format!("Struct '{}' has no field '{}'", name, field_name)))?;
+            
+            let value = self.generate_expr(field_expr)?;
+            self.bind_type_params(&declared[index].1, value.get_type(), &generic.generic_params, &mut bindings);
+            values.push((index, value));
+        }
+        
+        let type_args = Self::bound_type_args(name, &generic.generic_params, &bindings)?;
+        let symbol = self.specialize_type(name, type_args)?;
+        let layout = self.struct_layouts[&symbol].clone();
+        for (index, value) in &values {
+            let field_type = layout.llvm_type.get_field_type_at_index(*index as u32).unwrap();
+            self.check_value_type(*value, field_type, &format!("field '{}'", layout.fields[*index]))?;
+        }
+        
+        self.build_struct_value(&symbol, &layout, values)
+    }
+    
+    // Builds a variant of a generic enum. The type arguments come from `hint` when it is a
+    // specialization of the enum, since variants like `Option::None` have no values to infer them
+    // from, and otherwise from the types of the payload values.
+    fn generate_generic_variant(
+        &mut self,
+        enum_name: &str,
+        variant: &str,
+        args: &[Expr],
+        hint: Option<BasicTypeEnum<'ctx>>,
+    ) -> Result<Option<BasicValueEnum<'ctx>>, ShitRustError> {
+        let generic = self.generic_types[enum_name].clone();
+        let GenericShape::Enum(variants) = &generic.shape else {
+            return Ok(None);
+        };
+        let Some(tag) = variants.iter().position(|(name, _)| name == variant) else {
+            return Ok(None);
+        };
+        
+        let path = format!("{}::{}", enum_name, variant);
+        let payload = &variants[tag].1;
+        if payload.len() != args.len() {
+            return Err(ShitRustError::TypeError(format!(
+                "Variant '{}' expects {} values but got {}", path, payload.len(), args.len()
+            )));
+        }
+        
+        let hinted = hint.and_then(|typ| self.nominal_type_name(typ))
+            .and_then(|name| self.specialized_types.get(&name).cloned())
+            .filter(|(generic_name, _)| generic_name == enum_name);
+        let mut bindings = match hinted {
+            Some((_, type_args)) => Self::bind_type_args(enum_name, &generic.generic_params, &type_args)?,
+            None => HashMap::new(),
+        };
+        
+        let mut values = Vec::new();
+        for (typ, arg) in payload.iter().zip(args) {
+            let value = self.generate_expr(arg)?;
+            self.bind_type_params(typ, value.get_type(), &generic.generic_params, &mut bindings);
+            values.push(value);
+        }
+        
+        let type_args = Self::bound_type_args(&path, &generic.generic_params, &bindings)?;
+        let symbol = self.specialize_type(enum_name, type_args)?;
+        let layout = self.enum_layouts[&symbol].clone();
+        let payload_type = layout.variants[tag].1;
+        for (index, value) in values.iter().enumerate() {
+            let field_type = payload_type.get_field_type_at_index(index as u32).unwrap();
+            self.check_value_type(*value, field_type, &format!("value {} of '{}'", index + 1, path))?;
+        }
+        
+        self.build_variant(&symbol, &layout, tag, values).map(Some)
+    }
+    
+    // Closures
+    
+    // Function values are closures: a pair of a function pointer and the environment holding the
+    // variables the function captured, null if there are none. The function takes the environment
+    // as a leading parameter. Each function type gets a named struct, such as `shitrust.Fn(i64) -> i64`,
+    // so the signature is known from the LLVM type.
+    fn closure_type(&self, params: &[BasicTypeEnum<'ctx>], return_type: Option<BasicTypeEnum<'ctx>>) -> StructType<'ctx> {
+        let param_names: Vec<String> = params.iter().map(|param| param.print_to_string().to_string()).collect();
+        let return_name = return_type.map_or_else(|| "void".to_string(), |typ| typ.print_to_string().to_string());
+        let name = format!("shitrust.Fn({}) -> {}", param_names.join(", "), return_name);
+        
+        if let Some(existing) = self.module.get_struct_type(&name) {
+            return existing;
+        }
+        
+        let ptr_type = self.context.i8_type().ptr_type(Default::default());
+        let mut lifted_params: Vec<BasicMetadataTypeEnum> = vec![ptr_type.into()];
+        lifted_params.extend(params.iter().map(|param| BasicMetadataTypeEnum::from(*param)));
+        let signature = match return_type {
+            Some(return_type) => return_type.fn_type(&lifted_params, false),
+            None => self.context.void_type().fn_type(&lifted_params, false),
+        };
+        
+        let struct_type = self.context.opaque_struct_type(&name);
+        struct_type.set_body(&[ptr_type.into(), ptr_type.into()], false);
+        self.closure_types.borrow_mut().insert(name, signature);
+        struct_type
+    }
+    
+    // The signature of the functions in closures of type `typ`, including the environment parameter
+    fn closure_signature(&self, typ: BasicTypeEnum<'ctx>) -> Option<FunctionType<'ctx>> {
+        let BasicTypeEnum::StructType(struct_type) = typ else {
+            return None;
+        };
+        
+        let name = struct_type.get_name()?.to_string_lossy().into_owned();
+        self.closure_types.borrow().get(&name).copied()
+    }
+    
+    // Optional values are a presence flag next to the value, which is undefined when absent
+    fn option_type(&self, inner: BasicTypeEnum<'ctx>) -> StructType<'ctx> {
+        let name = format!("shitrust.Option<{}>", inner.print_to_string().to_string());
+        if let Some(existing) = self.module.get_struct_type(&name) {
+            return existing;
+        }
+        
+        let struct_type = self.context.opaque_struct_type(&name);
+        struct_type.set_body(&[self.context.bool_type().into(), inner], false);
+        struct_type
+    }
+    
+    // The type wrapped by an optional type
+    fn option_inner(&self, typ: BasicTypeEnum<'ctx>) -> Option<BasicTypeEnum<'ctx>> {
+        let BasicTypeEnum::StructType(struct_type) = typ else {
+            return None;
+        };
+        
+        let name = struct_type.get_name()?.to_string_lossy();
+        if name.starts_with("shitrust.Option<") {
+            struct_type.get_field_type_at_index(1)
+        } else {
+            None
+        }
+    }
+    
+    // Wraps `value` in its optional type, which holds it when `present` is true
+    fn build_some(&self, value: BasicValueEnum<'ctx>, present: IntValue<'ctx>) -> BasicValueEnum<'ctx> {
+        let option_type = self.option_type(value.get_type());
+        let option = self.builder.build_insert_value(option_type.get_undef(), present, 0, "present").unwrap();
+        self.builder.build_insert_value(option, value, 1, "option").unwrap().into_struct_value().into()
+    }
+    
+    // `left ?? right`: the value of an optional `left` if it is present and `right` otherwise.
+    // Any other `left` is never none, so it is the result.
+    fn generate_nullish(&self, left: BasicValueEnum<'ctx>, right: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let Some(inner) = self.option_inner(left.get_type()) else {
+            return Ok(left);
+        };
+        self.check_value_type(right, inner, "default value")?;
+        
+        let option = left.into_struct_value();
+        let present = self.builder.build_extract_value(option, 0, "present").unwrap().into_int_value();
+        let value = self.builder.build_extract_value(option, 1, "value").unwrap();
+        Ok(self.builder.build_select(present, value, right, "nullishtmp"))
+    }
+    
+    // Adds an internal function whose body `build` generates, then returns the builder to where it
+    // was. The body has no debug locations, as it is not in the current function's scope.
+    fn add_helper_function(
+        &self,
+        name: &str,
+        fn_type: FunctionType<'ctx>,
+        build: impl FnOnce(&Self, FunctionValue<'ctx>),
+    ) -> FunctionValue<'ctx> {
+        let function = self.module.add_function(name, fn_type, Some(Linkage::Internal));
+        let block = self.builder.get_insert_block();
+        
+        self.builder.unset_current_debug_location();
+        self.builder.position_at_end(self.context.append_basic_block(function, "entry"));
+        build(self, function);
+        
+        if let Some(block) = block {
+            self.builder.position_at_end(block);
+        }
+        self.apply_debug_location();
+        function
+    }
+    
+    // A closure of a named function, through a wrapper that takes the environment and ignores it
+    fn generate_function_value(&self, function: FunctionValue<'ctx>) -> BasicValueEnum<'ctx> {
+        let function_type = function.get_type();
+        let closure_type = self.closure_type(&function_type.get_param_types(), function_type.get_return_type());
+        let name = format!("{}.closure", function.get_name().to_string_lossy());
+        
+        let wrapper = self.module.get_function(&name).unwrap_or_else(|| {
+            let signature = self.closure_signature(closure_type.into()).unwrap();
+            self.add_helper_function(&name, signature, |this, wrapper| {
+                let args: Vec<BasicMetadataValueEnum> = wrapper.get_param_iter().skip(1).map(|param| param.into()).collect();
+                let call = this.builder.build_call(function, &args, "call");
+                match call.try_as_basic_value().left() {
+                    Some(value) => this.builder.build_return(Some(&value)),
+                    None => this.builder.build_return(None),
+                };
+            })
+        });
+        
+        let null = self.context.i8_type().ptr_type(Default::default()).const_null();
+        closure_type.const_named_struct(&[wrapper.as_global_value().as_pointer_value().into(), null.into()]).into()
+    }
+    
+    // Calls the function of a closure with its environment
+    fn generate_closure_call(&mut self, callee: BasicValueEnum<'ctx>, args: &[Expr]) -> Result<Option<BasicValueEnum<'ctx>>, ShitRustError> {
+        let signature = self.closure_signature(callee.get_type()).ok_or_else(|| ShitRustError::TypeError(
+            format!("Cannot call {} in compiled code", self.describe_type(callee.get_type()))
+        ))?;
+        
+        let param_types = signature.get_param_types();
+        if param_types.len() != args.len() + 1 {
+            return Err(ShitRustError::RuntimeError(
+                format!("Expected {} arguments but got {}", param_types.len() - 1, args.len())
+            ));
+        }
+        
+        let closure = callee.into_struct_value();
+        let function = self.builder.build_extract_value(closure, 0, "function").unwrap().into_pointer_value();
+        let environment = self.builder.build_extract_value(closure, 1, "environment").unwrap();
+        
+        let mut arg_values: Vec<BasicMetadataValueEnum> = vec![environment.into()];
+        for (arg, param_type) in args.iter().zip(&param_types[1..]) {
+            arg_values.push(self.generate_typed_expr(arg, *param_type, "argument")?.into());
+        }
+        
+        let call = self.builder.build_indirect_call(signature, function, &arg_values, "closure_call");
+        Ok(self.owned_call_result(call))
+    }
+    
+    // Lowers a lambda to a function lifted into the module and a closure of it. The variables of
+    // the enclosing function it refers to are copied into a new environment. Parameter and return
+    // types that are not written out come from `hint`, the function type the lambda is expected
+    // to have. Return types the type checker inferred are written out, see `annotate_lambdas`.
+    fn generate_lambda(
+        &mut self,
+        params: &[(String, Option<AstType>)],
+        body: &Expr,
+        return_type: Option<&AstType>,
+        hint: Option<BasicTypeEnum<'ctx>>,
+    ) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let expected = hint.and_then(|typ| self.closure_signature(typ))
+            .filter(|signature| signature.count_param_types() as usize == params.len() + 1);
+        
+        let mut param_types = Vec::new();
+        for (index, (name, typ)) in params.iter().enumerate() {
+            param_types.push(match (typ, expected) {
+                (Some(typ), _) => self.lower_type(typ)?,
+                (None, Some(signature)) => signature.get_param_types()[index + 1],
+                (None, None) => return Err(ShitRustError::TypeError(format!(
+                    "Cannot infer the type of lambda parameter '{}' in compiled code; annotate it", name
+                ))),
+            });
+        }
+        
+        let return_type = match (return_type, expected) {
+            (Some(AstType::Void), _) => None,
+            (Some(typ), _) => Some(self.lower_type(typ)?),
+            (None, Some(signature)) => signature.get_return_type(),
+            (None, None) => return Err(ShitRustError::TypeError(
+                "Cannot infer the return type of a lambda in compiled code; annotate it".to_string()
+            )),
+        };
+        
+        let mut referenced = BTreeSet::new();
+        Self::collect_identifiers(body, &mut referenced);
+        let captures: Vec<(String, PointerValue<'ctx>, BasicTypeEnum<'ctx>)> = referenced.into_iter()
+            .filter(|name| !params.iter().any(|(param, _)| param == name))
+            .filter_map(|name| self.named_values.get(&name).map(|&(slot, typ)| (name, slot, typ)))
+            .collect();
+        let capture_types: Vec<BasicTypeEnum> = captures.iter().map(|(_, _, typ)| *typ).collect();
+        let layout = self.context.struct_type(&capture_types, false);
+        
+        let enclosing = self.current_function.unwrap();
+        let lambda = LiftedLambda {
+            name: format!("{}.lambda.{}", enclosing.get_name().to_string_lossy(), self.lambda_count),
+            params: params.iter().map(|(param, _)| param.clone()).collect(),
+            param_types,
+            layout,
+            captured: captures.iter().map(|(name, _, _)| name.clone()).collect(),
+            body,
+        };
+        self.lambda_count += 1;
+        
+        let block = self.builder.get_insert_block().unwrap();
+        let lifted = self.lift_lambda(&lambda, return_type);
+        self.builder.position_at_end(block);
+        self.current_function = Some(enclosing);
+        self.apply_debug_location();
+        let (function, closure_type) = lifted?;
+        
+        let ptr_type = self.context.i8_type().ptr_type(Default::default());
+        let environment = if captures.is_empty() {
+            ptr_type.const_null()
+        } else {
+            let drop = self.generate_environment_drop(&lambda.name, layout)
+                .map_or_else(|| ptr_type.const_null(), |drop| drop.as_global_value().as_pointer_value());
+            let size = layout.size_of().unwrap();
+            let environment = self.call_runtime("shitrust_env_new", &[size.into(), drop.into()]).into_pointer_value();
+            let data = self.call_runtime("shitrust_env_data", &[environment.into()]).into_pointer_value();
+            
+            // Captured by value: the closure keeps what the variables hold now
+            for (index, (name, slot, typ)) in captures.iter().enumerate() {
+                let value = self.builder.build_load(*typ, *slot, name);
+                self.build_retain(value);
+                let field = self.builder.build_struct_gep(layout, data, index as u32, name).unwrap();
+                self.builder.build_store(field, value);
+            }
+            environment
+        };
+        
+        let closure = closure_type.const_named_struct(&[
+            function.as_global_value().as_pointer_value().into(),
+            ptr_type.const_null().into(),
+        ]);
+        let closure = self.builder.build_insert_value(closure, environment, 1, "closure")
+            .unwrap()
+            .into_struct_value();
+        
+        // The closure owns the new environment
+        if !captures.is_empty() {
+            self.register_temporary(closure.into());
+        }
+        Ok(closure.into())
+    }
+    
+    // Adds the function a lambda is lifted to and generates its body, removing it again on errors
+    fn lift_lambda(
+        &mut self,
+        lambda: &LiftedLambda<'_, 'ctx>,
+        return_type: Option<BasicTypeEnum<'ctx>>,
+    ) -> Result<(FunctionValue<'ctx>, StructType<'ctx>), ShitRustError> {
+        let closure_type = self.closure_type(&lambda.param_types, return_type);
+        let signature = self.closure_signature(closure_type.into()).unwrap();
+        let function = self.module.add_function(&lambda.name, signature, Some(Linkage::Internal));
+        
+        // An expression body of a void lambda is only evaluated
+        let statements = if return_type.is_none() && !matches!(lambda.body, Expr::Block(_)) {
+            vec![Stmt::Expr(lambda.body.clone())]
+        } else {
+            lambda.body.lambda_body()
+        };
+        let leading = LeadingParam::Environment {
+            layout: lambda.layout,
+            captured: lambda.captured.clone(),
+        };
+        
+        match self.generate_function_body(function, &lambda.params, leading, &statements) {
+            Ok(()) => Ok((function, closure_type)),
+            Err(e) => {
+                unsafe { function.delete() };
+                Err(e)
+            },
+        }
+    }
+    
+    // The function the runtime calls to release what an environment holds, if it holds references
+    fn generate_environment_drop(&self, name: &str, layout: StructType<'ctx>) -> Option<FunctionValue<'ctx>> {
+        if !layout.get_field_types().into_iter().any(|typ| self.needs_refcount(typ)) {
+            return None;
+        }
+        
+        let ptr_type = self.context.i8_type().ptr_type(Default::default());
+        let fn_type = self.context.void_type().fn_type(&[ptr_type.into()], false);
+        Some(self.add_helper_function(&format!("{}.drop", name), fn_type, |this, drop| {
+            let data = drop.get_first_param().unwrap().into_pointer_value();
+            for (index, typ) in layout.get_field_types().into_iter().enumerate() {
+                let field = this.builder.build_struct_gep(layout, data, index as u32, "captured").unwrap();
+                this.build_release(field, typ);
+            }
+            this.builder.build_return(None);
+        }))
+    }
+    
+    // Adds every name `expr` refers to, including names that turn out to be local to it
+    fn collect_identifiers(expr: &Expr, names: &mut BTreeSet<String>) {
+        match expr {
+            Expr::Identifier(name) => {
+                names.insert(name.clone());
+            },
+            Expr::Literal(Literal::List(items) | Literal::Tuple(items)) => {
+                items.iter().for_each(|item| Self::collect_identifiers(item, names));
+            },
+            Expr::Literal(Literal::Dict(entries)) => {
+                for (key, value) in entries {
+                    Self::collect_identifiers(key, names);
+                    Self::collect_identifiers(value, names);
+                }
+            },
+            Expr::Literal(Literal::Range { start, end, .. }) | Expr::RangeExpr { start, end, .. } => {
+                start.iter().chain(end).for_each(|bound| Self::collect_identifiers(bound, names));
+            },
+            Expr::Literal(_) => (),
+            Expr::BinaryOp { left, right, .. } => {
+                Self::collect_identifiers(left, names);
+                Self::collect_identifiers(right, names);
+            },
+            Expr::UnaryOp { expr, .. } |
+            Expr::Await { expr } |
+            Expr::Try { expr } |
+            Expr::TypeCast { expr, .. } |
+            Expr::TypeTest { expr, .. } |
+            Expr::FieldAccess { object: expr, .. } |
+            Expr::Lambda { body: expr, .. } => Self::collect_identifiers(expr, names),
+            Expr::Call { func, args } => {
+                Self::collect_identifiers(func, names);
+                args.iter().for_each(|arg| Self::collect_identifiers(arg, names));
+            },
+            Expr::MethodCall { object, args, .. } => {
+                Self::collect_identifiers(object, names);
+                args.iter().for_each(|arg| Self::collect_identifiers(arg, names));
+            },
+            Expr::Index { target, index } => {
+                Self::collect_identifiers(target, names);
+                Self::collect_identifiers(index, names);
+            },
+            Expr::TernaryIf { condition, then_expr, else_expr } => {
+                Self::collect_identifiers(condition, names);
+                Self::collect_identifiers(then_expr, names);
+                Self::collect_identifiers(else_expr, names);
+            },
+            Expr::OptionalChain { expr, chain } => {
+                Self::collect_identifiers(expr, names);
+                for item in chain {
+                    match item {
+                        OptionalChainItem::Field(_) => (),
+                        OptionalChainItem::Method(_, args) => args.iter().for_each(|arg| Self::collect_identifiers(arg, names)),
+                        OptionalChainItem::Index(index) => Self::collect_identifiers(index, names),
+                    }
+                }
+            },
+            Expr::ListComprehension { expr, iterable, condition, .. } => {
+                Self::collect_identifiers(expr, names);
+                Self::collect_identifiers(iterable, names);
+                condition.iter().for_each(|condition| Self::collect_identifiers(condition, names));
+            },
+            Expr::Match { expr, arms } => {
+                Self::collect_identifiers(expr, names);
+                arms.iter().for_each(|(_, arm)| Self::collect_identifiers(arm, names));
+            },
+            Expr::StructInit { fields, .. } => {
+                fields.iter().for_each(|(_, value)| Self::collect_identifiers(value, names));
+            },
+            Expr::PipelineChain { initial, chain } => {
+                Self::collect_identifiers(initial, names);
+                chain.iter().for_each(|stage| Self::collect_identifiers(stage, names));
+            },
+            Expr::Block(stmts) => stmts.iter().for_each(|stmt| Self::collect_stmt_identifiers(stmt, names)),
+        }
+    }
+    
+    fn collect_stmt_identifiers(stmt: &Stmt, names: &mut BTreeSet<String>) {
+        let mut blocks: Vec<&[Stmt]> = Vec::new();
+        match stmt {
+            Stmt::Expr(expr) | Stmt::Let { value: expr, .. } | Stmt::Const { value: expr, .. } | Stmt::Return(Some(expr)) => {
+                Self::collect_identifiers(expr, names);
+            },
+            Stmt::Assign { target, value } => {
+                Self::collect_identifiers(target, names);
+                Self::collect_identifiers(value, names);
+            },
+            Stmt::If { condition, then_block, else_block } => {
+                Self::collect_identifiers(condition, names);
+                blocks.push(then_block);
+                blocks.extend(else_block.as_deref());
+            },
+            Stmt::While { condition: expr, body } | Stmt::For { iterator: expr, body, .. } => {
+                Self::collect_identifiers(expr, names);
+                blocks.push(body);
+            },
+            Stmt::Match { expr, arms } => {
+                Self::collect_identifiers(expr, names);
+                blocks.extend(arms.iter().map(|(_, body)| body.as_slice()));
+            },
+            Stmt::Try { block, catch_blocks, finally_block } => {
+                blocks.push(block);
+                blocks.extend(catch_blocks.iter().map(|(_, body)| body.as_slice()));
+                blocks.extend(finally_block.as_deref());
+            },
+            Stmt::Async { block } | Stmt::Loop { body: block } => blocks.push(block),
+            Stmt::Attributed { stmt, .. } | Stmt::Located { stmt, .. } => Self::collect_stmt_identifiers(stmt, names),
+            // Nested declarations cannot refer to the lambda's variables
+            _ => (),
+        }
+        
+        for stmt in blocks.into_iter().flatten() {
+            Self::collect_stmt_identifiers(stmt, names);
+        }
+    }
+    
+    // Strings, lists and dicts
+    
+    fn heap_struct_type(&self, heap_type: HeapType<'ctx>) -> StructType<'ctx> {
+        let name = match heap_type {
+            HeapType::String => "shitrust.String".to_string(),
+            HeapType::List(element) => format!("shitrust.List<{}>", element.print_to_string()),
+            HeapType::Dict(key, value) => format!("shitrust.Dict<{}, {}>", key.print_to_string(), value.print_to_string()),
+        };
+        
+        if let Some(existing) = self.module.get_struct_type(&name) {
+            return existing;
+        }
+        let struct_type = self.context.opaque_struct_type(&name);
+        struct_type.set_body(&[self.context.i8_type().ptr_type(Default::default()).into()], false);
+        self.heap_types.borrow_mut().insert(name, heap_type);
+        struct_type
+    }
+    
+    fn heap_type_of(&self, typ: BasicTypeEnum<'ctx>) -> Option<HeapType<'ctx>> {
+        let BasicTypeEnum::StructType(struct_type) = typ else {
+            return None;
+        };
+        
+        let name = struct_type.get_name()?.to_string_lossy().into_owned();
+        self.heap_types.borrow().get(&name).copied()
+    }
+    
+    fn string_type(&self) -> BasicTypeEnum<'ctx> {
+        self.heap_struct_type(HeapType::String).into()
+    }
+    
+    fn is_string(&self, typ: BasicTypeEnum<'ctx>) -> bool {
+        matches!(self.heap_type_of(typ), Some(HeapType::String))
+    }
+    
+    fn list_type(&self, element: BasicTypeEnum<'ctx>) -> Result<BasicTypeEnum<'ctx>, ShitRustError> {
+        self.check_storable(element)?;
+        Ok(self.heap_struct_type(HeapType::List(element)).into())
+    }
+    
+    fn dict_type(&self, key: BasicTypeEnum<'ctx>, value: BasicTypeEnum<'ctx>) -> Result<BasicTypeEnum<'ctx>, ShitRustError> {
+        if !key.is_int_type() && !self.is_string(key) {
+            return Err(ShitRustError::TypeError(
+                format!("Dict keys must be ints, chars, bools or strings in compiled code, not {}", self.describe_type(key))
+            ));
+        }
+        self.check_storable(value)?;
+        Ok(self.heap_struct_type(HeapType::Dict(key, value)).into())
+    }
+    
+    // The runtime stores every element in 64 bits, so structs and enums cannot be put in lists or dicts
+    fn check_storable(&self, typ: BasicTypeEnum<'ctx>) -> Result<(), ShitRustError> {
+        match typ {
+            BasicTypeEnum::IntType(_) | BasicTypeEnum::FloatType(_) => Ok(()),
+            _ if self.heap_type_of(typ).is_some() => Ok(()),
+            _ => Err(ShitRustError::TypeError(
+                format!("Lists and dicts cannot hold {} in compiled code", self.describe_type(typ))
+            )),
+        }
+    }
+    
+    // How a type is written in ShitRust, for error messages
+    fn describe_type(&self, typ: BasicTypeEnum<'ctx>) -> String {
+        if let Some(signature) = self.closure_signature(typ) {
+            let params: Vec<String> = signature.get_param_types().into_iter()
+                .skip(1)
+                .map(|param| self.describe_type(param))
+                .collect();
+            let return_type = signature.get_return_type().map_or_else(|| "void".to_string(), |typ| self.describe_type(typ));
+            return format!("fn({}) -> {}", params.join(", "), return_type);
+        }
+        if let Some(inner) = self.option_inner(typ) {
+            return format!("{} | none", self.describe_type(inner));
+        }
+        
+        match (self.heap_type_of(typ), typ) {
+            (Some(HeapType::String), _) => "string".to_string(),
+            (Some(HeapType::List(element)), _) => format!("[{}]", self.describe_type(element)),
+            (Some(HeapType::Dict(key, value)), _) => format!("{{{}: {}}}", self.describe_type(key), self.describe_type(value)),
+            (None, BasicTypeEnum::IntType(int_type)) => match int_type.get_bit_width() {
+                1 => "bool".to_string(),
+                32 => "char".to_string(),
+                _ => "int".to_string(),
+            },
+            (None, BasicTypeEnum::FloatType(_)) => "float".to_string(),
+            (None, _) => self.nominal_type_name(typ).unwrap_or_else(|| typ.print_to_string().to_string()),
+        }
+    }
+    
+    fn check_value_type(&self, value: BasicValueEnum<'ctx>, expected: BasicTypeEnum<'ctx>, what: &str) -> Result<(), ShitRustError> {
+        if value.get_type() == expected {
+            Ok(())
+        } else {
+            Err(ShitRustError::TypeError(format!(
+                "Expected {} of type {} but got {}", what, self.describe_type(expected), self.describe_type(value.get_type())
+            )))
+        }
+    }
+    
+    fn generate_typed_expr(&mut self, expr: &Expr, expected: BasicTypeEnum<'ctx>, what: &str) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let value = self.generate_expr_with_hint(expr, Some(expected))?;
+        self.check_value_type(value, expected, what)?;
+        Ok(value)
+    }
+    
+    // Like `generate_expr`, but empty list and dict literals, lambdas and variants of generic enums
+    // take their type from `hint`
+    fn generate_expr_with_hint(&mut self, expr: &Expr, hint: Option<BasicTypeEnum<'ctx>>) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        if let Expr::Lambda { params, body, return_type } = expr {
+            return self.generate_lambda(params, body, return_type.as_ref(), hint);
+        }
+        
+        let variant = match expr {
+            Expr::Identifier(path) => Some((path, &[][..])),
+            Expr::Call { func, args } => match &**func {
+                Expr::Identifier(path) => Some((path, args.as_slice())),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let (Some((path, args)), Some(_)) = (variant, hint) {
+            if let Some(value) = self.generate_variant(path, args, hint)? {
+                return Ok(value);
+            }
+        }
+        
+        let is_empty_container = match expr {
+            Expr::Literal(Literal::List(items)) => items.is_empty(),
+            Expr::Literal(Literal::Dict(entries)) => entries.is_empty(),
+            _ => false,
+        };
+        
+        match hint {
+            Some(typ) if is_empty_container && matches!(self.heap_type_of(typ), Some(HeapType::List(_) | HeapType::Dict(..))) => {
+                Ok(self.generate_empty_container(typ).0)
+            },
+            _ => self.generate_expr(expr),
+        }
+    }
+    
+    fn generate_empty_container(&mut self, typ: BasicTypeEnum<'ctx>) -> (BasicValueEnum<'ctx>, PointerValue<'ctx>) {
+        let i64_type = self.context.i64_type();
+        match self.heap_type_of(typ) {
+            Some(HeapType::List(element)) => {
+                self.generate_runtime_object("shitrust_list_new", &[self.value_kind(element).into(), i64_type.const_zero().into()], typ)
+            },
+            Some(HeapType::Dict(key, value)) => {
+                let key_kind = i64_type.const_int(if self.is_string(key) { KEY_STRING } else { KEY_INT }, false);
+                self.generate_runtime_object("shitrust_dict_new", &[key_kind.into(), self.value_kind(value).into()], typ)
+            },
+            _ => unreachable!("not a container type"),
+        }
+    }
+    
+    // Whether the runtime has to manage references for elements of this type
+    fn value_kind(&self, typ: BasicTypeEnum<'ctx>) -> IntValue<'ctx> {
+        let kind = if self.heap_type_of(typ).is_some() { VALUE_OBJECT } else { VALUE_PLAIN };
+        self.context.i64_type().const_int(kind, false)
+    }
+    
+    // Converts a value to the 64 bits the runtime stores for it
+    fn to_slot_bits(&self, value: BasicValueEnum<'ctx>) -> IntValue<'ctx> {
+        let i64_type = self.context.i64_type();
+        match value {
+            BasicValueEnum::IntValue(i) if i.get_type().get_bit_width() < 64 => self.builder.build_int_z_extend(i, i64_type, "bits"),
+            BasicValueEnum::IntValue(i) => i,
+            BasicValueEnum::FloatValue(f) => self.builder.build_bitcast(f, i64_type, "bits").into_int_value(),
+            _ => self.builder.build_ptr_to_int(self.heap_pointer(value), i64_type, "bits"),
+        }
+    }
+    
+    fn from_slot_bits(&self, bits: IntValue<'ctx>, typ: BasicTypeEnum<'ctx>) -> BasicValueEnum<'ctx> {
+        match typ {
+            BasicTypeEnum::IntType(int_type) if int_type.get_bit_width() < 64 => {
+                self.builder.build_int_truncate(bits, int_type, "value").into()
+            },
+            BasicTypeEnum::IntType(_) => bits.into(),
+            BasicTypeEnum::FloatType(float_type) => self.builder.build_bitcast(bits, float_type, "value"),
+            _ => {
+                let object = self.builder.build_int_to_ptr(bits, self.context.i8_type().ptr_type(Default::default()), "object");
+                self.wrap_heap_pointer(object, typ)
+            },
+        }
+    }
+    
+    fn heap_pointer(&self, value: BasicValueEnum<'ctx>) -> PointerValue<'ctx> {
+        self.builder.build_extract_value(value.into_struct_value(), 0, "object").unwrap().into_pointer_value()
+    }
+    
+    fn wrap_heap_pointer(&self, object: PointerValue<'ctx>, typ: BasicTypeEnum<'ctx>) -> BasicValueEnum<'ctx> {
+        self.builder.build_insert_value(typ.into_struct_type().get_undef(), object, 0, "wrapped")
+            .unwrap()
+            .into_struct_value()
+            .into()
+    }
+    
+    // Declares a function of the runtime library on first use
+    fn runtime_function(&self, name: &str) -> FunctionValue<'ctx> {
+        if let Some(function) = self.module.get_function(name) {
+            return function;
+        }
+        
+        let ptr_type = self.context.i8_type().ptr_type(Default::default());
+        let (ptr, i64, f64) = (ptr_type.into(), self.context.i64_type().into(), self.context.f64_type().into());
+        let void = self.context.void_type();
+        let fn_type = match name {
+            "shitrust_retain" | "shitrust_release" | "shitrust_cstr_free" => void.fn_type(&[ptr], false),
+            "shitrust_print" | "shitrust_list_push" | "shitrust_fail" => void.fn_type(&[ptr, i64], false),
+            "shitrust_list_set" | "shitrust_dict_insert" => void.fn_type(&[ptr, i64, i64], false),
+            "shitrust_string_new" => ptr_type.fn_type(&[ptr, i64], false),
+            "shitrust_string_concat" => ptr_type.fn_type(&[ptr, ptr], false),
+            "shitrust_string_from_int" | "shitrust_string_from_bool" | "shitrust_string_from_char" => ptr_type.fn_type(&[i64], false),
+            "shitrust_string_from_float" => ptr_type.fn_type(&[f64], false),
+            "shitrust_list_new" | "shitrust_dict_new" => ptr_type.fn_type(&[i64, i64], false),
+            "shitrust_dict_keys" | "shitrust_env_data" | "shitrust_string_to_cstr" | "shitrust_string_from_cstr" => {
+                ptr_type.fn_type(&[ptr], false)
+            },
+            "shitrust_env_new" => ptr_type.fn_type(&[i64, ptr], false),
+            "shitrust_string_len" | "shitrust_list_len" | "shitrust_dict_len" | "shitrust_list_pop" => {
+                self.context.i64_type().fn_type(&[ptr], false)
+            },
+            "shitrust_list_get" | "shitrust_dict_get" => self.context.i64_type().fn_type(&[ptr, i64], false),
+            "shitrust_string_compare" => self.context.i32_type().fn_type(&[ptr, ptr], false),
+            "shitrust_string_eq" | "shitrust_string_contains" => self.context.bool_type().fn_type(&[ptr, ptr], false),
+            "shitrust_dict_contains" | "shitrust_dict_remove" => self.context.bool_type().fn_type(&[ptr, i64], false),
+            _ => unreachable!("unknown runtime function {}", name),
+        };
+        
+        self.module.add_function(name, fn_type, None)
+    }
+    
+    // Calls a runtime function; void ones, such as the release functions, are only called as
+    // statements and yield a dummy value
+    fn call_runtime(&self, name: &str, args: &[BasicMetadataValueEnum<'ctx>]) -> BasicValueEnum<'ctx> {
+        let call = self.builder.build_call(self.runtime_function(name), args, name);
+        self.call_result(call).unwrap_or_else(|| self.context.i32_type().const_int(0, false).into())
+    }
+    
+    // Calls a runtime function that creates an object of type `typ`. The object is a temporary
+    // of the current statement; its slot is returned too, for functions that mutate it.
+    fn generate_runtime_object(
+        &mut self,
+        name: &str,
+        args: &[BasicMetadataValueEnum<'ctx>],
+        typ: BasicTypeEnum<'ctx>,
+    ) -> (BasicValueEnum<'ctx>, PointerValue<'ctx>) {
+        let object = self.call_runtime(name, args).into_pointer_value();
+        let value = self.wrap_heap_pointer(object, typ);
+        let slot = self.register_temporary(value);
+        (value, slot)
+    }
+    
+    // Converts a value to a string the way the interpreter displays it
+    fn generate_to_string(&mut self, value: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let i64_type = self.context.i64_type();
+        let (function, arg): (&str, BasicMetadataValueEnum) = match value {
+            _ if self.is_string(value.get_type()) => return Ok(value),
+            BasicValueEnum::IntValue(i) => match i.get_type().get_bit_width() {
+                1 => ("shitrust_string_from_bool", self.builder.build_int_z_extend(i, i64_type, "bool").into()),
+                32 => ("shitrust_string_from_char", self.builder.build_int_z_extend(i, i64_type, "char").into()),
+                64 => ("shitrust_string_from_int", i.into()),
+                _ => ("shitrust_string_from_int", self.builder.build_int_s_extend(i, i64_type, "int").into()),
+            },
+            BasicValueEnum::FloatValue(f) => ("shitrust_string_from_float", f.into()),
+            _ => return Err(ShitRustError::TypeError(
+                format!("Cannot convert {} to a string in compiled code", self.describe_type(value.get_type()))
+            )),
+        };
+        
+        Ok(self.generate_runtime_object(function, &[arg], self.string_type()).0)
+    }
+    
+    fn generate_string_op(&mut self, op: &BinOp, left: BasicValueEnum<'ctx>, right: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let args = [self.heap_pointer(left).into(), self.heap_pointer(right).into()];
+        let predicate = match op {
+            BinOp::Add => return Ok(self.generate_runtime_object("shitrust_string_concat", &args, self.string_type()).0),
+            BinOp::Eq => return Ok(self.call_runtime("shitrust_string_eq", &args)),
+            BinOp::Ne => {
+                let equal = self.call_runtime("shitrust_string_eq", &args).into_int_value();
+                return Ok(self.builder.build_not(equal, "netmp").into());
+            },
+            BinOp::Lt => inkwell::IntPredicate::SLT,
+            BinOp::Le => inkwell::IntPredicate::SLE,
+            BinOp::Gt => inkwell::IntPredicate::SGT,
+            BinOp::Ge => inkwell::IntPredicate::SGE,
+            _ => return Err(ShitRustError::TypeError(format!("Operator {:?} is not supported for strings", op))),
+        };
+        
+        let ordering = self.call_runtime("shitrust_string_compare", &args).into_int_value();
+        Ok(self.builder.build_int_compare(predicate, ordering, self.context.i32_type().const_zero(), "cmptmp").into())
+    }
+    
+    // Calls a method of a string, list or dict stored in `slot`. Methods that change the value
+    // update the slot, since the runtime copies objects that are shared before mutating them.
+    fn generate_heap_method(
+        &mut self,
+        slot: PointerValue<'ctx>,
+        typ: BasicTypeEnum<'ctx>,
+        method: &str,
+        args: &[Expr],
+    ) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let heap_type = self.heap_type_of(typ).unwrap();
+        let expected: Vec<BasicTypeEnum> = match (heap_type, method) {
+            (HeapType::String, "contains") => vec![self.string_type()],
+            (HeapType::List(element), "push") => vec![element],
+            (HeapType::Dict(key, _), "contains_key" | "remove") => vec![key],
+            (HeapType::Dict(key, value), "insert") => vec![key, value],
+            _ => Vec::new(),
+        };
+        if args.len() != expected.len() {
+            return Err(ShitRustError::RuntimeError(format!(
+                "Method '{}' of {} expects {} argument(s) but {} were supplied",
+                method, self.describe_type(typ), expected.len(), args.len()
+            )));
+        }
+        
+        let mut arg_bits = Vec::with_capacity(args.len());
+        let mut arg_values = Vec::with_capacity(args.len());
+        for (arg, arg_type) in args.iter().zip(&expected) {
+            let value = self.generate_typed_expr(arg, *arg_type, "argument")?;
+            arg_bits.push(BasicMetadataValueEnum::from(self.to_slot_bits(value)));
+            arg_values.push(value);
+        }
+        
+        // Loaded after the arguments, which may have changed the value
+        let value = self.builder.build_load(typ, slot, "receiver");
+        let object = self.heap_pointer(value);
+        let len_function = match heap_type {
+            HeapType::String => "shitrust_string_len",
+            HeapType::List(_) => "shitrust_list_len",
+            HeapType::Dict(..) => "shitrust_dict_len",
+        };
+        let unit = self.context.i32_type().const_int(0, false).into();
+        
+        match (heap_type, method) {
+            (_, "len") => Ok(self.call_runtime(len_function, &[object.into()])),
+            (_, "is_empty") => {
+                let len = self.call_runtime(len_function, &[object.into()]).into_int_value();
+                Ok(self.builder.build_int_compare(inkwell::IntPredicate::EQ, len, len.get_type().const_zero(), "is_empty").into())
+            },
+            (HeapType::String, "to_string") => Ok(value),
+            (HeapType::String, "contains") => {
+                Ok(self.call_runtime("shitrust_string_contains", &[object.into(), self.heap_pointer(arg_values[0]).into()]))
+            },
+            (HeapType::List(_), "push") => {
+                self.call_runtime("shitrust_list_push", &[slot.into(), arg_bits[0]]);
+                Ok(unit)
+            },
+            (HeapType::List(element), "pop") => {
+                // The list's reference to the element passes to the caller
+                let bits = self.call_runtime("shitrust_list_pop", &[slot.into()]).into_int_value();
+                let element_value = self.from_slot_bits(bits, element);
+                if self.needs_refcount(element) {
+                    self.register_temporary(element_value);
                 }
-                
-                Ok(())
+                Ok(element_value)
             },
-            // Other statement types would be handled here
-            _ => Err(ShitRustError::RuntimeError(format!("Statement type not yet implemented: {:?}", stmt))),
+            (HeapType::Dict(..), "contains_key") => Ok(self.call_runtime("shitrust_dict_contains", &[object.into(), arg_bits[0]])),
+            (HeapType::Dict(..), "insert") => {
+                self.call_runtime("shitrust_dict_insert", &[slot.into(), arg_bits[0], arg_bits[1]]);
+                Ok(unit)
+            },
+            (HeapType::Dict(..), "remove") => Ok(self.call_runtime("shitrust_dict_remove", &[slot.into(), arg_bits[0]])),
+            (HeapType::Dict(key, _), "keys") => {
+                let list_type = self.list_type(key)?;
+                Ok(self.generate_runtime_object("shitrust_dict_keys", &[object.into()], list_type).0)
+            },
+            _ => Err(ShitRustError::RuntimeError(
+                format!("Method '{}' of {} is not supported in compiled code", method, self.describe_type(typ))
+            )),
         }
     }
     
-    fn generate_expr(&mut self, expr: &Expr) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
-        match expr {
-            Expr::Literal(lit) => self.generate_literal(lit),
-            Expr::Identifier(name) => {
-                if let Some(var) = self.named_values.get(name) {
-                    Ok(self.builder.build_load(*var, name))
-                } else {
-                    Err(ShitRustError::UndefinedVariable(name.clone()))
-                }
+    // `list[index] = value` and `dict[key] = value`
+    fn generate_element_assign(&mut self, container: &Expr, index: &Expr, value: &Expr) -> Result<(), ShitRustError> {
+        let (slot, typ) = self.generate_place(container)?
+            .ok_or_else(|| ShitRustError::RuntimeError("Only elements of variables can be assigned in compiled code".to_string()))?;
+        
+        match self.heap_type_of(typ) {
+            Some(HeapType::List(element)) => {
+                let index = self.generate_typed_expr(index, self.context.i64_type().into(), "list index")?;
+                let value = self.generate_typed_expr(value, element, "list element")?;
+                let bits = self.to_slot_bits(value);
+                self.call_runtime("shitrust_list_set", &[slot.into(), index.into(), bits.into()]);
             },
-            Expr::BinaryOp { left, op, right } => {
-                let l_val = self.generate_expr(left)?;
-                let r_val = self.generate_expr(right)?;
-                
-                match op {
-                    BinOp::Add => self.generate_add(l_val, r_val),
-                    BinOp::Sub => self.generate_sub(l_val, r_val),
-                    BinOp::Mul => self.generate_mul(l_val, r_val),
-                    BinOp::Div => self.generate_div(l_val, r_val),
-                    BinOp::Eq => self.generate_eq(l_val, r_val),
-                    BinOp::Ne => self.generate_ne(l_val, r_val),
-                    BinOp::Lt => self.generate_lt(l_val, r_val),
-                    BinOp::Le => self.generate_le(l_val, r_val),
-                    BinOp::Gt => self.generate_gt(l_val, r_val),
-                    BinOp::Ge => self.generate_ge(l_val, r_val),
-                    // Other operators would be implemented here
-                    _ => Err(ShitRustError::RuntimeError(format!("Binary operator not implemented: {:?}", op))),
+            Some(HeapType::Dict(key, element)) => {
+                let key = self.generate_typed_expr(index, key, "dict key")?;
+                let value = self.generate_typed_expr(value, element, "dict value")?;
+                let key_bits = self.to_slot_bits(key);
+                let value_bits = self.to_slot_bits(value);
+                self.call_runtime("shitrust_dict_insert", &[slot.into(), key_bits.into(), value_bits.into()]);
+            },
+            _ => return Err(ShitRustError::TypeError(
+                format!("Cannot assign to an element of {} in compiled code", self.describe_type(typ))
+            )),
+        }
+        Ok(())
+    }
+    
+    // Reference counting
+    
+    // Whether values of a type hold references: strings, lists, dicts and structs containing them
+    fn needs_refcount(&self, typ: BasicTypeEnum<'ctx>) -> bool {
+        if self.heap_type_of(typ).is_some() || self.closure_signature(typ).is_some() {
+            return true;
+        }
+        
+        match self.nominal_type_name(typ) {
+            Some(name) if self.struct_layouts.contains_key(&name) => {
+                typ.into_struct_type().get_field_types().into_iter().any(|field| self.needs_refcount(field))
+            },
+            _ => false,
+        }
+    }
+    
+    fn build_retain(&self, value: BasicValueEnum<'ctx>) {
+        if self.heap_type_of(value.get_type()).is_some() {
+            self.call_runtime("shitrust_retain", &[self.heap_pointer(value).into()]);
+        } else if self.closure_signature(value.get_type()).is_some() {
+            let environment = self.builder.build_extract_value(value.into_struct_value(), 1, "environment").unwrap();
+            self.call_runtime("shitrust_retain", &[environment.into()]);
+        } else if self.needs_refcount(value.get_type()) {
+            let struct_value = value.into_struct_value();
+            for index in 0..struct_value.get_type().count_fields() {
+                let field = self.builder.build_extract_value(struct_value, index, "field").unwrap();
+                self.build_retain(field);
+            }
+        }
+    }
+    
+    // Drops the references held by the value stored in `slot`
+    fn build_release(&self, slot: PointerValue<'ctx>, typ: BasicTypeEnum<'ctx>) {
+        if self.heap_type_of(typ).is_some() {
+            let object = self.builder.build_load(self.context.i8_type().ptr_type(Default::default()), slot, "object");
+            self.call_runtime("shitrust_release", &[object.into()]);
+        } else if self.closure_signature(typ).is_some() {
+            let environment_ptr = self.builder.build_struct_gep(typ.into_struct_type(), slot, 1, "environment").unwrap();
+            let environment = self.builder.build_load(self.context.i8_type().ptr_type(Default::default()), environment_ptr, "environment");
+            self.call_runtime("shitrust_release", &[environment.into()]);
+        } else if self.needs_refcount(typ) {
+            let struct_type = typ.into_struct_type();
+            for (index, field_type) in struct_type.get_field_types().into_iter().enumerate() {
+                if self.needs_refcount(field_type) {
+                    let field = self.builder.build_struct_gep(struct_type, slot, index as u32, "field").unwrap();
+                    self.build_release(field, field_type);
+                }
+            }
+        }
+    }
+    
+    // Stores into a slot that owns its value: the new value gains a reference, the old one loses one
+    fn store_owned(&self, slot: PointerValue<'ctx>, typ: BasicTypeEnum<'ctx>, value: BasicValueEnum<'ctx>) {
+        self.build_retain(value);
+        self.build_release(slot, typ);
+        self.builder.build_store(slot, value);
+    }
+    
+    // A stack slot for a variable. Slots of reference-counted types start out zeroed, so they can
+    // be released on every path, and whatever they hold is released when the function returns.
+    fn create_owned_slot(&mut self, name: &str, typ: BasicTypeEnum<'ctx>) -> PointerValue<'ctx> {
+        if !self.needs_refcount(typ) {
+            return self.create_entry_block_alloca(name, typ);
+        }
+        
+        let slot = self.create_zeroed_alloca(name, typ);
+        self.function_state.owned_slots.push((slot, typ));
+        slot
+    }
+    
+    // Keeps a new reference produced by an expression until the end of the current statement
+    fn register_temporary(&mut self, value: BasicValueEnum<'ctx>) -> PointerValue<'ctx> {
+        let slot = self.create_owned_slot("tmp", value.get_type());
+        // Inside a loop the slot may still hold the previous iteration's value, if a `break` or
+        // `continue` skipped the end of the statement
+        self.build_release(slot, value.get_type());
+        self.builder.build_store(slot, value);
+        self.function_state.temporaries.push((slot, value.get_type()));
+        slot
+    }
+    
+    // Lowers `as` conversions. Bools (i1) and chars (i32) widen with zero extension, float to int
+    // saturates like the interpreter, and int to char traps unless the value is a Unicode scalar
+    // value, which the interpreter rejects with an error.
+    fn generate_cast(&self, value: BasicValueEnum<'ctx>, target: &AstType) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        self.function_for("Casting")?;
+        let i64_type = self.context.i64_type();
+        let f64_type = self.context.f64_type();
+        
+        match (value, target) {
+            (BasicValueEnum::IntValue(i), AstType::Int) => {
+                if i.get_type().get_bit_width() < 64 {
+                    Ok(self.builder.build_int_z_extend(i, i64_type, "casttmp").into())
+                } else {
+                    Ok(i.into())
                 }
             },
-            Expr::Call { func, args } => {
-                if let Expr::Identifier(name) = &**func {
-                    // Handle print/println as special cases
-                    if name == "println" || name == "print" {
-                        return self.generate_print_call(args, name == "println");
-                    }
-                    
-                    // Get the function from the module
-                    let function = self.module.get_function(name)
-                        .ok_or_else(|| ShitRustError::UndefinedVariable(name.clone()))?;
-                    
-                    // Check that we have the right number of arguments
-                    if function.count_params() as usize != args.len() {
-                        return Err(ShitRustError::RuntimeError(
-                            format!("Expected {} arguments but got {}", function.count_params(), args.len())
-                        ));
-                    }
-                    
-                    // Generate code for each argument
-                    let mut arg_values = Vec::new();
-                    for arg in args {
-                        arg_values.push(self.generate_expr(arg)?);
-                    }
-                    
-                    // Convert BasicValueEnum to dyn BasicValue
-                    let args_refs: Vec<_> = arg_values.iter()
-                        .map(|val| val as &dyn inkwell::values::BasicValue)
-                        .collect();
-                    
-                    // Call the function
-                    let call = self.builder.build_call(function, &args_refs, &format!("{}_call", name));
-                    
-                    // Get the return value if not void
-                    match function.get_type().get_return_type() {
-                        Some(_) => Ok(call.try_as_basic_value().left().unwrap()),
-                        None => Err(ShitRustError::RuntimeError(
-                            format!("Cannot use void function '{}' in an expression", name)
-                        )),
-                    }
+            (BasicValueEnum::IntValue(i), AstType::Float) => {
+                if i.get_type().get_bit_width() < 64 {
+                    Ok(self.builder.build_unsigned_int_to_float(i, f64_type, "casttmp").into())
                 } else {
-                    Err(ShitRustError::RuntimeError("Callee is not a function name".to_string()))
+                    Ok(self.builder.build_signed_int_to_float(i, f64_type, "casttmp").into())
                 }
             },
-            // Other expression types would be handled here
-            _ => Err(ShitRustError::RuntimeError(format!("Expression type not yet implemented: {:?}", expr))),
+            (BasicValueEnum::FloatValue(f), AstType::Int) => {
+                let fptosi_sat = Intrinsic::find("llvm.fptosi.sat")
+                    .and_then(|intrinsic| intrinsic.get_declaration(&self.module, &[i64_type.into(), f64_type.into()]))
+                    .ok_or_else(|| ShitRustError::RuntimeError("Missing llvm.fptosi.sat intrinsic".to_string()))?;
+                
+                let call = self.builder.build_call(fptosi_sat, &[f.into()], "casttmp");
+                Ok(call.try_as_basic_value().left().unwrap())
+            },
+            (BasicValueEnum::FloatValue(f), AstType::Float) => Ok(f.into()),
+            (BasicValueEnum::PointerValue(p), AstType::Int) => Ok(self.builder.build_ptr_to_int(p, i64_type, "casttmp").into()),
+            (BasicValueEnum::IntValue(i), AstType::Pointer) if i.get_type().get_bit_width() == 64 => {
+                Ok(self.builder.build_int_to_ptr(i, self.context.i8_type().ptr_type(Default::default()), "casttmp").into())
+            },
+            (BasicValueEnum::PointerValue(p), AstType::Pointer) => Ok(p.into()),
+            (BasicValueEnum::IntValue(i), AstType::Char) if i.get_type().get_bit_width() == 32 => Ok(i.into()),
+            (BasicValueEnum::IntValue(i), AstType::Char) => {
+                // Unsigned comparisons also reject negative values. Surrogates are the code
+                // points 0xD800..=0xDFFF, which are below 0x800 once 0xD800 is subtracted.
+                let int_type = i.get_type();
+                let above_max = self.builder.build_int_compare(
+                    inkwell::IntPredicate::UGT,
+                    i,
+                    int_type.const_int(char::MAX as u64, false),
+                    "char_above_max"
+                );
+                let surrogate_offset = self.builder.build_int_sub(i, int_type.const_int(0xD800, false), "surrogate_offset");
+                let surrogate = self.builder.build_int_compare(
+                    inkwell::IntPredicate::ULT,
+                    surrogate_offset,
+                    int_type.const_int(0x800, false),
+                    "char_surrogate"
+                );
+                let invalid = self.builder.build_or(above_max, surrogate, "char_invalid");
+                self.build_fail_if(invalid, "char_invalid", "Cannot cast to char: not a valid Unicode scalar value")?;
+                
+                Ok(self.builder.build_int_truncate(i, self.context.i32_type(), "casttmp").into())
+            },
+            (value, target) => Err(ShitRustError::TypeError(
+                format!("Cannot cast {:?} to {:?}", value.get_type(), target)
+            )),
         }
     }
     
-    fn generate_print_call(&self, args: &[Expr], add_newline: bool) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
-        if args.is_empty() {
-            let format_str = if add_newline { "\n\0" } else { "\0" };
-            let fmt_ptr = self.builder.build_global_string_ptr(format_str, "empty_fmt");
-            
-            let call = self.builder.build_call(
-                self.printf_function, 
-                &[fmt_ptr.as_pointer_value().into()], 
-                "printf_call"
-            );
-            
-            // printf returns an i32, but we'll return void for print calls
-            Ok(self.context.i32_type().const_int(0, false).into())
-        } else {
-            // For now, we only support simple string printing
-            let expr = &args[0];
-            let value = self.generate_expr(expr)?;
-            
-            let format_str = match value.get_type() {
-                BasicTypeEnum::IntType(_) => {
-                    if add_newline { "%d\n\0" } else { "%d\0" }
-                },
-                BasicTypeEnum::FloatType(_) => {
-                    if add_newline { "%f\n\0" } else { "%f\0" }
-                },
-                BasicTypeEnum::PointerType(_) => {
-                    // Assuming this is a string
-                    if add_newline { "%s\n\0" } else { "%s\0" }
-                },
-                _ => return Err(ShitRustError::TypeError("Unsupported print type".to_string())),
-            };
-            
-            let fmt_ptr = self.builder.build_global_string_ptr(format_str, "fmt");
-            
-            let call = self.builder.build_call(
-                self.printf_function, 
-                &[fmt_ptr.as_pointer_value().into(), value.into()], 
-                "printf_call"
-            );
-            
-            // printf returns an i32, but we'll return void for print calls
-            Ok(self.context.i32_type().const_int(0, false).into())
-        }
+    fn generate_print_call(&mut self, args: &[Expr], add_newline: bool) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        // For now, only the first argument is printed; with none, print an empty line
+        let text = match args.first() {
+            Some(expr) => {
+                let value = self.generate_expr(expr)?;
+                self.generate_to_string(value)?
+            },
+            None => self.generate_literal(&Literal::String(String::new()))?,
+        };
+        
+        let newline = self.context.i64_type().const_int(add_newline as u64, false);
+        self.call_runtime("shitrust_print", &[self.heap_pointer(text).into(), newline.into()]);
+        
+        // print returns nothing; yield a dummy value so it can be used as a statement
+        Ok(self.context.i32_type().const_int(0, false).into())
     }
     
-    fn generate_literal(&self, lit: &Literal) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+    fn generate_literal(&mut self, lit: &Literal) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
         match lit {
             Literal::Int(value) => {
                 Ok(self.context.i64_type().const_int(*value as u64, false).into())
@@ -380,13 +3798,69 @@ impl<'ctx> CodeGen<'ctx> {
                 Ok(self.context.bool_type().const_int(*value as u64, false).into())
             },
             Literal::String(value) => {
-                // Add null terminator for C strings
-                let value_with_null = format!("{}\0", value);
-                let ptr = self.builder.build_global_string_ptr(&value_with_null, "str");
-                Ok(ptr.as_pointer_value().into())
+                // Every evaluation creates a new runtime string from the constant bytes
+                let bytes = self.builder.build_global_string_ptr(value, "str");
+                let len = self.context.i64_type().const_int(value.len() as u64, false);
+                let (string, _) = self.generate_runtime_object(
+                    "shitrust_string_new",
+                    &[bytes.as_pointer_value().into(), len.into()],
+                    self.string_type(),
+                );
+                Ok(string)
             },
             Literal::Char(value) => {
-                Ok(self.context.i8_type().const_int(*value as u64, false).into())
+                Ok(self.context.i32_type().const_int(*value as u64, false).into())
+            },
+            Literal::List(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(self.generate_expr(item)?);
+                }
+                
+                let element = values.first()
+                    .ok_or_else(|| ShitRustError::TypeError(
+                        "Cannot infer the element type of an empty list; add a type annotation".to_string()
+                    ))?
+                    .get_type();
+                let list_type = self.list_type(element)?;
+                
+                let capacity = self.context.i64_type().const_int(items.len() as u64, false);
+                let (_, slot) = self.generate_runtime_object(
+                    "shitrust_list_new",
+                    &[self.value_kind(element).into(), capacity.into()],
+                    list_type,
+                );
+                for value in values {
+                    self.check_value_type(value, element, "list element")?;
+                    let bits = self.to_slot_bits(value);
+                    self.call_runtime("shitrust_list_push", &[slot.into(), bits.into()]);
+                }
+                
+                Ok(self.builder.build_load(list_type, slot, "list"))
+            },
+            Literal::Dict(entries) => {
+                let mut pairs = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    pairs.push((self.generate_expr(key)?, self.generate_expr(value)?));
+                }
+                
+                let (key_type, value_type) = pairs.first()
+                    .map(|(key, value)| (key.get_type(), value.get_type()))
+                    .ok_or_else(|| ShitRustError::TypeError(
+                        "Cannot infer the key and value types of an empty dict; add a type annotation".to_string()
+                    ))?;
+                let dict_type = self.dict_type(key_type, value_type)?;
+                
+                let (_, slot) = self.generate_empty_container(dict_type);
+                for (key, value) in pairs {
+                    self.check_value_type(key, key_type, "dict key")?;
+                    self.check_value_type(value, value_type, "dict value")?;
+                    let key_bits = self.to_slot_bits(key);
+                    let value_bits = self.to_slot_bits(value);
+                    self.call_runtime("shitrust_dict_insert", &[slot.into(), key_bits.into(), value_bits.into()]);
+                }
+                
+                Ok(self.builder.build_load(dict_type, slot, "dict"))
             },
             // Other literal types would be handled here
             _ => Err(ShitRustError::RuntimeError(format!("Literal type not yet implemented: {:?}", lit))),
@@ -398,7 +3872,7 @@ impl<'ctx> CodeGen<'ctx> {
     fn generate_add(&self, left: BasicValueEnum<'ctx>, right: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
         match (left, right) {
             (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
-                Ok(self.builder.build_int_add(l, r, "addtmp").into())
+                Ok(self.build_checked_int_op("add", l, r, "addition")?.into())
             },
             (BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)) => {
                 Ok(self.builder.build_float_add(l, r, "addtmp").into())
@@ -411,7 +3885,7 @@ impl<'ctx> CodeGen<'ctx> {
     fn generate_sub(&self, left: BasicValueEnum<'ctx>, right: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
         match (left, right) {
             (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
-                Ok(self.builder.build_int_sub(l, r, "subtmp").into())
+                Ok(self.build_checked_int_op("sub", l, r, "subtraction")?.into())
             },
             (BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)) => {
                 Ok(self.builder.build_float_sub(l, r, "subtmp").into())
@@ -423,7 +3897,7 @@ impl<'ctx> CodeGen<'ctx> {
     fn generate_mul(&self, left: BasicValueEnum<'ctx>, right: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
         match (left, right) {
             (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
-                Ok(self.builder.build_int_mul(l, r, "multmp").into())
+                Ok(self.build_checked_int_op("mul", l, r, "multiplication")?.into())
             },
             (BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)) => {
                 Ok(self.builder.build_float_mul(l, r, "multmp").into())
@@ -435,15 +3909,253 @@ impl<'ctx> CodeGen<'ctx> {
     fn generate_div(&self, left: BasicValueEnum<'ctx>, right: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
         match (left, right) {
             (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
+                self.build_division_checks(l, r, "Division by zero", "division")?;
                 Ok(self.builder.build_int_signed_div(l, r, "divtmp").into())
             },
             (BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)) => {
+                // Float division by zero is an error too, as in the interpreter
+                let is_zero = self.builder.build_float_compare(
+                    inkwell::FloatPredicate::OEQ,
+                    r,
+                    r.get_type().const_zero(),
+                    "div_by_zero"
+                );
+                self.build_fail_if(is_zero, "div_by_zero", "Division by zero")?;
+                
                 Ok(self.builder.build_float_div(l, r, "divtmp").into())
             },
             _ => Err(ShitRustError::TypeError("Incompatible types for division".to_string())),
         }
     }
     
+    fn generate_rem(&self, left: BasicValueEnum<'ctx>, right: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        match (left, right) {
+            (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
+                self.build_division_checks(l, r, "Modulo by zero", "remainder")?;
+                Ok(self.builder.build_int_signed_rem(l, r, "remtmp").into())
+            },
+            _ => Err(ShitRustError::TypeError("Modulo only works with integers".to_string())),
+        }
+    }
+    
+    // Overflow semantics match the interpreter: int `+ - *`, negation and `int::MIN / -1` fail
+    // with its error message instead of wrapping, and so does division or remainder by zero.
+    
+    // Emits `llvm.s<op>.with.overflow` and fails when the result does not fit in the int type.
+    // `operation` names the operation in the error, e.g. `addition`.
+    fn build_checked_int_op(&self, op: &str, left: IntValue<'ctx>, right: IntValue<'ctx>, operation: &str) -> Result<IntValue<'ctx>, ShitRustError> {
+        let (value, overflowed) = self.build_int_op_with_overflow(op, left, right)?;
+        self.build_fail_if(overflowed, &format!("{}_overflow", op), &format!("Integer overflow in {}", operation))?;
+        Ok(value)
+    }
+    
+    // Emits `llvm.s<op>.with.overflow`, returning the wrapped result and the overflow flag
+    fn build_int_op_with_overflow(&self, op: &str, left: IntValue<'ctx>, right: IntValue<'ctx>) -> Result<(IntValue<'ctx>, IntValue<'ctx>), ShitRustError> {
+        let name = format!("llvm.s{}.with.overflow", op);
+        let intrinsic = Intrinsic::find(&name)
+            .and_then(|intrinsic| intrinsic.get_declaration(&self.module, &[left.get_type().into()]))
+            .ok_or_else(|| ShitRustError::RuntimeError(format!("Missing {} intrinsic", name)))?;
+        
+        let call = self.builder.build_call(intrinsic, &[left.into(), right.into()], &format!("{}_with_overflow", op));
+        let result = call.try_as_basic_value().left().unwrap().into_struct_value();
+        
+        let value = self.builder.build_extract_value(result, 0, &format!("{}tmp", op)).unwrap().into_int_value();
+        let overflowed = self.builder.build_extract_value(result, 1, "overflowed").unwrap().into_int_value();
+        Ok((value, overflowed))
+    }
+    
+    // Fails with `zero_message` on a zero divisor and on `int::MIN / -1`, which LLVM leaves
+    // undefined, with an overflow in `operation`
+    fn build_division_checks(&self, left: IntValue<'ctx>, right: IntValue<'ctx>, zero_message: &str, operation: &str) -> Result<(), ShitRustError> {
+        let int_type = left.get_type();
+        
+        let is_zero = self.builder.build_int_compare(inkwell::IntPredicate::EQ, right, int_type.const_zero(), "div_by_zero");
+        self.build_fail_if(is_zero, "div_by_zero", zero_message)?;
+        
+        let overflowed = self.build_division_overflowed(left, right);
+        self.build_fail_if(overflowed, "div_overflow", &format!("Integer overflow in {}", operation))
+    }
+    
+    // Whether dividing `left` by `right` is `int::MIN / -1`
+    fn build_division_overflowed(&self, left: IntValue<'ctx>, right: IntValue<'ctx>) -> IntValue<'ctx> {
+        let int_type = left.get_type();
+        let min = int_type.const_int(1 << (int_type.get_bit_width() - 1), false);
+        let is_min = self.builder.build_int_compare(inkwell::IntPredicate::EQ, left, min, "is_min");
+        let is_minus_one = self.builder.build_int_compare(inkwell::IntPredicate::EQ, right, int_type.const_all_ones(), "is_minus_one");
+        self.builder.build_and(is_min, is_minus_one, "div_overflowed")
+    }
+    
+    // Branches to a block that fails with `message` when `condition` holds and continues in a new block otherwise
+    fn build_fail_if(&self, condition: IntValue<'ctx>, name: &str, message: &str) -> Result<(), ShitRustError> {
+        let function = self.function_for("Checked arithmetic")?;
+        let fail_bb = self.context.append_basic_block(function, name);
+        let cont_bb = self.context.append_basic_block(function, &format!("{}_cont", name));
+        self.builder.build_conditional_branch(condition, fail_bb, cont_bb);
+        
+        self.builder.position_at_end(fail_bb);
+        self.build_fail(message);
+        
+        self.builder.position_at_end(cont_bb);
+        Ok(())
+    }
+    
+    // Reports a runtime error the way the interpreter words it and aborts, through the runtime library
+    fn build_fail(&self, message: &str) {
+        let bytes = self.builder.build_global_string_ptr(message, "error");
+        let len = self.context.i64_type().const_int(message.len() as u64, false);
+        self.call_runtime("shitrust_fail", &[bytes.as_pointer_value().into(), len.into()]);
+        self.builder.build_unreachable();
+    }
+    
+    // The function instructions are added to. Global and const initializers have none, so `what`
+    // cannot be used in them.
+    fn function_for(&self, what: &str) -> Result<FunctionValue<'ctx>, ShitRustError> {
+        self.current_function.ok_or_else(|| ShitRustError::TypeError(
+            format!("{} is not supported in global or const initializers", what)
+        ))
+    }
+    
+    // Aborts the program at the current position
+    fn build_trap(&self) -> Result<(), ShitRustError> {
+        let trap = Intrinsic::find("llvm.trap")
+            .and_then(|intrinsic| intrinsic.get_declaration(&self.module, &[]))
+            .ok_or_else(|| ShitRustError::RuntimeError("Missing llvm.trap intrinsic".to_string()))?;
+        self.builder.build_call(trap, &[], "trap");
+        self.builder.build_unreachable();
+        Ok(())
+    }
+    
+    // Lowers the `wrapping_*`, `checked_*` and `saturating_*` int methods like the interpreter:
+    // each operation yields the wrapped result and an overflow flag, which the mode turns into the
+    // result, none or the saturated bound.
+    fn generate_int_method(&self, value: IntValue<'ctx>, method: &str, args: &[BasicValueEnum<'ctx>]) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
+        let (mode, op) = method.split_once('_')
+            .filter(|_| crate::ast::INT_METHODS.contains(&method))
+            .ok_or_else(|| ShitRustError::RuntimeError(format!("Unknown int method '{}'", method)))?;
+        
+        let int_type = value.get_type();
+        let zero = int_type.const_zero();
+        let other = match (op, args) {
+            ("neg", []) => zero,
+            (_, [BasicValueEnum::IntValue(other)]) if op != "neg" => *other,
+            _ => return Err(ShitRustError::TypeError(format!("Invalid arguments for int method '{}'", method))),
+        };
+        
+        let (wrapped, overflowed) = match op {
+            "add" | "sub" | "mul" => self.build_int_op_with_overflow(op, value, other)?,
+            "neg" => self.build_int_op_with_overflow("sub", zero, value)?,
+            "div" | "rem" => {
+                let is_zero = self.builder.build_int_compare(inkwell::IntPredicate::EQ, other, zero, "is_zero");
+                if mode != "checked" {
+                    self.build_fail_if(is_zero, "div_by_zero", "Division by zero")?;
+                }
+                
+                // Divide by one instead when the result is none or saturated, as LLVM leaves
+                // both cases undefined
+                let overflowed = self.build_division_overflowed(value, other);
+                let overflowed = self.builder.build_or(is_zero, overflowed, "overflowed");
+                let divisor = self.builder.build_select(overflowed, int_type.const_int(1, false), other, "divisor").into_int_value();
+                let result = if op == "div" {
+                    self.builder.build_int_signed_div(value, divisor, "divtmp")
+                } else {
+                    self.builder.build_int_signed_rem(value, divisor, "remtmp")
+                };
+                (result, overflowed)
+            },
+            "pow" => {
+                let invalid = self.builder.build_int_compare(
+                    inkwell::IntPredicate::UGT, other, int_type.const_int(u32::MAX as u64, false), "invalid_exponent"
+                );
+                self.build_fail_if(invalid, "invalid_exponent", &format!("Invalid exponent in '{}'", method))?;
+                self.build_int_pow(value, other)?
+            },
+            _ => unreachable!("INT_METHODS only has these operations"),
+        };
+        
+        match mode {
+            "wrapping" => Ok(wrapped.into()),
+            "checked" => {
+                let present = self.builder.build_not(overflowed, "present");
+                Ok(self.build_some(wrapped.into(), present))
+            },
+            _ => {
+                // Whether the exact result is negative, which decides the bound it saturates to
+                let negative = match op {
+                    "add" => self.builder.build_int_compare(inkwell::IntPredicate::SLT, other, zero, "negative"),
+                    "sub" => self.builder.build_int_compare(inkwell::IntPredicate::SGT, other, zero, "negative"),
+                    "mul" => {
+                        let sign = self.builder.build_xor(value, other, "signtmp");
+                        self.builder.build_int_compare(inkwell::IntPredicate::SLT, sign, zero, "negative")
+                    },
+                    "neg" => self.builder.build_int_compare(inkwell::IntPredicate::SGT, value, zero, "negative"),
+                    "pow" => {
+                        let is_negative = self.builder.build_int_compare(inkwell::IntPredicate::SLT, value, zero, "is_negative");
+                        let odd = self.builder.build_int_truncate(other, self.context.bool_type(), "odd");
+                        self.builder.build_and(is_negative, odd, "negative")
+                    },
+                    // Only `int::MIN / -1` overflows, and its exact result is positive
+                    _ => self.context.bool_type().const_zero(),
+                };
+                
+                let max = int_type.const_int((1 << (int_type.get_bit_width() - 1)) - 1, false);
+                let min = int_type.const_int(1 << (int_type.get_bit_width() - 1), false);
+                let bound = self.builder.build_select(negative, min, max, "bound").into_int_value();
+                Ok(self.builder.build_select(overflowed, bound, wrapped, "sattmp"))
+            },
+        }
+    }
+    
+    // Exponentiation by squaring for a non-negative `exponent`, returning the wrapped power and
+    // whether any step overflowed. The base is only squared while bits of the exponent remain,
+    // so the flag matches Rust's `checked_pow`.
+    fn build_int_pow(&self, base: IntValue<'ctx>, exponent: IntValue<'ctx>) -> Result<(IntValue<'ctx>, IntValue<'ctx>), ShitRustError> {
+        let function = self.function_for("Exponentiation")?;
+        let int_type = base.get_type();
+        let bool_type = self.context.bool_type();
+        let one = int_type.const_int(1, false);
+        
+        let entry_bb = self.builder.get_insert_block().unwrap();
+        let loop_bb = self.context.append_basic_block(function, "pow_loop");
+        let body_bb = self.context.append_basic_block(function, "pow_body");
+        let done_bb = self.context.append_basic_block(function, "pow_done");
+        self.builder.build_unconditional_branch(loop_bb);
+        
+        self.builder.position_at_end(loop_bb);
+        let result = self.builder.build_phi(int_type, "result");
+        let power = self.builder.build_phi(int_type, "power");
+        let remaining = self.builder.build_phi(int_type, "remaining");
+        let overflowed = self.builder.build_phi(bool_type, "overflowed");
+        let result_value = result.as_basic_value().into_int_value();
+        let power_value = power.as_basic_value().into_int_value();
+        let remaining_value = remaining.as_basic_value().into_int_value();
+        let overflowed_value = overflowed.as_basic_value().into_int_value();
+        let finished = self.builder.build_int_compare(inkwell::IntPredicate::EQ, remaining_value, int_type.const_zero(), "finished");
+        self.builder.build_conditional_branch(finished, done_bb, body_bb);
+        
+        self.builder.position_at_end(body_bb);
+        let bit = self.builder.build_int_truncate(remaining_value, bool_type, "bit");
+        let (product, product_overflowed) = self.build_int_op_with_overflow("mul", result_value, power_value)?;
+        let next_result = self.builder.build_select(bit, product, result_value, "next_result").into_int_value();
+        let result_overflowed = self.builder.build_and(bit, product_overflowed, "result_overflowed");
+        
+        let next_remaining = self.builder.build_right_shift(remaining_value, one, false, "next_remaining");
+        let (square, square_overflowed) = self.build_int_op_with_overflow("mul", power_value, power_value)?;
+        let more = self.builder.build_int_compare(inkwell::IntPredicate::NE, next_remaining, int_type.const_zero(), "more");
+        let square_overflowed = self.builder.build_and(more, square_overflowed, "square_overflowed");
+        
+        let next_overflowed = self.builder.build_or(overflowed_value, result_overflowed, "next_overflowed");
+        let next_overflowed = self.builder.build_or(next_overflowed, square_overflowed, "next_overflowed");
+        self.builder.build_unconditional_branch(loop_bb);
+        
+        result.add_incoming(&[(&one, entry_bb), (&next_result, body_bb)]);
+        power.add_incoming(&[(&base, entry_bb), (&square, body_bb)]);
+        remaining.add_incoming(&[(&exponent, entry_bb), (&next_remaining, body_bb)]);
+        overflowed.add_incoming(&[(&bool_type.const_zero(), entry_bb), (&next_overflowed, body_bb)]);
+        
+        self.builder.position_at_end(done_bb);
+        Ok((result_value, overflowed_value))
+    }
+    
     // Comparison operators
     
     fn generate_eq(&self, left: BasicValueEnum<'ctx>, right: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, ShitRustError> {
@@ -530,6 +4242,26 @@ impl<'ctx> CodeGen<'ctx> {
         builder.build_alloca(typ, name)
     }
     
+    // An entry block alloca that is zeroed right after it is allocated
+    fn create_zeroed_alloca(&self, name: &str, typ: BasicTypeEnum<'ctx>) -> PointerValue<'ctx> {
+        let slot = self.create_entry_block_alloca(name, typ);
+        
+        let builder = self.context.create_builder();
+        let alloca = slot.as_instruction().unwrap();
+        match alloca.get_next_instruction() {
+            Some(next) => builder.position_before(&next),
+            None => builder.position_at_end(alloca.get_parent().unwrap()),
+        }
+        builder.build_store(slot, typ.const_zero());
+        
+        slot
+    }
+    
+    // The module's LLVM IR as text
+    pub fn ir_string(&self) -> String {
+        self.module.print_to_string().to_string()
+    }
+    
     pub fn write_to_file(&self, path: &Path) -> Result<(), ShitRustError> {
         match self.module.print_to_file(path) {
             Ok(_) => Ok(()),
@@ -540,27 +4272,222 @@ impl<'ctx> CodeGen<'ctx> {
         }
     }
     
-    pub fn compile_to_object_file(&self, path: &Path) -> Result<(), ShitRustError> {
-        let target_triple = inkwell::targets::TargetMachine::get_default_triple();
-        let target = inkwell::targets::Target::from_triple(&target_triple)
-            .map_err(|e| ShitRustError::RuntimeError(format!("Failed to get target: {}", e)))?;
-            
-        let target_machine = target.create_target_machine(
+    pub fn write_bitcode_to_file(&self, path: &Path) -> Result<(), ShitRustError> {
+        if self.module.write_bitcode_to_path(path) {
+            Ok(())
+        } else {
+            Err(ShitRustError::IOException(format!("Failed to write LLVM bitcode to {}", path.display())))
+        }
+    }
+    
+    // Runs the standard LLVM pipeline for the optimization level over the module, the same one
+    // `opt -O<n>` uses. Level `None` still runs the O0 pipeline, which only inlines
+    // `alwaysinline` functions.
+    pub fn optimize(&self) -> Result<(), ShitRustError> {
+        let pipeline = match self.optimization_level {
+            OptimizationLevel::None => "default<O0>",
+            OptimizationLevel::Less => "default<O1>",
+            OptimizationLevel::Default => "default<O2>",
+            OptimizationLevel::Aggressive => "default<O3>",
+        };
+        
+        let target_machine = self.create_target_machine()?;
+        let options = PassBuilderOptions::create();
+        let vectorize = matches!(self.optimization_level, OptimizationLevel::Default | OptimizationLevel::Aggressive);
+        options.set_loop_vectorization(vectorize);
+        options.set_loop_slp_vectorization(vectorize);
+        options.set_loop_interleaving(vectorize);
+        options.set_loop_unrolling(self.optimization_level != OptimizationLevel::None);
+        
+        self.module.run_passes(pipeline, &target_machine, options)
+            .map_err(|e| ShitRustError::RuntimeError(format!("Failed to optimize LLVM IR: {}", e)))
+    }
+    
+    fn create_target_machine(&self) -> Result<TargetMachine, ShitRustError> {
+        let target_triple = match &self.target_triple {
+            Some(triple) => TargetTriple::create(triple),
+            None => TargetMachine::get_default_triple(),
+        };
+        let target = Target::from_triple(&target_triple)
+            .map_err(|e| ShitRustError::RuntimeError(format!(
+                "Unknown target {}: {}", target_triple.as_str().to_string_lossy(), e
+            )))?;
+        
+        target.create_target_machine(
             &target_triple,
-            "generic",
-            "",
-            OptimizationLevel::Default,
-            inkwell::targets::RelocMode::Default,
-            inkwell::targets::CodeModel::Default,
-        ).ok_or_else(|| ShitRustError::RuntimeError("Failed to create target machine".to_string()))?;
-        
-        target_machine.write_to_file(
-            &self.module, 
-            inkwell::targets::FileType::Object, 
-            path
-        ).map_err(|e| ShitRustError::IoError(std::io::Error::new(
-            std::io::ErrorKind::Other, 
-            format!("Failed to write object file: {}", e)
+            &self.target_cpu,
+            &self.target_features,
+            self.optimization_level,
+            self.reloc_mode,
+            CodeModel::Default,
+        ).ok_or_else(|| ShitRustError::RuntimeError(format!(
+            "Failed to create a target machine for {} with CPU '{}'",
+            target_triple.as_str().to_string_lossy(), self.target_cpu
         )))
     }
-} 
\ No newline at end of file
+    
+    // Whether a function whose body failed to lower can be left to the interpreter: only with
+    // the fallback enabled, and only for functions of ints, floats and bools, which its stub
+    // can pass as 64-bit words. `main` holds the top-level statements, so it is never left out.
+    // The interpreter does not run them either, so functions that may read a top-level `let`
+    // are not left to it; any identifier naming one that is not a parameter counts.
+    fn can_interpret(&self, name: &str, params: &[(String, AstType)], return_type: &AstType, body: &[Stmt]) -> bool {
+        let word = |typ: &AstType| matches!(typ, AstType::Int | AstType::Float | AstType::Bool);
+        let mut reads_bindings = false;
+        walk_exprs(body, &mut |expr| {
+            if let Expr::Identifier(identifier) = expr {
+                reads_bindings |= self.module_bindings.contains(identifier)
+                    && !params.iter().any(|(param, _)| param == identifier);
+            }
+        });
+        
+        self.interpreter_fallback
+            && name != "main"
+            && !self.exports.iter().any(|export| export.name == name)
+            && params.iter().all(|(_, typ)| word(typ))
+            && (word(return_type) || *return_type == AstType::Void)
+            && !reads_bindings
+    }
+    
+    // Replaces `function`, whose body failed to lower, with a stub that calls `INTERPRETER_ENTRY`
+    // with the function's index in `interpreted_functions` and its arguments stored as 64-bit
+    // words, floats by their bits. The functions lambdas in the body were lifted to since
+    // `last_function` are dropped with it.
+    fn generate_interpreter_call(
+        &mut self,
+        function: FunctionValue<'ctx>,
+        last_function: Option<FunctionValue<'ctx>>,
+        name: &str,
+        params: &[(String, AstType)],
+        return_type: &AstType,
+    ) {
+        let lifted: Vec<FunctionValue> = std::iter::successors(
+            last_function.map_or(self.module.get_first_function(), |last| last.get_next_function()),
+            |next| next.get_next_function(),
+        ).filter(|lifted| *lifted != function && lifted.count_basic_blocks() > 0).collect();
+        
+        // Calls to the function go to the stub, which takes its name once the function is gone
+        let stub = self.module.add_function("", function.get_type(), None);
+        function.replace_all_uses_with(stub);
+        unsafe {
+            function.delete();
+            for lifted in lifted {
+                lifted.delete();
+            }
+        }
+        stub.as_global_value().set_name(name);
+        
+        let i64_type = self.context.i64_type();
+        let entry = self.module.get_function(INTERPRETER_ENTRY).unwrap_or_else(|| {
+            let word_pointer = i64_type.ptr_type(Default::default());
+            let entry_type = i64_type.fn_type(&[i64_type.into(), word_pointer.into()], false);
+            self.module.add_function(INTERPRETER_ENTRY, entry_type, Some(Linkage::External))
+        });
+        
+        // The stub has no debug info, so it must not take the location of the failed body
+        let block = self.context.append_basic_block(stub, "entry");
+        self.builder.position_at_end(block);
+        self.builder.unset_current_debug_location();
+        self.current_function = Some(stub);
+        let words_type = i64_type.array_type(params.len() as u32);
+        let words = self.builder.build_alloca(words_type, "args");
+        for (index, param) in stub.get_param_iter().enumerate() {
+            let word = match param {
+                BasicValueEnum::FloatValue(f) => self.builder.build_bitcast(f, i64_type, "word").into_int_value(),
+                BasicValueEnum::IntValue(i) => self.builder.build_int_z_extend_or_bit_cast(i, i64_type, "word"),
+                _ => unreachable!("only functions of ints, floats and bools are interpreted"),
+            };
+            let slot = unsafe {
+                self.builder.build_in_bounds_gep(words_type, words, &[i64_type.const_zero(), i64_type.const_int(index as u64, false)], "arg")
+            };
+            self.builder.build_store(slot, word);
+        }
+        
+        let index = i64_type.const_int(self.interpreted_functions.len() as u64, false);
+        let call = self.builder.build_call(entry, &[index.into(), words.into()], "interpreted");
+        let result = call.try_as_basic_value().left().unwrap().into_int_value();
+        match return_type {
+            AstType::Void => self.builder.build_return(None),
+            AstType::Float => {
+                let value = self.builder.build_bitcast(result, self.context.f64_type(), "result");
+                self.builder.build_return(Some(&value))
+            },
+            AstType::Bool => {
+                let value = self.builder.build_int_truncate(result, self.context.bool_type(), "result");
+                self.builder.build_return(Some(&value))
+            },
+            _ => self.builder.build_return(Some(&result)),
+        };
+        
+        self.interpreted_functions.push(ExternFunction {
+            name: name.to_string(),
+            params: params.to_vec(),
+            return_type: return_type.clone(),
+        });
+    }
+    
+    // Compiles the module in memory and calls its `main`, returning the exit code. The runtime
+    // library is loaded into the process to provide the functions compiled code calls, and
+    // `interpreter_entry` is the address `INTERPRETER_ENTRY` resolves to.
+    pub fn run_jit(&self, interpreter_entry: usize) -> Result<i32, ShitRustError> {
+        let runtime = crate::runtime::runtime_shared_library()?;
+        if inkwell::support::load_library_permanently(&runtime.to_string_lossy()) {
+            return Err(ShitRustError::RuntimeError(format!("Failed to load the runtime library {}", runtime.display())));
+        }
+        
+        // As in the interpreter, a library that fails to load is skipped: its functions may still
+        // be in the process, which has the C library loaded
+        for library in &self.link_libraries {
+            inkwell::support::load_library_permanently(&crate::ffi::library_file_name(library));
+        }
+        
+        let engine = self.module.create_jit_execution_engine(self.optimization_level)
+            .map_err(|e| ShitRustError::RuntimeError(format!("Failed to create JIT execution engine: {}", e)))?;
+        if let Some(entry) = self.module.get_function(INTERPRETER_ENTRY) {
+            engine.add_global_mapping(&entry, interpreter_entry);
+        }
+        
+        let main = self.module.get_function("main")
+            .ok_or_else(|| ShitRustError::RuntimeError("No main function to run".to_string()))?;
+        let jit_error = |e: inkwell::execution_engine::FunctionLookupError| {
+            ShitRustError::RuntimeError(format!("Failed to JIT-compile main: {}", e))
+        };
+        
+        // `main` is only called through the expected signature, which the module defines it with.
+        // A `fn main() -> int` returns 64 bits, which are truncated to the exit code as C does.
+        let exit_code = unsafe {
+            match main.get_type().get_return_type() {
+                Some(BasicTypeEnum::IntType(int_type)) if int_type.get_bit_width() == 32 => {
+                    engine.get_function::<unsafe extern "C" fn() -> i32>("main").map_err(jit_error)?.call()
+                },
+                Some(BasicTypeEnum::IntType(int_type)) if int_type.get_bit_width() == 64 => {
+                    engine.get_function::<unsafe extern "C" fn() -> i64>("main").map_err(jit_error)?.call() as i32
+                },
+                None => {
+                    engine.get_function::<unsafe extern "C" fn()>("main").map_err(jit_error)?.call();
+                    0
+                },
+                Some(_) => return Err(ShitRustError::TypeError("main must return int or nothing".to_string())),
+            }
+        };
+        
+        engine.remove_module(&self.module)
+            .map_err(|e| ShitRustError::RuntimeError(format!("Failed to release the JIT module: {}", e)))?;
+        Ok(exit_code)
+    }
+    
+    pub fn compile_to_object_file(&self, path: &Path) -> Result<(), ShitRustError> {
+        self.write_machine_code(path, FileType::Object)
+    }
+    
+    pub fn compile_to_assembly_file(&self, path: &Path) -> Result<(), ShitRustError> {
+        self.write_machine_code(path, FileType::Assembly)
+    }
+    
+    fn write_machine_code(&self, path: &Path, file_type: FileType) -> Result<(), ShitRustError> {
+        let target_machine = self.create_target_machine()?;
+        
+        target_machine.write_to_file(&self.module, file_type, path)
+            .map_err(|e| ShitRustError::IOException(format!("Failed to write {}: {}", path.display(), e)))
+    }
+}
@@ -1,4 +1,7 @@
-use std::path::Path;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use anyhow::{Result, Context};
 use inkwell::context::Context;
@@ -9,9 +12,12 @@ use std::time::{Instant, Duration};
 
 use crate::lexer::Lexer;
 use crate::parser::Parser;
-use crate::interpreter::Interpreter;
+use crate::interpreter::{Interpreter, Value};
 use crate::code_gen::CodeGen;
+use crate::build_cache::{BuildCache, ModuleArtifacts};
 use crate::error::ShitRustError;
+use crate::ast::{self, Expr, ExternFunction, Program, Stmt, Type};
+use crate::type_system::{TypeChecker, TypeRecording};
 
 /// Compiler configuration options
 #[derive(Debug, Clone)]
@@ -21,6 +27,15 @@ pub struct CompilerOptions {
     pub emit_debug_info: bool,
     pub show_timings: bool,     // New option to display timing information
     pub emit_llvm_ir: bool,     // New option to save LLVM IR to a file
+    pub emit: Vec<EmitKind>,    // Outputs written by `compile`
+    pub target: Option<String>, // Target triple; the host when not set
+    pub target_cpu: Option<String>,
+    pub target_features: Option<String>,
+    pub relocation_model: RelocationModel,
+    pub crate_type: CrateType,
+    pub linker: Option<String>,
+    pub sysroot: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>, // Build cache reused across compiles; none when not set
     pub color_output: bool,     // New option to control colored output
     pub strict_type_checking: bool,
 }
@@ -34,6 +49,74 @@ pub enum OptimizationLevel {
     Aggressive,
 }
 
+/// Kinds of output `compile` can produce
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmitKind {
+    /// Native assembly (`.s`)
+    Asm,
+    /// Object file (`.o`)
+    Obj,
+    /// LLVM bitcode (`.bc`)
+    Bc,
+    /// LLVM IR as text (`.ll`)
+    Ll,
+    /// The executable or library of the crate type
+    Exe,
+}
+
+/// What `compile` builds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrateType {
+    /// An executable that runs the top-level statements, or `main`
+    Bin,
+    /// A shared library exporting the `#[export]` functions, with a C header
+    Cdylib,
+    /// A static library with the runtime library, exporting the `#[export]` functions, with a C header
+    Staticlib,
+}
+
+impl CrateType {
+    /// The default output file for a program named `stem`, e.g. `libgeometry.so` for a shared
+    /// library on Linux. Follows the conventions of `target`, or the host when it is `None`.
+    pub fn output_file_name(&self, stem: &str, target: Option<&str>) -> String {
+        let (windows, apple, msvc) = match target {
+            Some(triple) => (triple.contains("windows"), triple.contains("apple"), triple.ends_with("-msvc")),
+            None => (cfg!(target_os = "windows"), cfg!(target_os = "macos"), cfg!(target_env = "msvc")),
+        };
+        
+        match self {
+            CrateType::Bin => format!("{}.exe", stem),
+            CrateType::Cdylib if windows => format!("{}.dll", stem),
+            CrateType::Cdylib if apple => format!("lib{}.dylib", stem),
+            CrateType::Cdylib => format!("lib{}.so", stem),
+            CrateType::Staticlib if msvc => format!("{}.lib", stem),
+            CrateType::Staticlib => format!("lib{}.a", stem),
+        }
+    }
+}
+
+/// How generated code refers to addresses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelocationModel {
+    /// Position independent code, which fits both executables and shared libraries
+    Pic,
+    /// Absolute addresses, for executables linked at a fixed address
+    Static,
+}
+
+impl EmitKind {
+    /// The file extension used for this kind of output
+    pub fn extension(&self) -> &'static str {
+        match self {
+            EmitKind::Asm => "s",
+            EmitKind::Obj => "o",
+            EmitKind::Bc => "bc",
+            EmitKind::Ll => "ll",
+            EmitKind::Exe => "exe",
+        }
+    }
+}
+
 impl Default for CompilerOptions {
     fn default() -> Self {
         Self {
@@ -42,6 +125,15 @@ impl Default for CompilerOptions {
             emit_debug_info: false,
             show_timings: false,
             emit_llvm_ir: false,
+            emit: vec![EmitKind::Exe],
+            target: None,
+            target_cpu: None,
+            target_features: None,
+            relocation_model: RelocationModel::Pic,
+            crate_type: CrateType::Bin,
+            linker: None,
+            sysroot: None,
+            cache_dir: None,
             color_output: true,
             strict_type_checking: false,
         }
@@ -166,113 +258,193 @@ impl Compiler {
             timer.checkpoint(msg)
         };
         
-        // Step 1: Tokenize the source code
-        log_msg("Tokenizing source code...", &mut timer);
-        
-        let mut lexer = if let Some(filename) = &filename {
-            Lexer::with_filename(source, filename.clone())
-        } else {
-            Lexer::new(source)
-        };
-        
-        let tokens = lexer.scan_tokens()
-            .context("Failed during lexical analysis")?;
-        
-        // Step 2: Parse the tokens into an AST
-        log_msg("Parsing tokens into AST...", &mut timer);
-        
-        let mut parser = Parser::new(tokens);
-        let program = if let Some(filename) = filename {
-            let statements = parser.parse()
-                .context("Failed during parsing")?
-                .statements;
-            crate::ast::Program::with_source(statements, filename)
-        } else {
-            parser.parse()
-                .context("Failed during parsing")?
-        };
+        // Steps 1 and 2: Tokenize the source code and parse the tokens into an AST
+        log_msg("Parsing source code...", &mut timer);
         
-        // Step 3: Generate LLVM IR code
-        log_msg("Generating LLVM IR code...", &mut timer);
+        // Libraries get a C header named after the source file
+        let header_name = filename.as_deref()
+            .and_then(|filename| Path::new(filename).file_stem())
+            .or_else(|| output_path.file_stem())
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "shitrust".to_string());
         
-        let context = Context::create();
-        let mut code_gen = CodeGen::new(&context, "shitrust_module");
+        // Debug info needs to know where each statement starts
+        let program = parse_source(source, filename, self.options.emit_debug_info)?;
         
-        // Set code generator options
-        code_gen.set_optimization_level(self.options.optimization_level);
-        if self.options.emit_debug_info {
-            code_gen.enable_debug_info();
+        if self.options.crate_type == CrateType::Cdylib && self.options.relocation_model == RelocationModel::Static {
+            return Err(anyhow::anyhow!("Shared libraries need position independent code, not --relocation-model static"));
         }
         
-        code_gen.generate_code(&program)
-            .context("Failed during code generation")?;
-        
-        // Step 4: Write IR to a temporary file
-        log_msg("Writing LLVM IR to file...", &mut timer);
+        // Step 3: Look for the object file of an earlier build of the same module. Only the
+        // executable or library can come from the cache; other outputs need the LLVM module.
+        let mut emit = self.options.emit.clone();
+        if self.options.emit_llvm_ir && !emit.contains(&EmitKind::Ll) {
+            emit.push(EmitKind::Ll);
+        }
         
-        let ir_file = NamedTempFile::new()
-            .context("Failed to create temporary file for IR")?;
-        let ir_path = ir_file.path();
-        code_gen.write_to_file(ir_path)
-            .context("Failed to write IR to file")?;
+        let cache = self.options.cache_dir.as_ref().map(BuildCache::new);
+        let cache_key = self.cache_key(&program)?;
+        let cached = match &cache {
+            Some(cache) if emit.iter().all(|kind| *kind == EmitKind::Exe) => cache.load(&header_name, &cache_key),
+            _ => None,
+        };
         
-        if self.options.verbose {
-            if self.options.color_output {
-                println!("{} {}", "Generated LLVM IR saved to:".blue(), 
-                       ir_path.display().to_string().cyan());
-            } else {
-                println!("Generated LLVM IR saved to: {}", ir_path.display());
+        // Keeps an object file that is not cached until it is linked
+        let mut _obj_file = None;
+        let artifacts = match cached {
+            Some(artifacts) => {
+                log_msg("Reusing cached object file...", &mut timer);
+                
+                if self.options.verbose {
+                    if self.options.color_output {
+                        println!("{} {}", "Cached object file:".blue(), 
+                               artifacts.object.display().to_string().cyan());
+                    } else {
+                        println!("Cached object file: {}", artifacts.object.display());
+                    }
+                }
+                artifacts
+            },
+            None => {
+                // Step 4: Generate LLVM IR code
+                log_msg("Generating LLVM IR code...", &mut timer);
+                
+                let context = Context::create();
+                let code_gen = self.generate_module(&context, &program)?;
+                
+                // Step 5: Run the optimization passes
+                log_msg("Optimizing LLVM IR...", &mut timer);
+                
+                code_gen.optimize()
+                    .context("Failed during optimization")?;
+                
+                // Step 6: Write the requested outputs; other kinds go next to the executable with their own extension
+                log_msg("Writing outputs...", &mut timer);
+                
+                for kind in &emit {
+                    let path = output_path.with_extension(kind.extension());
+                    let written = match kind {
+                        EmitKind::Asm => code_gen.compile_to_assembly_file(&path).map_err(Into::into),
+                        EmitKind::Obj => code_gen.compile_to_object_file(&path).map_err(Into::into),
+                        EmitKind::Bc => code_gen.write_bitcode_to_file(&path).map_err(Into::into),
+                        EmitKind::Ll => code_gen.write_to_file(&path).map_err(Into::into),
+                        EmitKind::Exe => continue,
+                    };
+                    written.with_context(|| format!("Failed to write {}", path.display()))?;
+                    
+                    if self.options.verbose || self.options.show_timings {
+                        if self.options.color_output {
+                            println!("{} {}", "Wrote:".blue(), path.display().to_string().cyan());
+                        } else {
+                            println!("Wrote: {}", path.display());
+                        }
+                    }
+                }
+                
+                if !emit.contains(&EmitKind::Exe) {
+                    timer.report_total(true);
+                    return Ok(());
+                }
+                
+                let file = NamedTempFile::new()
+                    .context("Failed to create temporary file for object code")?;
+                code_gen.compile_to_object_file(file.path())
+                    .context("Failed to compile IR to object file")?;
+                
+                if self.options.verbose {
+                    if self.options.color_output {
+                        println!("{} {}", "Generated object file:".blue(), 
+                               file.path().display().to_string().cyan());
+                    } else {
+                        println!("Generated object file: {}", file.path().display());
+                    }
+                }
+                
+                let artifacts = ModuleArtifacts {
+                    object: file.path().to_path_buf(),
+                    link_libraries: code_gen.link_libraries().to_vec(),
+                    c_header: (self.options.crate_type != CrateType::Bin).then(|| code_gen.c_header(&header_name)),
+                };
+                match &cache {
+                    Some(cache) => cache.store(&header_name, &cache_key, artifacts)
+                        .context("Failed to cache the object file")?,
+                    None => {
+                        _obj_file = Some(file);
+                        artifacts
+                    }
+                }
             }
-        }
+        };
         
-        // Optionally save LLVM IR to a file with the same name as output but .ll extension
-        if self.options.emit_llvm_ir {
-            let mut ir_output = output_path.to_path_buf();
-            ir_output.set_extension("ll");
-            fs::copy(ir_path, &ir_output)
-                .with_context(|| format!("Failed to save LLVM IR to {}", ir_output.display()))?;
+        if let Some(c_header) = &artifacts.c_header {
+            let header_path = output_path.with_file_name(format!("{}.h", header_name));
+            fs::write(&header_path, c_header)
+                .with_context(|| format!("Failed to write {}", header_path.display()))?;
             
-            if self.options.verbose || self.options.show_timings {
+            if self.options.verbose {
                 if self.options.color_output {
-                    println!("{} {}", "LLVM IR saved to:".blue(), 
-                           ir_output.display().to_string().cyan());
+                    println!("{} {}", "Wrote:".blue(), header_path.display().to_string().cyan());
                 } else {
-                    println!("LLVM IR saved to: {}", ir_output.display());
+                    println!("Wrote: {}", header_path.display());
                 }
             }
         }
         
-        // Step 5: Generate object file
-        log_msg("Generating object file...", &mut timer);
+        if self.options.crate_type == CrateType::Staticlib {
+            log_msg("Creating static library...", &mut timer);
+            self.write_static_library(&artifacts.object, output_path)?;
+            timer.report_total(true);
+            return Ok(());
+        }
         
-        let obj_file = NamedTempFile::new()
-            .context("Failed to create temporary file for object code")?;
-        let obj_path = obj_file.path();
-        code_gen.compile_to_object_file(obj_path)
-            .context("Failed to compile IR to object file")?;
+        // Step 7: Link to create the executable or shared library
+        log_msg("Linking...", &mut timer);
         
-        if self.options.verbose {
-            if self.options.color_output {
-                println!("{} {}", "Generated object file:".blue(), 
-                       obj_path.display().to_string().cyan());
+        let target = self.options.target.as_deref();
+        let cc_cmd = match (&self.options.linker, target) {
+            (Some(linker), _) => linker.as_str(),
+            // clang links for any target with `--target`
+            (None, Some(_)) => "clang",
+            (None, None) if cfg!(target_os = "windows") => "clang",
+            (None, None) => "cc",
+        };
+        
+        let runtime_lib = crate::runtime::runtime_library(target)
+            .context("Failed to build the runtime library")?;
+        
+        let mut command = Command::new(cc_cmd);
+        command
+            .arg(&artifacts.object)
+            .arg(&runtime_lib)
+            .arg("-o")
+            .arg(output_path);
+        // Libraries of extern blocks: paths are linked as they are, names with `-l`
+        for library in &artifacts.link_libraries {
+            if crate::ffi::library_file_name(library) == *library {
+                command.arg(library);
             } else {
-                println!("Generated object file: {}", obj_path.display());
+                command.arg(format!("-l{}", library));
             }
         }
+        if self.options.crate_type == CrateType::Cdylib {
+            command.arg("-shared");
+        }
+        if let (None, Some(target)) = (&self.options.linker, target) {
+            command.arg(format!("--target={}", target));
+        }
+        if let Some(sysroot) = &self.options.sysroot {
+            command.arg(format!("--sysroot={}", sysroot.display()));
+        }
+        // ELF linkers make position independent executables by default, which static code cannot go in
+        let elf = match target {
+            Some(target) => !target.contains("windows") && !target.contains("apple"),
+            None => !cfg!(target_os = "windows") && !cfg!(target_os = "macos"),
+        };
+        if self.options.relocation_model == RelocationModel::Static && elf {
+            command.arg("-no-pie");
+        }
         
-        // Step 6: Link to create executable
-        log_msg("Linking to create executable...", &mut timer);
-        
-        #[cfg(target_os = "windows")]
-        let cc_cmd = "clang";
-        #[cfg(not(target_os = "windows"))]
-        let cc_cmd = "cc";
-        
-        let status = Command::new(cc_cmd)
-            .arg(obj_path)
-            .arg("-o")
-            .arg(output_path)
-            .status()
+        let status = command.status()
             .with_context(|| format!("Failed to run linker ({}). Is it installed on your system?", cc_cmd))?;
         
         if !status.success() {
@@ -297,57 +469,165 @@ impl Compiler {
         Ok(())
     }
 
-    /// Run the source code using the interpreter
-    pub fn run(&self, source: &str) -> Result<()> {
-        self.run_with_filename(source, None)
+    /// Generate the LLVM module of a program with the configured target and code generator options
+    fn generate_module<'ctx>(&self, context: &'ctx Context, program: &Program) -> Result<CodeGen<'ctx>> {
+        let mut code_gen = CodeGen::new(context, "shitrust_module");
+        
+        // Set code generator options
+        code_gen.set_optimization_level(self.options.optimization_level);
+        code_gen.set_target(
+            self.options.target.as_deref(),
+            self.options.target_cpu.as_deref(),
+            self.options.target_features.as_deref(),
+        );
+        code_gen.set_relocation_model(self.options.relocation_model);
+        code_gen.set_crate_type(self.options.crate_type);
+        if self.options.emit_debug_info {
+            code_gen.enable_debug_info();
+        }
+        
+        let (program, instantiations) = type_check(program)
+            .context("Failed during type checking")?;
+        code_gen.set_instantiations(&instantiations);
+        
+        code_gen.generate_code(&program)
+            .context("Failed during code generation")?;
+        
+        Ok(code_gen)
     }
     
-    /// Run the source code with a filename for better error reporting
-    pub fn run_with_filename(&self, source: &str, filename: Option<String>) -> Result<()> {
+    /// Generate the LLVM IR of the source code as text, after the optimization passes when `optimize` is set
+    pub fn llvm_ir_with_filename(&self, source: &str, filename: Option<String>, optimize: bool) -> Result<String> {
+        // Debug info needs to know where each statement starts
+        let program = parse_source(source, filename, self.options.emit_debug_info)?;
+        
+        let context = Context::create();
+        let code_gen = self.generate_module(&context, &program)?;
+        if optimize {
+            code_gen.optimize()
+                .context("Failed during optimization")?;
+        }
+        
+        Ok(code_gen.ir_string())
+    }
+    
+    /// Bundle the source code into an executable that interprets it, so programs that use features
+    /// the code generator does not support yet run without their sources. The executable is a copy
+    /// of the running `shitrust` with the parsed program in it, so it can only be built for the host.
+    pub fn bundle_with_filename(&self, source: &str, output_path: &Path, filename: Option<String>) -> Result<()> {
         let mut timer = CompilationTimer::new(
             self.options.verbose || self.options.show_timings,
             self.options.color_output
         );
         
-        // Step 1: Tokenize the source code
-        if self.options.verbose {
+        let log_msg = |msg: &str, timer: &mut CompilationTimer| {
+            if self.options.verbose {
+                if self.options.color_output {
+                    println!("{}", msg.blue());
+                } else {
+                    println!("{}", msg);
+                }
+            }
+            timer.checkpoint(msg)
+        };
+        
+        if self.options.target.is_some() {
+            return Err(anyhow::anyhow!("Bundles contain the interpreter running them, so they cannot be cross-compiled with --target"));
+        }
+        if self.options.crate_type != CrateType::Bin {
+            return Err(anyhow::anyhow!("Only executables can be bundled, not libraries"));
+        }
+        
+        // Steps 1 and 2: Tokenize the source code and parse the tokens into an AST
+        log_msg("Parsing source code...", &mut timer);
+        
+        let program = parse_source(source, filename, false)?;
+        
+        // Step 3: Append the program to a copy of the interpreter
+        log_msg("Bundling program with the interpreter...", &mut timer);
+        
+        let runtime = std::env::current_exe()
+            .context("Failed to find the shitrust executable")?;
+        crate::bundle::write_bundle(&program, &runtime, output_path)
+            .context("Failed to write the bundle")?;
+        
+        if self.options.verbose || self.options.show_timings {
             if self.options.color_output {
-                println!("{}", "Tokenizing source code...".blue());
+                println!("{} {}", "Successfully bundled to:".green().bold(), 
+                       output_path.display().to_string().cyan());
             } else {
-                println!("Tokenizing source code...");
+                println!("Successfully bundled to: {}", output_path.display());
             }
         }
         
-        let mut lexer = if let Some(filename) = &filename {
-            Lexer::with_filename(source, filename.clone())
-        } else {
-            Lexer::new(source)
-        };
+        timer.report_total(true);
         
-        let tokens = lexer.scan_tokens()
-            .context("Failed during lexical analysis")?;
+        Ok(())
+    }
+    
+    /// Write a static library holding the program's object code and the runtime library it needs
+    fn write_static_library(&self, obj_path: &Path, path: &Path) -> Result<()> {
+        let runtime_lib = crate::runtime::runtime_library(self.options.target.as_deref())
+            .context("Failed to build the runtime library")?;
+        
+        // Add the object to a copy of the runtime archive
+        fs::copy(&runtime_lib, path)
+            .with_context(|| format!("Failed to copy the runtime library to {}", path.display()))?;
+        let ar_cmd = std::env::var("AR").unwrap_or_else(|_| "ar".to_string());
+        let status = Command::new(&ar_cmd)
+            .arg("rs")
+            .arg(path)
+            .arg(obj_path)
+            .status()
+            .with_context(|| format!("Failed to run archiver ({}). Is it installed on your system?", ar_cmd))?;
         
-        timer.checkpoint("Tokenization completed");
+        if !status.success() {
+            return Err(anyhow::anyhow!("Creating the static library failed with status: {}", status));
+        }
+        Ok(())
+    }
+    
+    /// The build cache key of the module `program`: a hash of its serialized AST and every option
+    /// that changes its object file
+    fn cache_key(&self, program: &Program) -> Result<String> {
+        let options = &self.options;
+        let codegen_options = format!(
+            "{:?} {:?} {:?} {:?} {:?} {:?} {}",
+            options.optimization_level,
+            options.target,
+            options.target_cpu,
+            options.target_features,
+            options.relocation_model,
+            options.crate_type,
+            options.emit_debug_info,
+        );
+        let ast = serde_json::to_string(program)
+            .context("Failed to serialize the program for the build cache")?;
+        Ok(BuildCache::key(&[env!("CARGO_PKG_VERSION"), &codegen_options, &ast]))
+    }
+    
+    /// Run the source code using the interpreter
+    pub fn run(&self, source: &str) -> Result<()> {
+        self.run_with_filename(source, None)
+    }
+    
+    /// Run the source code with a filename for better error reporting
+    pub fn run_with_filename(&self, source: &str, filename: Option<String>) -> Result<()> {
+        let mut timer = CompilationTimer::new(
+            self.options.verbose || self.options.show_timings,
+            self.options.color_output
+        );
         
-        // Step 2: Parse the tokens into an AST
+        // Steps 1 and 2: Tokenize the source code and parse the tokens into an AST
         if self.options.verbose {
             if self.options.color_output {
-                println!("{}", "Parsing tokens into AST...".blue());
+                println!("{}", "Parsing source code...".blue());
             } else {
-                println!("Parsing tokens into AST...");
+                println!("Parsing source code...");
             }
         }
         
-        let mut parser = Parser::new(tokens);
-        let program = if let Some(filename) = filename {
-            let statements = parser.parse()
-                .context("Failed during parsing")?
-                .statements;
-            crate::ast::Program::with_source(statements, filename)
-        } else {
-            parser.parse()
-                .context("Failed during parsing")?
-        };
+        let program = parse_source(source, filename, false)?;
         
         timer.checkpoint("Parsing completed");
         
@@ -381,27 +661,107 @@ impl Compiler {
         Ok(())
     }
 
-    /// Run a ShitRust program with asynchronous support from source string with an optional filename
-    pub fn run_async_with_filename(&self, source: &str, filename: Option<String>) -> Result<()> {
-        let start_time = std::time::Instant::now();
+    /// Run the source code by JIT-compiling it in memory, returning the exit code of `main`.
+    /// Programs that use features the code generator does not support yet are interpreted instead.
+    pub fn run_jit_with_filename(&self, source: &str, filename: Option<String>) -> Result<i32> {
+        let mut timer = CompilationTimer::new(
+            self.options.verbose || self.options.show_timings,
+            self.options.color_output
+        );
+        
+        let log_msg = |msg: &str, timer: &mut CompilationTimer| {
+            if self.options.verbose {
+                if self.options.color_output {
+                    println!("{}", msg.blue());
+                } else {
+                    println!("{}", msg);
+                }
+            }
+            timer.checkpoint(msg)
+        };
         
-        // Create lexer
-        let mut lexer = Lexer::with_filename(source, filename.unwrap_or_else(|| "unknown".to_string()));
+        // Steps 1 and 2: Tokenize the source code and parse the tokens into an AST
+        log_msg("Parsing source code...", &mut timer);
         
-        // Show timing if requested
-        if self.options.show_timings {
-            println!("{}: {:.2?}", "Lexing time".yellow().bold(), start_time.elapsed());
+        let program = parse_source(source, filename.clone(), false)?;
+        
+        // Step 3: Generate LLVM IR code. Functions the code generator cannot lower are left to
+        // the interpreter; if the top-level statements cannot be, the whole program is.
+        log_msg("Generating LLVM IR code...", &mut timer);
+        
+        let context = inkwell::context::Context::create();
+        let mut code_gen = CodeGen::new(&context, "shitrust_module");
+        code_gen.set_optimization_level(self.options.optimization_level);
+        code_gen.enable_interpreter_fallback();
+        
+        let generated = type_check(&program).and_then(|(program, instantiations)| {
+            code_gen.set_instantiations(&instantiations);
+            code_gen.generate_code(&program)
+        });
+        // The notices go to stderr, so they never mix with the program's output
+        if let Err(e) = generated {
+            if self.options.color_output {
+                eprintln!("{} {}", "Cannot JIT-compile this program, interpreting it instead:".yellow(), e);
+            } else {
+                eprintln!("Cannot JIT-compile this program, interpreting it instead: {}", e);
+            }
+            
+            self.run_with_filename(source, filename)?;
+            return Ok(0);
+        }
+        
+        let interpreted = code_gen.interpreted_functions().to_vec();
+        if !interpreted.is_empty() {
+            let names: Vec<&str> = interpreted.iter().map(|function| function.name.as_str()).collect();
+            let notice = format!("Cannot JIT-compile {}, interpreting them instead", names.join(", "));
+            if self.options.color_output {
+                eprintln!("{}", notice.yellow());
+            } else {
+                eprintln!("{}", notice);
+            }
         }
+        let mut interpreter = Interpreter::new();
+        interpreter.declare(&program)
+            .context("Failed to prepare the interpreted functions")?;
+        
+        // Step 4: Optimize and run
+        log_msg("Optimizing LLVM IR...", &mut timer);
+        
+        code_gen.optimize()
+            .context("Failed during optimization")?;
+        
+        log_msg("Running JIT-compiled code...", &mut timer);
+        
+        JIT_INTERPRETER.with(|state| *state.borrow_mut() = Some((interpreter, interpreted)));
+        let exit_code = code_gen.run_jit(interpret_function as usize);
+        JIT_INTERPRETER.with(|state| *state.borrow_mut() = None);
+        let exit_code = exit_code
+            .context("Failed during JIT execution")?;
+        
+        timer.checkpoint("Execution completed");
         
-        // Scan tokens
-        let tokens = lexer.scan_tokens()?;
+        // Report total execution time if enabled
+        if self.options.show_timings {
+            let total = timer.total();
+            let time_str = format!("{:.3}s", total.as_secs_f64());
+            
+            if self.options.color_output {
+                println!("{} in {}", "Program execution completed".green().bold(), time_str.yellow());
+            } else {
+                println!("Program execution completed in {}", time_str);
+            }
+        }
         
-        // Create parser
-        let mut parser = Parser::new(tokens);
+        Ok(exit_code)
+    }
+    
+    /// Run a ShitRust program with asynchronous support from source string with an optional filename
+    pub fn run_async_with_filename(&self, source: &str, filename: Option<String>) -> Result<()> {
+        let start_time = std::time::Instant::now();
         
-        // Parse into AST
+        // Tokenize and parse into AST
         let parse_start = std::time::Instant::now();
-        let program = parser.parse()?;
+        let program = parse_source(source, Some(filename.unwrap_or_else(|| "unknown".to_string())), false)?;
         
         // Show timing if requested
         if self.options.show_timings {
@@ -429,3 +789,221 @@ impl Compiler {
         Ok(())
     }
 } 
+
+thread_local! {
+    // The interpreter and the functions left to it while `run --jit` runs a program
+    static JIT_INTERPRETER: RefCell<Option<(Interpreter, Vec<ExternFunction>)>> = RefCell::new(None);
+}
+
+/// What `code_gen::INTERPRETER_ENTRY` resolves to in JIT-compiled programs: runs interpreted
+/// function `index` with the arguments in `args`, one 64-bit word each, and returns its result
+/// as a word. Errors end the program, as they do in compiled code.
+extern "C" fn interpret_function(index: i64, args: *const i64) -> i64 {
+    JIT_INTERPRETER.with(|state| {
+        let mut state = state.borrow_mut();
+        let (interpreter, functions) = state.as_mut().expect("no JIT-compiled program is running");
+        let function = &functions[index as usize];
+        
+        let args = function.params.iter().enumerate()
+            .map(|(i, (_, typ))| {
+                // The stub stores one word per parameter
+                let word = unsafe { *args.add(i) };
+                match typ {
+                    Type::Float => Value::Float(f64::from_bits(word as u64)),
+                    Type::Bool => Value::Bool(word != 0),
+                    _ => Value::Int(word),
+                }
+            })
+            .collect();
+        
+        match interpreter.call_named(&function.name, args) {
+            Ok(Value::Int(i)) => i,
+            Ok(Value::Float(f)) => f.to_bits() as i64,
+            Ok(Value::Bool(b)) => b as i64,
+            Ok(_) => 0,
+            Err(e) => {
+                eprintln!("{}", e.format_error());
+                std::process::exit(1);
+            },
+        }
+    })
+}
+
+/// Tokenize and parse a program. A program read from a file records its name, which syntax
+/// errors mention too; with `locations` each statement records where it starts, for debug info.
+pub fn parse_source(source: &str, filename: Option<String>, locations: bool) -> Result<Program> {
+    let mut lexer = match &filename {
+        Some(filename) => Lexer::with_filename(source, filename.clone()),
+        None => Lexer::new(source),
+    };
+    let tokens = lexer.scan_tokens()
+        .context("Failed during lexical analysis")?;
+    
+    let mut parser = if locations {
+        Parser::with_locations(tokens)
+    } else {
+        Parser::new(tokens)
+    };
+    let statements = parser.parse()
+        .context("Failed during parsing")?
+        .statements;
+    
+    Ok(match filename {
+        Some(filename) => Program::with_source(statements, filename),
+        None => Program::new(statements),
+    })
+}
+
+/// Type check a program for the code generator when it needs what the check infers: the type
+/// arguments generic functions and types are used with, to specialize them, and the return
+/// types of lambdas that declare none, which are written into a copy of the program. Programs
+/// without either are compiled without a type check, as they always were.
+fn type_check(program: &Program) -> crate::error::Result<(Cow<'_, Program>, HashMap<String, Vec<Vec<Type>>>)> {
+    let declares_generics = program.statements.iter().any(|stmt| match stmt.without_attributes() {
+        Stmt::Function { generic_params, .. }
+        | Stmt::Struct { generic_params, .. }
+        | Stmt::Enum { generic_params, .. } => !generic_params.is_empty(),
+        _ => false,
+    });
+    let mut unannotated_lambdas = false;
+    ast::walk_exprs(&program.statements, &mut |expr| {
+        unannotated_lambdas |= matches!(expr, Expr::Lambda { return_type: None, .. });
+    });
+    if !declares_generics && !unannotated_lambdas {
+        return Ok((Cow::Borrowed(program), HashMap::new()));
+    }
+    
+    let mut type_checker = TypeChecker::new();
+    if unannotated_lambdas {
+        type_checker.record_types(TypeRecording::Lambdas);
+    }
+    type_checker.check_program(&program.statements)?;
+    
+    let program = if unannotated_lambdas {
+        let mut annotated = program.clone();
+        type_checker.annotate_lambdas(&mut annotated.statements);
+        Cow::Owned(annotated)
+    } else {
+        Cow::Borrowed(program)
+    };
+    Ok((program, type_checker.instantiations().clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn ir(source: &str) -> String {
+        Compiler::new().llvm_ir_with_filename(source, None, false).unwrap()
+    }
+    
+    #[test]
+    fn generic_functions_are_specialized_for_each_use() {
+        let ir = ir("fn identity<T>(x: T) -> T { return x; }\n\
+                     let a = identity(1);\n\
+                     let b = identity(\"hi\");\n\
+                     let c = identity(2);\n");
+        
+        assert!(ir.contains("define i64 @\"identity<int>\""), "{}", ir);
+        assert!(ir.contains("@\"identity<string>\""), "{}", ir);
+        assert_eq!(ir.matches("define i64 @\"identity<int>\"").count(), 1);
+        assert!(!ir.contains("@identity("));
+    }
+    
+    #[test]
+    fn generic_structs_are_specialized_for_each_use() {
+        let ir = ir("struct Pair<T> { first: T, second: T }\n\
+                     let ints = Pair { first: 1, second: 2 };\n\
+                     let floats = Pair { first: 1.5, second: 2.5 };\n");
+        
+        assert!(ir.contains("%\"Pair<int>\" = type { i64, i64 }"), "{}", ir);
+        assert!(ir.contains("%\"Pair<float>\" = type { double, double }"), "{}", ir);
+    }
+    
+    fn library(source: &str) -> Result<String> {
+        let options = CompilerOptions { crate_type: CrateType::Cdylib, ..CompilerOptions::default() };
+        let program = parse_source(source, None, false)?;
+        let context = Context::create();
+        let code_gen = Compiler::with_options(options).generate_module(&context, &program)?;
+        Ok(code_gen.c_header("libgeometry"))
+    }
+    
+    #[test]
+    fn exported_functions_are_declared_in_the_header() {
+        let header = library("#[export]\npub fn area(width: float, height: float) -> float { return width * height; }\n\
+                              #[export]\npub fn reset() { }\n\
+                              #[export]\npub fn wrap(p: ptr, flag: bool) -> ptr { return p; }\n\
+                              pub fn hidden() -> int { return 1; }\n").unwrap();
+        
+        assert!(header.contains("#ifndef LIBGEOMETRY_H"));
+        assert!(header.contains("double area(double width, double height);"), "{}", header);
+        assert!(header.contains("void reset(void);"), "{}", header);
+        assert!(header.contains("void *wrap(void *p, bool flag);"), "{}", header);
+        assert!(!header.contains("hidden"));
+    }
+    
+    #[test]
+    fn exports_must_be_public_and_use_c_types() {
+        assert!(library("#[export]\nfn area(width: int) -> int { return width; }").is_err());
+        assert!(library("#[export]\npub fn name() -> string { return \"x\"; }").is_err());
+        assert!(library("#[export]\npub fn sum(values: [int]) -> int { return 0; }").is_err());
+        assert!(library("#[export]\npub fn identity<T>(x: T) -> T { return x; }").is_err());
+    }
+    
+    #[test]
+    fn library_file_names_follow_the_target() {
+        assert_eq!(CrateType::Cdylib.output_file_name("geometry", Some("x86_64-unknown-linux-gnu")), "libgeometry.so");
+        assert_eq!(CrateType::Cdylib.output_file_name("geometry", Some("aarch64-apple-darwin")), "libgeometry.dylib");
+        assert_eq!(CrateType::Cdylib.output_file_name("geometry", Some("x86_64-pc-windows-msvc")), "geometry.dll");
+        assert_eq!(CrateType::Staticlib.output_file_name("geometry", Some("x86_64-pc-windows-msvc")), "geometry.lib");
+        assert_eq!(CrateType::Staticlib.output_file_name("geometry", Some("x86_64-pc-windows-gnu")), "libgeometry.a");
+    }
+    
+    #[test]
+    fn cache_keys_follow_the_program_and_codegen_options() {
+        let program = parse_source("let x = 1;", None, false).unwrap();
+        let key = |options: CompilerOptions, program: &Program| Compiler::with_options(options).cache_key(program).unwrap();
+        let base = key(CompilerOptions::default(), &program);
+        
+        assert_eq!(base, key(CompilerOptions::default(), &parse_source("let x = 1;", None, false).unwrap()));
+        assert_ne!(base, key(CompilerOptions::default(), &parse_source("let x = 2;", None, false).unwrap()));
+        assert_ne!(base, key(CompilerOptions { optimization_level: OptimizationLevel::None, ..CompilerOptions::default() }, &program));
+        assert_ne!(base, key(CompilerOptions { crate_type: CrateType::Staticlib, ..CompilerOptions::default() }, &program));
+        assert_ne!(base, key(CompilerOptions { target: Some("aarch64-unknown-linux-gnu".to_string()), ..CompilerOptions::default() }, &program));
+        
+        // Options that do not change the object file share the entry
+        assert_eq!(base, key(CompilerOptions { verbose: true, ..CompilerOptions::default() }, &program));
+    }
+    
+    #[test]
+    fn structs_are_lowered_to_named_aggregates() {
+        let ir = ir("struct Point { x: int, y: float }\nlet p = Point { x: 1, y: 2.5 };\nprintln(p.x);\n");
+        assert!(ir.contains("%Point = type { i64, double }"), "{}", ir);
+    }
+    
+    #[test]
+    fn void_calls_cannot_be_used_as_values() {
+        let source = "fn log(message: string) { println(message); }\nlog(\"ok\");\nlet x = log(\"value\");\n";
+        let error = Compiler::new().llvm_ir_with_filename(source, None, false).unwrap_err();
+        assert!(format!("{:#}", error).contains("Cannot use void function 'log' in an expression"), "{:#}", error);
+        
+        ir("fn log(message: string) { println(message); }\nlog(\"ok\");\n");
+    }
+    
+    #[test]
+    fn int_methods_are_lowered_with_overflow_checks() {
+        let ir = ir("fn step(x: int) -> int {\n\
+                         let next = x.checked_add(1) ?? 0;\n\
+                         return next.wrapping_div(3) + next.saturating_pow(2) + (x.checked_rem(0) ?? 1);\n\
+                     }\n\
+                     println(step(41));\n");
+        
+        assert!(ir.contains("%\"shitrust.Option<i64>\" = type { i1, i64 }"), "{}", ir);
+        assert!(ir.contains("@llvm.sadd.with.overflow.i64"), "{}", ir);
+        assert!(ir.contains("@llvm.smul.with.overflow.i64"), "{}", ir);
+        assert!(ir.contains("Division by zero"), "{}", ir);
+        assert!(ir.contains("Integer overflow in addition"), "{}", ir);
+        assert!(ir.contains("call void @shitrust_fail"), "{}", ir);
+        assert!(!ir.contains("@llvm.trap"), "{}", ir);
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use serde_json::{Map, Value};
+use crate::ast::{self, Expr, Literal, OptionalChainItem, Program, Stmt, Type};
+use crate::error::{Result, ShitRustError};
+use crate::lexer::Token;
+use crate::type_system::TypeChecker;
+
+/// How `shitrust dump` prints a stage
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DumpFormat {
+    /// One token per line, or an indented tree of the AST
+    Text,
+    /// The serialized tokens or AST
+    Json,
+}
+
+/// The token stream, one `line:column kind lexeme` per line
+pub fn dump_tokens(tokens: &[Token], format: DumpFormat) -> Result<String> {
+    if format == DumpFormat::Json {
+        return to_json(&tokens);
+    }
+
+    let mut out = String::new();
+    for token in tokens {
+        let location = format!("{}:{}", token.line, token.column);
+        out.push_str(&format!("{:<8} {:<16} {}\n", location, format!("{:?}", token.token_type), token.lexeme));
+    }
+    Ok(out)
+}
+
+/// The program as the parser produced it
+pub fn dump_ast(program: &Program, format: DumpFormat) -> Result<String> {
+    match format {
+        DumpFormat::Text => Ok(render_tree(&to_value(program)?)),
+        DumpFormat::Json => to_json(program),
+    }
+}
+
+/// The program with the type `checker` inferred for each expression. The checker must have
+/// checked this program, or an unchanged copy of it, with `TypeRecording::All`; expressions
+/// checked more than once, as in generic functions, show their first type. Expressions get a
+/// `type` next to their kind, e.g.
+/// `{"Identifier": "x", "type": "Int"}`; those the checker never reached have none.
+pub fn dump_typed_ast(program: &Program, checker: &TypeChecker, format: DumpFormat) -> Result<String> {
+    // The checker knows expressions by id, their position in the walk order of the program
+    let mut types = ExprTypes::new();
+    let mut id = 0;
+    ast::walk_exprs(&program.statements, &mut |expr| {
+        if let Some(typ) = checker.recorded_type(id) {
+            types.insert(expr as *const Expr as usize, typ);
+        }
+        id += 1;
+    });
+
+    let mut value = to_value(program)?;
+    annotate_block(&types, &program.statements, &mut value["statements"]);
+    match format {
+        DumpFormat::Text => Ok(render_tree(&value)),
+        DumpFormat::Json => to_json(&value),
+    }
+}
+
+/// The types of a dumped program's expressions, by address
+type ExprTypes<'a> = HashMap<usize, &'a Type>;
+
+fn to_value<T: serde::Serialize>(value: &T) -> Result<Value> {
+    serde_json::to_value(value)
+        .map_err(|e| ShitRustError::RuntimeError(format!("Failed to serialize the program: {}", e)))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value)
+        .map(|json| json + "\n")
+        .map_err(|e| ShitRustError::RuntimeError(format!("Failed to serialize the program: {}", e)))
+}
+
+fn annotate_block(types: &ExprTypes, block: &[Stmt], value: &mut Value) {
+    if let Some(values) = value.as_array_mut() {
+        for (stmt, value) in block.iter().zip(values) {
+            annotate_stmt(types, stmt, value);
+        }
+    }
+}
+
+/// Annotates the expressions of `stmt`, whose JSON is `value`. Enums serialize as
+/// `{"Variant": fields}`, and unit variants as a plain string, which has nothing to annotate.
+fn annotate_stmt(types: &ExprTypes, stmt: &Stmt, value: &mut Value) {
+    let Some(fields) = variant_fields(value) else {
+        return;
+    };
+
+    match stmt {
+        Stmt::Expr(expr) | Stmt::Return(Some(expr)) => annotate_expr(types, expr, fields),
+        Stmt::Let { value, .. } | Stmt::Const { value, .. } => annotate_expr(types, value, &mut fields["value"]),
+        Stmt::Assign { target, value } => {
+            annotate_expr(types, target, &mut fields["target"]);
+            annotate_expr(types, value, &mut fields["value"]);
+        },
+        Stmt::If { condition, then_block, else_block } => {
+            annotate_expr(types, condition, &mut fields["condition"]);
+            annotate_block(types, then_block, &mut fields["then_block"]);
+            if let Some(else_block) = else_block {
+                annotate_block(types, else_block, &mut fields["else_block"]);
+            }
+        },
+        Stmt::While { condition, body } => {
+            annotate_expr(types, condition, &mut fields["condition"]);
+            annotate_block(types, body, &mut fields["body"]);
+        },
+        Stmt::For { iterator, body, .. } => {
+            annotate_expr(types, iterator, &mut fields["iterator"]);
+            annotate_block(types, body, &mut fields["body"]);
+        },
+        Stmt::Match { expr, arms } => {
+            annotate_expr(types, expr, &mut fields["expr"]);
+            for (i, (_, body)) in arms.iter().enumerate() {
+                annotate_block(types, body, &mut fields["arms"][i][1]);
+            }
+        },
+        Stmt::Function { body, .. } => annotate_block(types, body, &mut fields["body"]),
+        Stmt::Struct { methods, .. } | Stmt::Impl { methods, .. } => annotate_block(types, methods, &mut fields["methods"]),
+        Stmt::Trait { methods, .. } => {
+            for (i, method) in methods.iter().enumerate() {
+                if let Some(body) = &method.body {
+                    annotate_block(types, body, &mut fields["methods"][i]["body"]);
+                }
+            }
+        },
+        Stmt::Try { block, catch_blocks, finally_block } => {
+            annotate_block(types, block, &mut fields["block"]);
+            for (i, (_, body)) in catch_blocks.iter().enumerate() {
+                annotate_block(types, body, &mut fields["catch_blocks"][i][1]);
+            }
+            if let Some(finally_block) = finally_block {
+                annotate_block(types, finally_block, &mut fields["finally_block"]);
+            }
+        },
+        Stmt::Async { block } => annotate_block(types, block, &mut fields["block"]),
+        Stmt::Loop { body } => annotate_block(types, body, &mut fields["body"]),
+        Stmt::Attributed { stmt, .. } | Stmt::Located { stmt, .. } => annotate_stmt(types, stmt, &mut fields["stmt"]),
+        _ => (),
+    }
+}
+
+fn annotate_exprs(types: &ExprTypes, exprs: &[Expr], value: &mut Value) {
+    for (i, expr) in exprs.iter().enumerate() {
+        annotate_expr(types, expr, &mut value[i]);
+    }
+}
+
+fn annotate_optional(types: &ExprTypes, expr: &Option<Box<Expr>>, value: &mut Value) {
+    if let Some(expr) = expr {
+        annotate_expr(types, expr, value);
+    }
+}
+
+/// Annotates `expr`, whose JSON is `value`, and the expressions in it
+fn annotate_expr(types: &ExprTypes, expr: &Expr, value: &mut Value) {
+    let Some(fields) = variant_fields(value) else {
+        return;
+    };
+
+    match expr {
+        Expr::Literal(literal) => annotate_literal(types, literal, fields),
+        Expr::Identifier(_) => (),
+        Expr::BinaryOp { left, right, .. } => {
+            annotate_expr(types, left, &mut fields["left"]);
+            annotate_expr(types, right, &mut fields["right"]);
+        },
+        Expr::UnaryOp { expr, .. } | Expr::Await { expr } | Expr::Try { expr }
+        | Expr::TypeCast { expr, .. } | Expr::TypeTest { expr, .. } => annotate_expr(types, expr, &mut fields["expr"]),
+        Expr::Call { func, args } => {
+            annotate_expr(types, func, &mut fields["func"]);
+            annotate_exprs(types, args, &mut fields["args"]);
+        },
+        Expr::MethodCall { object, args, .. } => {
+            annotate_expr(types, object, &mut fields["object"]);
+            annotate_exprs(types, args, &mut fields["args"]);
+        },
+        Expr::Index { target, index } => {
+            annotate_expr(types, target, &mut fields["target"]);
+            annotate_expr(types, index, &mut fields["index"]);
+        },
+        Expr::FieldAccess { object, .. } => annotate_expr(types, object, &mut fields["object"]),
+        Expr::Lambda { body, .. } => annotate_expr(types, body, &mut fields["body"]),
+        Expr::TernaryIf { condition, then_expr, else_expr } => {
+            annotate_expr(types, condition, &mut fields["condition"]);
+            annotate_expr(types, then_expr, &mut fields["then_expr"]);
+            annotate_expr(types, else_expr, &mut fields["else_expr"]);
+        },
+        Expr::RangeExpr { start, end, .. } => {
+            annotate_optional(types, start, &mut fields["start"]);
+            annotate_optional(types, end, &mut fields["end"]);
+        },
+        Expr::OptionalChain { expr, chain } => {
+            annotate_expr(types, expr, &mut fields["expr"]);
+            for (i, item) in chain.iter().enumerate() {
+                match item {
+                    OptionalChainItem::Field(_) => (),
+                    OptionalChainItem::Method(_, args) => annotate_exprs(types, args, &mut fields["chain"][i]["Method"][1]),
+                    OptionalChainItem::Index(index) => annotate_expr(types, index, &mut fields["chain"][i]["Index"]),
+                }
+            }
+        },
+        Expr::ListComprehension { expr, iterable, condition, .. } => {
+            annotate_expr(types, expr, &mut fields["expr"]);
+            annotate_expr(types, iterable, &mut fields["iterable"]);
+            annotate_optional(types, condition, &mut fields["condition"]);
+        },
+        Expr::Match { expr, arms } => {
+            annotate_expr(types, expr, &mut fields["expr"]);
+            for (i, (_, body)) in arms.iter().enumerate() {
+                annotate_expr(types, body, &mut fields["arms"][i][1]);
+            }
+        },
+        Expr::StructInit { fields: inits, .. } => {
+            for (i, (_, init)) in inits.iter().enumerate() {
+                annotate_expr(types, init, &mut fields["fields"][i][1]);
+            }
+        },
+        Expr::PipelineChain { initial, chain } => {
+            annotate_expr(types, initial, &mut fields["initial"]);
+            for (i, stage) in chain.iter().enumerate() {
+                annotate_expr(types, stage, &mut fields["chain"][i]);
+            }
+        },
+        Expr::Block(block) => annotate_block(types, block, fields),
+    }
+
+    if let (Some(typ), Value::Object(object)) = (types.get(&(expr as *const Expr as usize)), value) {
+        object.insert("type".to_string(), Value::String(format!("{:?}", typ)));
+    }
+}
+
+fn annotate_literal(types: &ExprTypes, literal: &Literal, value: &mut Value) {
+    let Some(fields) = variant_fields(value) else {
+        return;
+    };
+
+    match literal {
+        Literal::List(items) | Literal::Tuple(items) => annotate_exprs(types, items, fields),
+        Literal::Dict(entries) => {
+            for (i, (key, value)) in entries.iter().enumerate() {
+                annotate_expr(types, key, &mut fields[i][0]);
+                annotate_expr(types, value, &mut fields[i][1]);
+            }
+        },
+        Literal::Range { start, end, .. } => {
+            annotate_optional(types, start, &mut fields["start"]);
+            annotate_optional(types, end, &mut fields["end"]);
+        },
+        _ => (),
+    }
+}
+
+/// The fields of an enum variant serialized as `{"Variant": fields}`
+fn variant_fields(value: &mut Value) -> Option<&mut Value> {
+    value.as_object_mut()?.values_mut().next()
+}
+
+/// Renders serialized AST as an indented tree. Objects with a single key (besides `type`) are
+/// enum variants and print as their name; short values print on one line, like `List(Int)`.
+fn render_tree(value: &Value) -> String {
+    let mut out = String::new();
+    render_children(&mut out, "Program".to_string(), value, 0);
+    out
+}
+
+/// Prints `value` as a field named `label`, as a list item when `label` is `-`, or on its own
+/// when `label` is empty
+fn render_node(out: &mut String, label: &str, value: &Value, depth: usize) {
+    let prefix = match label {
+        "" => "  ".repeat(depth),
+        "-" => format!("{}- ", "  ".repeat(depth)),
+        label => format!("{}{}: ", "  ".repeat(depth), label),
+    };
+
+    // Typed expressions print their type after them
+    let (value, suffix) = match value {
+        Value::Object(object) if object.contains_key("type") => {
+            let typ = object["type"].as_str().unwrap_or_default();
+            let mut untyped = object.clone();
+            untyped.remove("type");
+            (Value::Object(untyped), format!(" : {}", typ))
+        },
+        value => (value.clone(), String::new()),
+    };
+
+    if let Some(inline) = render_inline(&value) {
+        out.push_str(&format!("{}{}{}\n", prefix, inline, suffix));
+        return;
+    }
+
+    match &value {
+        Value::Object(object) => match variant(object) {
+            Some((name, fields)) => render_children(out, format!("{}{}{}", prefix, name, suffix), fields, depth),
+            None => render_children(out, prefix.trim_end().to_string(), &value, depth),
+        },
+        _ => render_children(out, prefix.trim_end().to_string(), &value, depth),
+    }
+}
+
+/// Prints `header` and then `value`'s fields or items, one level deeper
+fn render_children(out: &mut String, header: String, value: &Value, depth: usize) {
+    match value {
+        // The variant of a newtype variant, like the `Call` of `Expr(Call { .. })`
+        Value::Object(object) if variant(object).is_some() => {
+            out.push_str(&header);
+            out.push('\n');
+            render_node(out, "", value, depth + 1);
+        },
+        Value::Object(object) => {
+            out.push_str(&header);
+            out.push('\n');
+            for (key, field) in object {
+                render_node(out, key, field, depth + 1);
+            }
+        },
+        Value::Array(items) => {
+            out.push_str(&header);
+            out.push('\n');
+            for item in items {
+                render_node(out, "-", item, depth + 1);
+            }
+        },
+        scalar => {
+            let inline = render_inline(scalar).unwrap_or_default();
+            out.push_str(&format!("{}({})\n", header, inline));
+        },
+    }
+}
+
+/// The enum variant an object serializes, as its name and fields. Variant names are
+/// capitalized, which tells them from structs with a single field.
+fn variant(object: &Map<String, Value>) -> Option<(&str, &Value)> {
+    let mut keys = object.iter().filter(|(key, _)| *key != "type");
+    let (name, fields) = keys.next()?;
+    match keys.next() {
+        None if name.starts_with(|c: char| c.is_ascii_uppercase()) => Some((name.as_str(), fields)),
+        _ => None,
+    }
+}
+
+/// `value` on one line, if it is short and has no types to show
+fn render_inline(value: &Value) -> Option<String> {
+    const MAX_INLINE: usize = 60;
+
+    let inline = match value {
+        Value::Null => "none".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) if s.is_empty() => "\"\"".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) if items.is_empty() => "[]".to_string(),
+        Value::Array(items) => {
+            let items = items.iter().map(render_inline).collect::<Option<Vec<_>>>()?;
+            format!("[{}]", items.join(", "))
+        },
+        Value::Object(object) => {
+            if object.contains_key("type") {
+                return None;
+            }
+            let (name, fields) = variant(object)?;
+            match fields {
+                // Tuple variants list their values, like `Dict(String, Int)`
+                Value::Array(items) if !items.is_empty() => {
+                    let items = items.iter().map(render_inline).collect::<Option<Vec<_>>>()?;
+                    format!("{}({})", name, items.join(", "))
+                },
+                fields => format!("{}({})", name, render_inline(fields)?),
+            }
+        },
+    };
+
+    (inline.len() <= MAX_INLINE && !inline.contains('\n')).then_some(inline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::type_system::TypeRecording;
+
+    fn parse(source: &str) -> Program {
+        let tokens = crate::lexer::Lexer::new(source).scan_tokens().unwrap();
+        crate::parser::Parser::new(tokens).parse().unwrap()
+    }
+
+    fn typed_json(program: &Program, recording: TypeRecording) -> Value {
+        let mut checker = TypeChecker::new();
+        checker.record_types(recording);
+        checker.check_program(&program.statements).unwrap();
+        serde_json::from_str(&dump_typed_ast(program, &checker, DumpFormat::Json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn expressions_get_their_types() {
+        let program = parse("let x = [1.5];");
+        let json = typed_json(&program, TypeRecording::All);
+
+        let value = &json["statements"][0]["Let"]["value"];
+        assert_eq!(value["type"], "List(Float)");
+        assert_eq!(value["Literal"]["List"][0]["type"], "Float");
+    }
+
+    #[test]
+    fn copies_of_the_checked_program_get_the_same_types() {
+        let program = parse("let x = 1;");
+        let mut checker = TypeChecker::new();
+        checker.record_types(TypeRecording::All);
+        checker.check_program(&program.statements).unwrap();
+
+        let copy = program.clone();
+        let json: Value = serde_json::from_str(&dump_typed_ast(&copy, &checker, DumpFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["statements"][0]["Let"]["value"]["type"], "Int");
+    }
+
+    #[test]
+    fn types_are_only_recorded_for_dumps() {
+        let program = parse("let x = 1;");
+        let json = typed_json(&program, TypeRecording::None);
+        assert!(json["statements"][0]["Let"]["value"].get("type").is_none());
+    }
+}
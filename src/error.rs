@@ -79,6 +79,9 @@ pub enum ShitRustError {
     
     #[error("Timeout error: {0}")]
     TimeoutError(String),
+    
+    #[error("Lint error: {0}")]
+    LintError(String),
 }
 
 impl ShitRustError {
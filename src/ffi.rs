@@ -0,0 +1,329 @@
+use std::ffi::{c_void, CStr, CString};
+use std::fmt;
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+use std::sync::Arc;
+
+use libffi::middle::{arg, Arg, Cif, CodePtr, Type as FfiType};
+use libloading::Library;
+
+use crate::ast::{ExternFunction, Type};
+use crate::error::ShitRustError;
+use crate::interpreter::Value;
+
+/// A function of a C library, called by the interpreter through libffi
+pub struct ForeignFunction {
+    pub name: String,
+    params: Vec<Type>,
+    return_type: Type,
+    // The function's address. Kept as an integer, like pointers in the interpreter, so
+    // values holding the function can move between threads.
+    address: usize,
+    // Keeps the library loaded while the function can still be called
+    _library: Arc<Library>,
+}
+
+impl fmt::Debug for ForeignFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForeignFunction")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .field("return_type", &self.return_type)
+            .finish()
+    }
+}
+
+/// The libraries the functions of an `extern` block are looked up in
+pub struct ForeignLibraries {
+    libraries: Vec<Arc<Library>>,
+    // Libraries that could not be opened and why, reported when a function is not found
+    failures: Vec<String>,
+}
+
+impl ForeignLibraries {
+    /// Opens the libraries named by `#[link(...)]`, then the running program itself, which
+    /// has the C library loaded. A library that fails to open is only an error if one of
+    /// the block's functions cannot be found elsewhere.
+    pub fn open(names: &[String]) -> Result<Self, ShitRustError> {
+        let mut libraries = Vec::new();
+        let mut failures = Vec::new();
+        
+        for name in names {
+            // Opening a library runs its initializers, as linking against it would
+            match unsafe { Library::new(library_file_name(name)) } {
+                Ok(library) => libraries.push(Arc::new(library)),
+                Err(e) => failures.push(format!("{}: {}", name, e)),
+            }
+        }
+        libraries.push(Arc::new(this_process()?));
+        
+        Ok(ForeignLibraries { libraries, failures })
+    }
+    
+    /// Finds the function declared by `function` in the first library that has it
+    pub fn lookup(&self, function: &ExternFunction) -> Result<ForeignFunction, ShitRustError> {
+        for (_, typ) in &function.params {
+            ffi_type(typ, &function.name)?;
+        }
+        if function.return_type != Type::Void {
+            ffi_type(&function.return_type, &function.name)?;
+        }
+        
+        let symbol = format!("{}\0", function.name);
+        for library in &self.libraries {
+            // The symbol is only read as an address, the declaration says how to call it
+            let Ok(address) = (unsafe { library.get::<unsafe extern "C" fn()>(symbol.as_bytes()) }) else {
+                continue;
+            };
+            
+            return Ok(ForeignFunction {
+                name: function.name.clone(),
+                params: function.params.iter().map(|(_, typ)| typ.clone()).collect(),
+                return_type: function.return_type.clone(),
+                address: *address as usize,
+                _library: Arc::clone(library),
+            });
+        }
+        
+        let mut message = format!("Cannot find C function '{}'", function.name);
+        if !self.failures.is_empty() {
+            message.push_str(&format!(" (failed to load {})", self.failures.join("; ")));
+        }
+        Err(ShitRustError::RuntimeError(message))
+    }
+}
+
+impl ForeignFunction {
+    /// Calls the function, converting the arguments to C values and the result back
+    pub fn call(&self, args: Vec<Value>) -> Result<Value, ShitRustError> {
+        if args.len() != self.params.len() {
+            return Err(ShitRustError::RuntimeError(
+                format!("Expected {} arguments but got {}", self.params.len(), args.len())
+            ));
+        }
+        
+        // libffi takes the addresses of the arguments, so they are all converted up front
+        let values = args.into_iter()
+            .zip(&self.params)
+            .map(|(value, typ)| CValue::new(value, typ, &self.name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let args: Vec<Arg> = values.iter().map(CValue::arg).collect();
+        
+        let params = self.params.iter()
+            .map(|typ| ffi_type(typ, &self.name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let result = match &self.return_type {
+            Type::Void => FfiType::void(),
+            typ => ffi_type(typ, &self.name)?,
+        };
+        let cif = Cif::new(params, result);
+        let code = CodePtr::from_ptr(self.address as *const c_void);
+        
+        // The call matches the declaration in the extern block; whether that matches the
+        // C function is up to the program, as it is in C
+        unsafe {
+            match &self.return_type {
+                Type::Void => {
+                    cif.call::<()>(code, &args);
+                    Ok(Value::None)
+                },
+                Type::Int => Ok(Value::Int(cif.call::<i64>(code, &args))),
+                Type::CInt => Ok(Value::Int(cif.call::<c_int>(code, &args) as i64)),
+                Type::Float => Ok(Value::Float(cif.call::<f64>(code, &args))),
+                Type::Bool => Ok(Value::Bool(cif.call::<u8>(code, &args) != 0)),
+                Type::Pointer => Ok(Value::Int(cif.call::<*mut c_void>(code, &args) as i64)),
+                Type::String => {
+                    // A null string becomes an empty one; the C side keeps ownership either way
+                    let string = cif.call::<*const c_char>(code, &args);
+                    if string.is_null() {
+                        Ok(Value::String(String::new()))
+                    } else {
+                        Ok(Value::String(CStr::from_ptr(string).to_string_lossy().into_owned()))
+                    }
+                },
+                typ => unreachable!("{:?} is rejected when the function is looked up", typ),
+            }
+        }
+    }
+}
+
+// An argument as C sees it. Strings keep their copy alive next to the pointer passed to C.
+enum CValue {
+    Int(i64),
+    CInt(c_int),
+    Float(f64),
+    Bool(u8),
+    Pointer(*const c_void),
+    String(CString, *const c_char),
+}
+
+impl CValue {
+    fn new(value: Value, typ: &Type, function: &str) -> Result<Self, ShitRustError> {
+        match (value, typ) {
+            (Value::Int(i), Type::Int) => Ok(CValue::Int(i)),
+            // Like a conversion to `int` in C, only the low 32 bits are kept
+            (Value::Int(i), Type::CInt) => Ok(CValue::CInt(i as c_int)),
+            (Value::Float(f), Type::Float) => Ok(CValue::Float(f)),
+            (Value::Bool(b), Type::Bool) => Ok(CValue::Bool(b as u8)),
+            (Value::Int(address), Type::Pointer) => Ok(CValue::Pointer(address as usize as *const c_void)),
+            (Value::String(s), Type::String) => {
+                let string = CString::new(s).map_err(|_| ShitRustError::RuntimeError(
+                    format!("Cannot pass a string containing a NUL character to C function '{}'", function)
+                ))?;
+                let pointer = string.as_ptr();
+                Ok(CValue::String(string, pointer))
+            },
+            (value, typ) => Err(ShitRustError::TypeError(
+                format!("Cannot pass {} as {:?} to C function '{}'", value.type_name(), typ, function)
+            )),
+        }
+    }
+    
+    fn arg(&self) -> Arg {
+        match self {
+            CValue::Int(i) => arg(i),
+            CValue::CInt(i) => arg(i),
+            CValue::Float(f) => arg(f),
+            CValue::Bool(b) => arg(b),
+            CValue::Pointer(p) => arg(p),
+            CValue::String(_, pointer) => arg(pointer),
+        }
+    }
+}
+
+fn ffi_type(typ: &Type, function: &str) -> Result<FfiType, ShitRustError> {
+    match typ {
+        Type::Int => Ok(FfiType::i64()),
+        Type::CInt => Ok(FfiType::c_int()),
+        Type::Float => Ok(FfiType::f64()),
+        Type::Bool => Ok(FfiType::u8()),
+        Type::String | Type::Pointer => Ok(FfiType::pointer()),
+        _ => Err(ShitRustError::TypeError(
+            format!("C function '{}' uses {:?}, but only int, c_int, float, bool, string and ptr can be passed to C", function, typ)
+        )),
+    }
+}
+
+/// The file a `#[link(...)]` name refers to: paths and file names are used as they are,
+/// bare names get the platform's prefix and suffix, e.g. `sqlite3` is `libsqlite3.so` on Linux
+pub fn library_file_name(name: &str) -> String {
+    if name.contains('/') || name.contains('\\') || Path::new(name).extension().is_some() {
+        name.to_string()
+    } else {
+        format!("{}{}{}", std::env::consts::DLL_PREFIX, name, std::env::consts::DLL_SUFFIX)
+    }
+}
+
+#[cfg(unix)]
+fn this_process() -> Result<Library, ShitRustError> {
+    Ok(libloading::os::unix::Library::this().into())
+}
+
+#[cfg(windows)]
+fn this_process() -> Result<Library, ShitRustError> {
+    libloading::os::windows::Library::this()
+        .map(Into::into)
+        .map_err(|e| ShitRustError::RuntimeError(format!("Failed to look up the program's own symbols: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn lookup(name: &str, params: Vec<Type>, return_type: Type) -> ForeignFunction {
+        let declaration = ExternFunction {
+            name: name.to_string(),
+            params: params.into_iter().enumerate().map(|(i, typ)| (format!("p{}", i), typ)).collect(),
+            return_type,
+        };
+        ForeignLibraries::open(&[]).unwrap().lookup(&declaration).unwrap()
+    }
+    
+    #[test]
+    fn c_int_results_are_sign_extended() {
+        let abs = lookup("abs", vec![Type::CInt], Type::CInt);
+        assert!(matches!(abs.call(vec![Value::Int(-5)]), Ok(Value::Int(5))));
+        
+        let strcmp = lookup("strcmp", vec![Type::String, Type::String], Type::CInt);
+        match strcmp.call(vec![Value::String("a".to_string()), Value::String("b".to_string())]) {
+            Ok(Value::Int(order)) => assert!(order < 0, "strcmp gave {}", order),
+            other => panic!("strcmp gave {:?}", other.map(|value| value.type_name())),
+        }
+    }
+    
+    #[test]
+    fn c_int_arguments_keep_the_low_32_bits() {
+        let abs = lookup("abs", vec![Type::CInt], Type::CInt);
+        assert!(matches!(abs.call(vec![Value::Int((1 << 32) - 7)]), Ok(Value::Int(7))));
+    }
+    
+    #[test]
+    fn strings_and_ints_cross_into_c() {
+        let strlen = lookup("strlen", vec![Type::String], Type::Int);
+        assert!(matches!(strlen.call(vec![Value::String("hello".to_string())]), Ok(Value::Int(5))));
+        assert!(strlen.call(vec![Value::String("a\0b".to_string())]).is_err());
+        assert!(strlen.call(vec![Value::Int(1)]).is_err());
+    }
+    
+    #[test]
+    fn unsupported_types_are_rejected_on_lookup() {
+        let declaration = ExternFunction {
+            name: "abs".to_string(),
+            params: vec![("x".to_string(), Type::List(Box::new(Type::Int)))],
+            return_type: Type::Int,
+        };
+        assert!(ForeignLibraries::open(&[]).unwrap().lookup(&declaration).is_err());
+    }
+    
+    #[test]
+    fn library_names_get_the_platform_affixes() {
+        assert_eq!(library_file_name("./libfoo.so"), "./libfoo.so");
+        assert_eq!(library_file_name("foo.dll"), "foo.dll");
+        assert_eq!(
+            library_file_name("sqlite3"),
+            format!("{}sqlite3{}", std::env::consts::DLL_PREFIX, std::env::consts::DLL_SUFFIX)
+        );
+    }
+    
+    #[test]
+    fn floats_pointers_and_returned_strings_cross_back() {
+        let strtod = lookup("strtod", vec![Type::String, Type::Pointer], Type::Float);
+        match strtod.call(vec![Value::String("2.5".to_string()), Value::Int(0)]) {
+            Ok(Value::Float(f)) => assert_eq!(f, 2.5),
+            other => panic!("strtod gave {:?}", other.map(|value| value.type_name())),
+        }
+        
+        let getenv = lookup("getenv", vec![Type::String], Type::Pointer);
+        assert!(matches!(getenv.call(vec![Value::String("SHITRUST_UNSET_VARIABLE".to_string())]), Ok(Value::Int(0))));
+        
+        // A null string comes back empty
+        let strchr = lookup("strchr", vec![Type::String, Type::CInt], Type::String);
+        assert!(matches!(strchr.call(vec![Value::String("hello".to_string()), Value::Int('l' as i64)]), Ok(Value::String(s)) if s == "llo"));
+        assert!(matches!(strchr.call(vec![Value::String("hello".to_string()), Value::Int('z' as i64)]), Ok(Value::String(s)) if s.is_empty()));
+    }
+    
+    #[test]
+    fn calls_need_every_argument() {
+        let abs = lookup("abs", vec![Type::CInt], Type::CInt);
+        assert!(abs.call(vec![]).is_err());
+        assert!(abs.call(vec![Value::Int(1), Value::Int(2)]).is_err());
+    }
+    
+    #[test]
+    fn missing_functions_mention_the_libraries_that_failed_to_load() {
+        let libraries = ForeignLibraries::open(&["./no_such_library.so".to_string()]).unwrap();
+        let declaration = ExternFunction {
+            name: "no_such_function".to_string(),
+            params: Vec::new(),
+            return_type: Type::Void,
+        };
+        
+        match libraries.lookup(&declaration) {
+            Err(ShitRustError::RuntimeError(message)) => {
+                assert!(message.contains("Cannot find C function 'no_such_function'"));
+                assert!(message.contains("./no_such_library.so"));
+            },
+            other => panic!("expected a missing function, got {:?}", other),
+        }
+    }
+}
@@ -57,6 +57,10 @@ impl Formatter {
     
     /// Format a statement and write to the output
     fn format_stmt(&mut self, stmt: &Stmt, output: &mut Vec<u8>) -> io::Result<()> {
+        if let Stmt::Located { stmt, .. } = stmt {
+            return self.format_stmt(stmt, output);
+        }
+        
         self.write_indent(output)?;
         
         match stmt {
@@ -141,6 +145,21 @@ impl Formatter {
                     writeln!(output, "}}")?;
                 }
             }
+            Stmt::Attributed { attributes, stmt } => {
+                for (i, attribute) in attributes.iter().enumerate() {
+                    if i > 0 {
+                        self.write_indent(output)?;
+                    }
+                    
+                    if attribute.args.is_empty() {
+                        writeln!(output, "#[{}]", attribute.name)?;
+                    } else {
+                        writeln!(output, "#[{}({})]", attribute.name, attribute.args.join(", "))?;
+                    }
+                }
+                
+                self.format_stmt(stmt, output)?;
+            }
             // Handle other statement types...
             _ => {
                 // Fallback for unimplemented statement types
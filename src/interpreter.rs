@@ -1,19 +1,30 @@
-use std::collections::HashMap;
-use crate::ast::{Program, Stmt, Expr, Literal, BinOp, UnaryOp, Pattern, OptionalChainItem, Type};
+use std::collections::{HashMap, HashSet};
+use crate::ast::{Program, Stmt, Expr, Literal, BinOp, UnaryOp, Pattern, OptionalChainItem, Type, INT_METHODS};
 use crate::error::ShitRustError;
+use crate::ffi::{ForeignFunction, ForeignLibraries};
+use crate::stdlib::math::{self, Decimal};
+use num_bigint::BigInt;
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub enum Value {
     Int(i64),
     Float(f64),
+    BigInt(BigInt),
+    Decimal(Decimal),
     Bool(bool),
     String(String),
     Char(char),
     List(Vec<Value>),
     Dict(HashMap<String, Value>),
     Tuple(Vec<Value>),
+    // An instance of the struct `name`
+    Struct {
+        name: String,
+        fields: HashMap<String, Value>,
+    },
     Function {
         name: String,
         params: Vec<String>,
@@ -24,6 +35,8 @@ pub enum Value {
         name: String,
         func: fn(Vec<Value>) -> Result<Value, ShitRustError>,
     },
+    // A function of a C library, declared in an `extern` block
+    ForeignFunction(Arc<ForeignFunction>),
     None,
     Optional(Option<Box<Value>>),
     Trait(TraitDefinition),
@@ -35,14 +48,18 @@ impl Value {
         match self {
             Value::Int(_) => "int".to_string(),
             Value::Float(_) => "float".to_string(),
+            Value::BigInt(_) => "bigint".to_string(),
+            Value::Decimal(_) => "decimal".to_string(),
             Value::Bool(_) => "bool".to_string(),
             Value::String(_) => "string".to_string(),
             Value::Char(_) => "char".to_string(),
             Value::List(_) => "list".to_string(),
             Value::Dict(_) => "dict".to_string(),
             Value::Tuple(_) => "tuple".to_string(),
+            Value::Struct { name, .. } => name.clone(),
             Value::Function { .. } => "function".to_string(),
             Value::NativeFunction { .. } => "native function".to_string(),
+            Value::ForeignFunction(_) => "foreign function".to_string(),
             Value::None => "none".to_string(),
             Value::Optional(_) => "optional".to_string(),
             Value::Trait(_) => "trait".to_string(),
@@ -54,6 +71,8 @@ impl Value {
         match self {
             Value::Int(i) => i.to_string(),
             Value::Float(f) => f.to_string(),
+            Value::BigInt(b) => b.to_string(),
+            Value::Decimal(d) => d.to_string(),
             Value::Bool(b) => b.to_string(),
             Value::String(s) => s.clone(),
             Value::Char(c) => c.to_string(),
@@ -71,8 +90,15 @@ impl Value {
                 let items_str: Vec<String> = items.iter().map(|v| v.to_string()).collect();
                 format!("({})", items_str.join(", "))
             },
+            Value::Struct { name, fields } => {
+                let fields: Vec<String> = fields.iter()
+                    .map(|(field, value)| format!("{}: {}", field, value.to_string()))
+                    .collect();
+                format!("{} {{ {} }}", name, fields.join(", "))
+            },
             Value::Function { name, .. } => format!("<function {}>", name),
             Value::NativeFunction { name, .. } => format!("<native function {}>", name),
+            Value::ForeignFunction(function) => format!("<foreign function {}>", function.name),
             Value::None => "none".to_string(),
             Value::Optional(opt) => {
                 if let Some(value) = opt {
@@ -90,6 +116,8 @@ impl Value {
 #[derive(Clone)]
 pub struct Environment {
     values: HashMap<String, Value>,
+    // Names in this scope declared with `let mut`; everything else is immutable
+    mutable: HashSet<String>,
     parent: Option<Box<Environment>>,
 }
 
@@ -97,6 +125,7 @@ impl Environment {
     pub fn new() -> Self {
         let mut env = Environment {
             values: HashMap::new(),
+            mutable: HashSet::new(),
             parent: None,
         };
         
@@ -125,11 +154,18 @@ impl Environment {
     pub fn with_parent(parent: Environment) -> Self {
         Environment {
             values: HashMap::new(),
+            mutable: HashSet::new(),
             parent: Some(Box::new(parent)),
         }
     }
 
     pub fn define(&mut self, name: String, value: Value) {
+        self.mutable.remove(&name);
+        self.values.insert(name, value);
+    }
+    
+    pub fn define_mutable(&mut self, name: String, value: Value) {
+        self.mutable.insert(name.clone());
         self.values.insert(name, value);
     }
 
@@ -145,6 +181,12 @@ impl Environment {
 
     pub fn assign(&mut self, name: &str, value: Value) -> Result<(), ShitRustError> {
         if self.values.contains_key(name) {
+            if !self.mutable.contains(name) {
+                return Err(ShitRustError::RuntimeError(
+                    format!("Cannot assign to immutable variable '{}'", name)
+                ));
+            }
+            
             self.values.insert(name.to_string(), value);
             Ok(())
         } else if let Some(parent) = &mut self.parent {
@@ -160,6 +202,8 @@ pub struct Interpreter {
     environment: Environment,
     globals: Environment,
     current_source_file: String,
+    // The fields declared `mut` of each struct
+    mutable_fields: HashMap<String, HashSet<String>>,
 }
 
 impl Interpreter {
@@ -169,6 +213,7 @@ impl Interpreter {
             environment: globals.clone(),
             globals,
             current_source_file: String::new(),
+            mutable_fields: HashMap::new(),
         }
     }
 
@@ -186,19 +231,22 @@ impl Interpreter {
                 self.evaluate_expr(expr)?;
                 Ok(())
             },
-            Stmt::Let { name, type_hint: _, value, mutable: _ } => {
-                let evaluated = self.evaluate_expr(value)?;
-                self.environment.define(name.clone(), evaluated);
+            Stmt::Let { name, type_hint, value, mutable } => {
+                let mut evaluated = self.evaluate_expr(value)?;
+                if let Some(hint) = type_hint {
+                    evaluated = math::promote(evaluated, hint);
+                }
+                
+                if *mutable {
+                    self.environment.define_mutable(name.clone(), evaluated);
+                } else {
+                    self.environment.define(name.clone(), evaluated);
+                }
                 Ok(())
             },
             Stmt::Assign { target, value } => {
-                if let Expr::Identifier(name) = target {
-                    let evaluated = self.evaluate_expr(value)?;
-                    self.environment.assign(name, evaluated)?;
-                    Ok(())
-                } else {
-                    Err(ShitRustError::RuntimeError("Invalid assignment target".to_string()))
-                }
+                let evaluated = self.evaluate_expr(value)?;
+                self.assign(target, evaluated)
             },
             Stmt::If { condition, then_block, else_block } => {
                 let condition_value = self.evaluate_expr(condition)?;
@@ -295,12 +343,12 @@ impl Interpreter {
                 self.environment.define(name, Value::Trait(trait_def));
                 Ok(())
             },
-            Stmt::Impl { trait_name, type_name, methods, generic_params } => {
+            Stmt::Impl { trait_name, type_name, methods, generic_params, .. } => {
                 // Create function values for all methods
                 let mut method_map = HashMap::new();
                 
                 for method in methods {
-                    if let Stmt::Function { name, params, return_type, body, is_async, .. } = method {
+                    if let Stmt::Function { name, params, return_type, body, is_async, .. } = method.without_attributes() {
                         let func = FunctionValue {
                             name: name.clone(),
                             params: params.clone(),
@@ -335,14 +383,26 @@ impl Interpreter {
                 Ok(())
             },
             Stmt::Use { path, as_name } => {
-                // For now, we'll just register the module path in the environment
                 let module_name = as_name.clone().unwrap_or_else(|| {
                     path.split('.').last().unwrap_or(path).to_string()
                 });
                 
-                self.environment.define(&module_name, Value::String(path.clone()));
+                // Native stdlib modules are bound as a dict of their members;
+                // other modules are just registered by path for now
+                let module = match path.as_str() {
+                    "math" | "stdlib.math" => Value::Dict(math::init_math_module().into_iter().collect()),
+                    _ => Value::String(path.clone()),
+                };
+                
+                self.environment.define(&module_name, module);
                 Ok(())
             },
+            Stmt::Extern { .. } => self.define_extern_functions(stmt),
+            // `#[link(...)]` on an extern block names the libraries it comes from
+            Stmt::Attributed { .. } | Stmt::Located { .. } if matches!(stmt.without_attributes(), Stmt::Extern { .. }) => {
+                self.define_extern_functions(stmt)
+            },
+            Stmt::Attributed { stmt, .. } | Stmt::Located { stmt, .. } => self.execute_stmt(stmt),
             Stmt::Loop { body } => {
                 loop {
                     match self.execute_block(body, Environment::new_with_enclosing(self.environment.clone())) {
@@ -358,6 +418,14 @@ impl Interpreter {
                 self.environment.define(name, value);
                 Ok(())
             },
+            Stmt::Struct { name, fields, .. } => {
+                let mutable = fields.iter()
+                    .filter(|(_, _, mutable)| *mutable)
+                    .map(|(field, _, _)| field.clone())
+                    .collect();
+                self.mutable_fields.insert(name.clone(), mutable);
+                Ok(())
+            },
             _ => {
                 // Other statement types not yet implemented
                 println!("Statement type not yet implemented: {:?}", stmt);
@@ -366,6 +434,37 @@ impl Interpreter {
         }
     }
     
+    /// Stores a value in the variable or struct field `target` refers to. Structs are values, so
+    /// assigning to a field stores a changed copy of the struct in the place it was read from.
+    fn assign(&mut self, target: &Expr, value: Value) -> Result<(), ShitRustError> {
+        match target {
+            Expr::Identifier(name) => self.environment.assign(name, value),
+            Expr::FieldAccess { object, field } => {
+                let updated = match self.evaluate_expr(object)? {
+                    Value::Struct { name, mut fields } => {
+                        if !fields.contains_key(field) {
+                            return Err(ShitRustError::RuntimeError(format!("No member '{}'", field)));
+                        }
+                        let is_mutable = self.mutable_fields.get(&name).is_some_and(|mutable| mutable.contains(field));
+                        if !is_mutable {
+                            return Err(ShitRustError::RuntimeError(
+                                format!("Cannot assign to immutable field '{}' of struct '{}'", field, name)
+                            ));
+                        }
+                        
+                        fields.insert(field.clone(), value);
+                        Value::Struct { name, fields }
+                    },
+                    other => return Err(ShitRustError::TypeError(
+                        format!("Cannot assign to field '{}' of {}", field, other.type_name())
+                    )),
+                };
+                self.assign(object, updated)
+            },
+            _ => Err(ShitRustError::RuntimeError("Invalid assignment target".to_string())),
+        }
+    }
+    
     fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, ShitRustError> {
         match expr {
             Expr::Literal(lit) => self.evaluate_literal(lit),
@@ -374,6 +473,10 @@ impl Interpreter {
                 let left_val = self.evaluate_expr(left)?;
                 let right_val = self.evaluate_expr(right)?;
                 
+                if let Some(result) = math::exact_binary_op(op, &left_val, &right_val) {
+                    return result;
+                }
+                
                 match op {
                     BinOp::Add => self.add(left_val, right_val),
                     BinOp::Sub => self.subtract(left_val, right_val),
@@ -413,16 +516,33 @@ impl Interpreter {
                             }
                         }
                     },
+                    BinOp::NullishCoalescing => {
+                        match left_val {
+                            Value::None | Value::Optional(None) => Ok(right_val),
+                            Value::Optional(Some(inner)) => Ok(*inner),
+                            other => Ok(other),
+                        }
+                    },
                 }
             },
+            Expr::TypeTest { expr, target_type } => {
+                let value = self.evaluate_expr(expr)?;
+                Ok(Value::Bool(self.value_has_type(&value, target_type)))
+            },
+            Expr::TypeCast { expr, target_type } => {
+                let value = self.evaluate_expr(expr)?;
+                self.cast_value(value, target_type)
+            },
             Expr::UnaryOp { op, expr } => {
                 let value = self.evaluate_expr(expr)?;
                 
                 match op {
                     UnaryOp::Neg => {
                         match value {
-                            Value::Int(i) => Ok(Value::Int(-i)),
+                            Value::Int(i) => i.checked_neg().map(Value::Int).ok_or_else(|| int_overflow("negation")),
                             Value::Float(f) => Ok(Value::Float(-f)),
+                            Value::BigInt(b) => Ok(Value::BigInt(-b)),
+                            Value::Decimal(d) => Ok(Value::Decimal(-&d)),
                             _ => Err(ShitRustError::TypeError(format!("Cannot negate {}", value.type_name()))),
                         }
                     },
@@ -442,43 +562,7 @@ impl Interpreter {
                     evaluated_args.push(self.evaluate_expr(arg)?);
                 }
                 
-                match callee {
-                    Value::NativeFunction { func, .. } => {
-                        func(evaluated_args)
-                    },
-                    Value::Function { name, params, body, closure } => {
-                        if params.len() != evaluated_args.len() {
-                            return Err(ShitRustError::RuntimeError(
-                                format!("Expected {} arguments but got {}", params.len(), evaluated_args.len())
-                            ));
-                        }
-                        
-                        // Create new environment with function's closure as parent
-                        let mut env = Environment::with_parent(*closure);
-                        
-                        // Add parameters to the environment
-                        for (param, arg) in params.iter().zip(evaluated_args) {
-                            env.define(param.clone(), arg);
-                        }
-                        
-                        // Save current environment
-                        let previous_env = self.environment.clone();
-                        self.environment = env;
-                        
-                        // Execute function body
-                        for stmt in &body {
-                            // TODO: Handle return values properly
-                            self.execute_stmt(stmt)?;
-                        }
-                        
-                        // Restore previous environment
-                        self.environment = previous_env;
-                        
-                        // For now, always return None
-                        Ok(Value::None)
-                    },
-                    _ => Err(ShitRustError::RuntimeError(format!("Cannot call {}", callee.type_name()))),
-                }
+                self.call_callee(callee, evaluated_args)
             },
             Expr::MethodCall { object, method, args } => {
                 let obj_val = self.evaluate_expr(object)?;
@@ -488,6 +572,28 @@ impl Interpreter {
                     return Ok(Value::String(obj_val.to_string()));
                 }
                 
+                if let Value::Int(value) = obj_val {
+                    let mut evaluated_args = Vec::new();
+                    for arg in args {
+                        evaluated_args.push(self.evaluate_expr(arg)?);
+                    }
+                    
+                    if let Some(result) = self.int_method(value, method, &evaluated_args) {
+                        return result;
+                    }
+                }
+                
+                // Members of native modules such as `math`
+                if let Value::Dict(members) = &obj_val {
+                    if let Some(Value::NativeFunction { func, .. }) = members.get(method) {
+                        let mut evaluated_args = Vec::new();
+                        for arg in args {
+                            evaluated_args.push(self.evaluate_expr(arg)?);
+                        }
+                        return func(evaluated_args);
+                    }
+                }
+                
                 // Other methods would be implemented here
                 
                 Err(ShitRustError::RuntimeError(format!("Method '{}' not found on {}", method, obj_val.type_name())))
@@ -724,7 +830,48 @@ impl Interpreter {
                     obj.insert(field_name.clone(), field_value);
                 }
                 
-                Ok(Value::Object(obj))
+                Ok(Value::Struct { name: name.clone(), fields: obj })
+            },
+            Expr::FieldAccess { object, field } => {
+                match self.evaluate_expr(object)? {
+                    Value::Dict(members) | Value::Struct { fields: members, .. } => members.get(field).cloned().ok_or_else(|| {
+                        ShitRustError::RuntimeError(format!("No member '{}'", field))
+                    }),
+                    other => Err(ShitRustError::TypeError(
+                        format!("Cannot access field '{}' on {}", field, other.type_name())
+                    )),
+                }
+            },
+            Expr::Index { target, index } => {
+                let target = self.evaluate_expr(target)?;
+                let index = self.evaluate_expr(index)?;
+                
+                match target {
+                    Value::List(items) => match index {
+                        Value::Int(i) if i >= 0 && (i as usize) < items.len() => Ok(items[i as usize].clone()),
+                        Value::Int(i) => Err(ShitRustError::RuntimeError(
+                            format!("Index {} out of bounds for list of length {}", i, items.len())
+                        )),
+                        other => Err(ShitRustError::TypeError(
+                            format!("List index must be an integer, got {}", other.type_name())
+                        )),
+                    },
+                    Value::Dict(map) => {
+                        let key = Self::dict_key(&index)?;
+                        map.get(&key).cloned().ok_or_else(|| {
+                            ShitRustError::RuntimeError(format!("Key not found: {}", key))
+                        })
+                    },
+                    other => Err(ShitRustError::TypeError(format!("Cannot index {}", other.type_name()))),
+                }
+            },
+            Expr::Lambda { params, body, .. } => {
+                Ok(Value::Function {
+                    name: "<lambda>".to_string(),
+                    params: params.iter().map(|(name, _)| name.clone()).collect(),
+                    body: body.lambda_body(),
+                    closure: Box::new(self.environment.clone()),
+                })
             },
             _ => {
                 // Other expression types not yet implemented
@@ -733,30 +880,54 @@ impl Interpreter {
         }
     }
     
-    fn evaluate_literal(&self, lit: &Literal) -> Result<Value, ShitRustError> {
+    fn evaluate_literal(&mut self, lit: &Literal) -> Result<Value, ShitRustError> {
         match lit {
             Literal::Int(i) => Ok(Value::Int(*i)),
             Literal::Float(f) => Ok(Value::Float(*f)),
+            Literal::BigInt(digits) => digits.parse().map(Value::BigInt).map_err(|_| {
+                ShitRustError::ValueError(format!("Invalid bigint literal '{}'", digits))
+            }),
+            Literal::Decimal(digits) => Decimal::parse(digits).map(Value::Decimal),
             Literal::Bool(b) => Ok(Value::Bool(*b)),
             Literal::String(s) => Ok(Value::String(s.clone())),
             Literal::Char(c) => Ok(Value::Char(*c)),
             Literal::List(items) => {
                 let mut values = Vec::new();
                 for item in items {
-                    values.push(self.evaluate_literal(item)?);
+                    values.push(self.evaluate_expr(item)?);
                 }
                 Ok(Value::List(values))
             },
+            Literal::Dict(entries) => {
+                let mut map = HashMap::new();
+                for (key, value) in entries {
+                    let key = self.evaluate_expr(key)?;
+                    let value = self.evaluate_expr(value)?;
+                    map.insert(Self::dict_key(&key)?, value);
+                }
+                Ok(Value::Dict(map))
+            },
             Literal::None => Ok(Value::None),
             _ => Err(ShitRustError::RuntimeError(format!("Literal type not yet implemented: {:?}", lit))),
         }
     }
     
+    // Dicts are keyed by the canonical text of the key, so numerically equal keys collide:
+    // `1` and `1n` are the same key, as are `1.5d` and `1.50d`
+    fn dict_key(key: &Value) -> Result<String, ShitRustError> {
+        match key {
+            Value::String(s) => Ok(s.clone()),
+            Value::Decimal(d) => Ok(d.normalize().to_string()),
+            Value::Int(_) | Value::BigInt(_) | Value::Bool(_) | Value::Char(_) => Ok(key.to_string()),
+            other => Err(ShitRustError::TypeError(format!("{} cannot be used as a dict key", other.type_name()))),
+        }
+    }
+    
     // Operator implementation
     
     fn add(&self, left: Value, right: Value) -> Result<Value, ShitRustError> {
         match (left, right) {
-            (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a + b)),
+            (Value::Int(a), Value::Int(b)) => a.checked_add(b).map(Value::Int).ok_or_else(|| int_overflow("addition")),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
             (Value::Int(a), Value::Float(b)) => Ok(Value::Float(a as f64 + b)),
             (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a + b as f64)),
@@ -769,7 +940,7 @@ impl Interpreter {
     
     fn subtract(&self, left: Value, right: Value) -> Result<Value, ShitRustError> {
         match (left, right) {
-            (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a - b)),
+            (Value::Int(a), Value::Int(b)) => a.checked_sub(b).map(Value::Int).ok_or_else(|| int_overflow("subtraction")),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
             (Value::Int(a), Value::Float(b)) => Ok(Value::Float(a as f64 - b)),
             (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a - b as f64)),
@@ -779,7 +950,7 @@ impl Interpreter {
     
     fn multiply(&self, left: Value, right: Value) -> Result<Value, ShitRustError> {
         match (left, right) {
-            (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a * b)),
+            (Value::Int(a), Value::Int(b)) => a.checked_mul(b).map(Value::Int).ok_or_else(|| int_overflow("multiplication")),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
             (Value::Int(a), Value::Float(b)) => Ok(Value::Float(a as f64 * b)),
             (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a * b as f64)),
@@ -793,7 +964,8 @@ impl Interpreter {
                 if b == 0 {
                     return Err(ShitRustError::RuntimeError("Division by zero".to_string()));
                 }
-                Ok(Value::Int(a / b))
+                // Only int::MIN / -1 can overflow
+                a.checked_div(b).map(Value::Int).ok_or_else(|| int_overflow("division"))
             },
             (Value::Float(a), Value::Float(b)) => {
                 if b == 0.0 {
@@ -823,12 +995,138 @@ impl Interpreter {
                 if b == 0 {
                     return Err(ShitRustError::RuntimeError("Modulo by zero".to_string()));
                 }
-                Ok(Value::Int(a % b))
+                a.checked_rem(b).map(Value::Int).ok_or_else(|| int_overflow("remainder"))
             },
             _ => Err(ShitRustError::TypeError("Modulo only works with integers".to_string())),
         }
     }
     
+    // Overflow-aware arithmetic methods on ints: `wrapping_*` wraps around, `checked_*` returns
+    // none on overflow or division by zero, and `saturating_*` clamps to the int range.
+    // Returns None when `method` is not one of them.
+    fn int_method(&self, value: i64, method: &str, args: &[Value]) -> Option<Result<Value, ShitRustError>> {
+        if !INT_METHODS.contains(&method) {
+            return None;
+        }
+        let (mode, op) = method.split_once('_')?;
+        
+        let expected_args = if op == "neg" { 0 } else { 1 };
+        if args.len() != expected_args {
+            return Some(Err(ShitRustError::RuntimeError(
+                format!("Method '{}' expects {} argument(s) but {} were supplied", method, expected_args, args.len())
+            )));
+        }
+        
+        let other = match args.first() {
+            Some(Value::Int(other)) => *other,
+            Some(arg) => return Some(Err(ShitRustError::TypeError(
+                format!("Method '{}' expects an int argument, got {}", method, arg.type_name())
+            ))),
+            None => 0,
+        };
+        
+        if matches!(op, "div" | "rem") && other == 0 && mode != "checked" {
+            return Some(Err(ShitRustError::RuntimeError("Division by zero".to_string())));
+        }
+        
+        let exponent = if op == "pow" {
+            match u32::try_from(other) {
+                Ok(exponent) => exponent,
+                Err(_) => return Some(Err(ShitRustError::RuntimeError(
+                    format!("Invalid exponent {} in '{}'", other, method)
+                ))),
+            }
+        } else {
+            0
+        };
+        
+        let result = match (mode, op) {
+            ("wrapping", "add") => Value::Int(value.wrapping_add(other)),
+            ("wrapping", "sub") => Value::Int(value.wrapping_sub(other)),
+            ("wrapping", "mul") => Value::Int(value.wrapping_mul(other)),
+            ("wrapping", "div") => Value::Int(value.wrapping_div(other)),
+            ("wrapping", "rem") => Value::Int(value.wrapping_rem(other)),
+            ("wrapping", "pow") => Value::Int(value.wrapping_pow(exponent)),
+            ("wrapping", _) => Value::Int(value.wrapping_neg()),
+            ("saturating", "add") => Value::Int(value.saturating_add(other)),
+            ("saturating", "sub") => Value::Int(value.saturating_sub(other)),
+            ("saturating", "mul") => Value::Int(value.saturating_mul(other)),
+            ("saturating", "div") => Value::Int(value.saturating_div(other)),
+            ("saturating", "pow") => Value::Int(value.saturating_pow(exponent)),
+            ("saturating", _) => Value::Int(value.saturating_neg()),
+            (_, op) => {
+                let checked = match op {
+                    "add" => value.checked_add(other),
+                    "sub" => value.checked_sub(other),
+                    "mul" => value.checked_mul(other),
+                    "div" => value.checked_div(other),
+                    "rem" => value.checked_rem(other),
+                    "pow" => value.checked_pow(exponent),
+                    _ => value.checked_neg(),
+                };
+                Value::Optional(checked.map(|result| Box::new(Value::Int(result))))
+            },
+        };
+        
+        Some(Ok(result))
+    }
+    
+    // Float to int truncates toward zero and saturates at the int range (NaN becomes 0);
+    // int to char fails unless the value is a valid Unicode scalar value
+    fn cast_value(&self, value: Value, target: &Type) -> Result<Value, ShitRustError> {
+        if let Some(result) = math::exact_cast(&value, target) {
+            return result;
+        }
+        
+        match (value, target) {
+            (Value::Int(i), Type::Int) => Ok(Value::Int(i)),
+            (Value::Float(f), Type::Int) => Ok(Value::Int(f as i64)),
+            (Value::Char(c), Type::Int) => Ok(Value::Int(c as i64)),
+            (Value::Bool(b), Type::Int) => Ok(Value::Int(b as i64)),
+            (Value::Int(i), Type::Float) => Ok(Value::Float(i as f64)),
+            (Value::Float(f), Type::Float) => Ok(Value::Float(f)),
+            (Value::Char(c), Type::Char) => Ok(Value::Char(c)),
+            (Value::Int(i), Type::Char) => {
+                u32::try_from(i).ok()
+                    .and_then(char::from_u32)
+                    .map(Value::Char)
+                    .ok_or_else(|| ShitRustError::RuntimeError(
+                        format!("Cannot cast {} to char: not a valid Unicode scalar value", i)
+                    ))
+            },
+            (value, target) if self.value_has_type(&value, target) => Ok(value),
+            (value, target) => Err(ShitRustError::RuntimeError(
+                format!("Cannot cast {} to {:?}", value.type_name(), target)
+            )),
+        }
+    }
+    
+    fn value_has_type(&self, value: &Value, typ: &Type) -> bool {
+        match (value, typ) {
+            (_, Type::Custom(name)) if name == "any" => true,
+            (_, Type::Union(members)) => members.iter().any(|member| self.value_has_type(value, member)),
+            (Value::None, Type::Option(_)) | (Value::Optional(None), Type::Option(_)) => true,
+            (Value::Optional(Some(inner)), Type::Option(inner_type)) => self.value_has_type(inner, inner_type),
+            (_, Type::Option(inner_type)) => self.value_has_type(value, inner_type),
+            (Value::Int(_), Type::Int) |
+            (Value::Float(_), Type::Float) |
+            (Value::BigInt(_), Type::BigInt) |
+            (Value::Decimal(_), Type::Decimal) |
+            (Value::Bool(_), Type::Bool) |
+            (Value::String(_), Type::String) |
+            (Value::Char(_), Type::Char) |
+            (Value::List(_), Type::List(_)) |
+            (Value::Dict(_), Type::Dict(_, _)) |
+            (Value::Tuple(_), Type::Tuple(_)) => true,
+            (Value::Struct { name, .. }, Type::Custom(type_name)) => name == type_name,
+            (Value::Function { .. }, Type::Function(_, _)) |
+            (Value::NativeFunction { .. }, Type::Function(_, _)) |
+            (Value::ForeignFunction(_), Type::Function(_, _)) => true,
+            (Value::Int(_), Type::Pointer) => true,
+            _ => false,
+        }
+    }
+    
     fn equals(&self, left: Value, right: Value) -> Result<Value, ShitRustError> {
         match (left, right) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a == b)),
@@ -1007,9 +1305,17 @@ impl Interpreter {
             (Value::Bool(a), Value::Bool(b)) => a == b,
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Char(a), Value::Char(b)) => a == b,
+            (Value::BigInt(_) | Value::Decimal(_), _) | (_, Value::BigInt(_) | Value::Decimal(_)) => {
+                matches!(math::exact_binary_op(&BinOp::Eq, left, right), Some(Ok(Value::Bool(true))))
+            },
             (Value::List(a), Value::List(b)) => a == b,
             (Value::Dict(a), Value::Dict(b)) => a == b,
             (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Struct { name: a, fields: a_fields }, Value::Struct { name: b, fields: b_fields }) => {
+                a == b && a_fields.len() == b_fields.len() && a_fields.iter().all(|(field, value)| {
+                    b_fields.get(field).is_some_and(|other| self.values_equal(value, other))
+                })
+            },
             (Value::Function { .. }, Value::Function { .. }) => false,
             (Value::NativeFunction { .. }, Value::NativeFunction { .. }) => false,
             (Value::Optional(a), Value::Optional(b)) => {
@@ -1025,6 +1331,84 @@ impl Interpreter {
         }
     }
 
+    /// Binds the functions of an `extern` block, looking them up in its `#[link(...)]` libraries
+    fn define_extern_functions(&mut self, stmt: &Stmt) -> Result<(), ShitRustError> {
+        let Stmt::Extern { functions, .. } = stmt.without_attributes() else {
+            unreachable!("not an extern block");
+        };
+        
+        let libraries = ForeignLibraries::open(&stmt.link_libraries())?;
+        for function in functions {
+            let foreign = libraries.lookup(function)?;
+            self.environment.define(function.name.clone(), Value::ForeignFunction(Arc::new(foreign)));
+        }
+        
+        Ok(())
+    }
+    
+    /// Defines the program's functions, types and other declarations without running its
+    /// top-level statements, so that its functions can be called one at a time with `call_named`
+    pub fn declare(&mut self, program: &Program) -> Result<(), ShitRustError> {
+        self.current_source_file = program.source_file.clone();
+        
+        for stmt in &program.statements {
+            match stmt.without_attributes() {
+                Stmt::Function { .. } | Stmt::Struct { .. } | Stmt::Trait { .. } | Stmt::Impl { .. } |
+                Stmt::TypeAlias { .. } | Stmt::Use { .. } | Stmt::Extern { .. } | Stmt::Const { .. } => self.execute_stmt(stmt)?,
+                _ => (),
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Calls the function `name` defined by the program, as a call in the program would
+    pub fn call_named(&mut self, name: &str, args: Vec<Value>) -> Result<Value, ShitRustError> {
+        let callee = self.environment.get(name)?;
+        self.call_callee(callee, args)
+    }
+    
+    fn call_callee(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, ShitRustError> {
+        match callee {
+            Value::NativeFunction { func, .. } => {
+                func(args)
+            },
+            Value::ForeignFunction(function) => function.call(args),
+            Value::Function { name: _, params, body, closure } => {
+                if params.len() != args.len() {
+                    return Err(ShitRustError::RuntimeError(
+                        format!("Expected {} arguments but got {}", params.len(), args.len())
+                    ));
+                }
+                
+                // Create new environment with function's closure as parent
+                let mut env = Environment::with_parent(*closure);
+                
+                // Add parameters to the environment
+                for (param, arg) in params.iter().zip(args) {
+                    env.define(param.clone(), arg);
+                }
+                
+                // Save current environment
+                let previous_env = self.environment.clone();
+                self.environment = env;
+                
+                // Execute function body
+                for stmt in &body {
+                    // TODO: Handle return values properly
+                    self.execute_stmt(stmt)?;
+                }
+                
+                // Restore previous environment
+                self.environment = previous_env;
+                
+                // For now, always return None
+                Ok(Value::None)
+            },
+            _ => Err(ShitRustError::RuntimeError(format!("Cannot call {}", callee.type_name()))),
+        }
+    }
+    
     fn call_function(&mut self, func: &FunctionValue, args: &[Value], this: Option<Value>) -> Result<Value, ShitRustError> {
         let mut env = Environment::with_parent(self.environment.clone());
         
@@ -1033,9 +1417,9 @@ impl Interpreter {
             env.define(param.clone(), arg.clone());
         }
         
-        // Add 'this' to the environment if it's a method
+        // Add 'this' to the environment if it's a method. Its `mut` fields may be assigned.
         if let Some(this) = this {
-            env.define("this", this);
+            env.define_mutable("this".to_string(), this);
         }
         
         // Save current environment
@@ -1106,6 +1490,11 @@ impl Interpreter {
     }
 }
 
+// Int arithmetic that does not fit in 64 bits is an error rather than wrapping silently
+fn int_overflow(operation: &str) -> ShitRustError {
+    ShitRustError::RuntimeError(format!("Integer overflow in {}", operation))
+}
+
 // Add TraitDefinition and TraitImplementation structs
 #[derive(Debug, Clone)]
 struct TraitDefinition {
@@ -1130,3 +1519,120 @@ struct TraitImplementation {
     methods: HashMap<String, FunctionValue>,
     generic_params: Vec<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn int_to_char_accepts_unicode_scalar_values() {
+        let interpreter = Interpreter::new();
+        
+        for (code, expected) in [(97, 'a'), (233, '\u{e9}'), (0x10FFFF, '\u{10FFFF}')] {
+            match interpreter.cast_value(Value::Int(code), &Type::Char) {
+                Ok(Value::Char(c)) => assert_eq!(c, expected),
+                other => panic!("{} as char gave {:?}", code, other.map(|value| value.type_name())),
+            }
+        }
+    }
+    
+    #[test]
+    fn int_to_char_rejects_surrogates_and_out_of_range_values() {
+        let interpreter = Interpreter::new();
+        
+        for code in [-1, 0xD800, 0xDFFF, 0x110000] {
+            assert!(interpreter.cast_value(Value::Int(code), &Type::Char).is_err(), "{} as char", code);
+        }
+    }
+    
+    fn parse(source: &str) -> Program {
+        let tokens = crate::lexer::Lexer::new(source).scan_tokens().unwrap();
+        crate::parser::Parser::new(tokens).parse().unwrap()
+    }
+    
+    #[test]
+    fn declare_skips_top_level_statements() {
+        let program = parse("fn double(x: int) { let y = x * 2; }\nlet z = missing;\n");
+        let mut interpreter = Interpreter::new();
+        
+        interpreter.declare(&program).unwrap();
+        assert!(interpreter.call_named("double", vec![Value::Int(2)]).is_ok());
+        assert!(interpreter.call_named("double", vec![]).is_err());
+        assert!(interpreter.call_named("z", vec![]).is_err());
+    }
+    
+    #[test]
+    fn only_mut_fields_can_be_assigned() {
+        let declarations = "struct Counter { mut count: int, label: string }\n\
+                            let mut counter = Counter { count: 0, label: \"clicks\" };\n";
+        
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&parse(&format!("{}counter.count = 5;\n", declarations))).unwrap();
+        match interpreter.environment.get("counter") {
+            Ok(Value::Struct { fields, .. }) => assert!(matches!(fields.get("count"), Some(Value::Int(5)))),
+            other => panic!("expected a Counter, got {:?}", other.map(|value| value.type_name())),
+        }
+        
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.interpret(&parse(&format!("{}counter.label = \"taps\";\n", declarations))).unwrap_err();
+        assert!(matches!(error, ShitRustError::RuntimeError(message) if message.contains("immutable field 'label'")));
+    }
+    
+    #[test]
+    fn fields_of_immutable_bindings_cannot_be_assigned() {
+        let program = parse("struct Counter { mut count: int }\n\
+                             let counter = Counter { count: 0 };\n\
+                             counter.count = 1;\n");
+        assert!(Interpreter::new().interpret(&program).is_err());
+    }
+    
+    #[test]
+    fn float_to_int_truncates_toward_zero_and_saturates() {
+        let interpreter = Interpreter::new();
+        
+        for (value, expected) in [(2.9, 2), (-2.9, -2), (1e300, i64::MAX), (-1e300, i64::MIN), (f64::NAN, 0)] {
+            match interpreter.cast_value(Value::Float(value), &Type::Int) {
+                Ok(Value::Int(i)) => assert_eq!(i, expected, "{} as int", value),
+                other => panic!("{} as int gave {:?}", value, other.map(|value| value.type_name())),
+            }
+        }
+    }
+    
+    #[test]
+    fn int_arithmetic_errors_on_overflow_and_division_by_zero() {
+        for (expression, message) in [
+            ("9223372036854775807 + 1", "Integer overflow in addition"),
+            ("-9223372036854775807 - 2", "Integer overflow in subtraction"),
+            ("4611686018427387904 * 2", "Integer overflow in multiplication"),
+            ("1 / 0", "Division by zero"),
+            ("1 % 0", "Modulo by zero"),
+        ] {
+            let error = Interpreter::new().interpret(&parse(&format!("let x = {};\n", expression))).unwrap_err();
+            assert!(matches!(&error, ShitRustError::RuntimeError(m) if m == message), "{} gave {:?}", expression, error);
+        }
+    }
+    
+    #[test]
+    fn int_methods_wrap_check_or_saturate() {
+        let interpreter = Interpreter::new();
+        let call = |value: i64, method: &str, args: &[Value]| interpreter.int_method(value, method, args).unwrap();
+        
+        assert!(matches!(call(i64::MAX, "wrapping_add", &[Value::Int(1)]), Ok(Value::Int(i64::MIN))));
+        assert!(matches!(call(i64::MIN, "wrapping_neg", &[]), Ok(Value::Int(i64::MIN))));
+        assert!(matches!(call(i64::MAX, "saturating_mul", &[Value::Int(2)]), Ok(Value::Int(i64::MAX))));
+        assert!(matches!(call(2, "saturating_pow", &[Value::Int(64)]), Ok(Value::Int(i64::MAX))));
+        
+        assert!(matches!(call(i64::MAX, "checked_add", &[Value::Int(1)]), Ok(Value::Optional(None))));
+        assert!(matches!(call(7, "checked_div", &[Value::Int(0)]), Ok(Value::Optional(None))));
+        match call(7, "checked_rem", &[Value::Int(4)]) {
+            Ok(Value::Optional(Some(value))) => assert!(matches!(*value, Value::Int(3))),
+            other => panic!("7.checked_rem(4) gave {:?}", other.map(|value| value.type_name())),
+        }
+        
+        assert!(matches!(call(7, "wrapping_div", &[Value::Int(0)]), Err(ShitRustError::RuntimeError(_))));
+        assert!(call(2, "wrapping_pow", &[Value::Int(-1)]).is_err());
+        assert!(call(2, "checked_add", &[]).is_err());
+        assert!(interpreter.int_method(2, "wrapping_shl", &[Value::Int(1)]).is_none());
+    }
+}
+
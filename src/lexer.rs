@@ -1,12 +1,15 @@
 use std::fmt;
+use serde::Serialize;
 use crate::error::{ShitRustError, Result, SourceLocation};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum TokenType {
     // Identifiers & literals
     Identifier,
     IntLiteral,
     FloatLiteral,
+    BigIntLiteral,
+    DecimalLiteral,
     StringLiteral,
     CharLiteral,
     BoolLiteral,
@@ -51,6 +54,7 @@ pub enum TokenType {
     Use,
     Const,
     Loop,
+    Extern,
 
     // Types
     Int,
@@ -92,9 +96,15 @@ pub enum TokenType {
     Comma,
     Dot,
     Colon,
+    DoubleColon,
     Semicolon,
     Arrow,
     FatArrow,
+    Pipe,
+    Question,
+    QuestionDot,
+    DoubleQuestion,
+    Hash,
     
     // Special
     EOF,
@@ -106,6 +116,8 @@ impl fmt::Display for TokenType {
             TokenType::Identifier => write!(f, "identifier"),
             TokenType::IntLiteral => write!(f, "integer literal"),
             TokenType::FloatLiteral => write!(f, "float literal"),
+            TokenType::BigIntLiteral => write!(f, "bigint literal"),
+            TokenType::DecimalLiteral => write!(f, "decimal literal"),
             TokenType::StringLiteral => write!(f, "string literal"),
             TokenType::CharLiteral => write!(f, "character literal"),
             TokenType::BoolLiteral => write!(f, "boolean literal"),
@@ -149,6 +161,7 @@ impl fmt::Display for TokenType {
             TokenType::Use => write!(f, "use"),
             TokenType::Const => write!(f, "const"),
             TokenType::Loop => write!(f, "loop"),
+            TokenType::Extern => write!(f, "extern"),
             // Types
             TokenType::Int => write!(f, "int"),
             TokenType::Float => write!(f, "float"),
@@ -186,15 +199,21 @@ impl fmt::Display for TokenType {
             TokenType::Comma => write!(f, ","),
             TokenType::Dot => write!(f, "."),
             TokenType::Colon => write!(f, ":"),
+            TokenType::DoubleColon => write!(f, "::"),
             TokenType::Semicolon => write!(f, ";"),
             TokenType::Arrow => write!(f, "->"),
             TokenType::FatArrow => write!(f, "=>"),
+            TokenType::Pipe => write!(f, "|"),
+            TokenType::Question => write!(f, "?"),
+            TokenType::QuestionDot => write!(f, "?."),
+            TokenType::DoubleQuestion => write!(f, "??"),
+            TokenType::Hash => write!(f, "#"),
             TokenType::EOF => write!(f, "end of file"),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
@@ -301,7 +320,13 @@ impl Lexer {
             ']' => self.add_token(TokenType::RightBracket),
             ',' => self.add_token(TokenType::Comma),
             '.' => self.add_token(TokenType::Dot),
-            ':' => self.add_token(TokenType::Colon),
+            ':' => {
+                if self.match_char(':') {
+                    self.add_token(TokenType::DoubleColon)
+                } else {
+                    self.add_token(TokenType::Colon)
+                }
+            },
             ';' => self.add_token(TokenType::Semicolon),
             
             // Single or double character tokens
@@ -391,9 +416,19 @@ impl Lexer {
                 if self.match_char('|') {
                     self.add_token(TokenType::Or)
                 } else {
-                    return Err(self.error("Expected '|' after '|'"));
+                    self.add_token(TokenType::Pipe)
                 }
             },
+            '?' => {
+                if self.match_char('.') {
+                    self.add_token(TokenType::QuestionDot)
+                } else if self.match_char('?') {
+                    self.add_token(TokenType::DoubleQuestion)
+                } else {
+                    self.add_token(TokenType::Question)
+                }
+            },
+            '#' => self.add_token(TokenType::Hash),
             
             // String literals
             '"' => self.string()?,
@@ -519,6 +554,11 @@ impl Lexer {
                 self.advance();
             }
             
+            // A `d` suffix makes an exact decimal: 19.99d
+            if self.number_suffix('d') {
+                return Ok(());
+            }
+            
             // Check for exponent
             if self.peek() == 'e' || self.peek() == 'E' {
                 self.advance();
@@ -537,7 +577,7 @@ impl Lexer {
                 TokenType::FloatLiteral,
                 self.source[self.start..self.current].to_string(),
             );
-        } else {
+        } else if !self.number_suffix('n') && !self.number_suffix('d') {
             self.add_token_with_lexeme(
                 TokenType::IntLiteral,
                 self.source[self.start..self.current].to_string(),
@@ -547,6 +587,21 @@ impl Lexer {
         Ok(())
     }
 
+    // Consume a trailing `n` (bigint) or `d` (decimal) suffix and add the literal token.
+    // The suffix only counts when it ends the word, so `10days` is not a literal
+    fn number_suffix(&mut self, suffix: char) -> bool {
+        let next = self.peek_next();
+        if self.peek() != suffix || next.is_alphanumeric() || next == '_' {
+            return false;
+        }
+        
+        let lexeme = self.source[self.start..self.current].to_string();
+        self.advance();
+        let token_type = if suffix == 'n' { TokenType::BigIntLiteral } else { TokenType::DecimalLiteral };
+        self.add_token_with_lexeme(token_type, lexeme);
+        true
+    }
+    
     fn identifier(&mut self) {
         while self.peek().is_alphanumeric() || self.peek() == '_' {
             self.advance();
@@ -594,6 +649,7 @@ impl Lexer {
             "use" => TokenType::Use,
             "const" => TokenType::Const,
             "loop" => TokenType::Loop,
+            "extern" => TokenType::Extern,
             // Types
             "int" => TokenType::Int,
             "float" => TokenType::Float,
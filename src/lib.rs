@@ -4,13 +4,19 @@ pub mod lexer;
 pub mod parser;
 pub mod interpreter;
 pub mod formatter;
+pub mod dump;
 pub mod compiler;
 pub mod code_gen;
+pub mod build_cache;
+pub mod bundle;
+pub mod runtime;
+pub mod ffi;
 
 // New modules for language improvements
 pub mod stdlib;
 pub mod module_system;
 pub mod type_system;
+pub mod lint;
 pub mod traits;
 
 // Standard library modules
@@ -0,0 +1,767 @@
+use std::collections::{HashMap, HashSet};
+use crate::ast::{self, Attribute, Expr, Literal, OptionalChainItem, Pattern, Stmt};
+use crate::error::{Result, ShitRustError};
+use crate::type_system::TypeChecker;
+
+/// How findings of a lint are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// A named lint belonging to a lint group
+#[derive(Debug, Clone, Copy)]
+pub struct Lint {
+    pub name: &'static str,
+    pub group: &'static str,
+    pub default_level: LintLevel,
+    pub description: &'static str,
+}
+
+pub const UNUSED_VARIABLES: Lint = Lint {
+    name: "unused_variables",
+    group: "unused",
+    default_level: LintLevel::Warn,
+    description: "variables and parameters that are never read",
+};
+
+pub const UNUSED_IMPORTS: Lint = Lint {
+    name: "unused_imports",
+    group: "unused",
+    default_level: LintLevel::Warn,
+    description: "modules brought in with 'use' that are never referenced",
+};
+
+pub const SHADOWING: Lint = Lint {
+    name: "shadowing",
+    group: "style",
+    default_level: LintLevel::Warn,
+    description: "bindings that hide an earlier binding of the same name in the same function",
+};
+
+pub const UNREACHABLE_CODE: Lint = Lint {
+    name: "unreachable_code",
+    group: "unreachable",
+    default_level: LintLevel::Warn,
+    description: "statements after a 'return', 'break', 'continue' or diverging call",
+};
+
+pub const UNREACHABLE_PATTERNS: Lint = Lint {
+    name: "unreachable_patterns",
+    group: "unreachable",
+    default_level: LintLevel::Warn,
+    description: "match arms that earlier arms already cover",
+};
+
+/// All lints known to the linter
+pub const LINTS: &[Lint] = &[
+    UNUSED_VARIABLES,
+    UNUSED_IMPORTS,
+    SHADOWING,
+    UNREACHABLE_CODE,
+    UNREACHABLE_PATTERNS,
+];
+
+/// A single lint finding
+#[derive(Debug, Clone)]
+pub struct LintDiagnostic {
+    pub lint: &'static str,
+    pub level: LintLevel,
+    pub message: String,
+}
+
+/// Lint levels before any attributes in the source are applied
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    levels: HashMap<&'static str, LintLevel>,
+}
+
+impl LintConfig {
+    /// Create a configuration with every lint at its default level
+    pub fn new() -> Self {
+        LintConfig {
+            levels: LINTS.iter().map(|lint| (lint.name, lint.default_level)).collect(),
+        }
+    }
+    
+    /// Set the level of a lint, a lint group, or of `all` lints
+    pub fn set_level(&mut self, name: &str, level: LintLevel) -> Result<()> {
+        for lint in Self::resolve(name)? {
+            self.levels.insert(lint, level);
+        }
+        Ok(())
+    }
+    
+    /// Get the level of a lint
+    pub fn level(&self, lint: &str) -> LintLevel {
+        self.levels.get(lint).copied().unwrap_or(LintLevel::Allow)
+    }
+    
+    /// Expand a lint, group or `all` into the names of the lints it covers
+    fn resolve(name: &str) -> Result<Vec<&'static str>> {
+        let lints: Vec<&'static str> = LINTS.iter()
+            .filter(|lint| name == "all" || lint.name == name || lint.group == name)
+            .map(|lint| lint.name)
+            .collect();
+        
+        if lints.is_empty() {
+            return Err(ShitRustError::LintError(format!("Unknown lint or lint group '{}'", name)));
+        }
+        
+        Ok(lints)
+    }
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A variable or import tracked by the unused and shadowing lints
+struct Binding {
+    name: String,
+    is_import: bool,
+    used: bool,
+    /// Level of the unused lint where the binding was declared
+    level: LintLevel,
+    /// Where the binding was declared, for messages
+    context: String,
+}
+
+/// Walks a type-checked program and collects lint findings
+pub struct Linter<'a> {
+    checker: &'a TypeChecker,
+    
+    /// Lint levels in effect, innermost attribute last
+    levels: Vec<HashMap<&'static str, LintLevel>>,
+    
+    /// Bindings of each enclosing block scope
+    scopes: Vec<Vec<Binding>>,
+    
+    /// Index of the first scope belonging to the current function
+    function_scope: usize,
+    
+    /// Names of the enclosing functions
+    functions: Vec<String>,
+    
+    /// Set when the next statement follows one that never completes
+    unreachable_after: Option<&'static str>,
+    
+    /// Match arms the type checker found unreachable, by the address of the expression the
+    /// match is on in the linted program and the index of the arm
+    unreachable_arms: HashSet<(usize, usize)>,
+    
+    diagnostics: Vec<LintDiagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    /// Create a linter for a program that `checker` has already checked
+    pub fn new(config: &LintConfig, checker: &'a TypeChecker) -> Self {
+        Linter {
+            checker,
+            levels: vec![config.levels.clone()],
+            scopes: Vec::new(),
+            function_scope: 0,
+            functions: Vec::new(),
+            unreachable_after: None,
+            unreachable_arms: HashSet::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+    
+    /// Lint a program and return the findings that are not allowed
+    pub fn lint_program(mut self, program: &[Stmt]) -> Result<Vec<LintDiagnostic>> {
+        let checker = self.checker;
+        let unreachable = checker.unreachable_arms();
+        if !unreachable.is_empty() {
+            let mut id = 0;
+            ast::walk_exprs(program, &mut |expr| {
+                for &(match_id, arm) in unreachable {
+                    if match_id == id {
+                        self.unreachable_arms.insert((expr as *const Expr as usize, arm));
+                    }
+                }
+                id += 1;
+            });
+        }
+        
+        self.push_scope();
+        self.lint_block(program)?;
+        self.pop_scope();
+        
+        Ok(self.diagnostics)
+    }
+    
+    fn lint_block(&mut self, block: &[Stmt]) -> Result<()> {
+        let mut diverged = None;
+        let mut reported = false;
+        
+        for stmt in block {
+            // Only the first unreachable statement of a block is reported
+            if diverged.is_some() && !reported {
+                self.unreachable_after = diverged;
+                reported = true;
+            }
+            
+            self.lint_stmt(stmt)?;
+            
+            if diverged.is_none() {
+                diverged = self.divergence(stmt);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Lint a block in its own scope
+    fn lint_scoped_block(&mut self, block: &[Stmt]) -> Result<()> {
+        self.push_scope();
+        let result = self.lint_block(block);
+        self.pop_scope();
+        result
+    }
+    
+    fn lint_stmt(&mut self, stmt: &Stmt) -> Result<()> {
+        if let Stmt::Attributed { attributes, stmt } = stmt {
+            self.push_attributes(attributes)?;
+            let result = self.lint_stmt(stmt);
+            self.levels.pop();
+            return result;
+        }
+        
+        if let Stmt::Located { stmt, .. } = stmt {
+            return self.lint_stmt(stmt);
+        }
+        
+        if let Some(reason) = self.unreachable_after.take() {
+            let message = format!("Unreachable statement after {}{}", reason, self.context());
+            self.report(&UNREACHABLE_CODE, message);
+        }
+        
+        match stmt {
+            Stmt::Expr(expr) => self.lint_expr(expr)?,
+            Stmt::Let { name, value, .. } => {
+                self.lint_expr(value)?;
+                self.declare(name, false);
+            },
+            Stmt::Const { value, .. } => self.lint_expr(value)?,
+            Stmt::Assign { target, value } => {
+                self.lint_expr(value)?;
+                
+                // Writing to a variable is not a use of it
+                if !matches!(target, Expr::Identifier(_)) {
+                    self.lint_expr(target)?;
+                }
+            },
+            Stmt::If { condition, then_block, else_block } => {
+                self.lint_expr(condition)?;
+                self.lint_scoped_block(then_block)?;
+                
+                if let Some(else_block) = else_block {
+                    self.lint_scoped_block(else_block)?;
+                }
+            },
+            Stmt::While { condition, body } => {
+                self.lint_expr(condition)?;
+                self.lint_scoped_block(body)?;
+            },
+            Stmt::For { var, iterator, body } => {
+                self.lint_expr(iterator)?;
+                self.push_scope();
+                self.declare(var, false);
+                let result = self.lint_block(body);
+                self.pop_scope();
+                result?;
+            },
+            Stmt::Match { expr, arms } => {
+                self.lint_expr(expr)?;
+                
+                for (index, (pattern, body)) in arms.iter().enumerate() {
+                    self.check_arm(expr, index);
+                    self.push_scope();
+                    self.declare_pattern(pattern);
+                    let result = self.lint_block(body);
+                    self.pop_scope();
+                    result?;
+                }
+            },
+            Stmt::Return(value) => {
+                if let Some(value) = value {
+                    self.lint_expr(value)?;
+                }
+            },
+            Stmt::Break | Stmt::Continue => {},
+            Stmt::Function { name, params, body, .. } => {
+                let params: Vec<&str> = params.iter().map(|(param, _)| param.as_str()).collect();
+                self.lint_function(name.clone(), &params, body)?;
+            },
+            Stmt::Struct { name, methods, .. } => self.lint_methods(name, methods)?,
+            Stmt::Impl { type_name, methods, .. } => self.lint_methods(type_name, methods)?,
+            Stmt::Trait { name, methods, .. } => {
+                for method in methods {
+                    if let Some(body) = &method.body {
+                        let params: Vec<&str> = method.params.iter().map(|(param, _)| param.as_str()).collect();
+                        self.lint_function(format!("{}::{}", name, method.name), &params, body)?;
+                    }
+                }
+            },
+            Stmt::Enum { .. } | Stmt::TypeAlias { .. } | Stmt::Extern { .. } => {},
+            Stmt::Import { items, .. } => {
+                for item in items {
+                    self.declare(item, true);
+                }
+            },
+            Stmt::Use { path, as_name } => {
+                let module_name = as_name.clone().unwrap_or_else(|| {
+                    path.split('.').last().unwrap_or(path).to_string()
+                });
+                
+                self.declare(&module_name, true);
+            },
+            Stmt::Try { block, catch_blocks, finally_block } => {
+                self.lint_scoped_block(block)?;
+                
+                for (pattern, body) in catch_blocks {
+                    self.push_scope();
+                    self.declare_pattern(pattern);
+                    let result = self.lint_block(body);
+                    self.pop_scope();
+                    result?;
+                }
+                
+                if let Some(finally_block) = finally_block {
+                    self.lint_scoped_block(finally_block)?;
+                }
+            },
+            Stmt::Async { block } => self.lint_scoped_block(block)?,
+            Stmt::Loop { body } => self.lint_scoped_block(body)?,
+            Stmt::Attributed { .. } | Stmt::Located { .. } => unreachable!("attributes and locations are handled above"),
+        }
+        
+        Ok(())
+    }
+    
+    fn lint_methods(&mut self, type_name: &str, methods: &[Stmt]) -> Result<()> {
+        for method in methods {
+            if let Stmt::Function { name, params, body, .. } = method.without_attributes() {
+                let params: Vec<&str> = params.iter().map(|(param, _)| param.as_str()).collect();
+                self.lint_function(format!("{}::{}", type_name, name), &params, body)?;
+            }
+        }
+        Ok(())
+    }
+    
+    fn lint_function(&mut self, name: String, params: &[&str], body: &[Stmt]) -> Result<()> {
+        let old_function_scope = self.function_scope;
+        self.function_scope = self.scopes.len();
+        self.functions.push(name);
+        self.push_scope();
+        
+        for param in params {
+            if *param != "self" {
+                self.declare(param, false);
+            }
+        }
+        
+        let result = self.lint_block(body);
+        
+        self.pop_scope();
+        self.functions.pop();
+        self.function_scope = old_function_scope;
+        result
+    }
+    
+    fn lint_expr(&mut self, expr: &Expr) -> Result<()> {
+        match expr {
+            Expr::Literal(lit) => self.lint_literal(lit)?,
+            Expr::Identifier(name) => self.mark_used(name),
+            Expr::BinaryOp { left, right, .. } => {
+                self.lint_expr(left)?;
+                self.lint_expr(right)?;
+            },
+            Expr::UnaryOp { expr, .. } |
+            Expr::Await { expr } |
+            Expr::Try { expr } |
+            Expr::TypeCast { expr, .. } |
+            Expr::TypeTest { expr, .. } => self.lint_expr(expr)?,
+            Expr::Call { func, args } => {
+                self.lint_expr(func)?;
+                for arg in args {
+                    self.lint_expr(arg)?;
+                }
+            },
+            Expr::MethodCall { object, args, .. } => {
+                self.lint_expr(object)?;
+                for arg in args {
+                    self.lint_expr(arg)?;
+                }
+            },
+            Expr::Index { target, index } => {
+                self.lint_expr(target)?;
+                self.lint_expr(index)?;
+            },
+            Expr::FieldAccess { object, .. } => self.lint_expr(object)?,
+            Expr::Lambda { params, body, .. } => {
+                self.push_scope();
+                for (param, _) in params {
+                    self.declare(param, false);
+                }
+                let result = self.lint_expr(body);
+                self.pop_scope();
+                result?;
+            },
+            Expr::TernaryIf { condition, then_expr, else_expr } => {
+                self.lint_expr(condition)?;
+                self.lint_expr(then_expr)?;
+                self.lint_expr(else_expr)?;
+            },
+            Expr::RangeExpr { start, end, .. } => {
+                if let Some(start) = start {
+                    self.lint_expr(start)?;
+                }
+                if let Some(end) = end {
+                    self.lint_expr(end)?;
+                }
+            },
+            Expr::OptionalChain { expr, chain } => {
+                self.lint_expr(expr)?;
+                
+                for item in chain {
+                    match item {
+                        OptionalChainItem::Field(_) => {},
+                        OptionalChainItem::Method(_, args) => {
+                            for arg in args {
+                                self.lint_expr(arg)?;
+                            }
+                        },
+                        OptionalChainItem::Index(index) => self.lint_expr(index)?,
+                    }
+                }
+            },
+            Expr::ListComprehension { expr, iterable, var_name, condition } => {
+                self.lint_expr(iterable)?;
+                self.push_scope();
+                self.declare(var_name, false);
+                
+                let mut result = self.lint_expr(expr);
+                if let (Ok(()), Some(condition)) = (&result, condition) {
+                    result = self.lint_expr(condition);
+                }
+                
+                self.pop_scope();
+                result?;
+            },
+            Expr::Match { expr, arms } => {
+                self.lint_expr(expr)?;
+                
+                for (index, (pattern, body)) in arms.iter().enumerate() {
+                    self.check_arm(expr, index);
+                    self.push_scope();
+                    self.declare_pattern(pattern);
+                    let result = self.lint_expr(body);
+                    self.pop_scope();
+                    result?;
+                }
+            },
+            Expr::StructInit { fields, .. } => {
+                for (_, value) in fields {
+                    self.lint_expr(value)?;
+                }
+            },
+            Expr::PipelineChain { initial, chain } => {
+                self.lint_expr(initial)?;
+                for stage in chain {
+                    self.lint_expr(stage)?;
+                }
+            },
+            Expr::Block(stmts) => self.lint_scoped_block(stmts)?,
+        }
+        
+        Ok(())
+    }
+    
+    fn lint_literal(&mut self, lit: &Literal) -> Result<()> {
+        match lit {
+            Literal::List(items) | Literal::Tuple(items) => {
+                for item in items {
+                    self.lint_expr(item)?;
+                }
+            },
+            Literal::Dict(entries) => {
+                for (key, value) in entries {
+                    self.lint_expr(key)?;
+                    self.lint_expr(value)?;
+                }
+            },
+            Literal::Range { start, end, .. } => {
+                if let Some(start) = start {
+                    self.lint_expr(start)?;
+                }
+                if let Some(end) = end {
+                    self.lint_expr(end)?;
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+    
+    /// Report a match arm the type checker found to be unreachable
+    fn check_arm(&mut self, scrutinee: &Expr, index: usize) {
+        if self.unreachable_arms.contains(&(scrutinee as *const Expr as usize, index)) {
+            let message = format!("Unreachable match arm {}: earlier arms already cover it{}", index + 1, self.context());
+            self.report(&UNREACHABLE_PATTERNS, message);
+        }
+    }
+    
+    /// Describe why statements after `stmt` can never run
+    fn divergence(&self, stmt: &Stmt) -> Option<&'static str> {
+        match stmt.without_attributes() {
+            Stmt::Return(_) => Some("'return'"),
+            Stmt::Break => Some("'break'"),
+            Stmt::Continue => Some("'continue'"),
+            other if self.checker.stmt_diverges(other) => Some("a statement that never completes"),
+            _ => None,
+        }
+    }
+    
+    /// Apply the `allow`, `warn` and `deny` attributes of a statement
+    fn push_attributes(&mut self, attributes: &[Attribute]) -> Result<()> {
+        let mut levels = self.levels.last().cloned().unwrap_or_default();
+        
+        for attribute in attributes {
+            let level = match attribute.name.as_str() {
+                "allow" => LintLevel::Allow,
+                "warn" => LintLevel::Warn,
+                "deny" => LintLevel::Deny,
+                // Other attributes are not meant for the linter
+                _ => continue,
+            };
+            
+            for name in &attribute.args {
+                for lint in LintConfig::resolve(name)? {
+                    levels.insert(lint, level);
+                }
+            }
+        }
+        
+        self.levels.push(levels);
+        Ok(())
+    }
+    
+    fn level(&self, lint: &Lint) -> LintLevel {
+        self.levels.last()
+            .and_then(|levels| levels.get(lint.name).copied())
+            .unwrap_or(lint.default_level)
+    }
+    
+    fn report(&mut self, lint: &Lint, message: String) {
+        let level = self.level(lint);
+        self.report_at(lint, level, message);
+    }
+    
+    fn report_at(&mut self, lint: &Lint, level: LintLevel, message: String) {
+        if level != LintLevel::Allow {
+            self.diagnostics.push(LintDiagnostic {
+                lint: lint.name,
+                level,
+                message,
+            });
+        }
+    }
+    
+    /// Describe the enclosing function for messages
+    fn context(&self) -> String {
+        self.functions.last()
+            .map(|name| format!(" in function '{}'", name))
+            .unwrap_or_default()
+    }
+    
+    fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+    
+    /// Leave a scope, reporting the bindings in it that were never used
+    fn pop_scope(&mut self) {
+        for binding in self.scopes.pop().unwrap_or_default() {
+            if binding.used || binding.name.starts_with('_') {
+                continue;
+            }
+            
+            if binding.is_import {
+                let message = format!("Unused import '{}'", binding.name);
+                self.report_at(&UNUSED_IMPORTS, binding.level, message);
+            } else {
+                let message = format!("Unused variable '{}'{}", binding.name, binding.context);
+                self.report_at(&UNUSED_VARIABLES, binding.level, message);
+            }
+        }
+    }
+    
+    fn declare(&mut self, name: &str, is_import: bool) {
+        if !is_import && !name.starts_with('_') {
+            let shadows = self.scopes[self.function_scope..].iter()
+                .flatten()
+                .any(|binding| binding.name == name && !binding.is_import);
+            
+            if shadows {
+                let message = format!("'{}' shadows an earlier binding{}", name, self.context());
+                self.report(&SHADOWING, message);
+            }
+        }
+        
+        let level = self.level(if is_import { &UNUSED_IMPORTS } else { &UNUSED_VARIABLES });
+        let context = self.context();
+        
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Binding {
+                name: name.to_string(),
+                is_import,
+                used: false,
+                level,
+                context,
+            });
+        }
+    }
+    
+    fn declare_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Identifier(name) => self.declare(name, false),
+            Pattern::Destructure { fields, .. } => {
+                for (_, field) in fields {
+                    self.declare_pattern(field);
+                }
+            },
+            Pattern::EnumVariant { values, .. } => {
+                for value in values {
+                    self.declare_pattern(value);
+                }
+            },
+            // Every alternative binds the same names
+            Pattern::Or(alternatives) => {
+                if let Some(first) = alternatives.first() {
+                    self.declare_pattern(first);
+                }
+            },
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range { .. } => {},
+        }
+    }
+    
+    /// Mark the innermost binding a name refers to as used
+    fn mark_used(&mut self, name: &str) {
+        // `module::item` paths use the module binding
+        let name = name.split("::").next().unwrap_or(name);
+        
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.iter_mut().rev().find(|binding| binding.name == name) {
+                binding.used = true;
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    
+    fn lint_with(config: &LintConfig, source: &str) -> Result<Vec<LintDiagnostic>> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap().statements;
+        let mut checker = TypeChecker::new();
+        checker.check_program(&statements).unwrap();
+        Linter::new(config, &checker).lint_program(&statements)
+    }
+    
+    fn lint(source: &str) -> Vec<LintDiagnostic> {
+        lint_with(&LintConfig::new(), source).unwrap()
+    }
+    
+    fn findings(diagnostics: &[LintDiagnostic], lint: &Lint) -> Vec<String> {
+        diagnostics.iter()
+            .filter(|diagnostic| diagnostic.lint == lint.name)
+            .map(|diagnostic| diagnostic.message.clone())
+            .collect()
+    }
+    
+    #[test]
+    fn levels_apply_to_lints_groups_and_all() {
+        let mut config = LintConfig::new();
+        assert_eq!(config.level("shadowing"), LintLevel::Warn);
+        
+        config.set_level("unused", LintLevel::Deny).unwrap();
+        assert_eq!(config.level("unused_variables"), LintLevel::Deny);
+        assert_eq!(config.level("unused_imports"), LintLevel::Deny);
+        assert_eq!(config.level("shadowing"), LintLevel::Warn);
+        
+        config.set_level("all", LintLevel::Allow).unwrap();
+        assert!(LINTS.iter().all(|lint| config.level(lint.name) == LintLevel::Allow));
+        
+        assert!(config.set_level("no_such_lint", LintLevel::Warn).is_err());
+    }
+    
+    #[test]
+    fn unused_variables_and_parameters_are_reported() {
+        let diagnostics = lint("fn area(width: int, height: int) -> int {\n    let unused = 1;\n    let _ignored = 2;\n    return width * 2;\n}");
+        let unused = findings(&diagnostics, &UNUSED_VARIABLES);
+        
+        assert_eq!(unused.len(), 2);
+        assert!(unused.iter().any(|message| message.contains("'unused' in function 'area'")));
+        assert!(unused.iter().any(|message| message.contains("'height'")));
+    }
+    
+    #[test]
+    fn shadowing_is_reported_within_a_function() {
+        let diagnostics = lint("fn f() -> int {\n    let x = 1;\n    let x = x + 1;\n    return x;\n}");
+        let shadowing = findings(&diagnostics, &SHADOWING);
+        
+        assert_eq!(shadowing.len(), 1);
+        assert!(shadowing[0].contains("'x' shadows an earlier binding in function 'f'"));
+        
+        // Parameters of another function are not shadowed
+        let diagnostics = lint("let y = 1;\nfn g(y: int) -> int {\n    return y;\n}\nlet z = g(y);\nz;");
+        assert!(findings(&diagnostics, &SHADOWING).is_empty());
+    }
+    
+    #[test]
+    fn only_the_first_unreachable_statement_is_reported() {
+        let diagnostics = lint("fn f() -> int {\n    return 1;\n    let a = 2;\n    let b = 3;\n    return a + b;\n}");
+        let unreachable = findings(&diagnostics, &UNREACHABLE_CODE);
+        
+        // The statements after the first unreachable one are not reported again
+        assert_eq!(unreachable.len(), 1);
+        assert!(unreachable[0].contains("after 'return' in function 'f'"));
+    }
+    
+    #[test]
+    fn arms_the_checker_found_unreachable_are_reported() {
+        let diagnostics = lint("let n = 1;\nlet s = match n {\n    _ => 1,\n    2 => 3,\n};\ns;");
+        let unreachable = findings(&diagnostics, &UNREACHABLE_PATTERNS);
+        
+        assert_eq!(unreachable.len(), 1);
+        assert!(unreachable[0].contains("Unreachable match arm 2"));
+    }
+    
+    #[test]
+    fn attributes_override_the_configured_levels() {
+        let source = "#[allow(unused_variables)]\nfn f() {\n    let a = 1;\n}\n#[deny(unused)]\nfn g() {\n    let b = 2;\n}";
+        let diagnostics = lint(source);
+        
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].lint, "unused_variables");
+        assert_eq!(diagnostics[0].level, LintLevel::Deny);
+        assert!(diagnostics[0].message.contains("'b'"));
+        
+        // Attributes override the configured level
+        let mut config = LintConfig::new();
+        config.set_level("all", LintLevel::Deny).unwrap();
+        assert!(lint_with(&config, "#[allow(all)]\nfn f() {\n    let a = 1;\n}").unwrap().is_empty());
+        
+        assert!(lint_with(&config, "#[allow(bogus)]\nfn f() {}").is_err());
+    }
+}
@@ -5,10 +5,14 @@ use std::path::PathBuf;
 use std::process;
 use std::io::Write;
 use anyhow::{Result, Context};
-use shitrust::compiler::{Compiler, CompilerOptions, OptimizationLevel};
+use shitrust::build_cache::{BuildCache, DEFAULT_CACHE_DIR};
+use shitrust::compiler::{parse_source, Compiler, CompilerOptions, CrateType, EmitKind, OptimizationLevel, RelocationModel};
 use shitrust::error::ShitRustError;
+use shitrust::dump::{dump_ast, dump_tokens, dump_typed_ast, DumpFormat};
 use shitrust::formatter::Formatter;
-use shitrust::type_system::TypeChecker;
+use shitrust::interpreter::Interpreter;
+use shitrust::type_system::{TypeChecker, TypeRecording};
+use shitrust::lint::{LintConfig, LintLevel, Linter, LINTS};
 
 /// ShitRust programming language compiler and runtime
 #[derive(Parser)]
@@ -36,6 +40,10 @@ struct Cli {
     #[arg(long)]
     emit_llvm: bool,
 
+    /// Outputs to write when compiling, separated by commas
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "exe")]
+    emit: Vec<Emit>,
+    
     /// Disable colored output
     #[arg(long)]
     no_color: bool,
@@ -67,6 +75,90 @@ impl From<OptLevel> for OptimizationLevel {
     }
 }
 
+#[derive(Clone, Debug, ValueEnum)]
+enum Emit {
+    Asm,
+    Obj,
+    Bc,
+    Ll,
+    Lib,
+    Exe,
+}
+
+impl From<Emit> for EmitKind {
+    fn from(emit: Emit) -> Self {
+        match emit {
+            Emit::Asm => EmitKind::Asm,
+            Emit::Obj => EmitKind::Obj,
+            Emit::Bc => EmitKind::Bc,
+            Emit::Ll => EmitKind::Ll,
+            // A static library is the final output of the staticlib crate type, see `main`
+            Emit::Lib | Emit::Exe => EmitKind::Exe,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum Relocation {
+    Pic,
+    Static,
+}
+
+impl From<Relocation> for RelocationModel {
+    fn from(relocation: Relocation) -> Self {
+        match relocation {
+            Relocation::Pic => RelocationModel::Pic,
+            Relocation::Static => RelocationModel::Static,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum Crate {
+    Bin,
+    Cdylib,
+    Staticlib,
+}
+
+impl From<Crate> for CrateType {
+    fn from(crate_type: Crate) -> Self {
+        match crate_type {
+            Crate::Bin => CrateType::Bin,
+            Crate::Cdylib => CrateType::Cdylib,
+            Crate::Staticlib => CrateType::Staticlib,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum Stage {
+    /// The lexer's token stream
+    Tokens,
+    /// The parsed program
+    Ast,
+    /// The parsed program with the type of each expression, after type checking
+    TypedAst,
+    /// LLVM IR as the code generator produces it
+    Ir,
+    /// LLVM IR after the optimization passes of the `--optimization` level
+    Llvm,
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+impl From<Format> for DumpFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Text => DumpFormat::Text,
+            Format::Json => DumpFormat::Json,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Compile a ShitRust program
@@ -78,12 +170,52 @@ enum Commands {
         /// Output file
         #[arg(short, long, value_name = "FILE")]
         output: Option<PathBuf>,
+        
+        /// Target triple to compile for, such as aarch64-unknown-linux-gnu (default: the host)
+        #[arg(long, value_name = "TRIPLE")]
+        target: Option<String>,
+        
+        /// CPU to generate code for, or `native` for the host's
+        #[arg(long, value_name = "CPU")]
+        target_cpu: Option<String>,
+        
+        /// CPU features to enable or disable, such as +avx2,-sse4.1, or `native` for the host's
+        #[arg(long, value_name = "FEATURES")]
+        target_features: Option<String>,
+        
+        /// Relocation model of the generated code
+        #[arg(long, value_enum, default_value_t = Relocation::Pic)]
+        relocation_model: Relocation,
+        
+        /// Build an executable, or a shared or static library of the `#[export]` functions with a C header
+        #[arg(long, value_enum, default_value_t = Crate::Bin)]
+        crate_type: Crate,
+        
+        /// Program used to link executables (default: cc, or clang when cross-compiling)
+        #[arg(long, value_name = "PROGRAM")]
+        linker: Option<String>,
+        
+        /// Sysroot passed to the linker
+        #[arg(long, value_name = "DIR")]
+        sysroot: Option<PathBuf>,
+        
+        /// Compile from scratch, without reading or writing the build cache in target/shitrust
+        #[arg(long)]
+        no_cache: bool,
+        
+        /// Build an executable that interprets the program, for programs that cannot be compiled natively yet
+        #[arg(long)]
+        bundle: bool,
     },
     /// Run a ShitRust program
     Run {
         /// Input file
         #[arg(value_name = "FILE")]
         input: PathBuf,
+        
+        /// Compile the program in memory and run it natively instead of interpreting it
+        #[arg(long)]
+        jit: bool,
     },
     /// Format a ShitRust program
     Format {
@@ -100,6 +232,22 @@ enum Commands {
         /// Input file
         #[arg(value_name = "FILE")]
         input: PathBuf,
+        
+        /// Silence a lint or lint group (`all` for every lint)
+        #[arg(short = 'A', long, value_name = "LINT")]
+        allow: Vec<String>,
+        
+        /// Report a lint or lint group as a warning
+        #[arg(short = 'W', long, value_name = "LINT")]
+        warn: Vec<String>,
+        
+        /// Report a lint or lint group as an error; takes precedence over --allow and --warn
+        #[arg(short = 'D', long, value_name = "LINT")]
+        deny: Vec<String>,
+        
+        /// List the available lints and exit
+        #[arg(long)]
+        list_lints: bool,
     },
     /// Run a ShitRust program in async mode
     RunAsync {
@@ -107,11 +255,43 @@ enum Commands {
         #[arg(value_name = "FILE")]
         input: PathBuf,
     },
+    /// Print an intermediate representation of a ShitRust program
+    Dump {
+        /// Input file
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+        
+        /// Compilation stage to print
+        #[arg(long, value_enum)]
+        stage: Stage,
+        
+        /// Print tokens and ASTs as text or JSON; LLVM IR is always text
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Remove the build cache
+    Clean,
     /// Show information about ShitRust
     Info,
 }
 
 fn main() -> Result<()> {
+    // A bundled executable runs the program in it instead of taking commands
+    match shitrust::bundle::embedded_program() {
+        Ok(Some(program)) => {
+            if let Err(e) = Interpreter::new().interpret(&program) {
+                eprintln!("{}", e.format_error());
+                process::exit(1);
+            }
+            return Ok(());
+        },
+        Ok(None) => (),
+        Err(e) => {
+            eprintln!("{}", e.format_error());
+            process::exit(1);
+        }
+    }
+    
     let cli = Cli::parse();
     
     // Apply no-color setting if provided
@@ -120,24 +300,50 @@ fn main() -> Result<()> {
     }
     
     // Create compiler options
-    let options = CompilerOptions {
+    let mut options = CompilerOptions {
         verbose: cli.verbose,
         optimization_level: cli.optimization.into(),
         emit_debug_info: cli.debug,
         show_timings: cli.timings,
         emit_llvm_ir: cli.emit_llvm,
+        emit: Vec::new(),
         color_output: !cli.no_color,
         strict_type_checking: cli.strict_types,
+        ..CompilerOptions::default()
     };
     
+    for kind in cli.emit.iter().cloned().map(EmitKind::from) {
+        if !options.emit.contains(&kind) {
+            options.emit.push(kind);
+        }
+    }
+    
+    if let Commands::Compile { target, target_cpu, target_features, relocation_model, crate_type, linker, sysroot, no_cache, .. } = &cli.command {
+        options.target = target.clone();
+        options.target_cpu = target_cpu.clone();
+        options.target_features = target_features.clone();
+        options.relocation_model = relocation_model.clone().into();
+        // `--emit=lib` builds the program as a static library
+        options.crate_type = match crate_type {
+            Crate::Bin if cli.emit.iter().any(|emit| matches!(emit, Emit::Lib)) => CrateType::Staticlib,
+            Crate::Cdylib if cli.emit.iter().any(|emit| matches!(emit, Emit::Lib)) => {
+                return Err(anyhow::anyhow!("--emit=lib cannot be used with --crate-type cdylib"));
+            },
+            crate_type => crate_type.clone().into(),
+        };
+        options.linker = linker.clone();
+        options.sysroot = sysroot.clone();
+        options.cache_dir = (!no_cache).then(|| PathBuf::from(DEFAULT_CACHE_DIR));
+    }
+    
+    let crate_type = options.crate_type;
     let compiler = Compiler::with_options(options);
 
     let result = match &cli.command {
-        Commands::Compile { input, output } => {
+        Commands::Compile { input, output, target, bundle, .. } => {
             let output = output.clone().unwrap_or_else(|| {
-                let mut path = input.clone();
-                path.set_extension("exe");
-                path
+                let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+                input.with_file_name(crate_type.output_file_name(&stem, target.as_deref()))
             });
             
             println!("{} {} to {}", "Compiling".green().bold(), 
@@ -154,11 +360,7 @@ fn main() -> Result<()> {
                 println!("{} {}", "Type checking".green().bold(),
                     input.display().to_string().cyan());
                 
-                let mut lexer = shitrust::lexer::Lexer::with_filename(&source, filename.clone());
-                let tokens = lexer.scan_tokens()?;
-                
-                let mut parser = shitrust::parser::Parser::new(tokens);
-                let program = parser.parse()?;
+                let program = parse_source(&source, Some(filename.clone()), false)?;
                 
                 let mut type_checker = TypeChecker::new();
                 type_checker.check_program(&program.statements)?;
@@ -166,7 +368,13 @@ fn main() -> Result<()> {
                 println!("{}", "Type check passed".green().bold());
             }
             
-            match compiler.compile_with_filename(&source, &output, Some(filename)) {
+            let result = if *bundle {
+                compiler.bundle_with_filename(&source, &output, Some(filename))
+            } else {
+                compiler.compile_with_filename(&source, &output, Some(filename))
+            };
+            
+            match result {
                 Ok(_) => {
                     if !cli.verbose && !cli.timings {
                         println!("{} {}", "Successfully compiled".green().bold(), 
@@ -184,7 +392,7 @@ fn main() -> Result<()> {
                 }
             }
         }
-        Commands::Run { input } => {
+        Commands::Run { input, jit } => {
             println!("{} {}", "Running".green().bold(), 
                 input.display().to_string().cyan());
             
@@ -198,11 +406,7 @@ fn main() -> Result<()> {
                 println!("{} {}", "Type checking".green().bold(),
                     input.display().to_string().cyan());
                 
-                let mut lexer = shitrust::lexer::Lexer::with_filename(&source, filename.clone());
-                let tokens = lexer.scan_tokens()?;
-                
-                let mut parser = shitrust::parser::Parser::new(tokens);
-                let program = parser.parse()?;
+                let program = parse_source(&source, Some(filename.clone()), false)?;
                 
                 let mut type_checker = TypeChecker::new();
                 type_checker.check_program(&program.statements)?;
@@ -210,8 +414,15 @@ fn main() -> Result<()> {
                 println!("{}", "Type check passed".green().bold());
             }
             
-            match compiler.run_with_filename(&source, Some(filename)) {
-                Ok(_) => Ok(()),
+            let result = if *jit {
+                compiler.run_jit_with_filename(&source, Some(filename))
+            } else {
+                compiler.run_with_filename(&source, Some(filename)).map(|_| 0)
+            };
+            
+            match result {
+                Ok(0) => Ok(()),
+                Ok(exit_code) => process::exit(exit_code),
                 Err(e) => {
                     if let Some(sr_err) = e.downcast_ref::<ShitRustError>() {
                         eprintln!("{}", sr_err.format_error());
@@ -233,11 +444,7 @@ fn main() -> Result<()> {
             
             // Type check if strict types are enabled
             if cli.strict_types {
-                let mut lexer = shitrust::lexer::Lexer::with_filename(&source, filename.clone());
-                let tokens = lexer.scan_tokens()?;
-                
-                let mut parser = shitrust::parser::Parser::new(tokens);
-                let program = parser.parse()?;
+                let program = parse_source(&source, Some(filename.clone()), false)?;
                 
                 let mut type_checker = TypeChecker::new();
                 type_checker.check_program(&program.statements)?;
@@ -256,7 +463,22 @@ fn main() -> Result<()> {
                 }
             }
         }
-        Commands::Check { input } => {
+        Commands::Check { input, allow, warn, deny, list_lints } => {
+            if *list_lints {
+                println!("{}", "Lints:".yellow().bold());
+                for lint in LINTS {
+                    println!("  {:<22} {:<12} {}", lint.name.cyan(), lint.group, lint.description);
+                }
+                return Ok(());
+            }
+            
+            let mut lint_config = LintConfig::new();
+            for (names, level) in [(allow, LintLevel::Allow), (warn, LintLevel::Warn), (deny, LintLevel::Deny)] {
+                for name in names {
+                    lint_config.set_level(name, level)?;
+                }
+            }
+            
             println!("{} {}", "Type checking".green().bold(), 
                 input.display().to_string().cyan());
             
@@ -265,15 +487,33 @@ fn main() -> Result<()> {
             
             let filename = input.to_string_lossy().to_string();
             
-            let mut lexer = shitrust::lexer::Lexer::with_filename(&source, filename);
-            let tokens = lexer.scan_tokens()?;
-            
-            let mut parser = shitrust::parser::Parser::new(tokens);
-            let program = parser.parse()?;
+            let program = parse_source(&source, Some(filename), false)?;
             
             let mut type_checker = TypeChecker::new();
-            match type_checker.check_program(&program.statements) {
+            let result = type_checker.check_program(&program.statements);
+            
+            match result {
                 Ok(_) => {
+                    // Lints only run on programs that type check
+                    let diagnostics = Linter::new(&lint_config, &type_checker)
+                        .lint_program(&program.statements)?;
+                    
+                    let mut denied = 0;
+                    for diagnostic in &diagnostics {
+                        if diagnostic.level == LintLevel::Deny {
+                            denied += 1;
+                            eprintln!("{}: {} [{}]", "Error".red().bold(), diagnostic.message, diagnostic.lint);
+                        } else {
+                            eprintln!("{}: {} [{}]", "Warning".yellow().bold(), diagnostic.message, diagnostic.lint);
+                        }
+                    }
+                    
+                    if denied > 0 {
+                        let e = ShitRustError::LintError(format!("{} denied lint finding(s)", denied));
+                        eprintln!("{}", e.format_error());
+                        return Err(e.into());
+                    }
+                    
                     println!("{}", "Type check passed. No errors found.".green().bold());
                     Ok(())
                 },
@@ -319,6 +559,55 @@ fn main() -> Result<()> {
                 }
             }
         },
+        Commands::Dump { input, stage, format } => {
+            let source = fs::read_to_string(input)
+                .with_context(|| format!("Failed to read file: {}", input.display()))?;
+            
+            let filename = input.to_string_lossy().to_string();
+            let format = DumpFormat::from(format.clone());
+            
+            let dumped = match stage {
+                Stage::Ir | Stage::Llvm if format == DumpFormat::Json => {
+                    Err(anyhow::anyhow!("LLVM IR can only be dumped as text"))
+                },
+                Stage::Ir => compiler.llvm_ir_with_filename(&source, Some(filename), false),
+                Stage::Llvm => compiler.llvm_ir_with_filename(&source, Some(filename), true),
+                Stage::Tokens | Stage::Ast | Stage::TypedAst => dump_front_end(&source, filename, stage, format),
+            };
+            
+            match dumped {
+                Ok(dumped) => {
+                    std::io::stdout().write_all(dumped.as_bytes())
+                        .context("Failed to write to stdout")?;
+                    Ok(())
+                },
+                Err(e) => {
+                    if let Some(sr_err) = e.downcast_ref::<ShitRustError>() {
+                        eprintln!("{}", sr_err.format_error());
+                    } else {
+                        eprintln!("{}: {}", "Error".red().bold(), e);
+                    }
+                    Err(e)
+                }
+            }
+        },
+        Commands::Clean => {
+            let cache = BuildCache::new(DEFAULT_CACHE_DIR);
+            match cache.clean() {
+                Ok(true) => {
+                    println!("{} {}", "Removed".green().bold(), cache.dir().display().to_string().cyan());
+                    Ok(())
+                },
+                Ok(false) => {
+                    println!("Nothing to clean in {}", cache.dir().display().to_string().cyan());
+                    Ok(())
+                },
+                Err(e) => {
+                    eprintln!("{}", e.format_error());
+                    Err(e.into())
+                }
+            }
+        },
         Commands::Info => {
             println!("{}", "ShitRust Programming Language".green().bold());
             println!("Version: {}", env!("CARGO_PKG_VERSION").cyan());
@@ -349,6 +638,8 @@ fn main() -> Result<()> {
             println!("  Type check: {} examples/hello.sr", "shitrust check".cyan());
             println!("  Run async:  {} examples/async.sr", "shitrust run-async".cyan());
             println!("  Format:     {} -i examples/hello.sr", "shitrust format".cyan());
+            println!("  Dump AST:   {} --stage ast examples/hello.sr", "shitrust dump".cyan());
+            println!("  Clean:      {}", "shitrust clean".cyan());
             
             println!("\n{}:", "More Information".yellow().bold());
             println!("  Website: {}", "https://shitrust-lang.org".cyan());
@@ -367,4 +658,23 @@ fn main() -> Result<()> {
             std::process::exit(1);
         }
     }
-} 
+}
+
+/// Print the tokens, AST or typed AST of a program
+fn dump_front_end(source: &str, filename: String, stage: &Stage, format: DumpFormat) -> Result<String> {
+    if let Stage::Tokens = stage {
+        let tokens = shitrust::lexer::Lexer::with_filename(source, filename).scan_tokens()?;
+        return Ok(dump_tokens(&tokens, format)?);
+    }
+    
+    let program = parse_source(source, Some(filename), false)?;
+    if let Stage::Ast = stage {
+        return Ok(dump_ast(&program, format)?);
+    }
+    
+    // Only the dump pays for recording the type of every expression
+    let mut type_checker = TypeChecker::new();
+    type_checker.record_types(TypeRecording::All);
+    type_checker.check_program(&program.statements)?;
+    Ok(dump_typed_ast(&program, &type_checker, format)?)
+}
@@ -69,42 +69,43 @@ impl Module {
             interpreter.execute_statement(&stmt)?;
             
             // Check for exports
-            if let Stmt::Function { name, is_public, .. } = &stmt {
+            let stmt = stmt.without_attributes();
+            if let Stmt::Function { name, is_public, .. } = stmt {
                 if *is_public {
                     // Add to exports if it's public
                     if let Some(value) = interpreter.get_value(name) {
                         self.exports.insert(name.clone(), value);
                     }
                 }
-            } else if let Stmt::Struct { name, is_public, .. } = &stmt {
+            } else if let Stmt::Struct { name, is_public, .. } = stmt {
                 if *is_public {
                     // Add to exports if it's public
                     if let Some(value) = interpreter.get_value(name) {
                         self.exports.insert(name.clone(), value);
                     }
                 }
-            } else if let Stmt::Enum { name, is_public, .. } = &stmt {
+            } else if let Stmt::Enum { name, is_public, .. } = stmt {
                 if *is_public {
                     // Add to exports if it's public
                     if let Some(value) = interpreter.get_value(name) {
                         self.exports.insert(name.clone(), value);
                     }
                 }
-            } else if let Stmt::Trait { name, is_public, .. } = &stmt {
+            } else if let Stmt::Trait { name, is_public, .. } = stmt {
                 if *is_public {
                     // Add to exports if it's public
                     if let Some(value) = interpreter.get_value(name) {
                         self.exports.insert(name.clone(), value);
                     }
                 }
-            } else if let Stmt::Const { name, is_public, .. } = &stmt {
+            } else if let Stmt::Const { name, is_public, .. } = stmt {
                 if *is_public {
                     // Add to exports if it's public
                     if let Some(value) = interpreter.get_value(name) {
                         self.exports.insert(name.clone(), value);
                     }
                 }
-            } else if let Stmt::TypeAlias { name, is_public, .. } = &stmt {
+            } else if let Stmt::TypeAlias { name, is_public, .. } = stmt {
                 if *is_public {
                     // Add to exports if it's public
                     if let Some(value) = interpreter.get_value(&format!("type:{}", name)) {
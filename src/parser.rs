@@ -1,10 +1,13 @@
-use crate::ast::{Expr, Literal, Stmt, Program, BinOp, UnaryOp, Type};
+use crate::ast::{Expr, Literal, Stmt, Program, BinOp, UnaryOp, Type, Pattern, OptionalChainItem, Attribute, ExternFunction};
 use crate::error::ShitRustError;
 use crate::lexer::{Token, TokenType};
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    record_locations: bool,
+    // Set while parsing a `match` head, where a `{` after a type name starts the arms
+    no_struct_literal: bool,
 }
 
 impl Parser {
@@ -12,6 +15,18 @@ impl Parser {
         Parser {
             tokens,
             current: 0,
+            record_locations: false,
+            no_struct_literal: false,
+        }
+    }
+    
+    /// Create a parser that wraps every statement in `Stmt::Located`, for debug info
+    pub fn with_locations(tokens: Vec<Token>) -> Self {
+        Parser {
+            tokens,
+            current: 0,
+            record_locations: true,
+            no_struct_literal: false,
         }
     }
 
@@ -29,7 +44,37 @@ impl Parser {
     }
     
     fn declaration(&mut self) -> Result<Stmt, ShitRustError> {
-        if self.match_token(&[TokenType::Let]) {
+        let start = self.peek();
+        let (line, column) = (start.line, start.column);
+        let stmt = self.unlocated_declaration()?;
+        Ok(self.located(line, column, stmt))
+    }
+    
+    // Wraps a statement in its source location if locations are being recorded
+    fn located(&self, line: usize, column: usize, stmt: Stmt) -> Stmt {
+        if self.record_locations {
+            Stmt::Located { line, column, stmt: Box::new(stmt) }
+        } else {
+            stmt
+        }
+    }
+    
+    fn unlocated_declaration(&mut self) -> Result<Stmt, ShitRustError> {
+        if self.check(TokenType::Hash) {
+            return self.attributed_declaration();
+        } else if self.match_token(&[TokenType::Pub]) {
+            // The declaration finds the `pub` before its keyword with `previous_was`
+            let declarations = [TokenType::Fn, TokenType::Struct, TokenType::Enum, TokenType::Trait, TokenType::Type, TokenType::Const];
+            if !declarations.iter().any(|keyword| self.check(keyword.clone())) {
+                let token = self.peek();
+                return Err(ShitRustError::SyntaxError {
+                    line: token.line,
+                    column: token.column,
+                    message: "Expected a function, struct, enum, trait, type or const after 'pub'".to_string(),
+                });
+            }
+            return self.unlocated_declaration();
+        } else if self.match_token(&[TokenType::Let]) {
             return self.var_declaration();
         } else if self.match_token(&[TokenType::Fn]) {
             return self.function();
@@ -53,11 +98,64 @@ impl Parser {
             }
         } else if self.match_token(&[TokenType::Use]) {
             return self.use_declaration();
+        } else if self.match_token(&[TokenType::Extern]) {
+            return self.extern_block();
         }
         
         self.statement()
     }
     
+    /// Parse `#[name(arg, ...)]` attributes followed by the declaration they apply to.
+    /// Arguments are identifiers or strings, e.g. `#[link("./libgeometry.so")]`.
+    fn attributed_declaration(&mut self) -> Result<Stmt, ShitRustError> {
+        let mut attributes = Vec::new();
+        
+        while self.match_token(&[TokenType::Hash]) {
+            self.consume(TokenType::LeftBracket, "Expected '[' after '#'")?;
+            
+            loop {
+                let name = self.consume(TokenType::Identifier, "Expected attribute name")?.lexeme;
+                let mut args = Vec::new();
+                
+                if self.match_token(&[TokenType::LeftParen]) {
+                    if !self.check(TokenType::RightParen) {
+                        loop {
+                            if !self.match_token(&[TokenType::Identifier, TokenType::StringLiteral]) {
+                                let token = self.peek();
+                                return Err(ShitRustError::SyntaxError {
+                                    line: token.line,
+                                    column: token.column,
+                                    message: "Expected attribute argument".to_string(),
+                                });
+                            }
+                            args.push(self.previous().lexeme);
+                            
+                            if !self.match_token(&[TokenType::Comma]) {
+                                break;
+                            }
+                        }
+                    }
+                    self.consume(TokenType::RightParen, "Expected ')' after attribute arguments")?;
+                }
+                
+                attributes.push(Attribute { name, args });
+                
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            
+            self.consume(TokenType::RightBracket, "Expected ']' after attribute")?;
+        }
+        
+        let stmt = self.declaration()?;
+        
+        Ok(Stmt::Attributed {
+            attributes,
+            stmt: Box::new(stmt),
+        })
+    }
+    
     fn var_declaration(&mut self) -> Result<Stmt, ShitRustError> {
         // Parse variable name
         let name = self.consume(TokenType::Identifier, "Expected variable name")?;
@@ -87,50 +185,43 @@ impl Parser {
     }
     
     fn function(&mut self) -> Result<Stmt, ShitRustError> {
-        // Parse function name
-        let name = self.consume(TokenType::Identifier, "Expected function name")?;
-        let name_str = name.lexeme.clone();
-        
-        // Parse parameters
-        self.consume(TokenType::LeftParen, "Expected '(' after function name")?;
-        let mut params = Vec::new();
+        self.function_with_async(false)
+    }
+    
+    // Parses a type, including unions such as `int | string`; a `none` member makes the union optional
+    fn parse_type(&mut self) -> Result<Type, ShitRustError> {
+        let mut members = Vec::new();
+        let mut optional = false;
         
-        if !self.check(TokenType::RightParen) {
-            loop {
-                let param_name = self.consume(TokenType::Identifier, "Expected parameter name")?;
-                self.consume(TokenType::Colon, "Expected ':' after parameter name")?;
-                let param_type = self.parse_type()?;
-                
-                params.push((param_name.lexeme.clone(), param_type));
-                
-                if !self.match_token(&[TokenType::Comma]) {
-                    break;
-                }
+        loop {
+            if self.match_token(&[TokenType::None]) {
+                optional = true;
+            } else {
+                members.push(self.parse_single_type()?);
+            }
+            
+            if !self.match_token(&[TokenType::Pipe]) {
+                break;
             }
         }
         
-        self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
-        
-        // Parse return type
-        let return_type = if self.match_token(&[TokenType::Arrow]) {
-            self.parse_type()?
-        } else {
-            Type::Void
+        let typ = match members.len() {
+            0 => {
+                let token = self.previous();
+                return Err(ShitRustError::SyntaxError {
+                    line: token.line,
+                    column: token.column,
+                    message: "Expected a type other than 'none'".to_string(),
+                });
+            },
+            1 => members.remove(0),
+            _ => Type::Union(members),
         };
         
-        // Parse function body
-        self.consume(TokenType::LeftBrace, "Expected '{' before function body")?;
-        let body = self.block()?;
-        
-        Ok(Stmt::Function {
-            name: name_str,
-            params,
-            return_type,
-            body,
-        })
+        Ok(if optional { Type::Option(Box::new(typ)) } else { typ })
     }
     
-    fn parse_type(&mut self) -> Result<Type, ShitRustError> {
+    fn parse_single_type(&mut self) -> Result<Type, ShitRustError> {
         let type_token = self.advance();
         
         match type_token.token_type {
@@ -140,6 +231,66 @@ impl Parser {
             TokenType::String => Ok(Type::String),
             TokenType::Char => Ok(Type::Char),
             TokenType::Void => Ok(Type::Void),
+            // Function types: `fn(int, int) -> int`
+            TokenType::Fn => {
+                self.consume(TokenType::LeftParen, "Expected '(' after 'fn' in function type")?;
+                let mut param_types = Vec::new();
+                
+                if !self.check(TokenType::RightParen) {
+                    loop {
+                        param_types.push(self.parse_type()?);
+                        
+                        if !self.match_token(&[TokenType::Comma]) {
+                            break;
+                        }
+                    }
+                }
+                
+                self.consume(TokenType::RightParen, "Expected ')' after function type parameters")?;
+                
+                // A union after the arrow would be ambiguous, so the return type is a single type
+                let return_type = if self.match_token(&[TokenType::Arrow]) {
+                    self.parse_single_type()?
+                } else {
+                    Type::Void
+                };
+                
+                Ok(Type::Function(param_types, Box::new(return_type)))
+            },
+            TokenType::LeftBracket => {
+                let element_type = self.parse_type()?;
+                self.consume(TokenType::RightBracket, "Expected ']' after list element type")?;
+                Ok(Type::List(Box::new(element_type)))
+            },
+            TokenType::Identifier if type_token.lexeme == "never" => Ok(Type::Never),
+            TokenType::Identifier if type_token.lexeme == "bigint" => Ok(Type::BigInt),
+            TokenType::Identifier if type_token.lexeme == "decimal" => Ok(Type::Decimal),
+            TokenType::Identifier if type_token.lexeme == "ptr" => Ok(Type::Pointer),
+            TokenType::Identifier if type_token.lexeme == "c_int" => Ok(Type::CInt),
+            TokenType::Identifier if self.check(TokenType::Less) => {
+                self.advance();
+                let mut type_args = Vec::new();
+                
+                loop {
+                    type_args.push(self.parse_type()?);
+                    
+                    if !self.match_token(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+                
+                self.consume(TokenType::Greater, "Expected '>' after type arguments")?;
+                
+                let mut args = type_args.into_iter();
+                match (type_token.lexeme.as_str(), args.len()) {
+                    ("Option", 1) => Ok(Type::Option(Box::new(args.next().unwrap()))),
+                    ("List", 1) => Ok(Type::List(Box::new(args.next().unwrap()))),
+                    ("Future", 1) => Ok(Type::Future(Box::new(args.next().unwrap()))),
+                    ("Result", 2) => Ok(Type::Result(Box::new(args.next().unwrap()), Box::new(args.next().unwrap()))),
+                    ("Dict", 2) => Ok(Type::Dict(Box::new(args.next().unwrap()), Box::new(args.next().unwrap()))),
+                    (name, _) => Ok(Type::Generic(name.to_string(), args.collect())),
+                }
+            },
             TokenType::Identifier => Ok(Type::Custom(type_token.lexeme.clone())),
             _ => Err(ShitRustError::SyntaxError {
                 line: type_token.line,
@@ -158,6 +309,14 @@ impl Parser {
             return self.for_statement();
         } else if self.match_token(&[TokenType::Return]) {
             return self.return_statement();
+        } else if self.match_token(&[TokenType::Break]) {
+            self.consume(TokenType::Semicolon, "Expected ';' after 'break'")?;
+            return Ok(Stmt::Break);
+        } else if self.match_token(&[TokenType::Continue]) {
+            self.consume(TokenType::Semicolon, "Expected ';' after 'continue'")?;
+            return Ok(Stmt::Continue);
+        } else if self.match_token(&[TokenType::Match]) {
+            return self.match_statement();
         } else if self.match_token(&[TokenType::LeftBrace]) {
             let statements = self.block()?;
             // In our AST we don't have a block statement, so we'll wrap it in a dummy if
@@ -168,12 +327,60 @@ impl Parser {
             });
         }
         
-        // Default: expression statement
-        let expr = self.expression()?;
+        // Default: expression statement, or an assignment to a place expression
+        let expr = self.nullish_coalescing()?;
+        
+        if self.match_token(&[
+            TokenType::Equal,
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+            TokenType::PercentEqual,
+        ]) {
+            return self.assignment_statement(expr);
+        }
+        
         self.consume(TokenType::Semicolon, "Expected ';' after expression")?;
         Ok(Stmt::Expr(expr))
     }
     
+    fn assignment_statement(&mut self, target: Expr) -> Result<Stmt, ShitRustError> {
+        let operator = self.previous().clone();
+        
+        if !matches!(target, Expr::Identifier(_) | Expr::FieldAccess { .. } | Expr::Index { .. }) {
+            return Err(ShitRustError::SyntaxError {
+                line: operator.line,
+                column: operator.column,
+                message: "Invalid assignment target".to_string(),
+            });
+        }
+        
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expected ';' after assignment")?;
+        
+        // Compound assignments desugar to `target = target <op> value`
+        let op = match operator.token_type {
+            TokenType::PlusEqual => Some(BinOp::Add),
+            TokenType::MinusEqual => Some(BinOp::Sub),
+            TokenType::StarEqual => Some(BinOp::Mul),
+            TokenType::SlashEqual => Some(BinOp::Div),
+            TokenType::PercentEqual => Some(BinOp::Mod),
+            _ => None,
+        };
+        
+        let value = match op {
+            Some(op) => Expr::BinaryOp {
+                left: Box::new(target.clone()),
+                op,
+                right: Box::new(value),
+            },
+            None => value,
+        };
+        
+        Ok(Stmt::Assign { target, value })
+    }
+    
     fn if_statement(&mut self) -> Result<Stmt, ShitRustError> {
         self.consume(TokenType::LeftParen, "Expected '(' after 'if'")?;
         let condition = self.expression()?;
@@ -254,6 +461,180 @@ impl Parser {
         Ok(Stmt::Return(value))
     }
     
+    fn match_statement(&mut self) -> Result<Stmt, ShitRustError> {
+        let expr = self.with_struct_literals(false, Self::expression)?;
+        self.consume(TokenType::LeftBrace, "Expected '{' after match expression")?;
+        
+        let mut arms = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let pattern = self.pattern()?;
+            self.consume(TokenType::FatArrow, "Expected '=>' after match pattern")?;
+            
+            // Arms are either a block or a single expression
+            let body = if self.match_token(&[TokenType::LeftBrace]) {
+                self.block()?
+            } else {
+                vec![Stmt::Expr(self.expression()?)]
+            };
+            arms.push((pattern, body));
+            
+            if !self.match_token(&[TokenType::Comma]) && !self.previous_is(TokenType::RightBrace) {
+                break;
+            }
+        }
+        
+        self.consume(TokenType::RightBrace, "Expected '}' after match arms")?;
+        
+        Ok(Stmt::Match { expr, arms })
+    }
+    
+    fn match_expression(&mut self) -> Result<Expr, ShitRustError> {
+        let expr = self.with_struct_literals(false, Self::expression)?;
+        self.consume(TokenType::LeftBrace, "Expected '{' after match expression")?;
+        
+        let mut arms = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let pattern = self.pattern()?;
+            self.consume(TokenType::FatArrow, "Expected '=>' after match pattern")?;
+            arms.push((pattern, Box::new(self.expression()?)));
+            
+            if !self.match_token(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        
+        self.consume(TokenType::RightBrace, "Expected '}' after match arms")?;
+        
+        Ok(Expr::Match {
+            expr: Box::new(expr),
+            arms,
+        })
+    }
+    
+    fn pattern(&mut self) -> Result<Pattern, ShitRustError> {
+        let first = self.single_pattern()?;
+        
+        if !self.check(TokenType::Pipe) {
+            return Ok(first);
+        }
+        
+        let mut alternatives = vec![first];
+        while self.match_token(&[TokenType::Pipe]) {
+            alternatives.push(self.single_pattern()?);
+        }
+        
+        Ok(Pattern::Or(alternatives))
+    }
+    
+    fn single_pattern(&mut self) -> Result<Pattern, ShitRustError> {
+        // `ok(..)` and `err(..)` are keywords rather than identifiers
+        if self.match_token(&[TokenType::Ok, TokenType::Err]) {
+            let name = if self.previous().token_type == TokenType::Ok { "Ok" } else { "Err" };
+            let values = self.pattern_list()?;
+            return Ok(Pattern::EnumVariant { name: name.to_string(), values });
+        }
+        
+        if self.match_token(&[TokenType::Identifier]) {
+            let mut name = self.previous().lexeme.clone();
+            if name == "_" {
+                return Ok(Pattern::Wildcard);
+            }
+            
+            while self.match_token(&[TokenType::DoubleColon]) {
+                let segment = self.consume(TokenType::Identifier, "Expected identifier after '::'")?;
+                name.push_str("::");
+                name.push_str(&segment.lexeme);
+            }
+            
+            if self.check(TokenType::LeftParen) {
+                let values = self.pattern_list()?;
+                return Ok(Pattern::EnumVariant { name, values });
+            }
+            
+            if self.match_token(&[TokenType::LeftBrace]) {
+                let mut fields = Vec::new();
+                
+                while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+                    let field = self.consume(TokenType::Identifier, "Expected field name in pattern")?;
+                    let field_pattern = if self.match_token(&[TokenType::Colon]) {
+                        self.pattern()?
+                    } else {
+                        Pattern::Identifier(field.lexeme.clone())
+                    };
+                    fields.push((field.lexeme.clone(), field_pattern));
+                    
+                    if !self.match_token(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+                
+                self.consume(TokenType::RightBrace, "Expected '}' after struct pattern")?;
+                return Ok(Pattern::Destructure { name, fields });
+            }
+            
+            // Paths and capitalised names refer to unit variants; anything else binds
+            let is_variant = name.contains("::") || name.chars().next().map_or(false, |c| c.is_uppercase());
+            return Ok(if is_variant {
+                Pattern::EnumVariant { name, values: Vec::new() }
+            } else {
+                Pattern::Identifier(name)
+            });
+        }
+        
+        let start = self.literal_pattern()?;
+        
+        // Range patterns: `1..10` and `'a'..='z'`
+        if self.check(TokenType::Dot) && self.peek_next_is(TokenType::Dot) {
+            self.advance();
+            self.advance();
+            let inclusive = self.match_token(&[TokenType::Equal]);
+            let end = self.literal_pattern()?;
+            
+            return Ok(Pattern::Range { start, end, inclusive });
+        }
+        
+        Ok(Pattern::Literal(start))
+    }
+    
+    fn pattern_list(&mut self) -> Result<Vec<Pattern>, ShitRustError> {
+        let mut values = Vec::new();
+        
+        if self.match_token(&[TokenType::LeftParen]) {
+            if !self.check(TokenType::RightParen) {
+                loop {
+                    values.push(self.pattern()?);
+                    
+                    if !self.match_token(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after patterns")?;
+        }
+        
+        Ok(values)
+    }
+    
+    fn literal_pattern(&mut self) -> Result<Literal, ShitRustError> {
+        let negative = self.match_token(&[TokenType::Minus]);
+        
+        match self.primary()? {
+            Expr::Literal(Literal::Int(value)) => Ok(Literal::Int(if negative { -value } else { value })),
+            Expr::Literal(Literal::Float(value)) => Ok(Literal::Float(if negative { -value } else { value })),
+            Expr::Literal(Literal::BigInt(digits)) if negative => Ok(Literal::BigInt(format!("-{}", digits))),
+            Expr::Literal(Literal::Decimal(digits)) if negative => Ok(Literal::Decimal(format!("-{}", digits))),
+            Expr::Literal(lit) if !negative => Ok(lit),
+            _ => {
+                let token = self.previous();
+                Err(ShitRustError::SyntaxError {
+                    line: token.line,
+                    column: token.column,
+                    message: format!("Expected pattern, got '{}'", token.lexeme),
+                })
+            }
+        }
+    }
+    
     fn block(&mut self) -> Result<Vec<Stmt>, ShitRustError> {
         let mut statements = Vec::new();
         
@@ -271,7 +652,7 @@ impl Parser {
     }
     
     fn assignment(&mut self) -> Result<Expr, ShitRustError> {
-        let expr = self.logical_or()?;
+        let expr = self.nullish_coalescing()?;
         
         if self.match_token(&[TokenType::Equal]) {
             let value = self.assignment()?;
@@ -299,6 +680,21 @@ impl Parser {
         Ok(expr)
     }
     
+    fn nullish_coalescing(&mut self) -> Result<Expr, ShitRustError> {
+        let mut expr = self.logical_or()?;
+        
+        while self.match_token(&[TokenType::DoubleQuestion]) {
+            let right = self.logical_or()?;
+            expr = Expr::BinaryOp {
+                left: Box::new(expr),
+                op: BinOp::NullishCoalescing,
+                right: Box::new(right),
+            };
+        }
+        
+        Ok(expr)
+    }
+    
     fn logical_or(&mut self) -> Result<Expr, ShitRustError> {
         let mut expr = self.logical_and()?;
         
@@ -353,6 +749,16 @@ impl Parser {
     fn comparison(&mut self) -> Result<Expr, ShitRustError> {
         let mut expr = self.term()?;
         
+        // Type tests: `value is string`
+        while self.check(TokenType::Identifier) && self.peek().lexeme == "is" {
+            self.advance();
+            let target_type = self.parse_type()?;
+            expr = Expr::TypeTest {
+                expr: Box::new(expr),
+                target_type,
+            };
+        }
+        
         while self.match_token(&[
             TokenType::Greater,
             TokenType::GreaterEqual,
@@ -400,7 +806,7 @@ impl Parser {
     }
     
     fn factor(&mut self) -> Result<Expr, ShitRustError> {
-        let mut expr = self.unary()?;
+        let mut expr = self.cast()?;
         
         while self.match_token(&[TokenType::Star, TokenType::Slash, TokenType::Percent]) {
             let op = match self.previous().token_type {
@@ -410,7 +816,7 @@ impl Parser {
                 _ => unreachable!(),
             };
             
-            let right = self.unary()?;
+            let right = self.cast()?;
             expr = Expr::BinaryOp {
                 left: Box::new(expr),
                 op,
@@ -421,6 +827,21 @@ impl Parser {
         Ok(expr)
     }
     
+    // `value as type` binds tighter than arithmetic but looser than unary operators
+    fn cast(&mut self) -> Result<Expr, ShitRustError> {
+        let mut expr = self.unary()?;
+        
+        while self.match_token(&[TokenType::As]) {
+            let target_type = self.parse_single_type()?;
+            expr = Expr::TypeCast {
+                expr: Box::new(expr),
+                target_type,
+            };
+        }
+        
+        Ok(expr)
+    }
+    
     fn unary(&mut self) -> Result<Expr, ShitRustError> {
         if self.match_token(&[TokenType::Minus, TokenType::Not]) {
             let op = match self.previous().token_type {
@@ -447,9 +868,47 @@ impl Parser {
                 expr = self.finish_call(expr)?;
             } else if self.match_token(&[TokenType::Dot]) {
                 let name = self.consume(TokenType::Identifier, "Expected property name after '.'")?;
-                expr = Expr::FieldAccess {
-                    object: Box::new(expr),
-                    field: name.lexeme.clone(),
+                
+                if self.match_token(&[TokenType::LeftParen]) {
+                    let args = match self.finish_call(Expr::Identifier(name.lexeme.clone()))? {
+                        Expr::Call { args, .. } => args,
+                        _ => unreachable!(),
+                    };
+                    
+                    expr = Expr::MethodCall {
+                        object: Box::new(expr),
+                        method: name.lexeme.clone(),
+                        args,
+                    };
+                } else {
+                    expr = Expr::FieldAccess {
+                        object: Box::new(expr),
+                        field: name.lexeme.clone(),
+                    };
+                }
+            } else if self.match_token(&[TokenType::QuestionDot]) {
+                let name = self.consume(TokenType::Identifier, "Expected property name after '?.'")?;
+                let name = name.lexeme.clone();
+                
+                let item = if self.match_token(&[TokenType::LeftParen]) {
+                    match self.finish_call(Expr::Identifier(name.clone()))? {
+                        Expr::Call { args, .. } => OptionalChainItem::Method(name, args),
+                        _ => unreachable!(),
+                    }
+                } else {
+                    OptionalChainItem::Field(name)
+                };
+                
+                // Consecutive `?.` accesses share one chain so a missing value short-circuits the rest
+                expr = match expr {
+                    Expr::OptionalChain { expr, mut chain } => {
+                        chain.push(item);
+                        Expr::OptionalChain { expr, chain }
+                    },
+                    other => Expr::OptionalChain {
+                        expr: Box::new(other),
+                        chain: vec![item],
+                    },
                 };
             } else {
                 break;
@@ -472,7 +931,7 @@ impl Parser {
                     });
                 }
                 
-                arguments.push(self.expression()?);
+                arguments.push(self.with_struct_literals(true, Self::expression)?);
                 
                 if !self.match_token(&[TokenType::Comma]) {
                     break;
@@ -500,13 +959,23 @@ impl Parser {
         }
         
         if self.match_token(&[TokenType::IntLiteral]) {
-            let value = self.previous().lexeme.parse::<i64>().unwrap_or(0);
-            return Ok(Expr::Literal(Literal::Int(value)));
+            // Literals too large for int are promoted to bigint
+            let lexeme = self.previous().lexeme.clone();
+            return Ok(Expr::Literal(match lexeme.parse::<i64>() {
+                Ok(value) => Literal::Int(value),
+                Err(_) => Literal::BigInt(lexeme),
+            }));
         }
         if self.match_token(&[TokenType::FloatLiteral]) {
             let value = self.previous().lexeme.parse::<f64>().unwrap_or(0.0);
             return Ok(Expr::Literal(Literal::Float(value)));
         }
+        if self.match_token(&[TokenType::BigIntLiteral]) {
+            return Ok(Expr::Literal(Literal::BigInt(self.previous().lexeme.clone())));
+        }
+        if self.match_token(&[TokenType::DecimalLiteral]) {
+            return Ok(Expr::Literal(Literal::Decimal(self.previous().lexeme.clone())));
+        }
         if self.match_token(&[TokenType::StringLiteral]) {
             let text = self.previous().lexeme.clone();
             // Remove quotes from string literal
@@ -530,16 +999,46 @@ impl Parser {
             return Ok(Expr::Literal(Literal::Char(ch)));
         }
         
-        if self.match_token(&[TokenType::Identifier]) {
+        if self.match_token(&[TokenType::Match]) {
+            return self.match_expression();
+        }
+        
+        if self.match_token(&[TokenType::Self_, TokenType::This]) {
             return Ok(Expr::Identifier(self.previous().lexeme.clone()));
         }
         
+        if self.match_token(&[TokenType::Identifier]) {
+            // Paths such as `Color::Red` or `Rectangle::new` are kept as a single name
+            let mut name = self.previous().lexeme.clone();
+            while self.match_token(&[TokenType::DoubleColon]) {
+                let segment = self.consume(TokenType::Identifier, "Expected identifier after '::'")?;
+                name.push_str("::");
+                name.push_str(&segment.lexeme);
+            }
+            
+            // Capitalised names followed by `{ field:` or `{}` are struct literals, except in a
+            // `match` head, where the brace starts the arms
+            let is_type_name = name.chars().next().map_or(false, |c| c.is_uppercase());
+            if is_type_name && !name.contains("::") && !self.no_struct_literal && self.struct_literal_follows() {
+                self.advance();
+                return self.struct_init(name);
+            }
+            
+            return Ok(Expr::Identifier(name));
+        }
+        
         if self.match_token(&[TokenType::LeftParen]) {
-            let expr = self.expression()?;
+            // Parentheses end the expression, so struct literals are unambiguous in them
+            let expr = self.with_struct_literals(true, Self::expression)?;
             self.consume(TokenType::RightParen, "Expected ')' after expression")?;
             return Ok(expr);
         }
         
+        // `||` is lexed as a single token, which starts a lambda without parameters
+        if self.match_token(&[TokenType::Pipe, TokenType::Or]) {
+            return self.lambda();
+        }
+        
         Err(ShitRustError::SyntaxError {
             line: self.peek().line,
             column: self.peek().column,
@@ -547,6 +1046,96 @@ impl Parser {
         })
     }
     
+    // Whether the next tokens are `{ name:` or `{ }`, the start of a struct literal
+    fn struct_literal_follows(&self) -> bool {
+        let token_type = |offset: usize| self.tokens.get(self.current + offset).map(|token| &token.token_type);
+        
+        token_type(0) == Some(&TokenType::LeftBrace)
+            && match token_type(1) {
+                Some(TokenType::RightBrace) => true,
+                Some(TokenType::Identifier) => token_type(2) == Some(&TokenType::Colon),
+                _ => false,
+            }
+    }
+    
+    // Parses with struct literals allowed or not, restoring the previous setting afterwards
+    fn with_struct_literals<T>(
+        &mut self,
+        allowed: bool,
+        parse: impl FnOnce(&mut Self) -> Result<T, ShitRustError>,
+    ) -> Result<T, ShitRustError> {
+        let previous = std::mem::replace(&mut self.no_struct_literal, !allowed);
+        let result = parse(self);
+        self.no_struct_literal = previous;
+        result
+    }
+    
+    fn struct_init(&mut self, name: String) -> Result<Expr, ShitRustError> {
+        let mut fields = Vec::new();
+        
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let field_name = self.consume(TokenType::Identifier, "Expected field name in struct literal")?;
+            self.consume(TokenType::Colon, "Expected ':' after field name")?;
+            let value = self.expression()?;
+            
+            fields.push((field_name.lexeme.clone(), value));
+            
+            if !self.match_token(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        
+        self.consume(TokenType::RightBrace, "Expected '}' after struct literal")?;
+        
+        Ok(Expr::StructInit { name, fields })
+    }
+    
+    // Parses a lambda after its opening `|` or `||`: `|a: int, b| -> int { ... }` or `|x| x * 2`.
+    // Parameter types are single types, since a `|` after one closes the parameter list.
+    fn lambda(&mut self) -> Result<Expr, ShitRustError> {
+        let mut params = Vec::new();
+        let has_params = self.previous().token_type == TokenType::Pipe;
+        
+        if has_params && !self.check(TokenType::Pipe) {
+            loop {
+                let param_name = self.consume(TokenType::Identifier, "Expected parameter name in lambda")?;
+                let param_type = if self.match_token(&[TokenType::Colon]) {
+                    Some(self.parse_single_type()?)
+                } else {
+                    None
+                };
+                
+                params.push((param_name.lexeme.clone(), param_type));
+                
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        
+        if has_params {
+            self.consume(TokenType::Pipe, "Expected '|' after lambda parameters")?;
+        }
+        
+        let return_type = if self.match_token(&[TokenType::Arrow]) {
+            Some(self.parse_single_type()?)
+        } else {
+            None
+        };
+        
+        let body = if self.match_token(&[TokenType::LeftBrace]) {
+            Expr::Block(self.block()?)
+        } else {
+            self.expression()?
+        };
+        
+        Ok(Expr::Lambda {
+            params,
+            body: Box::new(body),
+            return_type,
+        })
+    }
+    
     // Helper methods for token management
     
     fn match_token(&mut self, types: &[TokenType]) -> bool {
@@ -588,6 +1177,14 @@ impl Parser {
         self.tokens[self.current - 1].clone()
     }
     
+    fn previous_is(&self, token_type: TokenType) -> bool {
+        self.current > 0 && self.previous().token_type == token_type
+    }
+    
+    fn peek_next_is(&self, token_type: TokenType) -> bool {
+        self.tokens.get(self.current + 1).map_or(false, |token| token.token_type == token_type)
+    }
+    
     fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, ShitRustError> {
         if self.check(token_type) {
             Ok(self.advance())
@@ -610,7 +1207,7 @@ impl Parser {
         let name_str = name.lexeme.clone();
         
         // Parse generic parameters if present
-        let generic_params = self.parse_generic_params();
+        let generic_params = self.parse_generic_params()?;
         
         // Parse trait body
         self.consume(TokenType::LeftBrace, "Expected '{' after trait name")?;
@@ -632,6 +1229,112 @@ impl Parser {
         })
     }
     
+    fn struct_declaration(&mut self) -> Result<Stmt, ShitRustError> {
+        // Check for public
+        let is_public = self.previous_was(&[TokenType::Pub]);
+        
+        // Parse struct name
+        let name = self.consume(TokenType::Identifier, "Expected struct name")?;
+        let name_str = name.lexeme.clone();
+        
+        // Parse generic parameters if present
+        let generic_params = self.parse_generic_params()?;
+        
+        self.consume(TokenType::LeftBrace, "Expected '{' after struct name")?;
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+        
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            self.match_token(&[TokenType::Pub]);
+            
+            if self.match_token(&[TokenType::Fn]) {
+                let start = self.previous();
+                let (line, column) = (start.line, start.column);
+                let method = self.function_with_async(false)?;
+                methods.push(self.located(line, column, method));
+                continue;
+            }
+            
+            // Fields are `[mut] name: Type`; the flag records whether the field may be reassigned
+            let mutable = self.match_token(&[TokenType::Mut]);
+            let field_name = self.consume(TokenType::Identifier, "Expected field name")?;
+            self.consume(TokenType::Colon, "Expected ':' after field name")?;
+            let field_type = self.parse_type()?;
+            
+            fields.push((field_name.lexeme.clone(), field_type, mutable));
+            
+            if !self.match_token(&[TokenType::Comma]) && !self.check(TokenType::Fn) {
+                break;
+            }
+        }
+        
+        self.consume(TokenType::RightBrace, "Expected '}' after struct body")?;
+        
+        Ok(Stmt::Struct {
+            name: name_str,
+            fields,
+            methods,
+            is_public,
+            generic_params,
+        })
+    }
+    
+    fn enum_declaration(&mut self) -> Result<Stmt, ShitRustError> {
+        // Check for public
+        let is_public = self.previous_was(&[TokenType::Pub]);
+        
+        // Parse enum name
+        let name = self.consume(TokenType::Identifier, "Expected enum name")?;
+        let name_str = name.lexeme.clone();
+        
+        // Parse generic parameters if present
+        let generic_params = self.parse_generic_params()?;
+        
+        self.consume(TokenType::LeftBrace, "Expected '{' after enum name")?;
+        let mut variants = Vec::new();
+        
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if self.check(TokenType::Fn) || self.check(TokenType::Pub) {
+                return Err(ShitRustError::SyntaxError {
+                    line: self.peek().line,
+                    column: self.peek().column,
+                    message: "Enum methods must be declared in an impl block".to_string(),
+                });
+            }
+            
+            let variant = self.consume(TokenType::Identifier, "Expected variant name")?;
+            let mut payload = Vec::new();
+            
+            if self.match_token(&[TokenType::LeftParen]) {
+                if !self.check(TokenType::RightParen) {
+                    loop {
+                        payload.push(self.parse_type()?);
+                        
+                        if !self.match_token(&[TokenType::Comma]) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(TokenType::RightParen, "Expected ')' after variant types")?;
+            }
+            
+            variants.push((variant.lexeme.clone(), payload));
+            
+            if !self.match_token(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        
+        self.consume(TokenType::RightBrace, "Expected '}' after enum body")?;
+        
+        Ok(Stmt::Enum {
+            name: name_str,
+            variants,
+            is_public,
+            generic_params,
+        })
+    }
+    
     // Helper for parsing trait methods
     fn trait_method(&mut self) -> Result<TraitMethod, ShitRustError> {
         // Check if method is async
@@ -701,23 +1404,23 @@ impl Parser {
     // New method for impl blocks
     fn impl_declaration(&mut self) -> Result<Stmt, ShitRustError> {
         // Parse generic parameters if present
-        let generic_params = self.parse_generic_params();
+        let (generic_params, mut bounds) = self.parse_bounded_generic_params()?;
         
-        // Check for trait implementation
-        let trait_name = if !self.check(TokenType::Identifier) {
-            let trait_name = self.consume(TokenType::Identifier, "Expected trait name")?;
-            let trait_name_str = trait_name.lexeme.clone();
-            
-            self.consume(TokenType::For, "Expected 'for' after trait name")?;
+        // Either `impl Type<Args>` or `impl Trait<Args> for Type<Args>`
+        let first = self.consume(TokenType::Identifier, "Expected type or trait name")?.lexeme.clone();
+        let first_args = self.parse_type_args()?;
+        
+        let (trait_name, trait_args, type_name_str, type_args) = if self.match_token(&[TokenType::For]) {
+            let type_name = self.consume(TokenType::Identifier, "Expected type name after 'for'")?;
+            let type_name_str = type_name.lexeme.clone();
+            let type_args = self.parse_type_args()?;
             
-            Some(trait_name_str)
+            (Some(first), first_args, type_name_str, type_args)
         } else {
-            None
+            (None, Vec::new(), first, first_args)
         };
         
-        // Parse type name that we're implementing for
-        let type_name = self.consume(TokenType::Identifier, "Expected type name")?;
-        let type_name_str = type_name.lexeme.clone();
+        self.parse_where_clause(&mut bounds)?;
         
         // Parse impl body
         self.consume(TokenType::LeftBrace, "Expected '{' after type name")?;
@@ -725,12 +1428,16 @@ impl Parser {
         
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
             // Method implementation within impl block
+            let start = self.peek();
+            let (line, column) = (start.line, start.column);
             if self.match_token(&[TokenType::Async]) {
                 if self.match_token(&[TokenType::Fn]) {
-                    methods.push(self.function_with_async(true)?);
+                    let method = self.function_with_async(true)?;
+                    methods.push(self.located(line, column, method));
                 }
             } else if self.match_token(&[TokenType::Fn]) {
-                methods.push(self.function_with_async(false)?);
+                let method = self.function_with_async(false)?;
+                methods.push(self.located(line, column, method));
             } else {
                 return Err(ShitRustError::SyntaxError {
                     line: self.peek().line,
@@ -744,32 +1451,104 @@ impl Parser {
         
         Ok(Stmt::Impl {
             trait_name,
+            trait_args,
             type_name: type_name_str,
+            type_args,
             methods,
             generic_params,
+            bounds,
         })
     }
     
-    fn parse_generic_params(&mut self) -> Vec<String> {
+    fn parse_generic_params(&mut self) -> Result<Vec<String>, ShitRustError> {
+        self.parse_bounded_generic_params().map(|(generic_params, _)| generic_params)
+    }
+    
+    // Parses optional type arguments after a name, such as `<int, T>`
+    fn parse_type_args(&mut self) -> Result<Vec<Type>, ShitRustError> {
+        let mut type_args = Vec::new();
+        
+        if self.match_token(&[TokenType::Less]) {
+            loop {
+                type_args.push(self.parse_type()?);
+                
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            
+            self.consume(TokenType::Greater, "Expected '>' after type arguments")?;
+        }
+        
+        Ok(type_args)
+    }
+    
+    // Parses `<T, U: Trait + Other>`, returning the parameter names and their bounds
+    fn parse_bounded_generic_params(&mut self) -> Result<(Vec<String>, Vec<(String, Vec<String>)>), ShitRustError> {
         let mut generic_params = Vec::new();
+        let mut bounds = Vec::new();
         
         if self.match_token(&[TokenType::Less]) {
             loop {
-                if let TokenType::Identifier = self.peek().token_type {
-                    let param = self.advance();
-                    generic_params.push(param.lexeme.clone());
+                let param = self.consume(TokenType::Identifier, "Expected type parameter name")?.lexeme.clone();
+                
+                if self.match_token(&[TokenType::Colon]) {
+                    bounds.push((param.clone(), self.parse_trait_bounds()?));
                 }
                 
+                generic_params.push(param);
+                
                 if !self.match_token(&[TokenType::Comma]) {
                     break;
                 }
             }
             
-            self.consume(TokenType::Greater, "Expected '>' after generic parameters")
-                .unwrap_or_else(|_| Token::new(TokenType::Greater, ">".to_string(), 0, 0));
+            self.consume(TokenType::Greater, "Expected '>' after generic parameters")?;
+        }
+        
+        Ok((generic_params, bounds))
+    }
+    
+    // Parses an optional `where T: Trait, U: Other + Another` clause into `bounds`
+    fn parse_where_clause(&mut self, bounds: &mut Vec<(String, Vec<String>)>) -> Result<(), ShitRustError> {
+        if !(self.check(TokenType::Identifier) && self.peek().lexeme == "where") {
+            return Ok(());
+        }
+        self.advance();
+        
+        loop {
+            let param = self.consume(TokenType::Identifier, "Expected type parameter in where clause")?;
+            let param = param.lexeme.clone();
+            self.consume(TokenType::Colon, "Expected ':' after type parameter in where clause")?;
+            let traits = self.parse_trait_bounds()?;
+            
+            match bounds.iter_mut().find(|(name, _)| *name == param) {
+                Some((_, existing)) => existing.extend(traits),
+                None => bounds.push((param, traits)),
+            }
+            
+            if !self.match_token(&[TokenType::Comma]) {
+                break;
+            }
         }
         
-        generic_params
+        Ok(())
+    }
+    
+    // Parses `Trait + Other`
+    fn parse_trait_bounds(&mut self) -> Result<Vec<String>, ShitRustError> {
+        let mut traits = Vec::new();
+        
+        loop {
+            let trait_name = self.consume(TokenType::Identifier, "Expected trait name in bound")?;
+            traits.push(trait_name.lexeme.clone());
+            
+            if !self.match_token(&[TokenType::Plus]) {
+                break;
+            }
+        }
+        
+        Ok(traits)
     }
     
     fn type_alias(&mut self) -> Result<Stmt, ShitRustError> {
@@ -781,7 +1560,7 @@ impl Parser {
         let name_str = name.lexeme.clone();
         
         // Parse generic parameters if present
-        let generic_params = self.parse_generic_params();
+        let generic_params = self.parse_generic_params()?;
         
         self.consume(TokenType::Equal, "Expected '=' after type name")?;
         
@@ -851,6 +1630,62 @@ impl Parser {
         })
     }
     
+    /// Parse `extern "C" { fn name(param: type, ...) -> type; ... }`; the ABI defaults to "C"
+    fn extern_block(&mut self) -> Result<Stmt, ShitRustError> {
+        let abi = if self.match_token(&[TokenType::StringLiteral]) {
+            self.previous().lexeme
+        } else {
+            "C".to_string()
+        };
+        
+        if abi != "C" {
+            let token = self.previous();
+            return Err(ShitRustError::SyntaxError {
+                line: token.line,
+                column: token.column,
+                message: format!("Unsupported ABI \"{}\", only \"C\" is supported", abi),
+            });
+        }
+        
+        self.consume(TokenType::LeftBrace, "Expected '{' after extern ABI")?;
+        let mut functions = Vec::new();
+        
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            self.consume(TokenType::Fn, "Expected 'fn' in extern block")?;
+            let name = self.consume(TokenType::Identifier, "Expected function name")?.lexeme;
+            
+            self.consume(TokenType::LeftParen, "Expected '(' after function name")?;
+            let mut params = Vec::new();
+            
+            if !self.check(TokenType::RightParen) {
+                loop {
+                    let param_name = self.consume(TokenType::Identifier, "Expected parameter name")?;
+                    self.consume(TokenType::Colon, "Expected ':' after parameter name")?;
+                    params.push((param_name.lexeme, self.parse_type()?));
+                    
+                    if !self.match_token(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            
+            self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
+            
+            let return_type = if self.match_token(&[TokenType::Arrow]) {
+                self.parse_type()?
+            } else {
+                Type::Void
+            };
+            
+            self.consume(TokenType::Semicolon, "Expected ';' after extern function declaration")?;
+            functions.push(ExternFunction { name, params, return_type });
+        }
+        
+        self.consume(TokenType::RightBrace, "Expected '}' after extern block")?;
+        
+        Ok(Stmt::Extern { abi, functions })
+    }
+    
     fn async_block(&mut self) -> Result<Stmt, ShitRustError> {
         self.consume(TokenType::LeftBrace, "Expected '{' after 'async'")?;
         let block = self.block()?;
@@ -873,7 +1708,7 @@ impl Parser {
         let name_str = name.lexeme.clone();
         
         // Parse generic parameters if present
-        let generic_params = self.parse_generic_params();
+        let (generic_params, mut bounds) = self.parse_bounded_generic_params()?;
         
         // Parse parameters
         self.consume(TokenType::LeftParen, "Expected '(' after function name")?;
@@ -902,6 +1737,8 @@ impl Parser {
             Type::Void
         };
         
+        self.parse_where_clause(&mut bounds)?;
+        
         // Parse function body
         self.consume(TokenType::LeftBrace, "Expected '{' before function body")?;
         let body = self.block()?;
@@ -914,16 +1751,211 @@ impl Parser {
             is_async,
             is_public,
             generic_params,
+            bounds,
         })
     }
     
     // Helper to check if the previous token was one of the given types
     fn previous_was(&self, types: &[TokenType]) -> bool {
-        if self.current == 0 {
+        if self.current < 2 {
             return false;
         }
         
         let prev_token = &self.tokens[self.current - 2]; // -2 because we've already consumed a token
         types.iter().any(|t| *t == prev_token.token_type)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    
+    fn parse(source: &str) -> Vec<Stmt> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        Parser::new(tokens).parse().unwrap().statements
+    }
+    
+    #[test]
+    fn brace_after_match_head_starts_the_arms() {
+        let statements = parse("match Color { _ => 1 }");
+        
+        match &statements[0] {
+            Stmt::Match { expr: Expr::Identifier(name), arms } => {
+                assert_eq!(name, "Color");
+                assert_eq!(arms.len(), 1);
+            },
+            other => panic!("expected a match on Color, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn constant_in_loop_condition_is_not_a_struct_literal() {
+        let statements = parse("while (i < MAX) { i = i + 1; }");
+        
+        match &statements[0] {
+            Stmt::While { condition: Expr::BinaryOp { right, .. }, body } => {
+                assert!(matches!(right.as_ref(), Expr::Identifier(name) if name == "MAX"));
+                assert_eq!(body.len(), 1);
+            },
+            other => panic!("expected a while loop, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn struct_literals_parse_in_expressions_and_parenthesized_match_heads() {
+        let statements = parse("let p = Point { x: 1, y: 2 }; match (Point {}) { _ => 0 }");
+        
+        assert!(matches!(&statements[0], Stmt::Let { value: Expr::StructInit { fields, .. }, .. } if fields.len() == 2));
+        assert!(matches!(&statements[1], Stmt::Match { expr: Expr::StructInit { .. }, .. }));
+    }
+    
+    fn parse_error(source: &str) -> bool {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        Parser::new(tokens).parse().is_err()
+    }
+    
+    #[test]
+    fn malformed_generic_parameters_are_errors() {
+        assert!(parse_error("struct Box<T { value: T }"));
+        assert!(parse_error("struct Box<int> { value: int }"));
+        assert!(parse_error("trait Show<T: > { fn show(self) -> string; }"));
+        assert!(parse_error("impl<T: > Box<T> { }"));
+    }
+    
+    #[test]
+    fn impls_keep_their_type_arguments() {
+        let statements = parse("impl<T> Printable<int> for Box<T> { }");
+        
+        match &statements[0] {
+            Stmt::Impl { trait_name: Some(trait_name), trait_args, type_name, type_args, generic_params, .. } => {
+                assert_eq!(trait_name, "Printable");
+                assert_eq!(trait_args, &vec![Type::Int]);
+                assert_eq!(type_name, "Box");
+                assert_eq!(type_args, &vec![Type::Custom("T".to_string())]);
+                assert_eq!(generic_params, &vec!["T".to_string()]);
+            },
+            other => panic!("expected an impl, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn patterns_parse_alternatives_ranges_and_destructuring() {
+        let statements = parse("match x { 1 | 2 => a, 3..=9 => b, Point { x, y: 0 } => c, Shape::Circle(r) => d, _ => e }");
+        let Stmt::Match { arms, .. } = &statements[0] else {
+            panic!("expected a match, got {:?}", statements[0]);
+        };
+        
+        assert!(matches!(&arms[0].0, Pattern::Or(alternatives) if alternatives.len() == 2));
+        assert!(matches!(&arms[1].0, Pattern::Range { start: Literal::Int(3), end: Literal::Int(9), inclusive: true }));
+        assert!(matches!(&arms[2].0, Pattern::Destructure { name, fields } if name == "Point" && fields.len() == 2));
+        assert!(matches!(&arms[3].0, Pattern::EnumVariant { name, values } if name == "Shape::Circle" && values.len() == 1));
+        assert!(matches!(&arms[4].0, Pattern::Wildcard));
+    }
+    
+    fn type_hint(source: &str) -> Option<Type> {
+        match parse(source).remove(0) {
+            Stmt::Let { type_hint, .. } => type_hint,
+            other => panic!("expected a let, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn unions_with_none_are_optional_types() {
+        assert_eq!(type_hint("let v: int | string = 1;"), Some(Type::Union(vec![Type::Int, Type::String])));
+        assert_eq!(type_hint("let v: string | none = none;"), Some(Type::Option(Box::new(Type::String))));
+        assert_eq!(
+            type_hint("let v: int | [string] | none = none;"),
+            Some(Type::Option(Box::new(Type::Union(vec![Type::Int, Type::List(Box::new(Type::String))]))))
+        );
+        assert!(parse_error("let v: none = none;"));
+    }
+    
+    #[test]
+    fn casts_bind_tighter_than_arithmetic_but_looser_than_negation() {
+        match &parse("let x = a + b as float;")[0] {
+            Stmt::Let { value: Expr::BinaryOp { right, .. }, .. } => {
+                assert!(matches!(right.as_ref(), Expr::TypeCast { target_type: Type::Float, .. }));
+            },
+            other => panic!("expected an addition, got {:?}", other),
+        }
+        
+        match &parse("let y = -x as int;")[0] {
+            Stmt::Let { value: Expr::TypeCast { expr, target_type: Type::Int }, .. } => {
+                assert!(matches!(expr.as_ref(), Expr::UnaryOp { op: UnaryOp::Neg, .. }));
+            },
+            other => panic!("expected a cast, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn suffixed_and_oversized_literals_are_exact_numbers() {
+        let statements = parse("let a = 123n;\nlet b = 1.50d;\nlet c = 99999999999999999999;\nlet d = 9223372036854775807;\n");
+        let values: Vec<&Expr> = statements.iter()
+            .map(|stmt| match stmt {
+                Stmt::Let { value, .. } => value,
+                other => panic!("expected a let, got {:?}", other),
+            })
+            .collect();
+        
+        assert!(matches!(values[0], Expr::Literal(Literal::BigInt(digits)) if digits == "123"));
+        assert!(matches!(values[1], Expr::Literal(Literal::Decimal(digits)) if digits == "1.50"));
+        assert!(matches!(values[2], Expr::Literal(Literal::BigInt(digits)) if digits == "99999999999999999999"));
+        assert!(matches!(values[3], Expr::Literal(Literal::Int(i64::MAX))));
+    }
+    
+    #[test]
+    fn extern_blocks_declare_c_functions_and_keep_their_attributes() {
+        let statements = parse("#[link(\"m\", \"./libgeometry.so\")]\n\
+                                extern \"C\" {\n    fn cos(x: float) -> float;\n    fn srand(seed: c_int);\n    fn getenv(name: string) -> ptr;\n}\n\
+                                #[export]\nfn area(width: int, height: int) -> int { return width * height; }\n");
+        
+        assert_eq!(statements[0].link_libraries(), vec!["m".to_string(), "./libgeometry.so".to_string()]);
+        match statements[0].without_attributes() {
+            Stmt::Extern { abi, functions } => {
+                assert_eq!(abi, "C");
+                let signatures: Vec<(&str, Vec<Type>, Type)> = functions.iter()
+                    .map(|f| (f.name.as_str(), f.params.iter().map(|(_, t)| t.clone()).collect(), f.return_type.clone()))
+                    .collect();
+                assert_eq!(signatures, vec![
+                    ("cos", vec![Type::Float], Type::Float),
+                    ("srand", vec![Type::CInt], Type::Void),
+                    ("getenv", vec![Type::String], Type::Pointer),
+                ]);
+            },
+            other => panic!("expected an extern block, got {:?}", other),
+        }
+        
+        let attributes: Vec<&str> = statements[1].attributes().iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(attributes, vec!["export"]);
+        assert!(statements[1].link_libraries().is_empty());
+        assert!(matches!(statements[1].without_attributes(), Stmt::Function { name, .. } if name == "area"));
+    }
+    
+    #[test]
+    fn extern_blocks_only_accept_the_c_abi() {
+        assert!(!parse_error("extern { fn abs(x: c_int) -> c_int; }"));
+        assert!(parse_error("extern \"system\" { fn abs(x: c_int) -> c_int; }"));
+        assert!(parse_error("extern \"C\" { fn abs(x: c_int) -> c_int }"));
+        assert!(parse_error("extern \"C\" { fn abs(x: c_int) -> c_int { return x; } }"));
+    }
+    
+    #[test]
+    fn pub_marks_top_level_declarations_public() {
+        let statements = parse("pub fn area(width: int) -> int { return width; }\nfn hidden() { }\npub const MAX: int = 3;\n");
+        
+        assert!(matches!(&statements[0], Stmt::Function { name, is_public: true, .. } if name == "area"));
+        assert!(matches!(&statements[1], Stmt::Function { is_public: false, .. }));
+        assert!(matches!(&statements[2], Stmt::Const { is_public: true, .. }));
+        assert!(parse_error("pub let x = 1;"));
+    }
+    
+    #[test]
+    fn break_and_continue_end_with_a_semicolon() {
+        let statements = parse("while (true) { continue; break; }");
+        assert!(matches!(&statements[0], Stmt::While { body, .. } if matches!(body.as_slice(), [Stmt::Continue, Stmt::Break])));
+        
+        let tokens = Lexer::new("while (true) { break }").scan_tokens().unwrap();
+        assert!(Parser::new(tokens).parse().is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::error::ShitRustError;
+
+/// Source of the runtime library that compiled programs link against
+const RUNTIME_SOURCE: &str = include_str!("../runtime/shitrust_rt.rs");
+
+/// `kind` arguments of `shitrust_list_new` and `shitrust_dict_new`
+pub const VALUE_PLAIN: u64 = 0;
+pub const VALUE_OBJECT: u64 = 1;
+pub const KEY_INT: u64 = 0;
+pub const KEY_STRING: u64 = 1;
+
+/// Returns the runtime static library for `target`, or the host when it is `None`, building
+/// it with `rustc` the first time. Builds are cached in the temp directory, keyed by the
+/// runtime's source. Other targets need their standard library installed, e.g. with `rustup target add`.
+pub fn runtime_library(target: Option<&str>) -> Result<PathBuf, ShitRustError> {
+    let msvc = match target {
+        Some(triple) => triple.ends_with("-msvc"),
+        None => cfg!(target_env = "msvc"),
+    };
+    let file_name = if msvc { "shitrust_rt.lib" } else { "libshitrust_rt.a" };
+    build_runtime("staticlib", file_name, target)
+}
+
+/// Returns the runtime as a shared library, which JIT-compiled code loads into the process
+pub fn runtime_shared_library() -> Result<PathBuf, ShitRustError> {
+    let file_name = format!("{}shitrust_rt{}", std::env::consts::DLL_PREFIX, std::env::consts::DLL_SUFFIX);
+    build_runtime("cdylib", &file_name, None)
+}
+
+fn build_runtime(crate_type: &str, file_name: &str, target: Option<&str>) -> Result<PathBuf, ShitRustError> {
+    let mut hasher = DefaultHasher::new();
+    RUNTIME_SOURCE.hash(&mut hasher);
+    let cache_dir = std::env::temp_dir()
+        .join(format!("shitrust-runtime-{}-{:016x}", env!("CARGO_PKG_VERSION"), hasher.finish()))
+        .join(target.unwrap_or("host"));
+    
+    let library = cache_dir.join(file_name);
+    if library.exists() {
+        return Ok(library);
+    }
+    
+    let io_error = |e: std::io::Error| ShitRustError::IOException(format!("Failed to build the runtime library: {}", e));
+    fs::create_dir_all(&cache_dir).map_err(io_error)?;
+    let source = cache_dir.join("shitrust_rt.rs");
+    fs::write(&source, RUNTIME_SOURCE).map_err(io_error)?;
+    
+    // Build next to the final path and rename, so a concurrent compile never links a partial file
+    let partial = cache_dir.join(format!("partial-{}-{}", crate_type, std::process::id()));
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let mut command = Command::new(&rustc);
+    command
+        .arg(format!("--crate-type={}", crate_type))
+        .args(["--crate-name", "shitrust_rt", "--edition", "2021"])
+        .args(["-C", "opt-level=2", "-C", "panic=abort"]);
+    if let Some(target) = target {
+        command.args(["--target", target]);
+    }
+    let output = command
+        .arg("-o")
+        .arg(&partial)
+        .arg(&source)
+        .output()
+        .map_err(|e| ShitRustError::IOException(
+            format!("Failed to run {} to build the runtime library. Is Rust installed? ({})", rustc, e)
+        ))?;
+    
+    if !output.status.success() {
+        return Err(ShitRustError::RuntimeError(format!(
+            "Building the runtime library failed:\n{}", String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    
+    fs::rename(&partial, &library).map_err(io_error)?;
+    Ok(library)
+}
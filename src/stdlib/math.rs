@@ -0,0 +1,730 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Mul, Neg, Sub};
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Pow, Signed, ToPrimitive, Zero};
+
+use crate::ast::{BinOp, Type};
+use crate::error::ShitRustError;
+use crate::interpreter::Value;
+
+/// Fractional digits kept when a decimal quotient is not exact
+pub const DIVISION_SCALE: u32 = 18;
+
+/// An exact decimal number `mantissa * 10^-scale`.
+/// The scale is the number of digits after the point and is kept through arithmetic,
+/// so `1.50d` stays `1.50`.
+#[derive(Debug, Clone)]
+pub struct Decimal {
+    mantissa: BigInt,
+    scale: u32,
+}
+
+fn pow10(exponent: u32) -> BigInt {
+    BigInt::from(10u32).pow(exponent)
+}
+
+/// Divide, rounding halfway cases to the even neighbour
+fn div_round_half_even(numerator: &BigInt, denominator: &BigInt) -> BigInt {
+    // BigInt division truncates toward zero
+    let quotient = numerator / denominator;
+    let remainder = numerator - &quotient * denominator;
+    
+    let twice_remainder = remainder.abs() * 2u32;
+    let round_away = match twice_remainder.cmp(&denominator.abs()) {
+        Ordering::Greater => true,
+        Ordering::Equal => !(&quotient % 2u32).is_zero(),
+        Ordering::Less => false,
+    };
+    
+    if !round_away {
+        quotient
+    } else if numerator.is_negative() != denominator.is_negative() {
+        quotient - 1
+    } else {
+        quotient + 1
+    }
+}
+
+impl Decimal {
+    pub fn new(mantissa: BigInt, scale: u32) -> Self {
+        Decimal { mantissa, scale }
+    }
+    
+    pub fn from_bigint(value: BigInt) -> Self {
+        Decimal::new(value, 0)
+    }
+    
+    /// Convert a finite float through its shortest exact representation
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+        Decimal::parse(&value.to_string()).ok()
+    }
+    
+    /// Parse `[-]digits[.digits]`; the scale is the number of digits after the point
+    pub fn parse(text: &str) -> Result<Self, ShitRustError> {
+        let invalid = || ShitRustError::ValueError(format!("Invalid decimal '{}'", text));
+        
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if int_part.is_empty() || !is_digits(int_part) || !is_digits(frac_part) ||
+            (unsigned.contains('.') && frac_part.is_empty()) {
+            return Err(invalid());
+        }
+        
+        let mantissa: BigInt = format!("{}{}", int_part, frac_part).parse().map_err(|_| invalid())?;
+        Ok(Decimal::new(if negative { -mantissa } else { mantissa }, frac_part.len() as u32))
+    }
+    
+    pub fn mantissa(&self) -> &BigInt {
+        &self.mantissa
+    }
+    
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+    
+    pub fn is_zero(&self) -> bool {
+        self.mantissa.is_zero()
+    }
+    
+    pub fn abs(&self) -> Decimal {
+        Decimal::new(self.mantissa.abs(), self.scale)
+    }
+    
+    /// Change the number of fractional digits, rounding half to even when digits are dropped
+    pub fn rescale(&self, scale: u32) -> Decimal {
+        if scale >= self.scale {
+            Decimal::new(&self.mantissa * pow10(scale - self.scale), scale)
+        } else {
+            Decimal::new(div_round_half_even(&self.mantissa, &pow10(self.scale - scale)), scale)
+        }
+    }
+    
+    /// The same value without trailing fractional zeros
+    pub fn normalize(&self) -> Decimal {
+        let mut result = self.clone();
+        while result.scale > 0 && (&result.mantissa % 10u32).is_zero() {
+            result.mantissa /= 10u32;
+            result.scale -= 1;
+        }
+        result
+    }
+    
+    /// Both mantissas at the larger of the two scales
+    fn aligned(&self, other: &Decimal) -> (BigInt, BigInt, u32) {
+        let scale = self.scale.max(other.scale);
+        (self.rescale(scale).mantissa, other.rescale(scale).mantissa, scale)
+    }
+    
+    /// Divide, keeping at least `DIVISION_SCALE` fractional digits; None when dividing by zero
+    pub fn checked_div(&self, other: &Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        
+        let scale = self.scale.max(other.scale).max(DIVISION_SCALE);
+        let numerator = &self.mantissa * pow10(scale + other.scale - self.scale);
+        Some(Decimal::new(div_round_half_even(&numerator, &other.mantissa), scale))
+    }
+    
+    /// Remainder of truncating division, with the sign of the dividend; None when dividing by zero
+    pub fn checked_rem(&self, other: &Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other);
+        if b.is_zero() {
+            return None;
+        }
+        Some(Decimal::new(a % b, scale))
+    }
+    
+    pub fn pow(&self, exponent: u32) -> Decimal {
+        Decimal::new(Pow::pow(&self.mantissa, exponent), self.scale * exponent)
+    }
+    
+    /// The integer part, rounding toward zero
+    pub fn trunc(&self) -> BigInt {
+        &self.mantissa / pow10(self.scale)
+    }
+    
+    pub fn floor(&self) -> BigInt {
+        let truncated = self.trunc();
+        if self.mantissa.is_negative() && !(&self.mantissa % pow10(self.scale)).is_zero() {
+            truncated - 1
+        } else {
+            truncated
+        }
+    }
+    
+    pub fn ceil(&self) -> BigInt {
+        -(-self).floor()
+    }
+    
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+}
+
+impl Add for &Decimal {
+    type Output = Decimal;
+    
+    fn add(self, other: &Decimal) -> Decimal {
+        let (a, b, scale) = self.aligned(other);
+        Decimal::new(a + b, scale)
+    }
+}
+
+impl Sub for &Decimal {
+    type Output = Decimal;
+    
+    fn sub(self, other: &Decimal) -> Decimal {
+        let (a, b, scale) = self.aligned(other);
+        Decimal::new(a - b, scale)
+    }
+}
+
+impl Mul for &Decimal {
+    type Output = Decimal;
+    
+    fn mul(self, other: &Decimal) -> Decimal {
+        Decimal::new(&self.mantissa * &other.mantissa, self.scale + other.scale)
+    }
+}
+
+impl Neg for &Decimal {
+    type Output = Decimal;
+    
+    fn neg(self) -> Decimal {
+        Decimal::new(-&self.mantissa, self.scale)
+    }
+}
+
+// Equality, ordering and hashing compare values, so `1.5d` and `1.50d` are the same key
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        let (a, b, _) = self.aligned(other);
+        a.cmp(&b)
+    }
+}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let normalized = self.normalize();
+        normalized.mantissa.hash(state);
+        normalized.scale.hash(state);
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.mantissa.is_negative() { "-" } else { "" };
+        let digits = self.mantissa.abs().to_string();
+        let scale = self.scale as usize;
+        
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, int_part, frac_part)
+    }
+}
+
+fn to_bigint(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::Int(i) => Some(BigInt::from(*i)),
+        Value::BigInt(b) => Some(b.clone()),
+        _ => None,
+    }
+}
+
+fn to_decimal(value: &Value) -> Option<Decimal> {
+    match value {
+        Value::Decimal(d) => Some(d.clone()),
+        other => to_bigint(other).map(Decimal::from_bigint),
+    }
+}
+
+fn operand_error(op: &BinOp, left: &Value, right: &Value) -> ShitRustError {
+    ShitRustError::TypeError(format!(
+        "Cannot apply {:?} to {} and {}; convert explicitly with 'as'", op, left.type_name(), right.type_name()
+    ))
+}
+
+fn compare_result(op: &BinOp, ordering: Ordering) -> Option<Value> {
+    let result = match op {
+        BinOp::Eq => ordering == Ordering::Equal,
+        BinOp::Ne => ordering != Ordering::Equal,
+        BinOp::Lt => ordering == Ordering::Less,
+        BinOp::Le => ordering != Ordering::Greater,
+        BinOp::Gt => ordering == Ordering::Greater,
+        BinOp::Ge => ordering != Ordering::Less,
+        _ => return None,
+    };
+    Some(Value::Bool(result))
+}
+
+/// Arithmetic and comparisons where an operand is a `bigint` or `decimal`.
+/// `int` operands are promoted to the other operand's type; floats never mix with exact numbers.
+/// Returns None when neither operand is exact, or for string concatenation.
+pub fn exact_binary_op(op: &BinOp, left: &Value, right: &Value) -> Option<Result<Value, ShitRustError>> {
+    let is_exact = |value: &Value| matches!(value, Value::BigInt(_) | Value::Decimal(_));
+    if !is_exact(left) && !is_exact(right) {
+        return None;
+    }
+    if matches!(left, Value::String(_)) || matches!(right, Value::String(_)) {
+        return None;
+    }
+    
+    if matches!(left, Value::Decimal(_)) || matches!(right, Value::Decimal(_)) {
+        let (Some(a), Some(b)) = (to_decimal(left), to_decimal(right)) else {
+            return Some(mismatched_operands(op, left, right));
+        };
+        
+        let result = match op {
+            BinOp::Add => Value::Decimal(&a + &b),
+            BinOp::Sub => Value::Decimal(&a - &b),
+            BinOp::Mul => Value::Decimal(&a * &b),
+            BinOp::Div => match a.checked_div(&b) {
+                Some(quotient) => Value::Decimal(quotient),
+                None => return Some(Err(ShitRustError::RuntimeError("Division by zero".to_string()))),
+            },
+            BinOp::Mod => match a.checked_rem(&b) {
+                Some(remainder) => Value::Decimal(remainder),
+                None => return Some(Err(ShitRustError::RuntimeError("Modulo by zero".to_string()))),
+            },
+            _ => match compare_result(op, a.cmp(&b)) {
+                Some(result) => result,
+                None => return Some(Err(operand_error(op, left, right))),
+            },
+        };
+        return Some(Ok(result));
+    }
+    
+    let (Some(a), Some(b)) = (to_bigint(left), to_bigint(right)) else {
+        return Some(mismatched_operands(op, left, right));
+    };
+    
+    let result = match op {
+        BinOp::Add => Value::BigInt(a + b),
+        BinOp::Sub => Value::BigInt(a - b),
+        BinOp::Mul => Value::BigInt(a * b),
+        BinOp::Div | BinOp::Mod if b.is_zero() => {
+            let message = if matches!(op, BinOp::Div) { "Division by zero" } else { "Modulo by zero" };
+            return Some(Err(ShitRustError::RuntimeError(message.to_string())));
+        },
+        // Truncating division, like int
+        BinOp::Div => Value::BigInt(a / b),
+        BinOp::Mod => Value::BigInt(a % b),
+        _ => match compare_result(op, a.cmp(&b)) {
+            Some(result) => result,
+            None => return Some(Err(operand_error(op, left, right))),
+        },
+    };
+    Some(Ok(result))
+}
+
+// Values of unrelated types are simply unequal, as elsewhere in the interpreter
+fn mismatched_operands(op: &BinOp, left: &Value, right: &Value) -> Result<Value, ShitRustError> {
+    match op {
+        BinOp::Eq => Ok(Value::Bool(false)),
+        BinOp::Ne => Ok(Value::Bool(true)),
+        _ => Err(operand_error(op, left, right)),
+    }
+}
+
+/// Narrow a bigint back to an int, failing when it does not fit
+pub fn bigint_to_int(value: &BigInt) -> Result<i64, ShitRustError> {
+    value.to_i64().ok_or_else(|| ShitRustError::RuntimeError(
+        format!("Cannot convert {} to int: value out of range", value)
+    ))
+}
+
+/// Promote an int to the exact type a binding was declared with
+pub fn promote(value: Value, target: &Type) -> Value {
+    match (value, target) {
+        (Value::Int(i), Type::BigInt) => Value::BigInt(BigInt::from(i)),
+        (Value::Int(i), Type::Decimal) => Value::Decimal(Decimal::from_bigint(BigInt::from(i))),
+        (Value::BigInt(b), Type::Decimal) => Value::Decimal(Decimal::from_bigint(b)),
+        (value, _) => value,
+    }
+}
+
+/// `as` conversions to and from `bigint` and `decimal`; None for other casts.
+/// Conversions to int fail when the value is out of range, and decimals truncate toward zero.
+pub fn exact_cast(value: &Value, target: &Type) -> Option<Result<Value, ShitRustError>> {
+    let is_exact = matches!(value, Value::BigInt(_) | Value::Decimal(_));
+    if !is_exact && !matches!(target, Type::BigInt | Type::Decimal) {
+        return None;
+    }
+    
+    let result = match (value, target) {
+        (Value::BigInt(b), Type::Int) => bigint_to_int(b).map(Value::Int),
+        (Value::Decimal(d), Type::Int) => bigint_to_int(&d.trunc()).map(Value::Int),
+        (Value::BigInt(b), Type::Float) => Ok(Value::Float(b.to_f64().unwrap_or(f64::NAN))),
+        (Value::Decimal(d), Type::Float) => Ok(Value::Float(d.to_f64())),
+        (Value::Float(f), Type::BigInt | Type::Decimal) if !f.is_finite() => Err(ShitRustError::RuntimeError(
+            format!("Cannot cast {} to {:?}", f, target)
+        )),
+        (Value::Float(f), Type::BigInt) => Ok(Value::BigInt(BigInt::from_f64(f.trunc()).unwrap_or_default())),
+        (Value::Float(f), Type::Decimal) => Ok(Value::Decimal(Decimal::from_f64(*f).unwrap_or_else(|| {
+            Decimal::from_bigint(BigInt::zero())
+        }))),
+        (Value::Decimal(d), Type::BigInt) => Ok(Value::BigInt(d.trunc())),
+        (value @ (Value::Int(_) | Value::BigInt(_) | Value::Decimal(_)), Type::BigInt | Type::Decimal) => {
+            Ok(promote(value.clone(), target))
+        },
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn native(name: &str, func: fn(Vec<Value>) -> Result<Value, ShitRustError>) -> (String, Value) {
+    (name.to_string(), Value::NativeFunction { name: name.to_string(), func })
+}
+
+fn expect_args(name: &str, args: &[Value], min: usize, max: usize) -> Result<(), ShitRustError> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max { min.to_string() } else { format!("{} to {}", min, max) };
+        return Err(ShitRustError::RuntimeError(
+            format!("math.{} expects {} argument(s) but {} were supplied", name, expected, args.len())
+        ));
+    }
+    Ok(())
+}
+
+fn unsupported(name: &str, value: &Value) -> ShitRustError {
+    ShitRustError::TypeError(format!("math.{} does not accept {}", name, value.type_name()))
+}
+
+fn int_arg(name: &str, value: &Value) -> Result<i64, ShitRustError> {
+    match value {
+        Value::Int(i) => Ok(*i),
+        other => Err(unsupported(name, other)),
+    }
+}
+
+fn u32_arg(name: &str, value: &Value) -> Result<u32, ShitRustError> {
+    let i = int_arg(name, value)?;
+    u32::try_from(i).map_err(|_| ShitRustError::RuntimeError(
+        format!("math.{} expects a non-negative int, got {}", name, i)
+    ))
+}
+
+/// Standard library for exact and floating-point math
+pub fn init_math_module() -> Vec<(String, Value)> {
+    vec![
+        ("PI".to_string(), Value::Float(std::f64::consts::PI)),
+        ("E".to_string(), Value::Float(std::f64::consts::E)),
+        native("bigint", math_bigint),
+        native("decimal", math_decimal),
+        native("round", math_round),
+        native("scale", math_scale),
+        native("abs", math_abs),
+        native("pow", math_pow),
+        native("sqrt", math_sqrt),
+        native("floor", math_floor),
+        native("ceil", math_ceil),
+        native("gcd", math_gcd),
+        native("factorial", math_factorial),
+        native("min", math_min),
+        native("max", math_max),
+    ]
+}
+
+// bigint(x): convert an int, string, decimal (truncating) or float (truncating)
+fn math_bigint(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("bigint", &args, 1, 1)?;
+    
+    let value = match &args[0] {
+        Value::Int(i) => BigInt::from(*i),
+        Value::BigInt(b) => b.clone(),
+        Value::Decimal(d) => d.trunc(),
+        Value::String(s) => s.trim().parse().map_err(|_| {
+            ShitRustError::ValueError(format!("Invalid bigint '{}'", s))
+        })?,
+        Value::Float(f) => BigInt::from_f64(f.trunc()).ok_or_else(|| {
+            ShitRustError::ValueError(format!("Cannot convert {} to bigint", f))
+        })?,
+        other => return Err(unsupported("bigint", other)),
+    };
+    Ok(Value::BigInt(value))
+}
+
+// decimal(x[, scale]): convert a number or string, optionally rescaling it
+fn math_decimal(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("decimal", &args, 1, 2)?;
+    
+    let value = match &args[0] {
+        Value::String(s) => Decimal::parse(s.trim())?,
+        Value::Float(f) => Decimal::from_f64(*f).ok_or_else(|| {
+            ShitRustError::ValueError(format!("Cannot convert {} to decimal", f))
+        })?,
+        other => to_decimal(other).ok_or_else(|| unsupported("decimal", other))?,
+    };
+    
+    match args.get(1) {
+        Some(scale) => Ok(Value::Decimal(value.rescale(u32_arg("decimal", scale)?))),
+        None => Ok(Value::Decimal(value)),
+    }
+}
+
+// round(x, places): decimals round half to even and keep `places` digits; floats round half away from zero
+fn math_round(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("round", &args, 1, 2)?;
+    let places = match args.get(1) {
+        Some(places) => u32_arg("round", places)?,
+        None => 0,
+    };
+    
+    match &args[0] {
+        Value::Decimal(d) => Ok(Value::Decimal(d.rescale(places))),
+        Value::Float(f) => {
+            let factor = 10f64.powi(places as i32);
+            Ok(Value::Float((f * factor).round() / factor))
+        },
+        value @ (Value::Int(_) | Value::BigInt(_)) => Ok(value.clone()),
+        other => Err(unsupported("round", other)),
+    }
+}
+
+// scale(d): the number of digits after the point
+fn math_scale(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("scale", &args, 1, 1)?;
+    match &args[0] {
+        Value::Decimal(d) => Ok(Value::Int(d.scale() as i64)),
+        other => Err(unsupported("scale", other)),
+    }
+}
+
+fn math_abs(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("abs", &args, 1, 1)?;
+    match &args[0] {
+        Value::Int(i) => i.checked_abs().map(Value::Int).ok_or_else(|| {
+            ShitRustError::RuntimeError("Integer overflow in math.abs".to_string())
+        }),
+        Value::Float(f) => Ok(Value::Float(f.abs())),
+        Value::BigInt(b) => Ok(Value::BigInt(b.abs())),
+        Value::Decimal(d) => Ok(Value::Decimal(d.abs())),
+        other => Err(unsupported("abs", other)),
+    }
+}
+
+// pow(base, exponent): exact for int, bigint and decimal bases with non-negative int exponents
+fn math_pow(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("pow", &args, 2, 2)?;
+    
+    match (&args[0], &args[1]) {
+        (Value::Float(base), Value::Float(exponent)) => Ok(Value::Float(base.powf(*exponent))),
+        (Value::Float(base), exponent) => Ok(Value::Float(base.powf(int_arg("pow", exponent)? as f64))),
+        (Value::Int(base), exponent) => {
+            let exponent = u32_arg("pow", exponent)?;
+            base.checked_pow(exponent).map(Value::Int).ok_or_else(|| {
+                ShitRustError::RuntimeError("Integer overflow in math.pow; use a bigint base".to_string())
+            })
+        },
+        (Value::BigInt(base), exponent) => Ok(Value::BigInt(Pow::pow(base, u32_arg("pow", exponent)?))),
+        (Value::Decimal(base), exponent) => Ok(Value::Decimal(base.pow(u32_arg("pow", exponent)?))),
+        (other, _) => Err(unsupported("pow", other)),
+    }
+}
+
+// sqrt(x): floats and ints give a float, bigints give the integer square root
+fn math_sqrt(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("sqrt", &args, 1, 1)?;
+    match &args[0] {
+        Value::Int(i) => Ok(Value::Float((*i as f64).sqrt())),
+        Value::Float(f) => Ok(Value::Float(f.sqrt())),
+        Value::BigInt(b) if b.is_negative() => Err(ShitRustError::ValueError(
+            "math.sqrt of a negative bigint".to_string()
+        )),
+        Value::BigInt(b) => Ok(Value::BigInt(b.sqrt())),
+        other => Err(unsupported("sqrt", other)),
+    }
+}
+
+fn math_floor(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("floor", &args, 1, 1)?;
+    match &args[0] {
+        Value::Float(f) => Ok(Value::Float(f.floor())),
+        Value::Decimal(d) => Ok(Value::BigInt(d.floor())),
+        value @ (Value::Int(_) | Value::BigInt(_)) => Ok(value.clone()),
+        other => Err(unsupported("floor", other)),
+    }
+}
+
+fn math_ceil(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("ceil", &args, 1, 1)?;
+    match &args[0] {
+        Value::Float(f) => Ok(Value::Float(f.ceil())),
+        Value::Decimal(d) => Ok(Value::BigInt(d.ceil())),
+        value @ (Value::Int(_) | Value::BigInt(_)) => Ok(value.clone()),
+        other => Err(unsupported("ceil", other)),
+    }
+}
+
+// gcd(a, b): an int when both arguments are ints and the result fits, otherwise a bigint
+fn math_gcd(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("gcd", &args, 2, 2)?;
+    
+    let mut a = to_bigint(&args[0]).ok_or_else(|| unsupported("gcd", &args[0]))?.abs();
+    let mut b = to_bigint(&args[1]).ok_or_else(|| unsupported("gcd", &args[1]))?.abs();
+    while !b.is_zero() {
+        let remainder = &a % &b;
+        a = b;
+        b = remainder;
+    }
+    
+    match (&args[0], &args[1], a.to_i64()) {
+        (Value::Int(_), Value::Int(_), Some(result)) => Ok(Value::Int(result)),
+        _ => Ok(Value::BigInt(a)),
+    }
+}
+
+fn math_factorial(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("factorial", &args, 1, 1)?;
+    let n = u32_arg("factorial", &args[0])?;
+    
+    let mut result = BigInt::from(1u32);
+    for i in 2..=n {
+        result *= i;
+    }
+    Ok(Value::BigInt(result))
+}
+
+fn compare_numbers(name: &str, left: &Value, right: &Value) -> Result<Ordering, ShitRustError> {
+    let ordering = match (left, right) {
+        (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::Int(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+        (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
+        _ => match exact_binary_op(&BinOp::Lt, left, right) {
+            Some(Ok(Value::Bool(true))) => Some(Ordering::Less),
+            Some(Ok(_)) if matches!(exact_binary_op(&BinOp::Eq, left, right), Some(Ok(Value::Bool(true)))) => Some(Ordering::Equal),
+            Some(Ok(_)) => Some(Ordering::Greater),
+            _ => None,
+        },
+    };
+    
+    ordering.ok_or_else(|| ShitRustError::TypeError(
+        format!("math.{} cannot compare {} and {}", name, left.type_name(), right.type_name())
+    ))
+}
+
+fn math_min(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("min", &args, 2, 2)?;
+    match compare_numbers("min", &args[0], &args[1])? {
+        Ordering::Greater => Ok(args[1].clone()),
+        _ => Ok(args[0].clone()),
+    }
+}
+
+fn math_max(args: Vec<Value>) -> Result<Value, ShitRustError> {
+    expect_args("max", &args, 2, 2)?;
+    match compare_numbers("max", &args[0], &args[1])? {
+        Ordering::Less => Ok(args[1].clone()),
+        _ => Ok(args[0].clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn decimal(text: &str) -> Decimal {
+        Decimal::parse(text).unwrap()
+    }
+    
+    #[test]
+    fn decimals_keep_their_scale() {
+        for text in ["1.50", "-0.05", "42", "0.000"] {
+            assert_eq!(decimal(text).to_string(), text);
+        }
+        assert_eq!(decimal("+3.1").to_string(), "3.1");
+        assert_eq!((&decimal("1.50") + &decimal("2.5")).to_string(), "4.00");
+        assert_eq!((&decimal("1.5") * &decimal("1.5")).to_string(), "2.25");
+        assert_eq!(decimal("2.500").normalize().to_string(), "2.5");
+        assert_eq!(decimal("2.345").rescale(2).to_string(), "2.34");
+        assert_eq!(decimal("2.355").rescale(2).to_string(), "2.36");
+    }
+    
+    #[test]
+    fn malformed_decimals_are_errors() {
+        for text in ["", "-", "1.", ".5", "1.2.3", "1e5", "abc"] {
+            assert!(Decimal::parse(text).is_err(), "{:?}", text);
+        }
+    }
+    
+    #[test]
+    fn decimals_compare_by_value() {
+        assert_eq!(decimal("1.50"), decimal("1.5"));
+        assert!(decimal("-1.25") < decimal("-1.2"));
+        assert_eq!(decimal("-2.5").floor(), BigInt::from(-3));
+        assert_eq!(decimal("-2.5").trunc(), BigInt::from(-2));
+        assert_eq!(decimal("2.1").ceil(), BigInt::from(3));
+    }
+    
+    #[test]
+    fn division_by_zero_is_none() {
+        assert!(decimal("1").checked_div(&decimal("0.00")).is_none());
+        assert!(decimal("1").checked_rem(&decimal("0")).is_none());
+        assert_eq!(decimal("1").checked_div(&decimal("4")).unwrap(), decimal("0.25"));
+        assert_eq!(decimal("1").checked_div(&decimal("3")).unwrap().scale(), DIVISION_SCALE);
+    }
+    
+    #[test]
+    fn ints_are_promoted_to_the_exact_operand_type() {
+        let big = Value::BigInt(BigInt::from(i64::MAX));
+        match exact_binary_op(&BinOp::Add, &big, &Value::Int(1)) {
+            Some(Ok(Value::BigInt(sum))) => assert_eq!(sum, BigInt::from(i64::MAX) + 1),
+            other => panic!("bigint + int gave {:?}", other.map(|result| result.map(|value| value.type_name()))),
+        }
+        
+        match exact_binary_op(&BinOp::Mul, &Value::Int(2), &Value::Decimal(decimal("1.25"))) {
+            Some(Ok(Value::Decimal(product))) => assert_eq!(product.to_string(), "2.50"),
+            other => panic!("int * decimal gave {:?}", other.map(|result| result.map(|value| value.type_name()))),
+        }
+        
+        assert!(exact_binary_op(&BinOp::Add, &Value::Int(1), &Value::Int(2)).is_none());
+        assert!(matches!(exact_binary_op(&BinOp::Add, &big, &Value::Float(1.0)), Some(Err(_))));
+        assert!(matches!(exact_binary_op(&BinOp::Eq, &big, &Value::Float(1.0)), Some(Ok(Value::Bool(false)))));
+        assert!(matches!(exact_binary_op(&BinOp::Div, &big, &Value::Int(0)), Some(Err(ShitRustError::RuntimeError(_)))));
+    }
+    
+    #[test]
+    fn casts_to_int_fail_out_of_range() {
+        let too_big = Value::BigInt(BigInt::from(i64::MAX) + 1);
+        assert!(matches!(exact_cast(&too_big, &Type::Int), Some(Err(_))));
+        assert!(matches!(exact_cast(&Value::BigInt(BigInt::from(-7)), &Type::Int), Some(Ok(Value::Int(-7)))));
+        assert!(matches!(exact_cast(&Value::Decimal(decimal("-7.9")), &Type::Int), Some(Ok(Value::Int(-7)))));
+        assert!(matches!(exact_cast(&Value::Float(f64::NAN), &Type::Decimal), Some(Err(_))));
+        assert!(exact_cast(&Value::Int(1), &Type::Float).is_none());
+        
+        match exact_cast(&Value::Float(0.1), &Type::Decimal) {
+            Some(Ok(Value::Decimal(d))) => assert_eq!(d.to_string(), "0.1"),
+            other => panic!("0.1 as decimal gave {:?}", other.map(|result| result.map(|value| value.type_name()))),
+        }
+    }
+}
@@ -148,7 +148,7 @@ impl TraitRegistry {
         
         // Process each method declaration
         for method in methods {
-            if let Stmt::Function { name, .. } = method {
+            if let Stmt::Function { name, .. } = method.without_attributes() {
                 // Execute the function declaration to get the function value
                 interpreter.execute_statement(method)?;
                 
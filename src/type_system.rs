@@ -1,12 +1,119 @@
-use std::collections::HashMap;
-use crate::ast::{Type, Expr, Stmt, Pattern, Literal};
+use std::collections::{HashMap, HashSet};
+use crate::ast::{self, Type, Expr, Stmt, Pattern, Literal, BinOp, UnaryOp, OptionalChainItem, INT_METHODS};
 use crate::error::{ShitRustError, Result};
 
+/// Signature of a named function, kept separately so generic parameters survive
+#[derive(Debug, Clone)]
+pub struct FunctionSignature {
+    /// Generic type parameters declared on the function
+    pub generic_params: Vec<String>,
+    
+    /// Parameter types, in declaration order
+    pub params: Vec<Type>,
+    
+    /// Declared return type
+    pub return_type: Type,
+    
+    /// Whether the function is async (calls produce a future)
+    pub is_async: bool,
+    
+    /// Trait bounds on the generic parameters, from `<T: Trait>` and `where` clauses
+    pub bounds: Vec<(String, Vec<String>)>,
+}
+
+/// Full definition of a struct type
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    /// Name of the struct
+    pub name: String,
+    
+    /// Generic type parameters
+    pub generic_params: Vec<String>,
+    
+    /// Fields as (name, type, mutable)
+    pub fields: Vec<(String, Type, bool)>,
+    
+    /// Method signatures, including methods added by impl blocks
+    pub methods: HashMap<String, FunctionSignature>,
+}
+
+/// Full definition of an enum type
+#[derive(Debug, Clone)]
+pub struct EnumDef {
+    /// Name of the enum
+    pub name: String,
+    
+    /// Generic type parameters
+    pub generic_params: Vec<String>,
+    
+    /// Variants and their payload types
+    pub variants: Vec<(String, Vec<Type>)>,
+    
+    /// Method signatures added by impl blocks
+    pub methods: HashMap<String, FunctionSignature>,
+}
+
+/// Full definition of a trait
+#[derive(Debug, Clone)]
+pub struct TraitDef {
+    /// Name of the trait
+    pub name: String,
+    
+    /// Generic type parameters
+    pub generic_params: Vec<String>,
+    
+    /// Method signatures, without the `self` parameter
+    pub methods: HashMap<String, FunctionSignature>,
+    
+    /// Methods that have a default implementation
+    pub defaults: Vec<String>,
+}
+
+/// Simplified pattern used by the exhaustiveness and reachability analysis
+#[derive(Debug, Clone)]
+enum PatternShape {
+    /// Matches anything (wildcards and bindings)
+    Wild,
+    
+    /// A constructor of a finite type (enum variant, bool, Option, Result) or a literal
+    Ctor(String, Vec<PatternShape>),
+    
+    /// One of several alternatives
+    Or(Vec<PatternShape>),
+}
+
 /// Represents a type environment for type checking
 pub struct TypeEnvironment {
     /// Variables and their types
     variables: HashMap<String, Type>,
     
+    /// Variables in this scope declared with `let mut`
+    mutable: HashSet<String>,
+    
+    /// Constants declared in this scope
+    constants: HashSet<String>,
+    
+    /// Declared types of variables whose type has been narrowed in this scope
+    declared: HashMap<String, Type>,
+    
+    /// Named function signatures
+    functions: HashMap<String, FunctionSignature>,
+    
+    /// Struct definitions
+    structs: HashMap<String, StructDef>,
+    
+    /// Enum definitions
+    enums: HashMap<String, EnumDef>,
+    
+    /// Trait definitions
+    traits: HashMap<String, TraitDef>,
+    
+    /// Traits implemented by each type, keyed by type name
+    impls: HashMap<String, Vec<String>>,
+    
+    /// Trait bounds on the generic parameters in scope
+    bounds: HashMap<String, Vec<String>>,
+    
     /// Type aliases
     type_aliases: HashMap<String, Type>,
     
@@ -22,6 +129,15 @@ impl TypeEnvironment {
     pub fn new() -> Self {
         TypeEnvironment {
             variables: HashMap::new(),
+            mutable: HashSet::new(),
+            constants: HashSet::new(),
+            declared: HashMap::new(),
+            functions: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            traits: HashMap::new(),
+            impls: HashMap::new(),
+            bounds: HashMap::new(),
             type_aliases: HashMap::new(),
             generic_params: Vec::new(),
             parent: None,
@@ -32,6 +148,15 @@ impl TypeEnvironment {
     pub fn new_child(&self) -> Self {
         TypeEnvironment {
             variables: HashMap::new(),
+            mutable: HashSet::new(),
+            constants: HashSet::new(),
+            declared: HashMap::new(),
+            functions: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            traits: HashMap::new(),
+            impls: HashMap::new(),
+            bounds: HashMap::new(),
             type_aliases: HashMap::new(),
             generic_params: Vec::new(),
             parent: Some(Box::new(self.clone())),
@@ -40,9 +165,77 @@ impl TypeEnvironment {
     
     /// Define a variable type
     pub fn define(&mut self, name: String, typ: Type) {
+        self.mutable.remove(&name);
+        self.constants.remove(&name);
+        self.declared.remove(&name);
         self.variables.insert(name, typ);
     }
     
+    /// Define a variable that may be reassigned
+    pub fn define_mutable(&mut self, name: String, typ: Type) {
+        self.define(name.clone(), typ);
+        self.mutable.insert(name);
+    }
+    
+    /// Define a constant
+    pub fn define_constant(&mut self, name: String, typ: Type) {
+        self.define(name.clone(), typ);
+        self.constants.insert(name);
+    }
+    
+    /// Refine the type of an existing variable in this scope, keeping its mutability
+    pub fn narrow(&mut self, name: String, typ: Type) {
+        let mutable = self.is_mutable(&name) == Some(true);
+        let constant = self.is_constant(&name);
+        let Some(declared) = self.declared_type(&name) else {
+            return;
+        };
+        
+        self.define(name.clone(), typ);
+        self.declared.insert(name.clone(), declared);
+        if mutable {
+            self.mutable.insert(name.clone());
+        }
+        if constant {
+            self.constants.insert(name);
+        }
+    }
+    
+    /// Get the type a variable was declared with, ignoring any narrowing
+    pub fn declared_type(&self, name: &str) -> Option<Type> {
+        if let Some(typ) = self.declared.get(name) {
+            Some(typ.clone())
+        } else if let Some(typ) = self.variables.get(name) {
+            Some(typ.clone())
+        } else if let Some(parent) = &self.parent {
+            parent.declared_type(name)
+        } else {
+            None
+        }
+    }
+    
+    /// Check whether the nearest binding of a name is mutable
+    pub fn is_mutable(&self, name: &str) -> Option<bool> {
+        if self.variables.contains_key(name) {
+            Some(self.mutable.contains(name))
+        } else if let Some(parent) = &self.parent {
+            parent.is_mutable(name)
+        } else {
+            None
+        }
+    }
+    
+    /// Check whether the nearest binding of a name is a constant
+    pub fn is_constant(&self, name: &str) -> bool {
+        if self.variables.contains_key(name) {
+            self.constants.contains(name)
+        } else if let Some(parent) = &self.parent {
+            parent.is_constant(name)
+        } else {
+            false
+        }
+    }
+    
     /// Get a variable's type
     pub fn get(&self, name: &str) -> Option<Type> {
         if let Some(typ) = self.variables.get(name) {
@@ -54,6 +247,101 @@ impl TypeEnvironment {
         }
     }
     
+    /// Define a function signature
+    pub fn define_function(&mut self, name: String, signature: FunctionSignature) {
+        self.functions.insert(name, signature);
+    }
+    
+    /// Get a function's signature
+    pub fn get_function(&self, name: &str) -> Option<FunctionSignature> {
+        if let Some(signature) = self.functions.get(name) {
+            Some(signature.clone())
+        } else if let Some(parent) = &self.parent {
+            parent.get_function(name)
+        } else {
+            None
+        }
+    }
+    
+    /// Define a struct type
+    pub fn define_struct(&mut self, def: StructDef) {
+        self.structs.insert(def.name.clone(), def);
+    }
+    
+    /// Get a struct definition
+    pub fn get_struct(&self, name: &str) -> Option<StructDef> {
+        if let Some(def) = self.structs.get(name) {
+            Some(def.clone())
+        } else if let Some(parent) = &self.parent {
+            parent.get_struct(name)
+        } else {
+            None
+        }
+    }
+    
+    /// Define an enum type
+    pub fn define_enum(&mut self, def: EnumDef) {
+        self.enums.insert(def.name.clone(), def);
+    }
+    
+    /// Get an enum definition
+    pub fn get_enum(&self, name: &str) -> Option<EnumDef> {
+        if let Some(def) = self.enums.get(name) {
+            Some(def.clone())
+        } else if let Some(parent) = &self.parent {
+            parent.get_enum(name)
+        } else {
+            None
+        }
+    }
+    
+    /// Define a trait
+    pub fn define_trait(&mut self, def: TraitDef) {
+        self.traits.insert(def.name.clone(), def);
+    }
+    
+    /// Get a trait definition
+    pub fn get_trait(&self, name: &str) -> Option<TraitDef> {
+        if let Some(def) = self.traits.get(name) {
+            Some(def.clone())
+        } else if let Some(parent) = &self.parent {
+            parent.get_trait(name)
+        } else {
+            None
+        }
+    }
+    
+    /// Record that a type implements a trait
+    pub fn add_impl(&mut self, type_name: String, trait_name: String) {
+        self.impls.entry(type_name).or_default().push(trait_name);
+    }
+    
+    /// Check if a type implements a trait
+    pub fn implements(&self, type_name: &str, trait_name: &str) -> bool {
+        self.impls.get(type_name).map_or(false, |traits| traits.iter().any(|t| t == trait_name)) ||
+            if let Some(parent) = &self.parent {
+                parent.implements(type_name, trait_name)
+            } else {
+                false
+            }
+    }
+    
+    /// Add trait bounds for a generic type parameter
+    pub fn add_bounds(&mut self, param: String, traits: Vec<String>) {
+        self.bounds.entry(param).or_default().extend(traits);
+    }
+    
+    /// Get the trait bounds of a generic type parameter
+    pub fn get_bounds(&self, param: &str) -> Vec<String> {
+        if let Some(traits) = self.bounds.get(param) {
+            traits.clone()
+        } else if let Some(parent) = &self.parent {
+            parent.get_bounds(param)
+        } else {
+            Vec::new()
+        }
+    }
+    
     /// Define a type alias
     pub fn define_alias(&mut self, name: String, typ: Type) {
         self.type_aliases.insert(name, typ);
@@ -90,6 +378,15 @@ impl Clone for TypeEnvironment {
     fn clone(&self) -> Self {
         TypeEnvironment {
             variables: self.variables.clone(),
+            mutable: self.mutable.clone(),
+            constants: self.constants.clone(),
+            declared: self.declared.clone(),
+            functions: self.functions.clone(),
+            structs: self.structs.clone(),
+            enums: self.enums.clone(),
+            traits: self.traits.clone(),
+            impls: self.impls.clone(),
+            bounds: self.bounds.clone(),
             type_aliases: self.type_aliases.clone(),
             generic_params: self.generic_params.clone(),
             parent: self.parent.clone(),
@@ -104,51 +401,217 @@ pub struct TypeChecker {
     
     /// Type constraints for generics
     constraints: HashMap<String, Vec<Type>>,
+    
+    /// Declared return type of the function whose body is being checked
+    current_return_type: Option<Type>,
+    
+    /// Concrete type arguments each generic function, struct and enum variant was used with
+    instantiations: HashMap<String, Vec<Vec<Type>>>,
+    
+    /// Match arms that earlier arms already cover, as the id of the expression the match is on
+    /// (see `ast::walk_exprs`) and the index of the arm
+    unreachable_arms: HashSet<(usize, usize)>,
+    
+    /// Unreachable arms found while `check_program` runs, by the address of the expression the
+    /// match is on. They get ids once the program is checked.
+    unreachable_found: Vec<(usize, usize)>,
+    
+    /// Types returned so far by the block lambda whose return type is being inferred
+    lambda_returns: Option<Vec<Type>>,
+    
+    /// Which expressions `check_program` records the types of
+    recording: TypeRecording,
+    
+    /// Ids of the expressions being recorded, by address. Only filled while `check_program`
+    /// runs, when the addresses are those of the program it checks.
+    recorded_ids: HashMap<usize, usize>,
+    
+    /// Types recorded for the checked program's expressions, by id, see `ast::walk_exprs`
+    recorded_types: HashMap<usize, Type>,
+}
+
+/// Which expressions `TypeChecker::check_program` records the inferred types of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeRecording {
+    None,
+    /// Lambdas, for `TypeChecker::annotate_lambdas`
+    Lambdas,
+    /// Every expression, for dumps of the typed AST
+    All,
 }
 
 impl TypeChecker {
     /// Create a new type checker
     pub fn new() -> Self {
+        let mut env = TypeEnvironment::new();
+        
+        // Built-in native functions accept any number of arguments of any type
+        for builtin in ["print", "println"] {
+            env.define(builtin.to_string(), Type::Custom("any".to_string()));
+        }
+        
         TypeChecker {
-            env: TypeEnvironment::new(),
+            env,
             constraints: HashMap::new(),
+            current_return_type: None,
+            instantiations: HashMap::new(),
+            unreachable_arms: HashSet::new(),
+            unreachable_found: Vec::new(),
+            lambda_returns: None,
+            recording: TypeRecording::None,
+            recorded_ids: HashMap::new(),
+            recorded_types: HashMap::new(),
+        }
+    }
+    
+    /// Record the types of some expressions of the programs checked from now on
+    pub fn record_types(&mut self, recording: TypeRecording) {
+        self.recording = recording;
+    }
+    
+    /// Get the type recorded for the expression with id `id` in the checked program. An
+    /// expression checked more than once, such as in a generic body, keeps its first type.
+    pub fn recorded_type(&self, id: usize) -> Option<&Type> {
+        self.recorded_types.get(&id)
+    }
+    
+    /// Give the lambdas of a checked program that declare no return type the one recorded for
+    /// them, when compiled code can hold its values. `statements` may be a copy of the checked
+    /// program, as long as it is unchanged.
+    pub fn annotate_lambdas(&self, statements: &mut [Stmt]) {
+        let mut id = 0;
+        ast::walk_exprs_mut(statements, &mut |expr| {
+            if let Expr::Lambda { return_type: return_type @ None, .. } = expr {
+                if let Some(Type::Function(_, returned)) = self.recorded_types.get(&id) {
+                    if self.is_compilable(returned) {
+                        *return_type = Some((**returned).clone());
+                    }
+                }
+            }
+            id += 1;
+        });
+    }
+    
+    /// Whether compiled code can hold the values of a type: it names no dynamic values, type
+    /// parameters or types the code generator has no layout for
+    fn is_compilable(&self, typ: &Type) -> bool {
+        match typ {
+            Type::Int | Type::Float | Type::Bool | Type::String | Type::Char | Type::Void => true,
+            Type::Custom(name) => self.env.get_struct(name).is_some() || self.env.get_enum(name).is_some(),
+            Type::Generic(_, args) => args.iter().all(|arg| self.is_compilable(arg)),
+            Type::List(element) => self.is_compilable(element),
+            Type::Dict(key, value) => self.is_compilable(key) && self.is_compilable(value),
+            Type::Function(params, ret) => params.iter().all(|param| self.is_compilable(param)) && self.is_compilable(ret),
+            _ => false,
         }
     }
     
+    /// Get the match arms of the checked program that can never match, as the id of the
+    /// expression each match is on (see `ast::walk_exprs`) and the index of the arm
+    pub fn unreachable_arms(&self) -> &HashSet<(usize, usize)> {
+        &self.unreachable_arms
+    }
+    
+    /// Get the distinct type arguments each generic function, struct and enum variant was
+    /// instantiated with. Variants are named like `Option::Some` and take the enum's type arguments.
+    pub fn instantiations(&self) -> &HashMap<String, Vec<Vec<Type>>> {
+        &self.instantiations
+    }
+    
     /// Type check a program
     pub fn check_program(&mut self, program: &[Stmt]) -> Result<()> {
-        for stmt in program {
-            self.check_statement(stmt)?;
+        let recording = self.recording;
+        let mut ids = HashMap::new();
+        if recording != TypeRecording::None {
+            let mut id = 0;
+            ast::walk_exprs(program, &mut |expr| {
+                if recording == TypeRecording::All || matches!(expr, Expr::Lambda { .. }) {
+                    ids.insert(expr as *const Expr as usize, id);
+                }
+                id += 1;
+            });
         }
-        Ok(())
+        self.recorded_ids = ids;
+        
+        let result = program.iter().try_for_each(|stmt| self.check_statement(stmt));
+        self.recorded_ids.clear();
+        
+        // Programs rarely have unreachable arms, so only they pay for numbering the expressions
+        let found = std::mem::take(&mut self.unreachable_found);
+        if !found.is_empty() {
+            let mut id = 0;
+            ast::walk_exprs(program, &mut |expr| {
+                let address = expr as *const Expr as usize;
+                for &(scrutinee, arm) in &found {
+                    if scrutinee == address {
+                        self.unreachable_arms.insert((id, arm));
+                    }
+                }
+                id += 1;
+            });
+        }
+        result
     }
     
     /// Type check a statement
     pub fn check_statement(&mut self, stmt: &Stmt) -> Result<()> {
         match stmt {
-            Stmt::Let { name, type_hint, value, mutable: _ } => {
+            Stmt::Let { name, type_hint, value, mutable } => {
                 let value_type = self.infer_expr(value)?;
                 
-                if let Some(hint) = type_hint {
+                let binding_type = if let Some(hint) = type_hint {
                     if !self.types_compatible(hint, &value_type)? {
                         return Err(ShitRustError::TypeError(
                             format!("Type mismatch: expected {:?}, found {:?}", hint, value_type)
                         ));
                     }
-                    self.env.define(name.clone(), hint.clone());
+                    hint.clone()
+                } else {
+                    value_type
+                };
+                
+                if *mutable {
+                    self.env.define_mutable(name.clone(), binding_type);
                 } else {
-                    self.env.define(name.clone(), value_type);
+                    self.env.define(name.clone(), binding_type);
                 }
             },
+            Stmt::Const { name, type_hint, value, is_public: _ } => {
+                let value_type = self.infer_expr(value)?;
+                
+                if !self.types_compatible(type_hint, &value_type)? {
+                    return Err(ShitRustError::TypeError(
+                        format!("Type mismatch in constant '{}': expected {:?}, found {:?}", name, type_hint, value_type)
+                    ));
+                }
+                
+                self.env.define_constant(name.clone(), type_hint.clone());
+            },
             Stmt::Assign { target, value } => {
-                let target_type = self.infer_expr(target)?;
+                // A narrowed variable can be assigned anything its declared type allows
+                let target_type = match target {
+                    Expr::Identifier(name) => match self.env.declared_type(name) {
+                        Some(declared) => declared,
+                        None => self.infer_expr(target)?,
+                    },
+                    _ => self.infer_expr(target)?,
+                };
                 let value_type = self.infer_expr(value)?;
                 
+                self.check_assignable(target)?;
+                
                 if !self.types_compatible(&target_type, &value_type)? {
                     return Err(ShitRustError::TypeError(
                         format!("Type mismatch in assignment: expected {:?}, found {:?}", target_type, value_type)
                     ));
                 }
+                
+                // The previous narrowing no longer holds
+                if let Expr::Identifier(name) = target {
+                    if self.env.get(name).as_ref() != Some(&target_type) {
+                        self.env.narrow(name.clone(), target_type);
+                    }
+                }
             },
             Stmt::If { condition, then_block, else_block } => {
                 let cond_type = self.infer_expr(condition)?;
@@ -158,8 +621,11 @@ impl TypeChecker {
                     ));
                 }
                 
+                let (then_narrowing, else_narrowing) = self.narrow_condition(condition);
+                
                 let child_env = self.env.new_child();
                 let old_env = std::mem::replace(&mut self.env, child_env);
+                self.apply_narrowing(&then_narrowing);
                 
                 for stmt in then_block {
                     self.check_statement(stmt)?;
@@ -168,6 +634,7 @@ impl TypeChecker {
                 if let Some(else_block) = else_block {
                     // Reset environment for else block
                     self.env = old_env.new_child();
+                    self.apply_narrowing(&else_narrowing);
                     
                     for stmt in else_block {
                         self.check_statement(stmt)?;
@@ -175,50 +642,222 @@ impl TypeChecker {
                 }
                 
                 self.env = old_env;
+                
+                // When one branch cannot fall through, the code after the `if` only runs via the other
+                let else_diverges = else_block.as_ref().map_or(false, |block| self.block_diverges(block));
+                if self.block_diverges(then_block) && !else_diverges {
+                    self.apply_narrowing(&else_narrowing);
+                } else if else_diverges && !self.block_diverges(then_block) {
+                    self.apply_narrowing(&then_narrowing);
+                }
             },
-            Stmt::Function { name, params, return_type, body, is_async, is_public: _, generic_params } => {
-                // Create a new environment for the function
-                let old_env = std::mem::replace(&mut self.env, self.env.new_child());
+            Stmt::While { condition, body } => {
+                let cond_type = self.infer_expr(condition)?;
+                if !matches!(cond_type, Type::Bool) {
+                    return Err(ShitRustError::TypeError(
+                        format!("Condition must be a boolean, found {:?}", cond_type)
+                    ));
+                }
                 
-                // Add generic parameters
-                self.env.add_generic_params(generic_params.clone());
+                let (body_narrowing, _) = self.narrow_condition(condition);
                 
-                // Add parameters to environment
-                for (param_name, param_type) in params {
-                    self.env.define(param_name.clone(), param_type.clone());
+                let child_env = self.env.new_child();
+                let old_env = std::mem::replace(&mut self.env, child_env);
+                self.apply_narrowing(&body_narrowing);
+                
+                let result = body.iter().try_for_each(|stmt| self.check_statement(stmt));
+                
+                self.env = old_env;
+                result?;
+            },
+            Stmt::Loop { body } => {
+                self.check_block(body)?;
+            },
+            Stmt::For { var, iterator, body } => {
+                let element_type = match self.infer_expr(iterator)? {
+                    Type::List(element) | Type::Array(element, _) | Type::Range(element) => *element,
+                    Type::String => Type::Char,
+                    _ => Type::Custom("any".to_string()),
+                };
+                
+                let child_env = self.env.new_child();
+                let old_env = std::mem::replace(&mut self.env, child_env);
+                self.env.define(var.clone(), element_type);
+                
+                let result = self.check_block(body);
+                
+                self.env = old_env;
+                result?;
+            },
+            Stmt::Expr(expr) => {
+                self.infer_expr(expr)?;
+            },
+            Stmt::Match { expr, arms } => {
+                let scrutinee = self.infer_expr(expr)?;
+                let patterns: Vec<&Pattern> = arms.iter().map(|(pattern, _)| pattern).collect();
+                for arm in self.check_match_patterns(&scrutinee, &patterns)? {
+                    self.unreachable_found.push((expr as *const Expr as usize, arm));
                 }
                 
-                // Check function body
-                for stmt in body {
-                    self.check_statement(stmt)?;
+                let mut remaining = scrutinee.clone();
+                let mut none_arms_diverge = false;
+                
+                for (pattern, body) in arms {
+                    let child_env = self.env.new_child();
+                    let old_env = std::mem::replace(&mut self.env, child_env);
+                    self.narrow_scrutinee(expr, pattern, &remaining);
+                    
+                    let result = self.check_pattern(pattern, &Self::pattern_target(pattern, &scrutinee, &remaining))
+                        .and_then(|_| body.iter().try_for_each(|stmt| self.check_statement(stmt)));
+                    
+                    self.env = old_env;
+                    result?;
+                    
+                    if let Some(rest) = Self::without_none_arm(pattern, &remaining) {
+                        remaining = rest;
+                        none_arms_diverge = self.block_diverges(body);
+                    }
+                }
+                
+                // `match x { none => return, ... }` leaves `x` non-optional for the rest of the block
+                if none_arms_diverge {
+                    if let Expr::Identifier(name) = expr {
+                        self.env.narrow(name.clone(), remaining);
+                    }
                 }
+            },
+            Stmt::Function { name, params, return_type, body, is_async, is_public: _, generic_params, bounds } => {
+                self.check_bounds(generic_params, bounds)?;
                 
                 // Create function type
                 let param_types: Vec<Type> = params.iter().map(|(_, t)| t.clone()).collect();
                 let func_type = Type::Function(param_types, Box::new(return_type.clone()));
-                
-                // Add function to parent environment
-                self.env = old_env;
-                self.env.define(name.clone(), if *is_async {
+                let func_type = if *is_async {
                     Type::Future(Box::new(func_type))
                 } else {
                     func_type
+                };
+                
+                let signature = FunctionSignature {
+                    generic_params: generic_params.clone(),
+                    params: params.iter().map(|(_, t)| t.clone()).collect(),
+                    return_type: return_type.clone(),
+                    is_async: *is_async,
+                    bounds: bounds.clone(),
+                };
+                
+                // Define the function before checking its body so recursive calls resolve
+                self.env.define(name.clone(), func_type.clone());
+                self.env.define_function(name.clone(), signature.clone());
+                
+                // Create a new environment for the function
+                let child_env = self.env.new_child();
+                let old_env = std::mem::replace(&mut self.env, child_env);
+                let old_return_type = self.current_return_type.replace(return_type.clone());
+                // Returns in the function are not those of a lambda around it
+                let old_lambda_returns = self.lambda_returns.take();
+                
+                // Add generic parameters and their bounds
+                self.env.add_generic_params(generic_params.clone());
+                for (param, traits) in bounds {
+                    self.env.add_bounds(param.clone(), traits.clone());
+                }
+                
+                // Add parameters to environment
+                for (param_name, param_type) in params {
+                    self.env.define(param_name.clone(), param_type.clone());
+                }
+                
+                // Check function body
+                let body_result = self.check_function_body(name, return_type, body);
+                
+                // Restore the enclosing environment even if the body failed to check
+                self.current_return_type = old_return_type;
+                self.lambda_returns = old_lambda_returns;
+                self.env = old_env;
+                body_result?;
+                
+                self.env.define(name.clone(), func_type);
+                self.env.define_function(name.clone(), signature);
+            },
+            Stmt::Extern { functions, .. } => {
+                for function in functions {
+                    let param_types: Vec<Type> = function.params.iter().map(|(_, t)| t.clone()).collect();
+                    let passable = |typ: &Type| matches!(typ, Type::Int | Type::CInt | Type::Float | Type::Bool | Type::String | Type::Pointer);
+                    let unsupported = param_types.iter().find(|typ| !passable(typ))
+                        .or(Some(&function.return_type).filter(|typ| !passable(typ) && **typ != Type::Void));
+                    
+                    if let Some(typ) = unsupported {
+                        return Err(ShitRustError::TypeError(format!(
+                            "Extern function '{}' uses {:?}, but only int, c_int, float, bool, string and ptr can be passed to C",
+                            function.name, typ
+                        )));
+                    }
+                    
+                    // C ints are converted at the call, so callers pass and get ints
+                    let as_seen = |typ: &Type| if *typ == Type::CInt { Type::Int } else { typ.clone() };
+                    let param_types: Vec<Type> = param_types.iter().map(as_seen).collect();
+                    let return_type = as_seen(&function.return_type);
+                    
+                    self.env.define(function.name.clone(), Type::Function(param_types.clone(), Box::new(return_type.clone())));
+                    self.env.define_function(function.name.clone(), FunctionSignature {
+                        generic_params: Vec::new(),
+                        params: param_types,
+                        return_type,
+                        is_async: false,
+                        bounds: Vec::new(),
+                    });
+                }
+            },
+            Stmt::Use { path, as_name } => {
+                // Module members are resolved at runtime, so the binding is dynamically typed
+                let module_name = as_name.clone().unwrap_or_else(|| {
+                    path.split('.').last().unwrap_or(path).to_string()
                 });
+                
+                self.env.define(module_name, Type::Custom("any".to_string()));
             },
-            Stmt::Struct { name, fields, methods, is_public: _, generic_params } => {
-                // Add generic parameters
-                let old_env = self.env.clone();
-                self.env.add_generic_params(generic_params.clone());
+            Stmt::Attributed { stmt, .. } | Stmt::Located { stmt, .. } => {
+                // Attributes only affect linting
+                self.check_statement(stmt)?;
+            },
+            Stmt::Return(value) => {
+                let expected = self.current_return_type.clone().ok_or_else(|| {
+                    ShitRustError::TypeError("Return statement outside of function".to_string())
+                })?;
                 
-                // Check field types
-                for (_, field_type, _) in fields {
-                    self.check_type(field_type)?;
+                let actual = match value {
+                    Some(expr) => self.infer_expr(expr)?,
+                    None => Type::Void,
+                };
+                
+                if matches!(expected, Type::Never) {
+                    return Err(ShitRustError::TypeError(
+                        "Function declared as 'never' cannot return".to_string()
+                    ));
+                }
+                
+                if !self.types_compatible(&expected, &actual)? {
+                    return Err(ShitRustError::TypeError(
+                        format!("Return type mismatch: expected {:?}, found {:?}", expected, actual)
+                    ));
                 }
                 
-                // Check methods
+                if let Some(returned) = self.lambda_returns.as_mut() {
+                    returned.push(actual);
+                }
+            },
+            Stmt::Struct { name, fields, methods, is_public: _, generic_params } => {
+                let mut method_signatures = HashMap::new();
                 for method in methods {
-                    if let Stmt::Function { .. } = method {
-                        self.check_statement(method)?;
+                    if let Stmt::Function { name: method_name, params, return_type, is_async, generic_params: method_generics, bounds, .. } = method.without_attributes() {
+                        method_signatures.insert(method_name.clone(), FunctionSignature {
+                            generic_params: generic_params.iter().chain(method_generics).cloned().collect(),
+                            params: params.iter().map(|(_, t)| t.clone()).collect(),
+                            return_type: return_type.clone(),
+                            is_async: *is_async,
+                            bounds: bounds.clone(),
+                        });
                     } else {
                         return Err(ShitRustError::TypeError(
                             "Only function definitions are allowed in structs".to_string()
@@ -226,116 +865,1651 @@ impl TypeChecker {
                     }
                 }
                 
-                // Define the struct type
-                self.env = old_env;
+                // Define the struct type up front so fields and methods can refer to it
                 self.env.define(name.clone(), Type::Custom(name.clone()));
-            },
-            Stmt::Enum { name, variants, is_public: _, generic_params } => {
-                // Add generic parameters
-                let old_env = self.env.clone();
-                self.env.add_generic_params(generic_params.clone());
+                self.env.define_struct(StructDef {
+                    name: name.clone(),
+                    generic_params: generic_params.clone(),
+                    fields: fields.clone(),
+                    methods: method_signatures.clone(),
+                });
                 
-                // Check variant types
-                for (_, variant_types) in variants {
-                    for typ in variant_types {
-                        self.check_type(typ)?;
-                    }
+                // Methods are also reachable as `Struct::method`
+                for (method_name, signature) in method_signatures {
+                    self.env.define_function(format!("{}::{}", name, method_name), signature);
                 }
                 
-                // Define the enum type
+                // Add generic parameters
+                let old_env = self.env.clone();
+                self.env.add_generic_params(generic_params.clone());
+                
+                // Check field types
+                let result = fields.iter()
+                    .try_for_each(|(_, field_type, _)| self.check_type(field_type))
+                    .and_then(|_| self.check_methods(Self::nominal_type(name, generic_params), methods));
+                
                 self.env = old_env;
+                result?;
+            },
+            Stmt::Enum { name, variants, is_public: _, generic_params } => {
+                // Define the enum type
                 self.env.define(name.clone(), Type::Custom(name.clone()));
+                self.env.define_enum(EnumDef {
+                    name: name.clone(),
+                    generic_params: generic_params.clone(),
+                    variants: variants.clone(),
+                    methods: HashMap::new(),
+                });
+                
+                // Add generic parameters
+                let old_env = self.env.clone();
+                self.env.add_generic_params(generic_params.clone());
+                
+                // Check variant types
+                let result = variants.iter()
+                    .flat_map(|(_, variant_types)| variant_types)
+                    .try_for_each(|typ| self.check_type(typ));
+                
+                self.env = old_env;
+                result?;
+                
+                // Register a constructor for every variant as `Enum::Variant`
+                let enum_type = Self::nominal_type(name, generic_params);
+                for (variant, payload) in variants {
+                    let constructor = format!("{}::{}", name, variant);
+                    
+                    if payload.is_empty() {
+                        // Unit variants do not constrain the enum's type arguments
+                        let unconstrained = generic_params.iter()
+                            .map(|param| (param.clone(), Type::Custom("any".to_string())))
+                            .collect();
+                        self.env.define(constructor, self.substitute(&enum_type, &unconstrained));
+                    } else {
+                        self.env.define_function(constructor, FunctionSignature {
+                            generic_params: generic_params.clone(),
+                            params: payload.clone(),
+                            return_type: enum_type.clone(),
+                            is_async: false,
+                            bounds: Vec::new(),
+                        });
+                    }
+                }
             },
             Stmt::Trait { name, methods, is_public: _, generic_params } => {
-                // Add generic parameters
+                // Add generic parameters; `Self` stands for the implementing type
                 let old_env = self.env.clone();
                 self.env.add_generic_params(generic_params.clone());
+                self.env.add_generic_params(vec!["Self".to_string()]);
+                
+                // Check method signatures
+                let result = methods.iter().try_for_each(|method| {
+                    method.params.iter()
+                        .try_for_each(|(_, param_type)| self.check_type(param_type))
+                        .and_then(|_| self.check_type(&method.return_type))
+                });
+                
+                // Define the trait type
+                self.env = old_env;
+                result?;
+                self.env.define(name.clone(), Type::Trait(name.clone()));
+                
+                let mut signatures = HashMap::new();
+                let mut defaults = Vec::new();
+                for method in methods {
+                    signatures.insert(method.name.clone(), FunctionSignature {
+                        generic_params: Vec::new(),
+                        params: method.params.iter()
+                            .filter(|(param_name, _)| param_name != "self")
+                            .map(|(_, t)| t.clone())
+                            .collect(),
+                        return_type: method.return_type.clone(),
+                        is_async: method.is_async,
+                        bounds: Vec::new(),
+                    });
+                    
+                    if method.body.is_some() {
+                        defaults.push(method.name.clone());
+                    }
+                }
+                
+                self.env.define_trait(TraitDef {
+                    name: name.clone(),
+                    generic_params: generic_params.clone(),
+                    methods: signatures,
+                    defaults,
+                });
+            },
+            Stmt::Impl { trait_name, trait_args, type_name, type_args, methods, generic_params, bounds } => {
+                self.check_bounds(generic_params, bounds)?;
+                
+                let mut method_signatures = HashMap::new();
+                for method in methods {
+                    if let Stmt::Function { name: method_name, params, return_type, is_async, generic_params: method_generics, bounds: method_bounds, .. } = method.without_attributes() {
+                        method_signatures.insert(method_name.clone(), FunctionSignature {
+                            generic_params: generic_params.iter().chain(method_generics).cloned().collect(),
+                            params: params.iter().map(|(_, t)| t.clone()).collect(),
+                            return_type: return_type.clone(),
+                            is_async: *is_async,
+                            bounds: bounds.iter().chain(method_bounds).cloned().collect(),
+                        });
+                    }
+                }
+                
+                let target = self.impl_target(type_name, type_args)?;
+                
+                if let Some(trait_name) = trait_name {
+                    self.check_impl_conformance(trait_name, trait_args, type_name, &target, &mut method_signatures)?;
+                    self.env.add_impl(type_name.clone(), trait_name.clone());
+                }
+                
+                // Make the methods visible on the implementing type
+                if let Some(mut def) = self.env.get_struct(type_name) {
+                    def.methods.extend(method_signatures.clone());
+                    self.env.define_struct(def);
+                } else if let Some(mut def) = self.env.get_enum(type_name) {
+                    def.methods.extend(method_signatures.clone());
+                    self.env.define_enum(def);
+                }
+                
+                for (method_name, signature) in method_signatures {
+                    self.env.define_function(format!("{}::{}", type_name, method_name), signature);
+                }
+                
+                // Check method bodies with the impl's generic parameters in scope
+                let old_env = self.env.clone();
+                self.env.add_generic_params(generic_params.clone());
+                for (param, traits) in bounds {
+                    self.env.add_bounds(param.clone(), traits.clone());
+                }
+                
+                let result = self.check_methods(target, methods);
+                
+                self.env = old_env;
+                result?;
+            },
+            // Add more statement types as needed
+            _ => (), // Handle other statement types
+        }
+        
+        Ok(())
+    }
+    
+    /// Check method bodies with `self`/`this` bound to the receiver type
+    fn check_methods(&mut self, receiver: Type, methods: &[Stmt]) -> Result<()> {
+        let child_env = self.env.new_child();
+        let old_env = std::mem::replace(&mut self.env, child_env);
+        // Methods cannot declare a `mut` receiver, so field mutability is governed by the field flags
+        self.env.define_mutable("self".to_string(), receiver.clone());
+        self.env.define_mutable("this".to_string(), receiver);
+        
+        let result = methods.iter().try_for_each(|method| self.check_statement(method));
+        
+        self.env = old_env;
+        result
+    }
+    
+    /// Verify that an assignment target refers to a mutable place
+    fn check_assignable(&mut self, target: &Expr) -> Result<()> {
+        match target {
+            Expr::Identifier(name) => {
+                if self.env.is_constant(name) {
+                    return Err(ShitRustError::TypeError(format!("Cannot assign to constant '{}'", name)));
+                }
+                
+                if self.env.is_mutable(name) == Some(false) {
+                    return Err(ShitRustError::TypeError(
+                        format!("Cannot assign twice to immutable variable '{}'; declare it with 'let mut'", name)
+                    ));
+                }
+                Ok(())
+            },
+            Expr::FieldAccess { object, field } => {
+                let object_type = self.infer_expr(object)?;
+                
+                if let Some((type_name, _)) = self.nominal_parts(&object_type) {
+                    if let Some(def) = self.env.get_struct(&type_name) {
+                        let is_mutable = def.fields.iter().any(|(name, _, mutable)| name == field && *mutable);
+                        if !is_mutable {
+                            return Err(ShitRustError::TypeError(
+                                format!("Cannot assign to immutable field '{}' of struct '{}'; declare it with 'mut'", field, type_name)
+                            ));
+                        }
+                    }
+                }
+                
+                self.check_assignable(object)
+            },
+            Expr::Index { target, .. } => self.check_assignable(target),
+            _ => Err(ShitRustError::TypeError("Invalid assignment target".to_string())),
+        }
+    }
+    
+    /// Verify that every bound names a declared type parameter and a known trait
+    fn check_bounds(&self, generic_params: &[String], bounds: &[(String, Vec<String>)]) -> Result<()> {
+        for (param, traits) in bounds {
+            if !generic_params.contains(param) {
+                return Err(ShitRustError::TypeError(
+                    format!("Trait bound on undeclared type parameter '{}'", param)
+                ));
+            }
+            
+            for trait_name in traits {
+                if self.env.get_trait(trait_name).is_none() {
+                    return Err(ShitRustError::TypeError(
+                        format!("Unknown trait '{}' in bound on '{}'", trait_name, param)
+                    ));
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Check that an impl provides every required trait method with a matching signature
+    fn check_impl_conformance(&self, trait_name: &str, trait_args: &[Type], type_name: &str, target: &Type,
+                              methods: &mut HashMap<String, FunctionSignature>) -> Result<()> {
+        let def = self.env.get_trait(trait_name)
+            .ok_or_else(|| ShitRustError::TypeError(format!("Cannot implement unknown trait '{}'", trait_name)))?;
+        
+        // Trait type parameters without arguments accept anything
+        if !trait_args.is_empty() && trait_args.len() != def.generic_params.len() {
+            return Err(ShitRustError::TypeError(format!(
+                "Trait '{}' takes {} type arguments, but the impl for '{}' gives {}",
+                trait_name, def.generic_params.len(), type_name, trait_args.len()
+            )));
+        }
+        
+        // `Self` is the implementing type
+        let mut bindings = Self::bind_type_args(&def.generic_params, trait_args);
+        bindings.insert("Self".to_string(), target.clone());
+        
+        for method_name in methods.keys() {
+            if !def.methods.contains_key(method_name) {
+                return Err(ShitRustError::TypeError(
+                    format!("Method '{}' is not a member of trait '{}'", method_name, trait_name)
+                ));
+            }
+        }
+        
+        for (method_name, required) in &def.methods {
+            let expected_params: Vec<Type> = required.params.iter().map(|t| self.substitute(t, &bindings)).collect();
+            let expected_return = self.substitute(&required.return_type, &bindings);
+            
+            let Some(provided) = methods.get(method_name) else {
+                if !def.defaults.contains(method_name) {
+                    return Err(ShitRustError::TypeError(
+                        format!("Impl of '{}' for '{}' is missing required method '{}'", trait_name, type_name, method_name)
+                    ));
+                }
+                
+                methods.insert(method_name.clone(), FunctionSignature {
+                    params: expected_params,
+                    return_type: expected_return,
+                    ..required.clone()
+                });
+                continue;
+            };
+            
+            let mut matches = provided.params.len() == expected_params.len() && provided.is_async == required.is_async;
+            for (expected, actual) in expected_params.iter().zip(&provided.params) {
+                matches = matches && self.types_compatible(expected, actual)? && self.types_compatible(actual, expected)?;
+            }
+            matches = matches && self.types_compatible(&expected_return, &provided.return_type)?;
+            
+            if !matches {
+                return Err(ShitRustError::TypeError(
+                    format!("Method '{}' in impl of '{}' for '{}' has signature ({:?}) -> {:?}, but the trait declares ({:?}) -> {:?}",
+                           method_name, trait_name, type_name, provided.params, provided.return_type,
+                           expected_params, expected_return)
+                ));
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Check that a type satisfies a trait bound
+    fn implements_trait(&self, typ: &Type, trait_name: &str) -> bool {
+        match typ {
+            Type::Custom(name) if name == "any" => true,
+            Type::Custom(name) if self.env.is_generic_param(name) => {
+                self.env.get_bounds(name).iter().any(|bound| bound == trait_name)
+            },
+            _ => self.type_key(typ).map_or(false, |key| self.env.implements(&key, trait_name)),
+        }
+    }
+    
+    /// The name impls are registered under for a type
+    fn type_key(&self, typ: &Type) -> Option<String> {
+        match typ {
+            Type::Int => Some("int".to_string()),
+            Type::Float => Some("float".to_string()),
+            Type::Bool => Some("bool".to_string()),
+            Type::String => Some("string".to_string()),
+            Type::Char => Some("char".to_string()),
+            Type::BigInt => Some("bigint".to_string()),
+            Type::Decimal => Some("decimal".to_string()),
+            _ => self.nominal_parts(typ).map(|(name, _)| name),
+        }
+    }
+    
+    /// The type named by an impl target
+    fn named_type(&self, name: &str) -> Result<Type> {
+        match name {
+            "int" => Ok(Type::Int),
+            "float" => Ok(Type::Float),
+            "bool" => Ok(Type::Bool),
+            "string" => Ok(Type::String),
+            "char" => Ok(Type::Char),
+            "bigint" => Ok(Type::BigInt),
+            "decimal" => Ok(Type::Decimal),
+            _ => {
+                if let Some(def) = self.env.get_struct(name) {
+                    Ok(Self::nominal_type(name, &def.generic_params))
+                } else if let Some(def) = self.env.get_enum(name) {
+                    Ok(Self::nominal_type(name, &def.generic_params))
+                } else if let Some(aliased) = self.env.resolve_alias(name) {
+                    Ok(aliased)
+                } else {
+                    Err(ShitRustError::TypeError(format!("Cannot implement methods for unknown type '{}'", name)))
+                }
+            },
+        }
+    }
+    
+    /// The type an impl block's methods receive: the named type, with the impl's type arguments
+    /// for its type parameters when it gives them
+    fn impl_target(&self, type_name: &str, type_args: &[Type]) -> Result<Type> {
+        let target = self.named_type(type_name)?;
+        if type_args.is_empty() {
+            return Ok(target);
+        }
+        
+        match target {
+            Type::Generic(name, params) if params.len() == type_args.len() => Ok(Type::Generic(name, type_args.to_vec())),
+            Type::Generic(_, params) => Err(ShitRustError::TypeError(format!(
+                "Type '{}' takes {} type arguments, but the impl gives {}", type_name, params.len(), type_args.len()
+            ))),
+            _ => Err(ShitRustError::TypeError(format!("Type '{}' takes no type arguments", type_name))),
+        }
+    }
+    
+    /// Resolve a method on a generic type parameter through its trait bounds
+    fn lookup_bound_method(&self, param: &str, receiver: &Type, method: &str) -> Result<FunctionSignature> {
+        for trait_name in self.env.get_bounds(param) {
+            let Some(def) = self.env.get_trait(&trait_name) else {
+                continue;
+            };
+            
+            if let Some(signature) = def.methods.get(method) {
+                let mut bindings = Self::bind_type_args(&def.generic_params, &[]);
+                bindings.insert("Self".to_string(), receiver.clone());
+                
+                return Ok(FunctionSignature {
+                    params: signature.params.iter().map(|t| self.substitute(t, &bindings)).collect(),
+                    return_type: self.substitute(&signature.return_type, &bindings),
+                    ..signature.clone()
+                });
+            }
+        }
+        
+        Err(ShitRustError::TypeError(
+            format!("Type parameter '{}' has no method '{}'; add a trait bound that provides it", param, method)
+        ))
+    }
+    
+    /// The type of a struct or enum as seen from inside its own definition
+    fn nominal_type(name: &str, generic_params: &[String]) -> Type {
+        if generic_params.is_empty() {
+            Type::Custom(name.to_string())
+        } else {
+            Type::Generic(
+                name.to_string(),
+                generic_params.iter().map(|param| Type::Custom(param.clone())).collect()
+            )
+        }
+    }
+    
+    /// Split a struct or enum type into its name and type arguments
+    fn nominal_parts(&self, typ: &Type) -> Option<(String, Vec<Type>)> {
+        match typ {
+            Type::Custom(name) => {
+                if let Some(aliased) = self.env.resolve_alias(name) {
+                    self.nominal_parts(&aliased)
+                } else {
+                    Some((name.clone(), Vec::new()))
+                }
+            },
+            Type::Generic(name, args) => Some((name.clone(), args.clone())),
+            Type::Reference(inner, _) => self.nominal_parts(inner),
+            _ => None,
+        }
+    }
+    
+    /// Bind a definition's generic parameters to the type arguments of a use site
+    fn bind_type_args(generic_params: &[String], args: &[Type]) -> HashMap<String, Type> {
+        generic_params.iter()
+            .enumerate()
+            .map(|(i, param)| {
+                (param.clone(), args.get(i).cloned().unwrap_or_else(|| Type::Custom("any".to_string())))
+            })
+            .collect()
+    }
+    
+    /// Look up a method on a struct or enum type, instantiated for the receiver's type arguments
+    fn lookup_method(&self, receiver: &Type, method: &str) -> Option<Result<FunctionSignature>> {
+        // Bounded type parameters expose exactly the methods of their traits
+        if let Type::Custom(param) = receiver {
+            if self.env.is_generic_param(param) && !self.env.get_bounds(param).is_empty() {
+                return Some(self.lookup_bound_method(param, receiver, method));
+            }
+        }
+        
+        let (type_name, args) = self.nominal_parts(receiver)?;
+        
+        let (generic_params, methods) = if let Some(def) = self.env.get_struct(&type_name) {
+            (def.generic_params, def.methods)
+        } else if let Some(def) = self.env.get_enum(&type_name) {
+            (def.generic_params, def.methods)
+        } else {
+            return None;
+        };
+        
+        let Some(signature) = methods.get(method) else {
+            return Some(Err(ShitRustError::TypeError(
+                format!("Type '{}' has no method '{}'", type_name, method)
+            )));
+        };
+        
+        let bindings = Self::bind_type_args(&generic_params, &args);
+        Some(Ok(FunctionSignature {
+            generic_params: signature.generic_params.iter()
+                .filter(|param| !generic_params.contains(param))
+                .cloned()
+                .collect(),
+            params: signature.params.iter().map(|t| self.substitute(t, &bindings)).collect(),
+            return_type: self.substitute(&signature.return_type, &bindings),
+            is_async: signature.is_async,
+            bounds: signature.bounds.clone(),
+        }))
+    }
+    
+    /// Result type of arithmetic involving `bigint` or `decimal`: ints are promoted to the
+    /// exact operand's type and bigints to decimal, while floats never mix with exact numbers
+    fn exact_arithmetic_type(left: &Type, right: &Type) -> Option<Type> {
+        match (left, right) {
+            (Type::Decimal, Type::Int | Type::BigInt | Type::Decimal) |
+            (Type::Int | Type::BigInt, Type::Decimal) => Some(Type::Decimal),
+            (Type::BigInt, Type::Int | Type::BigInt) |
+            (Type::Int, Type::BigInt) => Some(Type::BigInt),
+            _ => None,
+        }
+    }
+    
+    /// Signature of the `wrapping_*`, `checked_*` and `saturating_*` arithmetic methods on ints
+    fn int_method_signature(method: &str) -> Option<FunctionSignature> {
+        if !INT_METHODS.contains(&method) {
+            return None;
+        }
+        let (mode, op) = method.split_once('_')?;
+        
+        Some(FunctionSignature {
+            generic_params: Vec::new(),
+            params: if op == "neg" { Vec::new() } else { vec![Type::Int] },
+            return_type: if mode == "checked" { Type::Option(Box::new(Type::Int)) } else { Type::Int },
+            is_async: false,
+            bounds: Vec::new(),
+        })
+    }
+    
+    /// Check a lambda body and return its return type: the declared one, or else the type of
+    /// an expression body, or the join of the values a block body returns.
+    fn infer_lambda_return(&mut self, body: &Expr, declared: Option<&Type>) -> Result<Type> {
+        let Expr::Block(stmts) = body else {
+            let actual = self.infer_expr(body)?;
+            return match declared {
+                Some(expected) if !self.types_compatible(expected, &actual)? => Err(ShitRustError::TypeError(
+                    format!("Return type mismatch: expected {:?}, found {:?}", expected, actual)
+                )),
+                Some(expected) => Ok(expected.clone()),
+                None => Ok(actual),
+            };
+        };
+        
+        // Without a declared type the lambda returns what its `return`s do, or nothing
+        let return_type = declared.cloned().unwrap_or_else(|| Type::Custom("any".to_string()));
+        let old_return_type = self.current_return_type.replace(return_type.clone());
+        let old_lambda_returns = std::mem::replace(&mut self.lambda_returns, declared.is_none().then(Vec::new));
+        let result = match declared {
+            Some(declared) => self.check_function_body("<lambda>", declared, stmts),
+            None => stmts.iter().try_for_each(|stmt| self.check_statement(stmt)),
+        };
+        self.current_return_type = old_return_type;
+        let returned = std::mem::replace(&mut self.lambda_returns, old_lambda_returns);
+        result?;
+        
+        match returned {
+            Some(returned) => Ok(returned.into_iter()
+                .reduce(|joined, typ| self.join_types(joined, typ))
+                .unwrap_or(Type::Void)),
+            None => Ok(return_type),
+        }
+    }
+    
+    /// Check a function body and verify that every control path returns
+    fn check_function_body(&mut self, name: &str, return_type: &Type, body: &[Stmt]) -> Result<()> {
+        for stmt in body {
+            self.check_statement(stmt)?;
+        }
+        
+        if self.block_diverges(body) {
+            return Ok(());
+        }
+        
+        match return_type {
+            Type::Void => Ok(()),
+            Type::Never => Err(ShitRustError::TypeError(
+                format!("Function '{}' is declared as 'never' but can finish without diverging", name)
+            )),
+            _ => Err(ShitRustError::TypeError(
+                format!("Function '{}' does not return a value of type {:?} on all code paths", name, return_type)
+            )),
+        }
+    }
+    
+    /// Type check a block of statements in its own scope
+    fn check_block(&mut self, block: &[Stmt]) -> Result<()> {
+        let child_env = self.env.new_child();
+        let old_env = std::mem::replace(&mut self.env, child_env);
+        
+        let result = block.iter().try_for_each(|stmt| self.check_statement(stmt));
+        
+        self.env = old_env;
+        result
+    }
+    
+    /// Check whether control never falls off the end of a block
+    fn block_diverges(&self, block: &[Stmt]) -> bool {
+        block.iter().any(|stmt| self.stmt_diverges(stmt))
+    }
+    
+    /// Check whether a statement always returns or otherwise diverges
+    pub(crate) fn stmt_diverges(&self, stmt: &Stmt) -> bool {
+        match stmt {
+            Stmt::Return(_) => true,
+            Stmt::If { then_block, else_block: Some(else_block), .. } => {
+                self.block_diverges(then_block) && self.block_diverges(else_block)
+            },
+            Stmt::Match { arms, .. } => {
+                !arms.is_empty() && arms.iter().all(|(_, body)| self.block_diverges(body))
+            },
+            // A `loop` without a `break` can only be left by returning
+            Stmt::Loop { body } => !Self::contains_break(body),
+            Stmt::While { condition: Expr::Literal(Literal::Bool(true)), body } => {
+                !Self::contains_break(body)
+            },
+            Stmt::Try { block, catch_blocks, finally_block } => {
+                finally_block.as_ref().map_or(false, |finally| self.block_diverges(finally)) ||
+                    (self.block_diverges(block) &&
+                     catch_blocks.iter().all(|(_, body)| self.block_diverges(body)))
+            },
+            Stmt::Expr(expr) => self.expr_diverges(expr),
+            Stmt::Attributed { stmt, .. } | Stmt::Located { stmt, .. } => self.stmt_diverges(stmt),
+            _ => false,
+        }
+    }
+    
+    /// Check whether evaluating an expression never produces a value
+    fn expr_diverges(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Call { func, .. } => {
+                if let Expr::Identifier(name) = &**func {
+                    matches!(self.env.get(name), Some(Type::Function(_, ret)) if matches!(*ret, Type::Never))
+                } else {
+                    false
+                }
+            },
+            _ => false,
+        }
+    }
+    
+    /// Check whether a loop body contains a `break` that targets this loop
+    fn contains_break(block: &[Stmt]) -> bool {
+        block.iter().any(|stmt| match stmt {
+            Stmt::Break => true,
+            Stmt::If { then_block, else_block, .. } => {
+                Self::contains_break(then_block) ||
+                    else_block.as_ref().map_or(false, |b| Self::contains_break(b))
+            },
+            Stmt::Match { arms, .. } => arms.iter().any(|(_, body)| Self::contains_break(body)),
+            Stmt::Try { block, catch_blocks, finally_block } => {
+                Self::contains_break(block) ||
+                    catch_blocks.iter().any(|(_, body)| Self::contains_break(body)) ||
+                    finally_block.as_ref().map_or(false, |b| Self::contains_break(b))
+            },
+            Stmt::Attributed { stmt, .. } | Stmt::Located { stmt, .. } => Self::contains_break(std::slice::from_ref(stmt)),
+            // Breaks inside nested loops belong to those loops
+            _ => false,
+        })
+    }
+    
+    /// Infer the type of an expression
+    pub fn infer_expr(&mut self, expr: &Expr) -> Result<Type> {
+        let typ = self.infer_expr_type(expr)?;
+        if self.recorded_ids.is_empty() {
+            return Ok(typ);
+        }
+        
+        if let Some(&id) = self.recorded_ids.get(&(expr as *const Expr as usize)) {
+            self.recorded_types.entry(id).or_insert_with(|| typ.clone());
+        }
+        Ok(typ)
+    }
+    
+    fn infer_expr_type(&mut self, expr: &Expr) -> Result<Type> {
+        match expr {
+            Expr::Literal(lit) => self.infer_literal(lit),
+            Expr::Identifier(name) => {
+                if let Some(typ) = self.env.get(name) {
+                    Ok(typ)
+                } else {
+                    Err(ShitRustError::TypeError(format!("Undefined variable: {}", name)))
+                }
+            },
+            Expr::BinaryOp { left, op, right } => {
+                let left_type = self.infer_expr(left)?;
+                let right_type = self.infer_expr(right)?;
+                
+                match op {
+                    crate::ast::BinOp::Add | 
+                    crate::ast::BinOp::Sub | 
+                    crate::ast::BinOp::Mul | 
+                    crate::ast::BinOp::Div | 
+                    crate::ast::BinOp::Mod => {
+                        if let Some(result_type) = Self::exact_arithmetic_type(&left_type, &right_type) {
+                            Ok(result_type)
+                        } else if self.types_compatible(&left_type, &right_type)? {
+                            Ok(left_type)
+                        } else {
+                            Err(ShitRustError::TypeError(
+                                format!("Incompatible types for binary operation: {:?} and {:?}", left_type, right_type)
+                            ))
+                        }
+                    },
+                    crate::ast::BinOp::Eq | 
+                    crate::ast::BinOp::Ne | 
+                    crate::ast::BinOp::Lt | 
+                    crate::ast::BinOp::Le | 
+                    crate::ast::BinOp::Gt | 
+                    crate::ast::BinOp::Ge => {
+                        if Self::exact_arithmetic_type(&left_type, &right_type).is_some() ||
+                            self.types_compatible(&left_type, &right_type)? {
+                            Ok(Type::Bool)
+                        } else {
+                            Err(ShitRustError::TypeError(
+                                format!("Incompatible types for comparison: {:?} and {:?}", left_type, right_type)
+                            ))
+                        }
+                    },
+                    crate::ast::BinOp::NullishCoalescing => {
+                        // `a ?? b` is `a` with its `none` case replaced by `b`
+                        let present = match left_type {
+                            Type::Option(inner) => *inner,
+                            other => other,
+                        };
+                        Ok(self.join_types(present, right_type))
+                    },
+                    crate::ast::BinOp::And | 
+                    crate::ast::BinOp::Or => {
+                        if matches!(left_type, Type::Bool) && matches!(right_type, Type::Bool) {
+                            Ok(Type::Bool)
+                        } else {
+                            Err(ShitRustError::TypeError(
+                                format!("Boolean operations require boolean operands, found {:?} and {:?}", left_type, right_type)
+                            ))
+                        }
+                    },
+                    _ => Ok(left_type), // Handle other operators as needed
+                }
+            },
+            Expr::Call { func, args } => self.infer_call(func, args),
+            Expr::Lambda { params, body, return_type } => {
+                // Parameters without annotations are dynamically typed
+                let param_types: Vec<Type> = params.iter()
+                    .map(|(_, typ)| typ.clone().unwrap_or_else(|| Type::Custom("any".to_string())))
+                    .collect();
+                
+                let child_env = self.env.new_child();
+                let old_env = std::mem::replace(&mut self.env, child_env);
+                for ((name, _), typ) in params.iter().zip(&param_types) {
+                    self.env.define(name.clone(), typ.clone());
+                }
+                
+                let result = self.infer_lambda_return(body, return_type.as_ref());
+                
+                self.env = old_env;
+                Ok(Type::Function(param_types, Box::new(result?)))
+            },
+            Expr::MethodCall { object, method, args } => {
+                let object_type = self.infer_expr(object)?;
+                
+                let mut arg_types = Vec::with_capacity(args.len());
+                for arg in args {
+                    arg_types.push(self.infer_expr(arg)?);
+                }
+                
+                if matches!(object_type, Type::Int) {
+                    if let Some(signature) = Self::int_method_signature(method) {
+                        return self.check_call_signature(method, &signature, &arg_types);
+                    }
+                }
+                
+                match self.lookup_method(&object_type, method) {
+                    Some(signature) => self.check_call_signature(method, &signature?, &arg_types),
+                    // Built-in values expose their methods dynamically
+                    None => Ok(Type::Custom("any".to_string())),
+                }
+            },
+            Expr::FieldAccess { object, field } => {
+                let object_type = self.infer_expr(object)?;
+                self.field_type(&object_type, field)
+            },
+            Expr::TypeCast { expr, target_type } => {
+                let source = self.infer_expr(expr)?;
+                self.check_type(target_type)?;
+                
+                if !self.cast_allowed(&source, target_type) {
+                    return Err(ShitRustError::TypeError(
+                        format!("Cannot cast {:?} to {:?}", source, target_type)
+                    ));
+                }
+                
+                // Constant code points can be validated up front
+                if let (Expr::Literal(Literal::Int(value)), Type::Char) = (&**expr, target_type) {
+                    if u32::try_from(*value).ok().and_then(char::from_u32).is_none() {
+                        return Err(ShitRustError::TypeError(
+                            format!("Cannot cast {} to char: not a valid Unicode scalar value", value)
+                        ));
+                    }
+                }
+                
+                Ok(target_type.clone())
+            },
+            Expr::TypeTest { expr, target_type } => {
+                self.infer_expr(expr)?;
+                self.check_type(target_type)?;
+                Ok(Type::Bool)
+            },
+            Expr::OptionalChain { expr, chain } => {
+                // Each step works on the present value; the whole chain is `none` if any step is
+                let mut current = match self.infer_expr(expr)? {
+                    Type::Option(inner) => *inner,
+                    other => other,
+                };
+                
+                for item in chain {
+                    current = match item {
+                        OptionalChainItem::Field(field) => self.field_type(&current, field)?,
+                        OptionalChainItem::Method(method, args) => {
+                            let mut arg_types = Vec::with_capacity(args.len());
+                            for arg in args {
+                                arg_types.push(self.infer_expr(arg)?);
+                            }
+                            
+                            match self.lookup_method(&current, method) {
+                                Some(signature) => self.check_call_signature(method, &signature?, &arg_types)?,
+                                None => Type::Custom("any".to_string()),
+                            }
+                        },
+                        OptionalChainItem::Index(index) => {
+                            self.infer_expr(index)?;
+                            match current {
+                                Type::List(element) | Type::Array(element, _) => *element,
+                                Type::Dict(_, value) => *value,
+                                Type::String => Type::Char,
+                                _ => Type::Custom("any".to_string()),
+                            }
+                        },
+                    };
+                    
+                    if let Type::Option(inner) = current {
+                        current = *inner;
+                    }
+                }
+                
+                Ok(Type::Option(Box::new(current)))
+            },
+            Expr::StructInit { name, fields } => self.infer_struct_init(name, fields),
+            Expr::Match { expr, arms } => {
+                let scrutinee = self.infer_expr(expr)?;
+                let patterns: Vec<&Pattern> = arms.iter().map(|(pattern, _)| pattern).collect();
+                for arm in self.check_match_patterns(&scrutinee, &patterns)? {
+                    self.unreachable_found.push((&**expr as *const Expr as usize, arm));
+                }
+                
+                let mut result_type = Type::Never;
+                let mut remaining = scrutinee.clone();
+                for (pattern, arm) in arms {
+                    let child_env = self.env.new_child();
+                    let old_env = std::mem::replace(&mut self.env, child_env);
+                    self.narrow_scrutinee(expr, pattern, &remaining);
+                    
+                    let arm_type = self.check_pattern(pattern, &Self::pattern_target(pattern, &scrutinee, &remaining))
+                        .and_then(|_| self.infer_expr(arm));
+                    
+                    self.env = old_env;
+                    let arm_type = arm_type?;
+                    
+                    if let Some(rest) = Self::without_none_arm(pattern, &remaining) {
+                        remaining = rest;
+                    }
+                    
+                    if matches!(result_type, Type::Never) {
+                        result_type = arm_type;
+                    } else if !self.types_compatible(&result_type, &arm_type)? {
+                        return Err(ShitRustError::TypeError(
+                            format!("Match arms have incompatible types: {:?} and {:?}", result_type, arm_type)
+                        ));
+                    }
+                }
+                
+                Ok(result_type)
+            },
+            // Add more expression types as needed
+            _ => Ok(Type::Custom("any".to_string())), // Default case, should be replaced with proper handling
+        }
+    }
+    
+    /// Whether an `as` conversion between two types is permitted
+    fn cast_allowed(&self, source: &Type, target: &Type) -> bool {
+        let resolve = |typ: &Type| match typ {
+            Type::Custom(name) => self.env.resolve_alias(name).unwrap_or_else(|| typ.clone()),
+            _ => typ.clone(),
+        };
+        
+        match (resolve(source), resolve(target)) {
+            // Dynamically typed values are checked when the cast runs
+            (Type::Custom(name), _) if name == "any" => true,
+            (a, b) if a == b => true,
+            (Type::Int | Type::Float | Type::Char | Type::Bool, Type::Int) => true,
+            (Type::Int | Type::Float, Type::Float) => true,
+            (Type::Int, Type::Char) => true,
+            // Pointers are addresses, `0 as ptr` is the null pointer
+            (Type::Pointer, Type::Int) | (Type::Int, Type::Pointer) => true,
+            // Exact to inexact conversions round; bigint and decimal to int fail when out of range
+            (Type::BigInt | Type::Decimal, Type::Int | Type::Float) => true,
+            (Type::Int | Type::Decimal | Type::Float, Type::BigInt) => true,
+            (Type::Int | Type::BigInt | Type::Float, Type::Decimal) => true,
+            _ => false,
+        }
+    }
+    
+    /// Type of a field on a struct value, or `any` for values without a known struct type
+    fn field_type(&self, object_type: &Type, field: &str) -> Result<Type> {
+        let Some((type_name, args)) = self.nominal_parts(object_type) else {
+            return Ok(Type::Custom("any".to_string()));
+        };
+        
+        match self.env.get_struct(&type_name) {
+            Some(def) => {
+                let (_, field_type, _) = def.fields.iter()
+                    .find(|(name, _, _)| name == field)
+                    .ok_or_else(|| ShitRustError::TypeError(
+                        format!("Struct '{}' has no field '{}'", type_name, field)
+                    ))?;
+                
+                let bindings = Self::bind_type_args(&def.generic_params, &args);
+                Ok(self.substitute(field_type, &bindings))
+            },
+            None => Ok(Type::Custom("any".to_string())),
+        }
+    }
+    
+    /// Build the narrowest type that covers both inputs
+    fn join_types(&self, a: Type, b: Type) -> Type {
+        if matches!(b, Type::Never) || self.types_compatible(&a, &b).unwrap_or(false) {
+            a
+        } else if matches!(a, Type::Never) || self.types_compatible(&b, &a).unwrap_or(false) {
+            b
+        } else {
+            Self::union_of(vec![a, b])
+        }
+    }
+    
+    /// Build a union, flattening nested unions and pulling `none` out into an optional
+    fn union_of(types: Vec<Type>) -> Type {
+        let mut members: Vec<Type> = Vec::new();
+        let mut optional = false;
+        
+        let mut pending = types;
+        while let Some(typ) = pending.pop() {
+            match typ {
+                Type::Union(nested) => pending.extend(nested),
+                Type::Option(inner) => {
+                    optional = true;
+                    pending.push(*inner);
+                },
+                Type::Never => (),
+                other => {
+                    if !members.contains(&other) {
+                        members.insert(0, other);
+                    }
+                },
+            }
+        }
+        
+        let union = match members.len() {
+            0 => Type::Never,
+            1 => members.remove(0),
+            _ => Type::Union(members),
+        };
+        
+        if optional {
+            Type::Option(Box::new(union))
+        } else {
+            union
+        }
+    }
+    
+    /// Remove the members matching `removed` from a union or optional type
+    fn exclude_type(&self, typ: &Type, removed: &Type) -> Option<Type> {
+        match typ {
+            Type::Union(members) => {
+                let rest: Vec<Type> = members.iter().filter(|member| *member != removed).cloned().collect();
+                (rest.len() < members.len() && !rest.is_empty()).then(|| Self::union_of(rest))
+            },
+            Type::Option(inner) => {
+                let rest = self.exclude_type(inner, removed)?;
+                Some(Type::Option(Box::new(rest)))
+            },
+            _ => None,
+        }
+    }
+    
+    /// Work out what a condition implies about variables when it is true and when it is false
+    fn narrow_condition(&self, condition: &Expr) -> (Vec<(String, Type)>, Vec<(String, Type)>) {
+        match condition {
+            Expr::TypeTest { expr, target_type } => {
+                let Expr::Identifier(name) = &**expr else {
+                    return (Vec::new(), Vec::new());
+                };
+                let Some(current) = self.env.get(name) else {
+                    return (Vec::new(), Vec::new());
+                };
+                
+                let when_true = vec![(name.clone(), target_type.clone())];
+                let when_false = self.exclude_type(&current, target_type)
+                    .map(|rest| vec![(name.clone(), rest)])
+                    .unwrap_or_default();
+                (when_true, when_false)
+            },
+            Expr::BinaryOp { left, op: op @ (BinOp::Eq | BinOp::Ne), right } => {
+                let name = match (&**left, &**right) {
+                    (Expr::Identifier(name), Expr::Literal(Literal::None)) |
+                    (Expr::Literal(Literal::None), Expr::Identifier(name)) => name,
+                    _ => return (Vec::new(), Vec::new()),
+                };
+                
+                let present = match self.env.get(name) {
+                    Some(Type::Option(inner)) => vec![(name.clone(), *inner)],
+                    _ => Vec::new(),
+                };
+                
+                if matches!(op, BinOp::Ne) {
+                    (present, Vec::new())
+                } else {
+                    (Vec::new(), present)
+                }
+            },
+            Expr::UnaryOp { op: UnaryOp::Not, expr } => {
+                let (when_true, when_false) = self.narrow_condition(expr);
+                (when_false, when_true)
+            },
+            Expr::BinaryOp { left, op: BinOp::And, right } => {
+                let (mut when_true, _) = self.narrow_condition(left);
+                when_true.extend(self.narrow_condition(right).0);
+                (when_true, Vec::new())
+            },
+            Expr::BinaryOp { left, op: BinOp::Or, right } => {
+                let (_, mut when_false) = self.narrow_condition(left);
+                when_false.extend(self.narrow_condition(right).1);
+                (Vec::new(), when_false)
+            },
+            _ => (Vec::new(), Vec::new()),
+        }
+    }
+    
+    /// Refine variable types in the current scope
+    fn apply_narrowing(&mut self, narrowing: &[(String, Type)]) {
+        for (name, typ) in narrowing {
+            self.env.narrow(name.clone(), typ.clone());
+        }
+    }
+    
+    /// Narrow a matched variable to the part of its type a match arm can see
+    fn narrow_scrutinee(&mut self, scrutinee: &Expr, pattern: &Pattern, remaining: &Type) {
+        let Expr::Identifier(name) = scrutinee else {
+            return;
+        };
+        
+        let narrowed = match pattern {
+            Pattern::Wildcard | Pattern::Identifier(_) => remaining.clone(),
+            Pattern::Literal(Literal::None) => return,
+            Pattern::Literal(Literal::Int(_)) if matches!(remaining, Type::Union(_)) => Type::Int,
+            Pattern::Literal(Literal::Float(_)) if matches!(remaining, Type::Union(_)) => Type::Float,
+            Pattern::Literal(Literal::BigInt(_)) if matches!(remaining, Type::Union(_)) => Type::BigInt,
+            Pattern::Literal(Literal::Decimal(_)) if matches!(remaining, Type::Union(_)) => Type::Decimal,
+            Pattern::Literal(Literal::Bool(_)) if matches!(remaining, Type::Union(_)) => Type::Bool,
+            Pattern::Literal(Literal::String(_)) if matches!(remaining, Type::Union(_)) => Type::String,
+            Pattern::Literal(Literal::Char(_)) if matches!(remaining, Type::Union(_)) => Type::Char,
+            _ => return,
+        };
+        
+        self.env.narrow(name.clone(), narrowed);
+    }
+    
+    /// The type a pattern's bindings see: catch-all arms only see values earlier arms let through
+    fn pattern_target(pattern: &Pattern, scrutinee: &Type, remaining: &Type) -> Type {
+        match pattern {
+            Pattern::Wildcard | Pattern::Identifier(_) => remaining.clone(),
+            _ => scrutinee.clone(),
+        }
+    }
+    
+    /// The matched type left over after an arm that handles `none`
+    fn without_none_arm(pattern: &Pattern, remaining: &Type) -> Option<Type> {
+        let handles_none = match pattern {
+            Pattern::Literal(Literal::None) => true,
+            Pattern::EnumVariant { name, values } => name == "None" && values.is_empty(),
+            Pattern::Or(alternatives) => alternatives.iter()
+                .any(|alternative| matches!(alternative, Pattern::Literal(Literal::None))),
+            _ => false,
+        };
+        
+        match remaining {
+            Type::Option(inner) if handles_none => Some((**inner).clone()),
+            _ => None,
+        }
+    }
+    
+    /// Check a pattern against the type it is matched with, binding its variables
+    fn check_pattern(&mut self, pattern: &Pattern, expected: &Type) -> Result<()> {
+        match pattern {
+            Pattern::Wildcard => Ok(()),
+            Pattern::Identifier(name) => {
+                self.env.define(name.clone(), expected.clone());
+                Ok(())
+            },
+            Pattern::Literal(lit) => {
+                let lit_type = if matches!(lit, Literal::None) {
+                    Type::Option(Box::new(Type::Custom("any".to_string())))
+                } else {
+                    self.infer_literal(lit)?
+                };
+                
+                if !self.types_compatible(expected, &lit_type)? {
+                    return Err(ShitRustError::TypeError(
+                        format!("Pattern of type {:?} cannot match a value of type {:?}", lit_type, expected)
+                    ));
+                }
+                Ok(())
+            },
+            Pattern::Range { start, end, .. } => {
+                for bound in [start, end] {
+                    let bound_type = self.infer_literal(bound)?;
+                    if !matches!(bound_type, Type::Int | Type::Float | Type::Char) {
+                        return Err(ShitRustError::TypeError(
+                            format!("Range patterns require int, float or char bounds, found {:?}", bound_type)
+                        ));
+                    }
+                    if !self.types_compatible(expected, &bound_type)? {
+                        return Err(ShitRustError::TypeError(
+                            format!("Range pattern of type {:?} cannot match a value of type {:?}", bound_type, expected)
+                        ));
+                    }
+                }
+                Ok(())
+            },
+            Pattern::Or(alternatives) => {
+                alternatives.iter().try_for_each(|alternative| self.check_pattern(alternative, expected))
+            },
+            Pattern::EnumVariant { name, values } => {
+                let payload = self.variant_payload(name, expected)?;
+                
+                if payload.len() != values.len() {
+                    return Err(ShitRustError::TypeError(
+                        format!("Variant '{}' has {} field(s) but the pattern has {}", name, payload.len(), values.len())
+                    ));
+                }
+                
+                for (value, typ) in values.iter().zip(&payload) {
+                    self.check_pattern(value, typ)?;
+                }
+                Ok(())
+            },
+            Pattern::Destructure { name, fields } => {
+                let def = self.env.get_struct(name)
+                    .ok_or_else(|| ShitRustError::TypeError(format!("Unknown struct '{}' in pattern", name)))?;
+                
+                let args = match self.nominal_parts(expected) {
+                    Some((type_name, args)) if type_name == *name => args,
+                    Some((type_name, _)) if type_name != "any" => {
+                        return Err(ShitRustError::TypeError(
+                            format!("Pattern for struct '{}' cannot match a value of type {:?}", name, expected)
+                        ));
+                    },
+                    _ => Vec::new(),
+                };
+                let bindings = Self::bind_type_args(&def.generic_params, &args);
+                
+                for (field, field_pattern) in fields {
+                    let (_, field_type, _) = def.fields.iter()
+                        .find(|(declared, _, _)| declared == field)
+                        .ok_or_else(|| ShitRustError::TypeError(
+                            format!("Struct '{}' has no field '{}'", name, field)
+                        ))?;
+                    let field_type = self.substitute(field_type, &bindings);
+                    self.check_pattern(field_pattern, &field_type)?;
+                }
+                Ok(())
+            },
+        }
+    }
+    
+    /// Resolve the payload types of a variant pattern for the matched type
+    fn variant_payload(&self, name: &str, expected: &Type) -> Result<Vec<Type>> {
+        let variant = name.rsplit("::").next().unwrap_or(name);
+        
+        match (expected, variant) {
+            (Type::Option(inner), "Some") => return Ok(vec![(**inner).clone()]),
+            (Type::Option(_), "None") => return Ok(Vec::new()),
+            (Type::Result(ok, _), "Ok") => return Ok(vec![(**ok).clone()]),
+            (Type::Result(_, err), "Err") => return Ok(vec![(**err).clone()]),
+            _ => (),
+        }
+        
+        if let Some((type_name, args)) = self.nominal_parts(expected) {
+            if let Some(def) = self.env.get_enum(&type_name) {
+                if name.contains("::") && !name.starts_with(&format!("{}::", type_name)) {
+                    return Err(ShitRustError::TypeError(
+                        format!("Pattern '{}' does not belong to enum '{}'", name, type_name)
+                    ));
+                }
+                
+                let (_, payload) = def.variants.iter()
+                    .find(|(declared, _)| declared == variant)
+                    .ok_or_else(|| ShitRustError::TypeError(
+                        format!("Enum '{}' has no variant '{}'", type_name, variant)
+                    ))?;
+                
+                let bindings = Self::bind_type_args(&def.generic_params, &args);
+                return Ok(payload.iter().map(|t| self.substitute(t, &bindings)).collect());
+            }
+            
+            if type_name == "any" {
+                return Ok(Vec::new());
+            }
+        }
+        
+        Err(ShitRustError::TypeError(
+            format!("Variant pattern '{}' cannot match a value of type {:?}", name, expected)
+        ))
+    }
+    
+    /// Verify that match arms are exhaustive and return the indices of arms that can never match
+    fn check_match_patterns(&self, scrutinee: &Type, patterns: &[&Pattern]) -> Result<Vec<usize>> {
+        // Nothing is known about the constructors of a dynamic value
+        if matches!(scrutinee, Type::Custom(name) if name == "any") {
+            return Ok(Vec::new());
+        }
+        
+        let mut rows: Vec<Vec<PatternShape>> = Vec::new();
+        let mut unreachable = Vec::new();
+        
+        for (index, pattern) in patterns.iter().enumerate() {
+            let shape = Self::pattern_shape(pattern);
+            
+            if !self.is_useful(&rows, &[shape.clone()], &[scrutinee.clone()]) {
+                unreachable.push(index);
+            }
+            rows.push(vec![shape]);
+        }
+        
+        let missing: Vec<String> = match self.constructors(scrutinee) {
+            Some(ctors) => ctors.into_iter()
+                .filter(|(name, payload)| {
+                    let probe = PatternShape::Ctor(name.clone(), vec![PatternShape::Wild; payload.len()]);
+                    self.is_useful(&rows, &[probe], &[scrutinee.clone()])
+                })
+                .map(|(name, payload)| Self::describe_ctor(scrutinee, &name, payload.len()))
+                .collect(),
+            None if self.is_useful(&rows, &[PatternShape::Wild], &[scrutinee.clone()]) => vec!["_".to_string()],
+            None => Vec::new(),
+        };
+        
+        if !missing.is_empty() {
+            return Err(ShitRustError::TypeError(
+                format!("Non-exhaustive match on {:?}: missing {}", scrutinee, missing.join(", "))
+            ));
+        }
+        
+        Ok(unreachable)
+    }
+    
+    /// Reduce a pattern to the shape used by the usefulness algorithm
+    fn pattern_shape(pattern: &Pattern) -> PatternShape {
+        match pattern {
+            Pattern::Wildcard | Pattern::Identifier(_) => PatternShape::Wild,
+            Pattern::Literal(Literal::None) => PatternShape::Ctor("None".to_string(), Vec::new()),
+            Pattern::Literal(lit) => PatternShape::Ctor(format!("{:?}", lit), Vec::new()),
+            Pattern::Range { .. } => PatternShape::Ctor(format!("{:?}", pattern), Vec::new()),
+            Pattern::Or(alternatives) => PatternShape::Or(alternatives.iter().map(Self::pattern_shape).collect()),
+            Pattern::EnumVariant { name, values } => PatternShape::Ctor(
+                name.rsplit("::").next().unwrap_or(name).to_string(),
+                values.iter().map(Self::pattern_shape).collect()
+            ),
+            // A struct has a single constructor, so only its fields matter
+            Pattern::Destructure { fields, .. } => {
+                if fields.iter().all(|(_, field)| matches!(Self::pattern_shape(field), PatternShape::Wild)) {
+                    PatternShape::Wild
+                } else {
+                    PatternShape::Ctor(format!("{:?}", pattern), Vec::new())
+                }
+            },
+        }
+    }
+    
+    /// Enumerate the constructors of a type with finitely many of them
+    fn constructors(&self, typ: &Type) -> Option<Vec<(String, Vec<Type>)>> {
+        match typ {
+            Type::Bool => Some(vec![
+                (format!("{:?}", Literal::Bool(true)), Vec::new()),
+                (format!("{:?}", Literal::Bool(false)), Vec::new()),
+            ]),
+            Type::Option(inner) => Some(vec![
+                ("Some".to_string(), vec![(**inner).clone()]),
+                ("None".to_string(), Vec::new()),
+            ]),
+            Type::Result(ok, err) => Some(vec![
+                ("Ok".to_string(), vec![(**ok).clone()]),
+                ("Err".to_string(), vec![(**err).clone()]),
+            ]),
+            _ => {
+                let (type_name, args) = self.nominal_parts(typ)?;
+                let def = self.env.get_enum(&type_name)?;
+                let bindings = Self::bind_type_args(&def.generic_params, &args);
+                
+                Some(def.variants.iter()
+                    .map(|(name, payload)| {
+                        (name.clone(), payload.iter().map(|t| self.substitute(t, &bindings)).collect())
+                    })
+                    .collect())
+            },
+        }
+    }
+    
+    /// Render a missing constructor for error messages
+    fn describe_ctor(scrutinee: &Type, name: &str, arity: usize) -> String {
+        let base = match scrutinee {
+            Type::Bool => return name.trim_start_matches("Bool(").trim_end_matches(')').to_string(),
+            Type::Custom(type_name) | Type::Generic(type_name, _) => format!("{}::{}", type_name, name),
+            _ => name.to_string(),
+        };
+        
+        if arity == 0 {
+            base
+        } else {
+            format!("{}({})", base, vec!["_"; arity].join(", "))
+        }
+    }
+    
+    /// Whether a row of patterns matches some value not matched by any row of the matrix
+    fn is_useful(&self, matrix: &[Vec<PatternShape>], row: &[PatternShape], types: &[Type]) -> bool {
+        let Some((head, rest)) = row.split_first() else {
+            return matrix.is_empty();
+        };
+        let head_type = types.first().cloned().unwrap_or_else(|| Type::Custom("any".to_string()));
+        let rest_types = types.get(1..).unwrap_or(&[]);
+        
+        // Expand or-patterns in the matrix so every row starts with a constructor or a wildcard
+        let matrix: Vec<Vec<PatternShape>> = matrix.iter().flat_map(Self::expand_or).collect();
+        
+        match head {
+            PatternShape::Or(alternatives) => alternatives.iter().any(|alternative| {
+                let mut candidate = vec![alternative.clone()];
+                candidate.extend_from_slice(rest);
+                self.is_useful(&matrix, &candidate, types)
+            }),
+            PatternShape::Ctor(name, args) => {
+                let payload_types = self.payload_types(&head_type, name, args.len());
+                let specialized = Self::specialize(&matrix, name, args.len());
                 
-                // Check method signatures
-                for method in methods {
-                    for (_, param_type) in &method.params {
-                        self.check_type(param_type)?;
-                    }
-                    self.check_type(&method.return_type)?;
-                }
+                let mut candidate = args.clone();
+                candidate.extend_from_slice(rest);
+                let mut candidate_types = payload_types;
+                candidate_types.extend_from_slice(rest_types);
                 
-                // Define the trait type
-                self.env = old_env;
-                self.env.define(name.clone(), Type::Trait(name.clone()));
+                self.is_useful(&specialized, &candidate, &candidate_types)
             },
-            // Add more statement types as needed
-            _ => (), // Handle other statement types
+            PatternShape::Wild => {
+                let heads: Vec<&String> = matrix.iter()
+                    .filter_map(|r| match r.first() {
+                        Some(PatternShape::Ctor(name, _)) => Some(name),
+                        _ => None,
+                    })
+                    .collect();
+                
+                match self.constructors(&head_type) {
+                    Some(ctors) if ctors.iter().all(|(name, _)| heads.contains(&name)) => {
+                        ctors.iter().any(|(name, payload)| {
+                            let mut candidate = vec![PatternShape::Wild; payload.len()];
+                            candidate.extend_from_slice(rest);
+                            let mut candidate_types = payload.clone();
+                            candidate_types.extend_from_slice(rest_types);
+                            
+                            self.is_useful(&Self::specialize(&matrix, name, payload.len()), &candidate, &candidate_types)
+                        })
+                    },
+                    _ => {
+                        // Some constructor is not covered, so only the catch-all rows matter
+                        let default: Vec<Vec<PatternShape>> = matrix.iter()
+                            .filter(|r| matches!(r.first(), Some(PatternShape::Wild)))
+                            .map(|r| r[1..].to_vec())
+                            .collect();
+                        self.is_useful(&default, rest, rest_types)
+                    },
+                }
+            },
+        }
+    }
+    
+    /// Payload types of a constructor, falling back to unknown types
+    fn payload_types(&self, typ: &Type, name: &str, arity: usize) -> Vec<Type> {
+        self.constructors(typ)
+            .and_then(|ctors| ctors.into_iter().find(|(ctor, _)| ctor == name))
+            .map(|(_, payload)| payload)
+            .filter(|payload| payload.len() == arity)
+            .unwrap_or_else(|| vec![Type::Custom("any".to_string()); arity])
+    }
+    
+    /// Split rows whose first pattern is an or-pattern into one row per alternative
+    fn expand_or(row: &Vec<PatternShape>) -> Vec<Vec<PatternShape>> {
+        match row.first() {
+            Some(PatternShape::Or(alternatives)) => alternatives.iter()
+                .flat_map(|alternative| {
+                    let mut expanded = vec![alternative.clone()];
+                    expanded.extend_from_slice(&row[1..]);
+                    Self::expand_or(&expanded)
+                })
+                .collect(),
+            _ => vec![row.clone()],
         }
+    }
+    
+    /// Keep the rows that can match constructor `name`, replacing it with its fields
+    fn specialize(matrix: &[Vec<PatternShape>], name: &str, arity: usize) -> Vec<Vec<PatternShape>> {
+        matrix.iter()
+            .filter_map(|row| {
+                let (head, rest) = row.split_first()?;
+                let mut specialized = match head {
+                    PatternShape::Ctor(ctor, args) if ctor == name && args.len() == arity => args.clone(),
+                    PatternShape::Wild => vec![PatternShape::Wild; arity],
+                    _ => return None,
+                };
+                specialized.extend_from_slice(rest);
+                Some(specialized)
+            })
+            .collect()
+    }
+    
+    /// Check a struct literal against the struct's definition
+    fn infer_struct_init(&mut self, name: &str, fields: &[(String, Expr)]) -> Result<Type> {
+        let def = self.env.get_struct(name)
+            .ok_or_else(|| ShitRustError::TypeError(format!("Unknown struct '{}'", name)))?;
         
-        Ok(())
+        let mut bindings = HashMap::new();
+        let mut seen: Vec<&str> = Vec::new();
+        
+        for (field_name, value) in fields {
+            if seen.contains(&field_name.as_str()) {
+                return Err(ShitRustError::TypeError(
+                    format!("Field '{}' is initialized more than once in '{}'", field_name, name)
+                ));
+            }
+            seen.push(field_name);
+            
+            let (_, field_type, _) = def.fields.iter()
+                .find(|(declared, _, _)| declared == field_name)
+                .ok_or_else(|| ShitRustError::TypeError(
+                    format!("Struct '{}' has no field '{}'", name, field_name)
+                ))?;
+            
+            let value_type = self.infer_expr(value)?;
+            if !self.unify(field_type, &value_type, &def.generic_params, &mut bindings)? {
+                return Err(ShitRustError::TypeError(
+                    format!("Field '{}' of '{}' expects {:?}, found {:?}",
+                           field_name, name, self.substitute(field_type, &bindings), value_type)
+                ));
+            }
+        }
+        
+        let missing: Vec<&str> = def.fields.iter()
+            .map(|(field_name, _, _)| field_name.as_str())
+            .filter(|field_name| !seen.contains(field_name))
+            .collect();
+        
+        if !missing.is_empty() {
+            return Err(ShitRustError::TypeError(
+                format!("Missing field(s) in initializer of '{}': {}", name, missing.join(", "))
+            ));
+        }
+        
+        let type_args: Vec<Type> = def.generic_params.iter()
+            .map(|param| bindings.get(param).cloned().unwrap_or_else(|| Type::Custom("any".to_string())))
+            .collect();
+        
+        if type_args.is_empty() {
+            return Ok(Type::Custom(name.to_string()));
+        }
+        
+        self.record_instantiation(name, type_args.clone());
+        Ok(Type::Generic(name.to_string(), type_args))
     }
     
-    /// Infer the type of an expression
-    pub fn infer_expr(&mut self, expr: &Expr) -> Result<Type> {
-        match expr {
-            Expr::Literal(lit) => self.infer_literal(lit),
-            Expr::Identifier(name) => {
-                if let Some(typ) = self.env.get(name) {
-                    Ok(typ)
-                } else {
-                    Err(ShitRustError::TypeError(format!("Undefined variable: {}", name)))
-                }
+    /// Infer the result type of a call, checking arity and argument types
+    fn infer_call(&mut self, func: &Expr, args: &[Expr]) -> Result<Type> {
+        let mut arg_types = Vec::with_capacity(args.len());
+        for arg in args {
+            arg_types.push(self.infer_expr(arg)?);
+        }
+        
+        // Named functions carry their generic parameters in the signature table
+        if let Expr::Identifier(name) = func {
+            if let Some(signature) = self.env.get_function(name) {
+                return self.check_call_signature(name, &signature, &arg_types);
+            }
+        }
+        
+        let callee_type = self.infer_expr(func)?;
+        let callee_name = match func {
+            Expr::Identifier(name) => name.clone(),
+            _ => "function value".to_string(),
+        };
+        
+        let (is_async, callee_type) = match callee_type {
+            Type::Future(inner) if matches!(*inner, Type::Function(..)) => (true, *inner),
+            other => (false, other),
+        };
+        
+        match callee_type {
+            Type::Function(params, return_type) => {
+                let signature = FunctionSignature {
+                    generic_params: Vec::new(),
+                    params,
+                    return_type: *return_type,
+                    is_async,
+                    bounds: Vec::new(),
+                };
+                self.check_call_signature(&callee_name, &signature, &arg_types)
             },
-            Expr::BinaryOp { left, op, right } => {
-                let left_type = self.infer_expr(left)?;
-                let right_type = self.infer_expr(right)?;
-                
-                match op {
-                    crate::ast::BinOp::Add | 
-                    crate::ast::BinOp::Sub | 
-                    crate::ast::BinOp::Mul | 
-                    crate::ast::BinOp::Div | 
-                    crate::ast::BinOp::Mod => {
-                        if self.types_compatible(&left_type, &right_type)? {
-                            Ok(left_type)
-                        } else {
-                            Err(ShitRustError::TypeError(
-                                format!("Incompatible types for binary operation: {:?} and {:?}", left_type, right_type)
-                            ))
-                        }
-                    },
-                    crate::ast::BinOp::Eq | 
-                    crate::ast::BinOp::Ne | 
-                    crate::ast::BinOp::Lt | 
-                    crate::ast::BinOp::Le | 
-                    crate::ast::BinOp::Gt | 
-                    crate::ast::BinOp::Ge => {
-                        if self.types_compatible(&left_type, &right_type)? {
-                            Ok(Type::Bool)
-                        } else {
-                            Err(ShitRustError::TypeError(
-                                format!("Incompatible types for comparison: {:?} and {:?}", left_type, right_type)
-                            ))
-                        }
+            Type::Custom(name) if name == "any" => Ok(Type::Custom("any".to_string())),
+            other => Err(ShitRustError::TypeError(
+                format!("Cannot call '{}' of non-function type {:?}", callee_name, other)
+            )),
+        }
+    }
+    
+    /// Check argument types against a signature and return the instantiated result type
+    fn check_call_signature(&mut self, name: &str, signature: &FunctionSignature, arg_types: &[Type]) -> Result<Type> {
+        if signature.params.len() != arg_types.len() {
+            return Err(ShitRustError::TypeError(
+                format!("Function '{}' expects {} argument(s) but {} were supplied",
+                       name, signature.params.len(), arg_types.len())
+            ));
+        }
+        
+        // Infer generic arguments from the argument types
+        let mut bindings = HashMap::new();
+        for (index, (param_type, arg_type)) in signature.params.iter().zip(arg_types).enumerate() {
+            if !self.unify(param_type, arg_type, &signature.generic_params, &mut bindings)? {
+                return Err(ShitRustError::TypeError(
+                    format!("Argument {} of '{}' has the wrong type: expected {:?}, found {:?}",
+                           index + 1, name, self.substitute(param_type, &bindings), arg_type)
+                ));
+            }
+        }
+        
+        for (param, traits) in &signature.bounds {
+            let Some(type_arg) = bindings.get(param) else {
+                continue;
+            };
+            
+            for trait_name in traits {
+                if !self.implements_trait(type_arg, trait_name) {
+                    return Err(ShitRustError::TypeError(
+                        format!("Type {:?} does not implement trait '{}' required by '{}' for type parameter '{}'",
+                               type_arg, trait_name, name, param)
+                    ));
+                }
+            }
+        }
+        
+        if !signature.generic_params.is_empty() {
+            let type_args: Vec<Type> = signature.generic_params.iter()
+                .map(|param| bindings.get(param).cloned().unwrap_or_else(|| Type::Custom("any".to_string())))
+                .collect();
+            
+            self.record_instantiation(name, type_args);
+        }
+        
+        let return_type = self.substitute(&signature.return_type, &bindings);
+        Ok(if signature.is_async {
+            Type::Future(Box::new(return_type))
+        } else {
+            return_type
+        })
+    }
+    
+    /// Remember the type arguments a generic function or type was used with
+    fn record_instantiation(&mut self, name: &str, type_args: Vec<Type>) {
+        let known = self.instantiations.entry(name.to_string()).or_default();
+        if !known.contains(&type_args) {
+            known.push(type_args);
+        }
+    }
+    
+    /// Match a parameter type against an argument type, binding generic parameters
+    fn unify(&self, param: &Type, actual: &Type, generics: &[String], bindings: &mut HashMap<String, Type>) -> Result<bool> {
+        match (param, actual) {
+            (Type::Custom(name), _) if generics.contains(name) => {
+                match bindings.get(name).cloned() {
+                    Some(Type::Custom(bound)) if bound == "any" => {
+                        bindings.insert(name.clone(), actual.clone());
+                        Ok(true)
                     },
-                    crate::ast::BinOp::And | 
-                    crate::ast::BinOp::Or => {
-                        if matches!(left_type, Type::Bool) && matches!(right_type, Type::Bool) {
-                            Ok(Type::Bool)
-                        } else {
-                            Err(ShitRustError::TypeError(
-                                format!("Boolean operations require boolean operands, found {:?} and {:?}", left_type, right_type)
-                            ))
-                        }
+                    Some(bound) => self.types_compatible(&bound, actual),
+                    None => {
+                        bindings.insert(name.clone(), actual.clone());
+                        Ok(true)
                     },
-                    _ => Ok(left_type), // Handle other operators as needed
                 }
             },
-            // Add more expression types as needed
-            _ => Ok(Type::Custom("any".to_string())), // Default case, should be replaced with proper handling
+            (Type::Generic(a_name, a_args), Type::Generic(b_name, b_args)) => {
+                if a_name != b_name || a_args.len() != b_args.len() {
+                    return Ok(false);
+                }
+                self.unify_all(a_args, b_args, generics, bindings)
+            },
+            (Type::List(a), Type::List(b)) |
+            (Type::Option(a), Type::Option(b)) |
+            (Type::Future(a), Type::Future(b)) |
+            (Type::Range(a), Type::Range(b)) |
+            (Type::Array(a, _), Type::Array(b, _)) => self.unify(a, b, generics, bindings),
+            (Type::Result(a1, a2), Type::Result(b1, b2)) |
+            (Type::Dict(a1, a2), Type::Dict(b1, b2)) => {
+                Ok(self.unify(a1, b1, generics, bindings)? && self.unify(a2, b2, generics, bindings)?)
+            },
+            (Type::Tuple(a), Type::Tuple(b)) => {
+                if a.len() != b.len() {
+                    return Ok(false);
+                }
+                self.unify_all(a, b, generics, bindings)
+            },
+            (Type::Function(a_params, a_ret), Type::Function(b_params, b_ret)) => {
+                if a_params.len() != b_params.len() {
+                    return Ok(false);
+                }
+                Ok(self.unify_all(a_params, b_params, generics, bindings)? &&
+                   self.unify(a_ret, b_ret, generics, bindings)?)
+            },
+            _ => self.types_compatible(&self.substitute(param, bindings), actual),
+        }
+    }
+    
+    /// Unify two lists of types pairwise
+    fn unify_all(&self, params: &[Type], actuals: &[Type], generics: &[String], bindings: &mut HashMap<String, Type>) -> Result<bool> {
+        for (param, actual) in params.iter().zip(actuals) {
+            if !self.unify(param, actual, generics, bindings)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+    
+    /// Replace bound generic parameters in a type with their concrete types
+    pub fn substitute(&self, typ: &Type, bindings: &HashMap<String, Type>) -> Type {
+        let sub = |t: &Type| Box::new(self.substitute(t, bindings));
+        match typ {
+            Type::Custom(name) => bindings.get(name).cloned().unwrap_or_else(|| typ.clone()),
+            Type::Generic(name, args) => Type::Generic(
+                name.clone(),
+                args.iter().map(|arg| self.substitute(arg, bindings)).collect()
+            ),
+            Type::List(inner) => Type::List(sub(inner)),
+            Type::Option(inner) => Type::Option(sub(inner)),
+            Type::Future(inner) => Type::Future(sub(inner)),
+            Type::Range(inner) => Type::Range(sub(inner)),
+            Type::Array(inner, size) => Type::Array(sub(inner), *size),
+            Type::Reference(inner, mutable) => Type::Reference(sub(inner), *mutable),
+            Type::Result(ok, err) => Type::Result(sub(ok), sub(err)),
+            Type::Dict(key, value) => Type::Dict(sub(key), sub(value)),
+            Type::Tuple(items) => Type::Tuple(items.iter().map(|t| self.substitute(t, bindings)).collect()),
+            Type::Union(items) => Type::Union(items.iter().map(|t| self.substitute(t, bindings)).collect()),
+            Type::Function(params, ret) => Type::Function(
+                params.iter().map(|t| self.substitute(t, bindings)).collect(),
+                sub(ret)
+            ),
+            _ => typ.clone(),
         }
     }
     
     /// Infer the type of a literal
-    fn infer_literal(&self, lit: &Literal) -> Result<Type> {
+    fn infer_literal(&mut self, lit: &Literal) -> Result<Type> {
         match lit {
             Literal::Int(_) => Ok(Type::Int),
             Literal::Float(_) => Ok(Type::Float),
+            Literal::BigInt(_) => Ok(Type::BigInt),
+            Literal::Decimal(_) => Ok(Type::Decimal),
             Literal::Bool(_) => Ok(Type::Bool),
             Literal::String(_) => Ok(Type::String),
             Literal::Char(_) => Ok(Type::Char),
@@ -361,6 +2535,7 @@ impl TypeChecker {
                     Ok(Type::List(Box::new(first_type)))
                 }
             },
+            Literal::None => Ok(Type::Option(Box::new(Type::Custom("any".to_string())))),
             // Add more literal types as needed
             _ => Ok(Type::Custom("any".to_string())), // Default case, should be replaced with proper handling
         }
@@ -372,13 +2547,57 @@ impl TypeChecker {
             // Same types are always compatible
             (a, b) if a == b => Ok(true),
             
+            // Diverging expressions can stand in for any type
+            (_, Type::Never) => Ok(true),
+            
+            // Ints widen to exact numbers without loss
+            (Type::BigInt, Type::Int) | (Type::Decimal, Type::Int | Type::BigInt) => Ok(true),
+            
+            // Types the checker could not infer are not reported as mismatches
+            (Type::Custom(name), _) | (_, Type::Custom(name)) if name == "any" => Ok(true),
+            
             // Generic type parameters can match any type
             (Type::Custom(name), _) if self.env.is_generic_param(name) => Ok(true),
             (_, Type::Custom(name)) if self.env.is_generic_param(name) => Ok(true),
             
             // Container types compatibility
             (Type::List(a), Type::List(b)) => self.types_compatible(a, b),
+            (Type::Generic(a_name, a_args), Type::Generic(b_name, b_args)) => {
+                if a_name != b_name || a_args.len() != b_args.len() {
+                    return Ok(false);
+                }
+                
+                for (a, b) in a_args.iter().zip(b_args.iter()) {
+                    if !self.types_compatible(a, b)? {
+                        return Ok(false);
+                    }
+                }
+                
+                Ok(true)
+            },
             (Type::Option(a), Type::Option(b)) => self.types_compatible(a, b),
+            
+            // A present value can be used where an optional one is expected
+            (Type::Option(a), b) if !matches!(b, Type::Union(_)) => self.types_compatible(a, b),
+            
+            // Union types: every possible actual type must fit one of the expected members
+            (_, Type::Union(actual_members)) => {
+                for member in actual_members {
+                    if !self.types_compatible(expected, member)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            },
+            (Type::Union(expected_members), _) => {
+                for member in expected_members {
+                    if self.types_compatible(member, actual)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            },
+            
             (Type::Result(a1, a2), Type::Result(b1, b2)) => {
                 Ok(self.types_compatible(a1, b1)? && self.types_compatible(a2, b2)?)
             },
@@ -428,7 +2647,20 @@ impl TypeChecker {
                 }
             },
             Type::List(element_type) => self.check_type(element_type)?,
+            Type::Generic(name, args) => {
+                if self.env.get(name).is_none() && self.env.resolve_alias(name).is_none() {
+                    return Err(ShitRustError::TypeError(format!("Undefined type: {}", name)));
+                }
+                for arg in args {
+                    self.check_type(arg)?;
+                }
+            },
             Type::Option(inner_type) => self.check_type(inner_type)?,
+            Type::Union(members) => {
+                for member in members {
+                    self.check_type(member)?;
+                }
+            },
             Type::Result(ok_type, err_type) => {
                 self.check_type(ok_type)?;
                 self.check_type(err_type)?;
@@ -445,4 +2677,331 @@ impl TypeChecker {
         
         Ok(())
     }
-} 
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    
+    fn parse(source: &str) -> Vec<Stmt> {
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        Parser::new(tokens).parse().unwrap().statements
+    }
+    
+    // The return types of the lambdas in `source` once the checker has annotated them
+    fn annotated_lambda_returns(source: &str) -> Vec<Option<Type>> {
+        let statements = parse(source);
+        let mut checker = TypeChecker::new();
+        checker.record_types(TypeRecording::Lambdas);
+        checker.check_program(&statements).unwrap();
+        
+        let mut annotated = statements.clone();
+        checker.annotate_lambdas(&mut annotated);
+        let mut returns = Vec::new();
+        ast::walk_exprs(&annotated, &mut |expr| {
+            if let Expr::Lambda { return_type, .. } = expr {
+                returns.push(return_type.clone());
+            }
+        });
+        returns
+    }
+    
+    #[test]
+    fn block_lambdas_return_the_join_of_their_returns() {
+        let returns = annotated_lambda_returns(
+            "let sign = |x: int| { if (x < 0) { return 0 - 1; } return 1; };\n\
+             let log = |x: int| { print(x); };"
+        );
+        assert_eq!(returns, vec![Some(Type::Int), Some(Type::Void)]);
+    }
+    
+    #[test]
+    fn expression_lambdas_return_their_body() {
+        let returns = annotated_lambda_returns("let half = |x: float| x / 2.0;");
+        assert_eq!(returns, vec![Some(Type::Float)]);
+    }
+    
+    #[test]
+    fn returns_of_nested_functions_are_not_the_lambdas() {
+        let returns = annotated_lambda_returns(
+            "let f = |x: int| { fn name() -> string { return \"inner\"; } print(name()); };"
+        );
+        assert_eq!(returns, vec![Some(Type::Void)]);
+    }
+    
+    #[test]
+    fn dynamic_return_types_are_left_out() {
+        let returns = annotated_lambda_returns("let id = |x| x; let seven = || -> int { return 7; };");
+        assert_eq!(returns, vec![None, Some(Type::Int)]);
+    }
+    
+    fn check(source: &str) -> Result<()> {
+        TypeChecker::new().check_program(&parse(source))
+    }
+    
+    const PRINTABLE_BOX: &str = "trait Printable<T> { fn print(value: T) -> string; }\n\
+                                 struct Box<T> { value: T }\n";
+    
+    #[test]
+    fn impls_bind_their_trait_type_arguments() {
+        let source = format!("{}impl Printable<int> for Box<int> {{ fn print(value: int) -> string {{ return \"int\"; }} }}", PRINTABLE_BOX);
+        assert!(check(&source).is_ok());
+        
+        let source = format!("{}impl Printable<int> for Box<int> {{ fn print(value: string) -> string {{ return value; }} }}", PRINTABLE_BOX);
+        assert!(check(&source).is_err());
+    }
+    
+    #[test]
+    fn impl_type_arguments_must_match_the_declarations() {
+        let source = format!("{}impl Printable<int, int> for Box<int> {{ fn print(value: int) -> string {{ return \"int\"; }} }}", PRINTABLE_BOX);
+        assert!(check(&source).is_err());
+        
+        let source = format!("{}impl Printable<int> for Box<int, int> {{ fn print(value: int) -> string {{ return \"int\"; }} }}", PRINTABLE_BOX);
+        assert!(check(&source).is_err());
+    }
+    
+    #[test]
+    fn unreachable_arms_are_keyed_by_the_matched_expression_and_arm() {
+        let statements = parse("let flag = true;\n\
+                                match flag { _ => 1, true => 2 }\n\
+                                let n = match flag { true => 1, false => 2, _ => 3 };");
+        let mut checker = TypeChecker::new();
+        checker.check_program(&statements).unwrap();
+        
+        // `flag` is expression 1 in the match statement and expression 5 in the match expression
+        let expected: HashSet<(usize, usize)> = [(1, 1), (5, 2)].into_iter().collect();
+        assert_eq!(checker.unreachable_arms(), &expected);
+    }
+    
+    #[test]
+    fn types_are_not_recorded_unless_asked() {
+        let statements = parse("let double = |x: int| x * 2;");
+        let mut checker = TypeChecker::new();
+        checker.check_program(&statements).unwrap();
+        assert!(checker.recorded_type(0).is_none());
+        
+        let mut annotated = statements.clone();
+        checker.annotate_lambdas(&mut annotated);
+        assert!(matches!(&annotated[0], Stmt::Let { value: Expr::Lambda { return_type: None, .. }, .. }));
+    }
+    
+    fn check_error(source: &str) -> String {
+        check(source).unwrap_err().to_string()
+    }
+    
+    #[test]
+    fn returned_values_must_have_the_declared_type() {
+        assert!(check("fn answer() -> int { return 42; }").is_ok());
+        assert!(check_error("fn answer() -> int { return \"42\"; }").contains("Return type mismatch"));
+        assert!(check("let n = 1; return n;").is_err());
+    }
+    
+    #[test]
+    fn every_path_must_return_a_value() {
+        assert!(check("fn sign(x: int) -> int { if (x < 0) { return 0 - 1; } else { return 1; } }").is_ok());
+        assert!(check("fn spin() -> int { while (true) { } }").is_ok());
+        assert!(check_error("fn sign(x: int) -> int { if (x < 0) { return 0 - 1; } }").contains("on all code paths"));
+        assert!(check_error("fn stop() -> int { while (true) { break; } }").contains("on all code paths"));
+    }
+    
+    #[test]
+    fn located_breaks_still_end_loops() {
+        // `--debug` parses with locations, which wraps every statement in `Stmt::Located`
+        let source = "fn stop() -> int { while (true) { if (true) { break; } } }";
+        let tokens = Lexer::new(source).scan_tokens().unwrap();
+        let statements = Parser::with_locations(tokens).parse().unwrap().statements;
+        let error = TypeChecker::new().check_program(&statements).unwrap_err().to_string();
+        assert!(error.contains("on all code paths"), "{}", error);
+    }
+    
+    #[test]
+    fn never_functions_diverge_and_calls_to_them_end_paths() {
+        let fail = "fn fail() -> never { while (true) { } }\n";
+        assert!(check(&format!("{}fn pick(x: int) -> int {{ if (x > 0) {{ return x; }} fail(); }}", fail)).is_ok());
+        assert!(check_error("fn fail() -> never { return; }").contains("cannot return"));
+        assert!(check_error("fn fail() -> never { }").contains("without diverging"));
+    }
+    
+    #[test]
+    fn calls_are_checked_against_the_signature() {
+        let add = "fn add(a: int, b: int) -> int { return a + b; }\n";
+        assert!(check(&format!("{}let sum: int = add(1, 2);", add)).is_ok());
+        assert!(check_error(&format!("{}add(1);", add)).contains("expects 2 argument(s) but 1 were supplied"));
+        assert!(check_error(&format!("{}add(1, \"2\");", add)).contains("Argument 2 of 'add'"));
+        assert!(check("let n = 1; n(2);").is_err());
+    }
+    
+    #[test]
+    fn generic_calls_infer_and_record_their_type_arguments() {
+        let statements = parse("fn identity<T>(value: T) -> T { return value; }\n\
+                                let n: int = identity(1);\n\
+                                let s: string = identity(\"one\");\n\
+                                let again: int = identity(2);");
+        let mut checker = TypeChecker::new();
+        checker.check_program(&statements).unwrap();
+        assert_eq!(checker.instantiations()["identity"], vec![vec![Type::Int], vec![Type::String]]);
+        
+        assert!(check("fn same<T>(a: T, b: T) -> T { return a; }\nsame(1, \"one\");").is_err());
+        assert!(check("fn identity<T>(value: T) -> T { return value; }\nlet s: string = identity(1);").is_err());
+    }
+    
+    #[test]
+    fn generic_struct_literals_instantiate_the_struct() {
+        let statements = parse("struct Wrapper<T> { value: T }\n\
+                                let wrapped = Wrapper { value: 1 };\n\
+                                let n: int = wrapped.value;");
+        let mut checker = TypeChecker::new();
+        checker.check_program(&statements).unwrap();
+        assert_eq!(checker.instantiations()["Wrapper"], vec![vec![Type::Int]]);
+    }
+    
+    const POINT: &str = "struct Point { x: int, y: int }\n";
+    
+    #[test]
+    fn struct_literals_and_field_accesses_follow_the_definition() {
+        assert!(check(&format!("{}let p = Point {{ x: 1, y: 2 }}; let x: int = p.x;", POINT)).is_ok());
+        assert!(check_error(&format!("{}let p = Point {{ x: 1 }};", POINT)).contains("Missing field(s) in initializer of 'Point': y"));
+        assert!(check_error(&format!("{}let p = Point {{ x: 1, y: 2, z: 3 }};", POINT)).contains("has no field 'z'"));
+        assert!(check_error(&format!("{}let p = Point {{ x: 1, y: \"2\" }};", POINT)).contains("Field 'y' of 'Point'"));
+        assert!(check_error(&format!("{}let p = Point {{ x: 1, y: 2 }}; let z = p.z;", POINT)).contains("has no field 'z'"));
+    }
+    
+    #[test]
+    fn enum_constructors_check_their_payloads() {
+        let shape = "enum Shape { Circle(float), Square(float), Empty }\n";
+        assert!(check(&format!("{}let a = Shape::Circle(1.0); let b = Shape::Empty;", shape)).is_ok());
+        assert!(check(&format!("{}let a = Shape::Circle(\"big\");", shape)).is_err());
+        assert!(check(&format!("{}let a = Shape::Circle(1.0, 2.0);", shape)).is_err());
+        assert!(check(&format!("{}let a = Shape::Triangle(1.0);", shape)).is_err());
+    }
+    
+    #[test]
+    fn method_calls_are_checked_against_the_method_signature() {
+        let counter = "struct Counter { count: int fn add(amount: int) -> int { return this.count + amount; } }\n\
+                       let counter = Counter { count: 0 };\n";
+        assert!(check(&format!("{}let n: int = counter.add(1);", counter)).is_ok());
+        assert!(check(&format!("{}counter.add(\"one\");", counter)).is_err());
+        assert!(check_error(&format!("{}counter.reset();", counter)).contains("has no method 'reset'"));
+    }
+    
+    #[test]
+    fn matches_on_enums_must_cover_every_variant() {
+        let shape = "enum Shape { Circle(float), Square(float) }\nlet s = Shape::Circle(1.0);\n";
+        assert!(check(&format!("{}let area = match s {{ Shape::Circle(r) => r * r, Shape::Square(side) => side * side }};", shape)).is_ok());
+        assert!(check(&format!("{}let area = match s {{ Shape::Circle(r) => r * r, _ => 0.0 }};", shape)).is_ok());
+        
+        let error = check_error(&format!("{}let area = match s {{ Shape::Circle(r) => r * r }};", shape));
+        assert!(error.contains("Non-exhaustive") && error.contains("Square"), "{}", error);
+    }
+    
+    #[test]
+    fn matches_on_bools_and_ints_must_be_exhaustive() {
+        assert!(check("let b = true; let n = match b { true => 1, false => 0 };").is_ok());
+        assert!(check_error("let b = true; let n = match b { true => 1 };").contains("Non-exhaustive"));
+        assert!(check("let n = 1; let s = match n { 1 | 2 => \"few\", _ => \"many\" };").is_ok());
+        assert!(check_error("let n = 1; let s = match n { 1 => \"one\" };").contains("missing _"));
+    }
+    
+    #[test]
+    fn patterns_must_fit_the_matched_type() {
+        assert!(check("let n = 1; let s = match n { \"one\" => 1, _ => 0 };").is_err());
+        assert!(check("let b = true; let s = match b { 0..10 => 1, _ => 0 };").is_err());
+    }
+    
+    #[test]
+    fn only_mutable_bindings_can_be_reassigned() {
+        assert!(check("let mut n = 1; n = 2;").is_ok());
+        assert!(check_error("let n = 1; n = 2;").contains("immutable variable 'n'"));
+        assert!(check_error("const MAX: int = 10; MAX = 11;").contains("constant 'MAX'"));
+        assert!(check_error("fn bump(n: int) -> int { n = n + 1; return n; }").contains("immutable variable 'n'"));
+    }
+    
+    #[test]
+    fn only_mut_fields_of_mutable_bindings_can_be_assigned() {
+        let counter = "struct Counter { mut count: int, label: string }\n\
+                       let mut counter = Counter { count: 0, label: \"clicks\" };\n";
+        assert!(check(&format!("{}counter.count = 1;", counter)).is_ok());
+        assert!(check_error(&format!("{}counter.label = \"taps\";", counter)).contains("immutable field 'label'"));
+        assert!(check("struct Counter { mut count: int }\nlet counter = Counter { count: 0 };\ncounter.count = 1;").is_err());
+    }
+    
+    #[test]
+    fn union_values_are_narrowed_by_type_tests() {
+        assert!(check("let v: int | string = 1; let w: int | string = \"one\";").is_ok());
+        assert!(check("let v: int | string = 1.5;").is_err());
+        
+        let narrowed = "fn describe(value: int | string) -> string { if (value is int) { return \"number\"; } let text: string = value; return text; }";
+        assert!(check(narrowed).is_ok());
+        assert!(check("fn describe(value: int | string) -> string { let text: string = value; return text; }").is_err());
+    }
+    
+    #[test]
+    fn optional_values_are_narrowed_by_none_checks_and_defaults() {
+        assert!(check("fn show(text: string | none) -> string { if (text != none) { let s: string = text; return s; } return \"\"; }").is_ok());
+        assert!(check("fn show(text: string | none) -> string { let s: string = text; return s; }").is_err());
+        assert!(check("fn show(text: string | none) -> string { return text ?? \"anonymous\"; }").is_ok());
+        assert!(check("fn show(text: string | none) -> int { return text ?? \"anonymous\"; }").is_err());
+    }
+    
+    #[test]
+    fn casts_are_limited_to_legal_conversions() {
+        assert!(check("let f: float = 3 as float; let n: int = 2.5 as int; let c: char = 65 as char; let code: int = 'A' as int;").is_ok());
+        assert!(check_error("let n = \"3\" as int;").contains("Cannot cast String to Int"));
+        assert!(check("let f = true as float;").is_err());
+        assert!(check_error("let c = 55296 as char;").contains("not a valid Unicode scalar value"));
+    }
+    
+    #[test]
+    fn extern_functions_only_take_c_types() {
+        check("extern \"C\" {\n    fn abs(x: c_int) -> c_int;\n    fn puts(s: string);\n}\nlet n: int = abs(-3);\nputs(\"hi\");").unwrap();
+        
+        assert!(check_error("extern \"C\" { fn sum(values: [int]) -> int; }").contains("Extern function 'sum' uses"));
+        assert!(check_error("extern \"C\" { fn big() -> bigint; }").contains("Extern function 'big' uses"));
+        assert!(check_error("extern \"C\" { fn abs(x: c_int) -> c_int; }\nabs(\"three\");").contains("Argument 1 of 'abs'"));
+    }
+    
+    const SHAPE: &str = "trait Shape { fn area(side: float) -> float; }\n\
+                         struct Square { side: float }\n\
+                         fn total<T: Shape>(shape: T) -> int { return 1; }\n";
+    
+    #[test]
+    fn type_arguments_must_satisfy_the_bounds() {
+        let source = format!("{}impl Shape for Square {{ fn area(side: float) -> float {{ return side * side; }} }}\n\
+                              let n = total(Square {{ side: 2.0 }});", SHAPE);
+        check(&source).unwrap();
+        
+        let source = format!("{}impl Shape for int {{ fn area(side: float) -> float {{ return side; }} }}\nlet n = total(3);", SHAPE);
+        check(&source).unwrap();
+        
+        let error = check_error(&format!("{}let n = total(3);", SHAPE));
+        assert!(error.contains("does not implement trait 'Shape' required by 'total'"), "{}", error);
+        
+        let source = "trait Shape { fn area(side: float) -> float; }\n\
+                      fn total<T>(shape: T) -> int where T: Shape { return 1; }\n\
+                      let n = total(\"square\");";
+        assert!(check_error(source).contains("does not implement trait 'Shape'"));
+    }
+    
+    #[test]
+    fn bounds_must_name_declared_parameters_and_known_traits() {
+        assert!(check_error("fn f<T: Drawable>(x: T) -> int { return 1; }").contains("Unknown trait 'Drawable'"));
+        assert!(check_error("trait Shape { fn area(side: float) -> float; }\n\
+                             fn f<T>(x: T) -> int where U: Shape { return 1; }").contains("undeclared type parameter 'U'"));
+    }
+    
+    #[test]
+    fn impls_must_conform_to_their_trait() {
+        let error = check_error(&format!("{}impl Shape for Square {{ }}", SHAPE));
+        assert!(error.contains("missing required method 'area'"), "{}", error);
+        
+        let error = check_error(&format!("{}impl Shape for Square {{ fn area(side: float) -> float {{ return side; }} fn perimeter(side: float) -> float {{ return side; }} }}", SHAPE));
+        assert!(error.contains("Method 'perimeter' is not a member of trait 'Shape'"), "{}", error);
+        
+        let error = check_error(&format!("{}impl Shape for Square {{ fn area(side: int) -> float {{ return 1.0; }} }}", SHAPE));
+        assert!(error.contains("Method 'area' in impl of 'Shape' for 'Square' has signature"), "{}", error);
+        
+        assert!(check_error("impl Drawable for int { }").contains("Cannot implement unknown trait 'Drawable'"));
+    }
+}
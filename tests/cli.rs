@@ -0,0 +1,210 @@
+//! Runs the `shitrust` binary on small programs, one test per subcommand
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use shitrust::compiler::CrateType;
+use tempfile::TempDir;
+
+const HELLO: &str = "let name = \"ShitRust\";\nprintln(\"Hello, \" + name + \"!\");\n";
+
+/// Run `shitrust` in `dir`, where the build cache is created
+fn shitrust(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_shitrust"))
+        .arg("--no-color")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("failed to run shitrust")
+}
+
+/// A scratch directory with `main.sr` containing `source`
+fn project(source: &str) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("main.sr"), source).unwrap();
+    dir
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn run_interprets_the_program() {
+    let dir = project(HELLO);
+    let output = shitrust(dir.path(), &["run", "main.sr"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Hello, ShitRust!"));
+    
+    let output = shitrust(dir.path(), &["run", "--jit", "main.sr"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Hello, ShitRust!"));
+    
+    let dir = project("let x = 1 / 0;\n");
+    let output = shitrust(dir.path(), &["run", "main.sr"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Division by zero"), "{}", stderr(&output));
+}
+
+#[test]
+fn run_jit_interprets_programs_whose_functions_read_top_level_bindings() {
+    let dir = project("let base = 40;\nfn answer(x: int) -> int {\n    return base + x;\n}\nprintln(answer(2));\n");
+    let output = shitrust(dir.path(), &["run", "--jit", "main.sr"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).lines().any(|line| line == "42"), "{}", stdout(&output));
+    assert!(stderr(&output).contains("interpreting it instead"), "{}", stderr(&output));
+}
+
+#[test]
+fn run_async_interprets_the_program() {
+    let dir = project(HELLO);
+    let output = shitrust(dir.path(), &["run-async", "main.sr"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Hello, ShitRust!"));
+}
+
+#[test]
+fn compile_builds_an_executable_and_caches_it() {
+    let dir = project(HELLO);
+    let output = shitrust(dir.path(), &["compile", "main.sr", "-o", "hello"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(dir.path().join("target/shitrust").is_dir());
+    
+    let program = Command::new(dir.path().join("hello")).output().unwrap();
+    assert!(program.status.success());
+    assert!(stdout(&program).contains("Hello, ShitRust!"));
+    
+    // A second build is served from the cache and gives the same program
+    fs::remove_file(dir.path().join("hello")).unwrap();
+    let output = shitrust(dir.path(), &["compile", "main.sr", "-o", "hello"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&Command::new(dir.path().join("hello")).output().unwrap()).contains("Hello, ShitRust!"));
+    
+    let output = shitrust(dir.path(), &["compile", "main.sr", "-o", "bundled", "--bundle", "--no-cache"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&Command::new(dir.path().join("bundled")).output().unwrap()).contains("Hello, ShitRust!"));
+}
+
+#[test]
+fn compiled_arithmetic_matches_the_interpreter() {
+    let dir = project("fn show(x: int) {\n    let three = 3;\n    println(x.checked_add(1) ?? -1);\n    println(x.saturating_mul(2));\n    println(x.wrapping_add(1));\n    println(x.checked_div(0) ?? 0);\n    println(three.wrapping_pow(4));\n}\nshow(9223372036854775807);\n");
+    let output = shitrust(dir.path(), &["compile", "main.sr", "-o", "show", "--no-cache"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let program = Command::new(dir.path().join("show")).output().unwrap();
+    assert!(program.status.success(), "{}", stderr(&program));
+    assert_eq!(stdout(&program).lines().collect::<Vec<_>>(), ["-1", "9223372036854775807", "-9223372036854775808", "0", "81"]);
+    
+    let dir = project("fn add(a: int, b: int) -> int {\n    return a + b;\n}\nprintln(add(9223372036854775807, 1));\n");
+    let output = shitrust(dir.path(), &["compile", "main.sr", "-o", "overflow", "--no-cache"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let program = Command::new(dir.path().join("overflow")).output().unwrap();
+    assert!(!program.status.success());
+    assert!(stderr(&program).contains("Integer overflow in addition"), "{}", stderr(&program));
+    
+    let output = shitrust(dir.path(), &["run", "main.sr"]);
+    assert!(stderr(&output).contains("Integer overflow in addition"), "{}", stderr(&output));
+}
+
+#[test]
+fn compile_builds_libraries_with_a_header() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("geometry.sr"), "#[export]\npub fn area(width: float, height: float) -> float {\n    return width * height;\n}\n").unwrap();
+    
+    let output = shitrust(dir.path(), &["compile", "geometry.sr", "--crate-type", "staticlib", "--no-cache"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(dir.path().join(CrateType::Staticlib.output_file_name("geometry", None)).exists());
+    
+    let header = fs::read_to_string(dir.path().join("geometry.h")).unwrap();
+    assert!(header.contains("double area(double width, double height);"), "{}", header);
+    
+    let output = shitrust(dir.path(), &["--emit", "lib", "compile", "geometry.sr", "--crate-type", "cdylib"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn check_reports_type_errors_and_lints() {
+    let dir = project("fn f() -> int {\n    let unused = 1;\n    return 2;\n}\nlet n: int = f();\nprintln(n);\n");
+    let output = shitrust(dir.path(), &["check", "main.sr"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Unused variable 'unused' in function 'f' [unused_variables]"), "{}", stderr(&output));
+    
+    let output = shitrust(dir.path(), &["check", "main.sr", "-A", "unused"]);
+    assert!(output.status.success());
+    assert!(!stderr(&output).contains("unused_variables"));
+    
+    let output = shitrust(dir.path(), &["check", "main.sr", "-D", "unused_variables"]);
+    assert!(!output.status.success());
+    
+    let dir = project("let n: int = \"three\";\n");
+    let output = shitrust(dir.path(), &["check", "main.sr"]);
+    assert!(!output.status.success());
+    
+    let output = shitrust(dir.path(), &["check", "main.sr", "--list-lints"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("unreachable_patterns"));
+}
+
+#[test]
+fn format_prints_or_rewrites_the_program() {
+    let dir = project("let   x=1 ;\n");
+    let output = shitrust(dir.path(), &["format", "main.sr"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("let x = 1;"));
+    assert_eq!(fs::read_to_string(dir.path().join("main.sr")).unwrap(), "let   x=1 ;\n");
+    
+    let output = shitrust(dir.path(), &["format", "-i", "main.sr"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::read_to_string(dir.path().join("main.sr")).unwrap().contains("let x = 1;"));
+}
+
+#[test]
+fn dump_prints_each_stage() {
+    let dir = project("let x = 1 + 2;\n");
+    
+    let output = shitrust(dir.path(), &["dump", "main.sr", "--stage", "tokens"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("let"));
+    
+    let output = shitrust(dir.path(), &["dump", "main.sr", "--stage", "ast", "--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    serde_json::from_str::<serde_json::Value>(&stdout(&output)).unwrap();
+    
+    let output = shitrust(dir.path(), &["dump", "main.sr", "--stage", "typed-ast"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Int"), "{}", stdout(&output));
+    
+    let output = shitrust(dir.path(), &["dump", "main.sr", "--stage", "ir"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("define"));
+    
+    let output = shitrust(dir.path(), &["dump", "main.sr", "--stage", "ir", "--format", "json"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn clean_removes_the_build_cache() {
+    let dir = project(HELLO);
+    fs::create_dir_all(dir.path().join("target/shitrust/main")).unwrap();
+    
+    let output = shitrust(dir.path(), &["clean"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Removed"));
+    assert!(!dir.path().join("target/shitrust").exists());
+    
+    let output = shitrust(dir.path(), &["clean"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Nothing to clean"));
+}
+
+#[test]
+fn info_describes_the_compiler() {
+    let output = shitrust(Path::new("."), &["info"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("ShitRust Programming Language"));
+    assert!(stdout(&output).contains(env!("CARGO_PKG_VERSION")));
+}